@@ -0,0 +1,39 @@
+use log::{debug, info};
+
+use crate::asynchronous::execute_borg;
+use crate::common::{
+    benchmark_crud_fmt_args, benchmark_crud_parse_output, BenchmarkOptions, CommonOptions,
+};
+use crate::errors::BenchmarkError;
+use crate::output::benchmark::BenchmarkResult;
+use crate::utils::find_borg;
+
+/// Run the borg benchmark crud command
+///
+/// This measures the throughput of creating, reading, updating and deleting
+/// archives in the repository, using files written to [BenchmarkOptions::path].
+///
+/// **Parameter**:
+/// - `options`: Reference to [BenchmarkOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %options.repository))
+)]
+pub async fn benchmark_crud(
+    options: &BenchmarkOptions,
+    common_options: &CommonOptions,
+) -> Result<BenchmarkResult, BenchmarkError> {
+    let local_path = &find_borg(common_options)?;
+
+    let args = benchmark_crud_fmt_args(options, common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(BenchmarkError::ShlexError)?;
+    let res = execute_borg(local_path, args, &options.passphrase, common_options).await?;
+
+    let result = benchmark_crud_parse_output(res, common_options)?;
+
+    info!("Finished benchmark");
+
+    Ok(result)
+}
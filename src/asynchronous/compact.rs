@@ -1,7 +1,18 @@
-use log::{debug, info};
+use std::fmt::{Display, Formatter};
+use std::process::{Output, Stdio};
 
-use crate::common::{compact_fmt_args, compact_parse_output, CommonOptions, CompactOptions};
+use log::{debug, error, info, trace};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::asynchronous::{execute_borg, with_timeout};
+use crate::common::{
+    compact_check_version, compact_fmt_args, compact_parse_output, CommonOptions, CompactOptions,
+};
 use crate::errors::CompactError;
+use crate::output::compact::CompactReport;
+use crate::output::logging::{LoggingMessage, MessageId};
+use crate::utils::{borg_command, find_borg};
 
 ///This command frees repository space by compacting segments.
 ///
@@ -15,23 +26,203 @@ use crate::errors::CompactError;
 /// **Parameter**:
 /// - `options`: Reference to [CompactOptions]
 /// - `common_options`: Reference to [CommonOptions]
+///
+/// The freed space is returned as [CompactReport].
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %options.repository))
+)]
 pub async fn compact(
     options: &CompactOptions,
     common_options: &CommonOptions,
-) -> Result<(), CompactError> {
-    let local_path = common_options.local_path.as_ref().map_or("borg", |x| x);
+) -> Result<CompactReport, CompactError> {
+    let res = compact_raw(options, common_options).await?;
+
+    let report = compact_parse_output(res, common_options)?;
+
+    info!("Finished compacting");
+
+    Ok(report)
+}
+
+/// Run the borg compact command without parsing its output, see [compact].
+///
+/// **Parameter**:
+/// - `options`: Reference to [CompactOptions]
+/// - `common_options`: Reference to [CommonOptions]
+pub async fn compact_raw(
+    options: &CompactOptions,
+    common_options: &CommonOptions,
+) -> Result<Output, CompactError> {
+    compact_check_version(options, common_options)?;
+
+    let local_path = &find_borg(common_options)?;
 
-    let args = compact_fmt_args(options, common_options);
+    let args = compact_fmt_args(options, common_options, false);
     debug!("Calling borg: {local_path} {args}");
     let args = shlex::split(&args).ok_or(CompactError::ShlexError)?;
-    let res = tokio::process::Command::new(local_path)
+    let res = execute_borg(local_path, args, &None, common_options).await?;
+
+    Ok(res)
+}
+
+/// The progress of a borg compact command.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CompactProgress {
+    /// The number of segments that were compacted so far
+    ///
+    /// Absent if the compaction has finished
+    pub current: Option<u64>,
+    /// The total number of segments to compact
+    ///
+    /// Absent if the compaction has finished
+    pub total: Option<u64>,
+    /// Indicating whether the compaction has finished
+    pub finished: bool,
+}
+
+impl Display for CompactProgress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompactProgress {
+                current: Some(current),
+                total: Some(total),
+                finished: false,
+            } => write!(f, "{current}/{total}"),
+            _ => write!(f, "Finished"),
+        }
+    }
+}
+
+/// This command frees repository space by compacting segments.
+///
+/// The progress of the compaction will be sent back through the provided channel.
+///
+/// **Parameter**:
+/// - `options`: Reference to [CompactOptions]
+/// - `common_options`: Reference to [CommonOptions]
+/// - `progress_channel`: A [tokio::sync::mpsc::Sender] of [CompactProgress]. On every progress
+///   update, a message will be sent to this channel
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %options.repository))
+)]
+pub async fn compact_progress(
+    options: &CompactOptions,
+    common_options: &CommonOptions,
+    progress_channel: tokio::sync::mpsc::Sender<CompactProgress>,
+) -> Result<CompactReport, CompactError> {
+    compact_check_version(options, common_options)?;
+
+    let local_path = &find_borg(common_options)?;
+
+    let args = compact_fmt_args(options, common_options, true);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(CompactError::ShlexError)?;
+    let mut child = tokio::process::Command::from(borg_command(local_path, &None, common_options))
         .args(args)
-        .output()
-        .await?;
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
 
-    compact_parse_output(res)?;
+    with_timeout(common_options.timeout, async move {
+        let stderr = child.stderr.take().ok_or(CompactError::PipeFailed)?;
+        let mut stderr_reader = BufReader::new(stderr).lines();
 
-    info!("Finished compacting");
+        // Everything except the progress is kept to be parsed after borg exited
+        let mut stderr = Vec::new();
+
+        while let Some(line) = stderr_reader
+            .next_line()
+            .await
+            .map_err(CompactError::InvalidBorgOutput)?
+        {
+            if let Ok(LoggingMessage::ProgressPercent {
+                msg_id: Some(MessageId::RepositoryCompactSegments),
+                current,
+                total,
+                finished,
+                ..
+            }) = LoggingMessage::from_str(&line)
+            {
+                trace!("Progress: {line}");
+                if let Err(err) = progress_channel
+                    .send(CompactProgress {
+                        current,
+                        total,
+                        finished,
+                    })
+                    .await
+                {
+                    error!("Could not send to progress channel: {err}");
+                }
+
+                continue;
+            }
+
+            stderr.extend_from_slice(line.as_bytes());
+            stderr.push(b'\n');
+        }
+
+        let status = child.wait().await?;
+        debug!("Child process exited with {status}");
+
+        let report = compact_parse_output(
+            Output {
+                status,
+                stdout: Vec::new(),
+                stderr,
+            },
+            common_options,
+        )?;
+
+        info!("Finished compacting");
+
+        Ok(report)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::Path;
+
+    use crate::asynchronous::compact_progress;
+    use crate::common::CompactOptions;
+    use crate::test_utils::fake_borg;
+
+    #[tokio::test]
+    async fn test_compact_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo '{"type": "progress_percent", "operation": 1, "msgid": "repository.compact_segments", "time": 1.0, "finished": false, "current": 1, "total": 2, "info": null}' >&2
+echo '{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.repository", "message": "compacting"}' >&2
+echo '{"type": "progress_percent", "operation": 1, "msgid": "repository.compact_segments", "time": 1.0, "finished": true}' >&2
+echo '{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.repository", "message": "compaction freed about 12.50 kB repository space."}' >&2
+"#,
+        );
+        let options = CompactOptions::new(Path::new("/tmp/repo"));
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let report = compact_progress(&options, &common_options, tx)
+            .await
+            .unwrap();
+        assert_eq!(report.freed_size, Some(12_500));
 
-    Ok(())
+        let progress = rx.recv().await.unwrap();
+        assert_eq!(
+            (progress.current, progress.total, progress.finished),
+            (Some(1), Some(2), false)
+        );
+        let progress = rx.recv().await.unwrap();
+        assert_eq!(
+            (progress.current, progress.total, progress.finished),
+            (None, None, true)
+        );
+        assert!(rx.recv().await.is_none());
+    }
 }
@@ -0,0 +1,36 @@
+use log::{debug, info};
+
+use crate::asynchronous::execute_borg;
+use crate::common::{config_fmt_args, config_parse_output, CommonOptions, ConfigOptions};
+use crate::errors::ConfigError;
+use crate::utils::find_borg;
+
+/// Read or set a setting of the config of a repository
+///
+/// Returns the current value if [ConfigOptions::value] is [None],
+/// otherwise the value is set and [None] is returned.
+///
+/// **Parameter**:
+/// - `options`: Reference to [ConfigOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %options.repository, key = options.key))
+)]
+pub async fn config(
+    options: &ConfigOptions,
+    common_options: &CommonOptions,
+) -> Result<Option<String>, ConfigError> {
+    let local_path = &find_borg(common_options)?;
+
+    let args = config_fmt_args(options, common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(ConfigError::ShlexError)?;
+    let res = execute_borg(local_path, args, &options.passphrase, common_options).await?;
+
+    let value = config_parse_output(res, options, common_options)?;
+
+    info!("Finished config");
+
+    Ok(value)
+}
@@ -1,15 +1,29 @@
-use std::fmt::{Display, Formatter, Write};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::process::Output;
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
 
-use log::{debug, error, info, trace, warn};
-use serde::{Deserialize, Serialize};
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use futures_core::Stream;
 
-use crate::asynchronous::execute_borg;
-use crate::common::{create_fmt_args, create_parse_output, CommonOptions, CreateOptions};
+use log::{debug, error, info, trace};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::process::Child;
+use tokio::sync::oneshot;
+
+use crate::asynchronous::{execute_borg, execute_borg_with_stdin, info, list, with_timeout};
+use crate::common::{
+    create_fmt_args, create_parse_output, CommonOptions, CreateOptions, CreateSource,
+    CreateTempFiles, InfoOptions, ListOptions,
+};
 use crate::errors::CreateError;
-use crate::output::create::Create;
-use crate::output::logging::{LevelName, LoggingMessage, MessageId};
+use crate::output::create::{Create, CreateEvent, CreateOutcome, CreateProgress};
+use crate::output::list::is_checkpoint;
+use crate::output::logging::LoggingMessage;
+use crate::output::progress::ProgressParser;
+use crate::utils::{borg_command, find_borg};
 
 /// This command creates a backup archive containing all files found
 /// while recursively traversing all paths specified.
@@ -19,64 +33,146 @@ use crate::output::logging::{LevelName, LoggingMessage, MessageId};
 /// **Parameter**:
 /// - `options`: Reference to [CreateOptions]
 /// - `common_options`: Reference to [CommonOptions]
+///
+/// Returns [None] if [CreateOptions::dry_run] is set, as no archive is created.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.archive.repository(),
+            archive = options.archive.archive(),
+        )
+    )
+)]
 pub async fn create(
     options: &CreateOptions,
     common_options: &CommonOptions,
-) -> Result<Create, CreateError> {
-    let local_path = common_options.local_path.as_ref().map_or("borg", |x| x);
+) -> Result<Option<Create>, CreateError> {
+    let res = create_raw(options, common_options).await?;
 
-    let args = create_fmt_args(options, common_options, false);
-    debug!("Calling borg: {local_path} {args}");
-    let args = shlex::split(&args).ok_or(CreateError::ShlexError)?;
-    let res = execute_borg(local_path, args, &options.passphrase).await?;
-
-    let stats = create_parse_output(res)?;
+    let stats = create_parse_output(res, options, common_options)?;
 
     info!("Finished creating archive");
 
     Ok(stats)
 }
 
-/// The progress of a borg create command.
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub enum CreateProgress {
-    /// The current progress of the archive
-    Progress {
-        /// The size of the original files
-        original_size: u64,
-        /// The size of the compressed files
-        compressed_size: u64,
-        /// The size of the deduplicated files
-        deduplicated_size: u64,
-        /// The current number of files
-        nfiles: u64,
-        /// The path to the current file
-        path: String,
-    },
-    /// Finished the creation of the archive
-    Finished,
-}
-
-impl Display for CreateProgress {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            CreateProgress::Progress {
-                original_size,
-                compressed_size,
-                deduplicated_size,
-                nfiles,
-                path,
-            } => {
-                write!(
-                    f,
-                    "O {original_size} C {compressed_size} D {deduplicated_size} N {nfiles} {path}",
-                )
-            }
-            CreateProgress::Finished => write!(f, "Finished"),
+/// Run the borg create command without parsing its output, see [create].
+///
+/// **Parameter**:
+/// - `options`: Reference to [CreateOptions]
+/// - `common_options`: Reference to [CommonOptions]
+pub async fn create_raw(
+    options: &CreateOptions,
+    common_options: &CommonOptions,
+) -> Result<Output, CreateError> {
+    if let CreateSource::Stdin { .. } = options.source {
+        return Err(CreateError::MissingStdin);
+    }
+
+    let local_path = &find_borg(common_options)?;
+
+    let temp_files = CreateTempFiles::new(options)?;
+    let args = create_fmt_args(options, common_options, false, &temp_files)?;
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(CreateError::ShlexError)?;
+    let res = match temp_files.open_paths()? {
+        Some(paths) => {
+            let paths = tokio::fs::File::from_std(paths);
+            execute_borg_with_stdin(local_path, args, &options.passphrase, common_options, paths)
+                .await?
+        }
+        None => execute_borg(local_path, args, &options.passphrase, common_options).await?,
+    };
+
+    Ok(res)
+}
+
+/// Like [create], but an already existing archive is not an error.
+///
+/// If the archive already exists, e.g. because a job is retried after borg
+/// created the archive, the information of the existing archive is returned instead.
+/// Fetching it requires a second invocation of borg info, so its stats are those
+/// reported by borg info, not the stats of the original run of borg create.
+///
+/// **Parameter**:
+/// - `options`: Reference to [CreateOptions]
+/// - `common_options`: Reference to [CommonOptions]
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.archive.repository(),
+            archive = options.archive.archive(),
+        )
+    )
+)]
+pub async fn create_idempotent(
+    options: &CreateOptions,
+    common_options: &CommonOptions,
+) -> Result<CreateOutcome, CreateError> {
+    match create(options, common_options).await {
+        Ok(stats) => Ok(CreateOutcome::Created(stats)),
+        Err(CreateError::ArchiveAlreadyExists) => {
+            info!("Archive already exists, retrieving its info");
+
+            let mut info_options = InfoOptions::new(options.archive.repository().clone());
+            info_options.passphrase = options.passphrase.clone();
+            info_options.archive = Some(options.archive.archive().to_string());
+            let info = info(&info_options, common_options)
+                .await
+                .map_err(CreateError::ExistingArchiveInfo)?;
+
+            Ok(CreateOutcome::Existing(info))
         }
+        Err(err) => Err(err),
     }
 }
 
+/// This command creates a backup archive containing the data read from `stdin`.
+///
+/// This is intended to be used with [CreateSource::Stdin], which specifies the metadata
+/// of the file in the archive.
+///
+/// **Parameter**:
+/// - `options`: Reference to [CreateOptions]
+/// - `common_options`: Reference to [CommonOptions]
+///
+/// Returns [None] if [CreateOptions::dry_run] is set, as no archive is created.
+/// - `stdin`: The data that should be archived
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.archive.repository(),
+            archive = options.archive.archive(),
+        )
+    )
+)]
+pub async fn create_stdin(
+    options: &CreateOptions,
+    common_options: &CommonOptions,
+    stdin: impl AsyncRead + Unpin,
+) -> Result<Option<Create>, CreateError> {
+    let local_path = &find_borg(common_options)?;
+
+    let temp_files = CreateTempFiles::new(options)?;
+    let args = create_fmt_args(options, common_options, false, &temp_files)?;
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(CreateError::ShlexError)?;
+    let res = execute_borg_with_stdin(local_path, args, &options.passphrase, common_options, stdin)
+        .await?;
+
+    let stats = create_parse_output(res, options, common_options)?;
+
+    info!("Finished creating archive");
+
+    Ok(stats)
+}
+
 /// This command creates a backup archive containing all files found
 /// while recursively traversing all paths specified.
 /// Paths are added to the archive as they are given,
@@ -88,121 +184,758 @@ impl Display for CreateProgress {
 /// - `options`: Reference to [CreateOptions]
 /// - `common_options`: Reference to [CommonOptions]
 /// - `progress_channel`: A [tokio::sync::mpsc::Sender] of [CreateProgress]. On every progress
-/// update, a message will be sent to this channel
+///   update, a message will be sent to this channel
+///
+/// Returns [None] if [CreateOptions::dry_run] is set, as no archive is created.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.archive.repository(),
+            archive = options.archive.archive(),
+        )
+    )
+)]
 pub async fn create_progress(
     options: &CreateOptions,
     common_options: &CommonOptions,
     progress_channel: tokio::sync::mpsc::Sender<CreateProgress>,
-) -> Result<Create, CreateError> {
-    let local_path = common_options.local_path.as_ref().map_or("borg", |x| x);
+) -> Result<Option<Create>, CreateError> {
+    let (_handle, create) =
+        create_progress_with_handle(options, common_options, progress_channel).await?;
+    create.await
+}
+
+/// A handle to a running borg create process, see [create_progress_with_handle]
+#[derive(Debug)]
+pub struct CreateHandle {
+    id: Option<u32>,
+    interrupt: Option<oneshot::Sender<()>>,
+}
 
-    let args = create_fmt_args(options, common_options, true);
+impl CreateHandle {
+    /// The process id of the borg process.
+    ///
+    /// Returns [None] if the process had already exited when it was spawned.
+    pub fn id(&self) -> Option<u32> {
+        self.id
+    }
+
+    /// Request borg to stop gracefully, by sending `SIGINT` to the borg process.
+    ///
+    /// On `SIGINT`, borg create saves a checkpoint archive with the files that were backed up
+    /// so far and exits. The next run of borg create only has to back up the remaining files,
+    /// as their chunks are already in the repository.
+    ///
+    /// On platforms without signals, borg is killed instead and no checkpoint is saved.
+    ///
+    /// Calling this more than once has no effect.
+    pub fn interrupt(&mut self) {
+        if let Some(interrupt) = self.interrupt.take() {
+            // The receiver is gone if borg already exited
+            let _ = interrupt.send(());
+        }
+    }
+}
+
+/// Like [create_progress], but a [CreateHandle] is returned alongside the future that
+/// drives borg create.
+///
+/// The handle provides the process id of borg and allows to interrupt it gracefully,
+/// see [CreateHandle::interrupt]. Dropping the handle doesn't affect borg.
+/// The future has to be polled for borg to make progress, dropping it kills borg.
+///
+/// **Parameter**:
+/// - `options`: Reference to [CreateOptions]
+/// - `common_options`: Reference to [CommonOptions]
+/// - `progress_channel`: A [tokio::sync::mpsc::Sender] of [CreateProgress]. On every progress
+///   update, a message will be sent to this channel
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.archive.repository(),
+            archive = options.archive.archive(),
+        )
+    )
+)]
+pub async fn create_progress_with_handle<'a>(
+    options: &'a CreateOptions,
+    common_options: &'a CommonOptions,
+    progress_channel: tokio::sync::mpsc::Sender<CreateProgress>,
+) -> Result<
+    (
+        CreateHandle,
+        impl Future<Output = Result<Option<Create>, CreateError>> + 'a,
+    ),
+    CreateError,
+> {
+    let (child, temp_files) = spawn_create_progress(options, common_options)?;
+
+    let (interrupt_tx, interrupt_rx) = oneshot::channel();
+    let handle = CreateHandle {
+        id: child.id(),
+        interrupt: Some(interrupt_tx),
+    };
+    let interrupt = async move {
+        // A dropped handle never interrupts borg
+        if interrupt_rx.await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+
+    let create = wait_create_progress(
+        child,
+        temp_files,
+        options,
+        common_options,
+        progress_channel,
+        interrupt,
+    );
+    Ok((handle, create))
+}
+
+/// Like [create_progress], but borg is stopped gracefully once `cancel` completes.
+///
+/// `cancel` can be any future, e.g. `CancellationToken::cancelled` of `tokio-util`.
+/// On cancellation, `SIGINT` is sent to borg instead of killing it,
+/// so borg saves a checkpoint archive with the files that were backed up so far.
+/// The next run of borg create can resume from it, as the chunks of these files are
+/// already in the repository.
+///
+/// If borg was cancelled, [CreateError::Cancelled] is returned, which tells whether this run
+/// saved a new checkpoint of the archive. To tell them apart, the checkpoints which already
+/// exist are listed before borg create is started. If borg finished before it received the signal,
+/// the archive is returned as usual.
+///
+/// **Parameter**:
+/// - `options`: Reference to [CreateOptions]
+/// - `common_options`: Reference to [CommonOptions]
+/// - `progress_channel`: A [tokio::sync::mpsc::Sender] of [CreateProgress]. On every progress
+///   update, a message will be sent to this channel
+/// - `cancel`: A future which cancels borg create when it completes
+///
+/// Returns [None] if [CreateOptions::dry_run] is set, as no archive is created.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.archive.repository(),
+            archive = options.archive.archive(),
+        )
+    )
+)]
+pub async fn create_progress_cancellable(
+    options: &CreateOptions,
+    common_options: &CommonOptions,
+    progress_channel: tokio::sync::mpsc::Sender<CreateProgress>,
+    cancel: impl Future<Output = ()>,
+) -> Result<Option<Create>, CreateError> {
+    let existing_checkpoints = list_checkpoints(options, common_options).await?;
+
+    let (child, temp_files) = spawn_create_progress(options, common_options)?;
+
+    let cancelled = AtomicBool::new(false);
+    let cancel = async {
+        cancel.await;
+        cancelled.store(true, Ordering::Relaxed);
+    };
+
+    let res = wait_create_progress(
+        child,
+        temp_files,
+        options,
+        common_options,
+        progress_channel,
+        cancel,
+    )
+    .await;
+    if !cancelled.load(Ordering::Relaxed) {
+        return res;
+    }
+
+    match res {
+        Ok(create) => Ok(create),
+        Err(err) => {
+            debug!("borg create was cancelled: {err}");
+
+            let checkpoint_created = list_checkpoints(options, common_options)
+                .await?
+                .iter()
+                .any(|x| !existing_checkpoints.contains(x));
+            info!("Cancelled creating archive, checkpoint created: {checkpoint_created}");
+
+            Err(CreateError::Cancelled { checkpoint_created })
+        }
+    }
+}
+
+/// Like [create_progress], but the progress is returned as a [Stream] instead of
+/// being sent to a channel.
+///
+/// Every progress update is yielded as [CreateEvent::Progress]. Once borg finished,
+/// [CreateEvent::Finished] with the stats of the archive is yielded as last item.
+/// If borg failed, the error is yielded as last item instead.
+///
+/// borg only runs while the stream is polled, dropping the stream kills borg.
+///
+/// **Parameter**:
+/// - `options`: Reference to [CreateOptions]
+/// - `common_options`: Reference to [CommonOptions]
+pub fn create_stream<'a>(
+    options: &'a CreateOptions,
+    common_options: &'a CommonOptions,
+) -> impl Stream<Item = Result<CreateEvent, CreateError>> + 'a {
+    let (tx, rx) = tokio::sync::mpsc::channel(PROGRESS_STREAM_BUFFER);
+    CreateStream {
+        create: Some(Box::pin(create_progress(options, common_options, tx))),
+        result: None,
+        progress: rx,
+    }
+}
+
+/// The number of progress updates that are buffered by [create_stream]
+const PROGRESS_STREAM_BUFFER: usize = 16;
+
+/// The stream returned by [create_stream]
+///
+/// The progress is passed through a channel, which is drained while borg create is polled.
+struct CreateStream<F> {
+    /// The future running borg, which is [None] once it finished
+    create: Option<Pin<Box<F>>>,
+    /// The result of borg, which is yielded after all progress was yielded
+    result: Option<Result<Option<Create>, CreateError>>,
+    progress: tokio::sync::mpsc::Receiver<CreateProgress>,
+}
+
+impl<F> Stream for CreateStream<F>
+where
+    F: Future<Output = Result<Option<Create>, CreateError>>,
+{
+    type Item = Result<CreateEvent, CreateError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(create) = self.create.as_mut() {
+            if let Poll::Ready(res) = create.as_mut().poll(cx) {
+                self.create = None;
+                self.result = Some(res);
+            }
+        }
+
+        // The channel is closed once borg finished and all progress was received
+        match self.progress.poll_recv(cx) {
+            Poll::Ready(Some(progress)) => Poll::Ready(Some(Ok(CreateEvent::Progress(progress)))),
+            Poll::Ready(None) => Poll::Ready(
+                self.result
+                    .take()
+                    .map(|res| res.map(|create| CreateEvent::Finished(create.map(Box::new)))),
+            ),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Spawn borg create, which reports its progress on stderr
+fn spawn_create_progress(
+    options: &CreateOptions,
+    common_options: &CommonOptions,
+) -> Result<(Child, CreateTempFiles), CreateError> {
+    if let CreateSource::Stdin { .. } = options.source {
+        return Err(CreateError::MissingStdin);
+    }
+
+    let local_path = &find_borg(common_options)?;
+
+    let temp_files = CreateTempFiles::new(options)?;
+    let args = create_fmt_args(options, common_options, true, &temp_files)?;
     debug!("Calling borg: {local_path} {args}");
     let args = shlex::split(&args).ok_or(CreateError::ShlexError)?;
-    let mut child = if let Some(passphrase) = &options.passphrase {
-        tokio::process::Command::new(local_path)
-            .env("BORG_PASSPHRASE", passphrase)
-            .args(args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .kill_on_drop(true)
-            .spawn()?
-    } else {
-        tokio::process::Command::new(local_path)
-            .args(args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .kill_on_drop(true)
-            .spawn()?
-    };
+    let child = tokio::process::Command::from(borg_command(
+        local_path,
+        &options.passphrase,
+        common_options,
+    ))
+    .args(args)
+    .stdin(temp_files.open_paths()?.map_or(Stdio::null(), Stdio::from))
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .kill_on_drop(true)
+    .spawn()?;
 
-    let mut stdout = child.stdout.take().ok_or(CreateError::PipeFailed)?;
-    let stderr = child.stderr.take().ok_or(CreateError::PipeFailed)?;
-
-    let mut stderr_reader = BufReader::new(stderr).lines();
-
-    let mut output = String::new();
-
-    loop {
-        tokio::select! {
-            result = stderr_reader.next_line() => match result {
-                Ok(Some(line)) => {
-                    writeln!(output, "{line}").unwrap();
-                    let res: LoggingMessage = serde_json::from_str(&line)?;
-
-                    if let LoggingMessage::ArchiveProgress {
-                        original_size,
-                        compressed_size,
-                        deduplicated_size,
-                        nfiles,
-                        path,
-                        finished,
-                        ..
-                    } = res {
-                        if finished {
-                            trace!("Progress: finished");
-                            if let Err(err) = progress_channel.send(CreateProgress::Finished).await {
-                                error!("Could not send to progress channel: {err}");
+    Ok((child, temp_files))
+}
+
+/// Wait for borg create to exit, while sending its progress to the channel.
+///
+/// borg is interrupted gracefully once `interrupt` completes.
+/// The temporary files are kept until borg exited.
+async fn wait_create_progress(
+    mut child: Child,
+    _temp_files: CreateTempFiles,
+    options: &CreateOptions,
+    common_options: &CommonOptions,
+    progress_channel: tokio::sync::mpsc::Sender<CreateProgress>,
+    interrupt: impl Future<Output = ()>,
+) -> Result<Option<Create>, CreateError> {
+    with_timeout(common_options.timeout, async move {
+        let mut stdout = child.stdout.take().ok_or(CreateError::PipeFailed)?;
+        let stderr = child.stderr.take().ok_or(CreateError::PipeFailed)?;
+
+        // stderr is read until borg closes it, so no progress is lost when borg exits.
+        // stdout has to be drained meanwhile, otherwise borg may block.
+        let (stderr_res, stdout_res) = {
+            let output = async {
+                tokio::join!(
+                    async {
+                        let mut stderr_reader = BufReader::new(stderr).lines();
+                        let mut parser = ProgressParser::new();
+                        // Everything except the progress is kept to be parsed after borg exited
+                        let mut stderr = Vec::new();
+
+                        while let Some(line) = stderr_reader
+                            .next_line()
+                            .await
+                            .map_err(CreateError::InvalidBorgOutput)?
+                        {
+                            let progress = LoggingMessage::from_str(&line).ok().and_then(|res| {
+                                let progress = parser.parse_message(&res)?;
+                                common_options.notify_log_callback(&res);
+                                Some(progress)
+                            });
+                            if let Some(progress) = progress {
+                                trace!("Progress: {progress}");
+                                if let Err(err) = progress_channel.send(progress).await {
+                                    error!("Could not send to progress channel: {err}");
+                                }
+                                continue;
                             }
 
-                            continue;
+                            stderr.extend_from_slice(line.as_bytes());
+                            stderr.push(b'\n');
                         }
+                        Ok::<_, CreateError>(stderr)
+                    },
+                    async {
+                        let mut buf = Vec::new();
+                        stdout.read_to_end(&mut buf).await.map(|_| buf)
+                    }
+                )
+            };
+            tokio::pin!(output);
+            tokio::pin!(interrupt);
 
-                        if let Err(err) = progress_channel.send(CreateProgress::Progress {
-                            original_size: original_size.unwrap(),
-                            compressed_size: compressed_size.unwrap(),
-                            deduplicated_size: deduplicated_size.unwrap(),
-                            nfiles: nfiles.unwrap(),
-                            path: path.unwrap(),
-                        }).await {
-                            error!("Could not send to progress channel: {err}");
-                        }
-                    } else if let LoggingMessage::LogMessage {
-                        name,
-                        message,
-                        level_name,
-                        time,
-                        msg_id,
-                    } = res {
-                        match level_name {
-                            LevelName::Debug => debug!("{time} {name}: {message}"),
-                            LevelName::Info => info!("{time} {name}: {message}"),
-                            LevelName::Warning => warn!("{time} {name}: {message}"),
-                            LevelName::Error => error!("{time} {name}: {message}"),
-                            LevelName::Critical => error!("{time} {name}: {message}"),
-                        }
+            tokio::select! {
+                res = &mut output => res,
+                _ = &mut interrupt => {
+                    info!("Interrupting borg, it saves a checkpoint and exits");
+                    send_sigint(&mut child)?;
+                    output.await
+                }
+            }
+        };
+        let stderr = stderr_res?;
+        let stdout = stdout_res.map_err(CreateError::InvalidBorgOutput)?;
 
-                        if let Some(MessageId::RepositoryAlreadyExists) = msg_id {
-                            return Err(CreateError::ArchiveAlreadyExists);
-                        }
+        let status = child.wait().await?;
+        debug!("Child process exited with {status}");
 
-                    }
-                },
-                Err(_) => break,
-                _ => (),
+        let stats = create_parse_output(
+            Output {
+                status,
+                stdout,
+                stderr,
             },
-            result = child.wait() => {
-                if let Ok(exit_code) = result {
-                    debug!("Child process exited with {exit_code}");
-                    if exit_code.code().unwrap() > 1 {
-                        return Err(CreateError::Unknown(output));
-                    }
-                }
-                break // child process exited
+            options,
+            common_options,
+        )?;
+
+        info!("Finished creating archive");
+
+        Ok(stats)
+    })
+    .await
+}
+
+/// List the names of the checkpoints of the archive that is created
+async fn list_checkpoints(
+    options: &CreateOptions,
+    common_options: &CommonOptions,
+) -> Result<Vec<String>, CreateError> {
+    let archive = options.archive.archive();
+    let mut list_options = ListOptions::new(options.archive.repository().clone());
+    list_options.passphrase = options.passphrase.clone();
+    list_options.glob_archives = Some(format!("{}.checkpoint*", escape_glob(archive)));
+    list_options.consider_checkpoints = true;
+    let list = list(&list_options, common_options)
+        .await
+        .map_err(CreateError::CheckpointList)?;
+
+    Ok(list
+        .archives
+        .into_iter()
+        .map(|x| x.name)
+        .filter(|name| {
+            let Some(suffix) = name.strip_prefix(archive) else {
+                return false;
+            };
+            match suffix.strip_prefix(".checkpoint") {
+                Some("") => true,
+                Some(n) => is_checkpoint(suffix) && !n[1..].contains('.'),
+                None => false,
             }
+        })
+        .collect())
+}
+
+/// Escape the special characters of a shell glob, so the name is matched literally
+fn escape_glob(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '*' | '?' | '[' | ']' => {
+                escaped.push('[');
+                escaped.push(c);
+                escaped.push(']');
+            }
+            _ => escaped.push(c),
         }
     }
+    escaped
+}
 
-    let mut stdout_str = String::new();
-    stdout
-        .read_to_string(&mut stdout_str)
-        .await
-        .map_err(CreateError::InvalidBorgOutput)?;
+/// Send `SIGINT` to borg, which makes borg create save a checkpoint and exit
+#[cfg(unix)]
+fn send_sigint(child: &mut Child) -> io::Result<()> {
+    // borg already exited if it has no pid anymore
+    let Some(pid) = child.id() else {
+        return Ok(());
+    };
+    // SAFETY: kill doesn't access memory of this process
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGINT) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
 
-    trace!("Parsing stats: {stdout_str}");
-    let stats: Create = serde_json::from_str(&stdout_str)?;
+/// There are no signals on this platform, so borg is killed without saving a checkpoint
+#[cfg(not(unix))]
+fn send_sigint(child: &mut Child) -> io::Result<()> {
+    child.start_kill()
+}
 
-    info!("Finished creating archive");
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::pin::pin;
 
-    Ok(stats)
+    use futures_core::Stream;
+
+    use crate::asynchronous::{
+        create, create_progress, create_progress_cancellable, create_progress_with_handle,
+        create_stdin, create_stream,
+    };
+    use crate::common::{CreateOptions, CreateSource, PATHS_FROM_FILE_THRESHOLD};
+    use crate::errors::CreateError;
+    use crate::output::create::{CreateEvent, CreateProgress};
+    use crate::test_utils::fake_borg;
+
+    #[tokio::test]
+    async fn test_create_progress_terminated_by_signal() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(&dir, "#!/bin/sh\nkill -TERM $$\nsleep 30\n");
+        let options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/etc".to_string()],
+            vec![],
+        );
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let res = create_progress(&options, &common_options, tx).await;
+        assert!(
+            matches!(res, Err(CreateError::TerminatedBySignal)),
+            "{res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_progress_invalid_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo 'Traceback (most recent call last):' >&2
+echo '{"type": "archive_progress", "original_size": 100, "compressed_size": 50, "deduplicated_size": 25, "nfiles": 1, "path": "/etc/hosts", "time": 1.0, "finished": false}' >&2
+echo '{"type": "archive_progr' >&2
+echo '{"type": "archive_progress", "time": 2.0, "finished": true}' >&2
+echo '{"archive": {"command_line": ["borg"], "duration": 0.1, "end": "2023-01-02T10:00:01.000000", "id": "0b1e", "limits": {"max_archive_size": 0.0}, "name": "archive", "start": "2023-01-02T10:00:00.000000", "stats": {"compressed_size": 50, "deduplicated_size": 25, "nfiles": 1, "original_size": 100}}, "repository": {"id": "e2ff", "last_modified": "2023-01-02T10:00:01.000000", "location": "/tmp/repo"}}'
+"#,
+        );
+        let options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/etc".to_string()],
+            vec![],
+        );
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let create = create_progress(&options, &common_options, tx)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(create.archive.stats.nfiles, 1);
+
+        assert!(matches!(
+            rx.recv().await,
+            Some(CreateProgress::Progress { nfiles: 1, .. })
+        ));
+        assert!(matches!(rx.recv().await, Some(CreateProgress::Finished)));
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_create_progress_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/etc".to_string()],
+            vec![],
+        );
+
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo '{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "Archive archive already exists", "msgid": "Archive.AlreadyExists"}' >&2
+exit 2
+"#,
+        );
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let res = create_progress(&options, &common_options, tx).await;
+        assert!(
+            matches!(res, Err(CreateError::ArchiveAlreadyExists)),
+            "{res:?}"
+        );
+
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo '{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "passphrase supplied in BORG_PASSPHRASE is incorrect", "msgid": "PassphraseWrong"}' >&2
+exit 2
+"#,
+        );
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let res = create_progress(&options, &common_options, tx).await;
+        assert!(matches!(res, Err(CreateError::PassphraseWrong)), "{res:?}");
+
+        // Modern borg reports the error by its exit code only
+        let common_options = fake_borg(&dir, "#!/bin/sh\nexit 52\n");
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let res = create_progress(&options, &common_options, tx).await;
+        assert!(matches!(res, Err(CreateError::PassphraseWrong)), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn test_create_progress_with_handle_interrupt() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let common_options = fake_borg(
+            &dir,
+            &format!(
+                r#"#!/bin/sh
+trap 'echo "{{\"archive\": {{\"command_line\": [\"borg\"], \"duration\": 0.1, \"end\": \"2023-01-02T10:00:01.000000\", \"id\": \"0b1e\", \"limits\": {{\"max_archive_size\": 0.0}}, \"name\": \"archive.checkpoint\", \"start\": \"2023-01-02T10:00:00.000000\", \"stats\": {{\"compressed_size\": 50, \"deduplicated_size\": 25, \"nfiles\": 1, \"original_size\": 100}}}}, \"repository\": {{\"id\": \"e2ff\", \"last_modified\": \"2023-01-02T10:00:01.000000\", \"location\": \"/tmp/repo\"}}}}"; exit 0' INT
+echo $$ > {}
+while :; do sleep 0.1; done
+"#,
+                pid_file.display()
+            ),
+        );
+        let options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/etc".to_string()],
+            vec![],
+        );
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let (mut handle, create) = create_progress_with_handle(&options, &common_options, tx)
+            .await
+            .unwrap();
+
+        // Wait for the trap to be installed
+        let pid = loop {
+            match fs::read_to_string(&pid_file) {
+                Ok(pid) if pid.ends_with('\n') => break pid,
+                _ => tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+            }
+        };
+        assert_eq!(handle.id(), Some(pid.trim().parse().unwrap()));
+
+        handle.interrupt();
+        handle.interrupt();
+        let create = create.await.unwrap().unwrap();
+        assert_eq!(create.archive.name, "archive.checkpoint");
+    }
+
+    #[tokio::test]
+    async fn test_create_progress_cancellable() {
+        let dir = tempfile::tempdir().unwrap();
+        let started = dir.path().join("started");
+        let common_options = fake_borg(
+            &dir,
+            &format!(
+                r#"#!/bin/sh
+case "$*" in
+*" list "*)
+    if [ -e {0} ]; then
+        printf '%s\n' '{{"repository": {{"id": "e2ff", "last_modified": "2023-01-02T10:00:01.000000", "location": "/tmp/repo"}}, "archives": [{{"archive": "archive.checkpoint", "name": "archive.checkpoint", "id": "0b1e", "start": "2023-01-02T10:00:00.000000", "time": "2023-01-02T10:00:00.000000"}}]}}'
+    else
+        printf '%s\n' '{{"repository": {{"id": "e2ff", "last_modified": "2023-01-02T10:00:01.000000", "location": "/tmp/repo"}}, "archives": []}}'
+    fi
+    ;;
+*)
+    trap 'echo "Got Ctrl-C / SIGINT." >&2; exit 2' INT
+    touch {0}
+    while :; do sleep 0.1; done
+    ;;
+esac
+"#,
+                started.display()
+            ),
+        );
+        let options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/etc".to_string()],
+            vec![],
+        );
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let cancel = async {
+            // Wait for the trap to be installed
+            while !started.exists() {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        };
+        let res = create_progress_cancellable(&options, &common_options, tx, cancel).await;
+        assert!(
+            matches!(
+                res,
+                Err(CreateError::Cancelled {
+                    checkpoint_created: true
+                })
+            ),
+            "{res:?}"
+        );
+
+        // The checkpoint of the previous run is not reported again
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let cancel = tokio::time::sleep(std::time::Duration::from_millis(100));
+        let res = create_progress_cancellable(&options, &common_options, tx, cancel).await;
+        assert!(
+            matches!(
+                res,
+                Err(CreateError::Cancelled {
+                    checkpoint_created: false
+                })
+            ),
+            "{res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo '{"type": "archive_progress", "original_size": 100, "compressed_size": 50, "deduplicated_size": 25, "nfiles": 1, "path": "/etc/hosts", "time": 1.0, "finished": false}' >&2
+echo '{"type": "archive_progress", "time": 2.0, "finished": true}' >&2
+echo '{"archive": {"command_line": ["borg"], "duration": 0.1, "end": "2023-01-02T10:00:01.000000", "id": "0b1e", "limits": {"max_archive_size": 0.0}, "name": "archive", "start": "2023-01-02T10:00:00.000000", "stats": {"compressed_size": 50, "deduplicated_size": 25, "nfiles": 1, "original_size": 100}}, "repository": {"id": "e2ff", "last_modified": "2023-01-02T10:00:01.000000", "location": "/tmp/repo"}}'
+"#,
+        );
+        let options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/etc".to_string()],
+            vec![],
+        );
+
+        let mut stream = pin!(create_stream(&options, &common_options));
+        let mut events = Vec::new();
+        while let Some(event) = std::future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+            events.push(event.unwrap());
+        }
+
+        assert_eq!(events.len(), 3, "{events:?}");
+        assert!(matches!(
+            events[0],
+            CreateEvent::Progress(CreateProgress::Progress { nfiles: 1, .. })
+        ));
+        assert!(matches!(
+            events[1],
+            CreateEvent::Progress(CreateProgress::Finished)
+        ));
+        assert!(
+            matches!(&events[2], CreateEvent::Finished(Some(create)) if create.archive.stats.nfiles == 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_stdin_passphrase_wrong() {
+        let dir = tempfile::tempdir().unwrap();
+        // borg exits without reading its input
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo '{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "passphrase supplied in BORG_PASSPHRASE is incorrect", "msgid": "PassphraseWrong"}' >&2
+exit 2
+"#,
+        );
+        let mut options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec![],
+            vec![],
+        );
+        options.source = CreateSource::Stdin {
+            name: "dump.sql".to_string(),
+            mode: None,
+            user: None,
+            group: None,
+        };
+
+        // The input exceeds the pipe buffer, so writing it fails once borg exited
+        let input = vec![0u8; 4 * 1024 * 1024];
+        let res = create_stdin(&options, &common_options, input.as_slice()).await;
+        assert!(matches!(res, Err(CreateError::PassphraseWrong)), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn test_create_large_paths_borg_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        // borg exits without reading the paths from stdin
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo '{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "Archive archive already exists", "msgid": "Archive.AlreadyExists"}' >&2
+exit 2
+"#,
+        );
+        // The paths exceed the threshold and the pipe buffer
+        let paths: Vec<_> = (0..50_000).map(|x| format!("/srv/data/file-{x}")).collect();
+        assert!(paths.iter().map(|x| x.len() + 1).sum::<usize>() > 4 * PATHS_FROM_FILE_THRESHOLD);
+        let options =
+            CreateOptions::new(Path::new("/tmp/repo"), "archive".to_string(), paths, vec![]);
+
+        let res = create(&options, &common_options).await;
+        assert!(
+            matches!(res, Err(CreateError::ArchiveAlreadyExists)),
+            "{res:?}"
+        );
+    }
 }
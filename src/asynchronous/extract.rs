@@ -0,0 +1,86 @@
+use log::{debug, info};
+
+use crate::asynchronous::{execute_borg, execute_borg_in_dir};
+use crate::common::{
+    extract_fmt_args, extract_parse_output, list_items_parse_output, list_stream_fmt_args,
+    CommonOptions, ExtractOptions, ListOptions,
+};
+use crate::errors::ExtractError;
+use crate::utils::find_borg;
+
+/// Extract the contents of an archive into [ExtractOptions::destination].
+///
+/// The paths of the archive are extracted relative to the destination.
+/// Use [ExtractOptions::strip_components] to remove leading path elements.
+///
+/// Returns the paths of the extracted files if [ExtractOptions::list_files] is set.
+/// If [ExtractOptions::verify_sizes] is set, differing sizes are returned as
+/// [ExtractError::SizeMismatch].
+///
+/// **Parameter**:
+/// - `options`: Reference to [ExtractOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.archive.repository(),
+            archive = options.archive.archive(),
+        )
+    )
+)]
+pub async fn extract(
+    options: &ExtractOptions,
+    common_options: &CommonOptions,
+) -> Result<Vec<String>, ExtractError> {
+    let local_path = &find_borg(common_options)?;
+
+    let args = extract_fmt_args(options, common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(ExtractError::ShlexError)?;
+    let res = execute_borg_in_dir(
+        local_path,
+        args,
+        &options.passphrase,
+        common_options,
+        &options.destination,
+    )
+    .await?;
+
+    let files = extract_parse_output(res, common_options)?;
+
+    if options.verify_sizes {
+        verify_sizes(local_path, options, common_options).await?;
+    }
+
+    info!("Finished extracting archive");
+
+    Ok(files)
+}
+
+/// List the files of the archive and compare their sizes with the extracted files,
+/// see [ExtractOptions::verify_sizes]
+async fn verify_sizes(
+    local_path: &str,
+    options: &ExtractOptions,
+    common_options: &CommonOptions,
+) -> Result<(), ExtractError> {
+    let mut list_options = ListOptions::new(options.archive.repository().clone());
+    list_options.passphrase = options.passphrase.clone();
+
+    let args = list_stream_fmt_args(&list_options, options.archive.archive(), common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(ExtractError::ShlexError)?;
+    let res = execute_borg(local_path, args, &options.passphrase, common_options).await?;
+
+    let items = list_items_parse_output(res, common_options).map_err(ExtractError::VerifyFailed)?;
+    let mismatches = options.size_mismatches(&items);
+    if !mismatches.is_empty() {
+        return Err(ExtractError::SizeMismatch(mismatches));
+    }
+
+    info!("Verified the sizes of the extracted files");
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use log::{debug, info};
+
+use crate::asynchronous::execute_borg;
+use crate::common::{info_fmt_args, info_parse_output, CommonOptions, InfoOptions, Repository};
+use crate::errors::InfoError;
+use crate::output::info::Info;
+use crate::utils::find_borg;
+
+/// The entry point for the borg info command
+///
+/// Returns [Info::Archives] if an archive or any of the archive filters of [InfoOptions]
+/// is set, otherwise [Info::Repository].
+///
+/// **Parameter**:
+/// - `options`: Reference to [InfoOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.repository,
+            archive = options.archive.as_deref(),
+        )
+    )
+)]
+pub async fn info(
+    options: &InfoOptions,
+    common_options: &CommonOptions,
+) -> Result<Info, InfoError> {
+    let local_path = &find_borg(common_options)?;
+
+    let args = info_fmt_args(options, common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(InfoError::ShlexError)?;
+    let res = execute_borg(local_path, args, &options.passphrase, common_options).await?;
+
+    let info = info_parse_output(res, options, common_options)?;
+
+    info!("Finished retrieving info");
+
+    Ok(info)
+}
+
+/// Retrieve the comment of an archive.
+///
+/// The comment is set with [CreateOptions::comment](crate::common::CreateOptions::comment).
+/// Returns an empty string if the archive has no comment.
+///
+/// **Parameter**:
+/// - `repository`: The repository that contains the archive
+/// - `archive`: The name of the archive
+/// - `passphrase`: The passphrase for the repository
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %repository, archive = archive))
+)]
+pub async fn get_comment(
+    repository: &Repository,
+    archive: &str,
+    passphrase: &Option<String>,
+    common_options: &CommonOptions,
+) -> Result<String, InfoError> {
+    let mut options = InfoOptions::new(repository.clone());
+    options.passphrase = passphrase.clone();
+    options.archive = Some(archive.to_string());
+
+    match info(&options, common_options).await? {
+        Info::Archives { archives, .. } => archives
+            .into_iter()
+            .next()
+            .map(|archive| archive.comment)
+            .ok_or(InfoError::ArchiveDoesNotExist),
+        Info::Repository { .. } => Err(InfoError::ArchiveDoesNotExist),
+    }
+}
@@ -1,27 +1,48 @@
+use std::process::Output;
+
 use log::{debug, info};
 
 use crate::asynchronous::execute_borg;
 use crate::common::{init_fmt_args, init_parse_result, CommonOptions, InitOptions};
 use crate::errors::InitError;
+use crate::utils::find_borg;
 
 /// The entry point for the borg init command
 ///
 /// **Parameter**:
 /// - `options`: Reference to [InitOptions]
 /// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %options.repository))
+)]
 pub async fn init(options: &InitOptions, common_options: &CommonOptions) -> Result<(), InitError> {
-    let local_path = common_options.local_path.as_ref().map_or("borg", |x| x);
+    let res = init_raw(options, common_options).await?;
+
+    init_parse_result(res, common_options)?;
+
+    info!("Repository {} created", options.repository);
+
+    Ok(())
+}
+
+/// Run the borg init command without parsing its output, see [init].
+///
+/// **Parameter**:
+/// - `options`: Reference to [InitOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+pub async fn init_raw(
+    options: &InitOptions,
+    common_options: &CommonOptions,
+) -> Result<Output, InitError> {
+    let local_path = &find_borg(common_options)?;
 
     let args = init_fmt_args(options, common_options);
     let passphrase = options.encryption_mode.get_passphrase();
 
     debug!("Calling borg: {local_path} {args}");
     let args = shlex::split(&args).ok_or(InitError::ShlexError)?;
-    let res = execute_borg(local_path, args, &passphrase).await?;
+    let res = execute_borg(local_path, args, &passphrase, common_options).await?;
 
-    init_parse_result(res)?;
-
-    info!("Repository {} created", options.repository);
-
-    Ok(())
+    Ok(res)
 }
@@ -1,29 +1,296 @@
+use std::process::{Output, Stdio};
+
+use chrono::NaiveDateTime;
 use log::{debug, info};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
 
-use crate::asynchronous::execute_borg;
-use crate::common::{list_fmt_args, list_parse_output, CommonOptions, ListOptions};
+use crate::asynchronous::{execute_borg, with_timeout};
+use crate::common::{
+    list_fmt_args, list_parse_log, list_parse_output, list_stream_fmt_args, CommonOptions,
+    ListOptions, Repository,
+};
 use crate::errors::ListError;
-use crate::output::list::ListRepository;
+use crate::output::list::{archives_since, ListArchive, ListItem, ListRepository};
+use crate::utils::{borg_command, find_borg};
 
 /// The entry point for the borg list command
 ///
+/// The archives are requested with `--json`, so borg emits the listing as one document,
+/// which is collected completely. To list the contents of an archive, which can be huge,
+/// use [list_stream], which uses `--json-lines` to process one item at a time.
+///
 /// **Parameter**:
 /// - `options`: Reference to [ListOptions]
 /// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %options.repository))
+)]
 pub async fn list(
     options: &ListOptions,
     common_options: &CommonOptions,
 ) -> Result<ListRepository, ListError> {
-    let local_path = common_options.local_path.as_ref().map_or("borg", |x| x);
+    let res = list_raw(options, common_options).await?;
+
+    let list_repo = list_parse_output(res, options, common_options)?;
+
+    info!("Finished listing repository");
+
+    Ok(list_repo)
+}
+
+/// Run the borg list command without parsing its output, see [list].
+///
+/// **Parameter**:
+/// - `options`: Reference to [ListOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+pub async fn list_raw(
+    options: &ListOptions,
+    common_options: &CommonOptions,
+) -> Result<Output, ListError> {
+    let local_path = &find_borg(common_options)?;
 
     let args = list_fmt_args(options, common_options);
     debug!("Calling borg: {local_path} {args}");
     let args = shlex::split(&args).ok_or(ListError::ShlexError)?;
-    let res = execute_borg(local_path, args, &options.passphrase).await?;
+    let res = execute_borg(local_path, args, &options.passphrase, common_options).await?;
 
-    let list_repo = list_parse_output(res)?;
+    Ok(res)
+}
 
-    info!("Finished listing repository");
+/// List the archives of a repository that were started at or after `since`.
+///
+/// Borg has no option to select archives by time, so all archives are listed
+/// and filtered on [ListArchive::start] on the client side.
+/// Borg reports the timestamps in the local time of the host running borg,
+/// so `since` has to be given in that time as well.
+/// Checkpoints are excluded, see [ListOptions::consider_checkpoints].
+///
+/// **Parameter**:
+/// - `repository`: The repository to list
+/// - `since`: The earliest start of the returned archives
+/// - `passphrase`: The passphrase for the repository
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %repository, since = %since))
+)]
+pub async fn list_since(
+    repository: &Repository,
+    since: NaiveDateTime,
+    passphrase: &Option<String>,
+    common_options: &CommonOptions,
+) -> Result<Vec<ListArchive>, ListError> {
+    let mut options = ListOptions::new(repository.clone());
+    options.passphrase = passphrase.clone();
 
-    Ok(list_repo)
+    let list_repo = list(&options, common_options).await?;
+
+    Ok(archives_since(list_repo.archives, since))
+}
+
+/// List the contents of an archive.
+///
+/// Instead of collecting the whole listing, every item is sent to the provided channel
+/// as soon as borg emitted it, which keeps the memory usage low for huge archives.
+///
+/// **Parameter**:
+/// - `options`: Reference to [ListOptions]
+/// - `archive`: The name of the archive to list
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+/// - `item_channel`: A [tokio::sync::mpsc::Sender] of [ListItem]. Every item of the
+///   archive is sent to this channel
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %options.repository, archive = archive))
+)]
+pub async fn list_stream(
+    options: &ListOptions,
+    archive: &str,
+    common_options: &CommonOptions,
+    item_channel: tokio::sync::mpsc::Sender<ListItem>,
+) -> Result<(), ListError> {
+    let local_path = &find_borg(common_options)?;
+
+    let args = list_stream_fmt_args(options, archive, common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(ListError::ShlexError)?;
+    let mut child = tokio::process::Command::from(borg_command(
+        local_path,
+        &options.passphrase,
+        common_options,
+    ))
+    .args(args)
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .kill_on_drop(true)
+    .spawn()?;
+
+    with_timeout(common_options.timeout, async move {
+        let stdout = child.stdout.take().ok_or(ListError::PipeFailed)?;
+        let mut stderr = child.stderr.take().ok_or(ListError::PipeFailed)?;
+
+        // stderr has to be drained while reading stdout, otherwise borg may block
+        let (items, stderr) = tokio::join!(
+            async {
+                let mut lines = BufReader::new(stdout).lines();
+                while let Some(line) = lines
+                    .next_line()
+                    .await
+                    .map_err(ListError::InvalidBorgOutput)?
+                {
+                    let item: ListItem = serde_json::from_str(&line)?;
+                    if item_channel.send(item).await.is_err() {
+                        return Ok(true);
+                    }
+                }
+                Ok::<bool, ListError>(false)
+            },
+            async {
+                let mut buf = Vec::new();
+                stderr.read_to_end(&mut buf).await.map(|_| buf)
+            }
+        );
+
+        // Stdout was closed by now, so borg terminates if it is still running
+        if items? {
+            debug!("Item channel was closed, stopped listing");
+            return Ok(());
+        }
+        let stderr = stderr.map_err(ListError::InvalidBorgOutput)?;
+
+        let status = child.wait().await?;
+        list_parse_log(
+            &Output {
+                status,
+                stdout: Vec::new(),
+                stderr,
+            },
+            common_options,
+        )?;
+
+        info!("Finished listing archive");
+
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::asynchronous::{list, list_stream};
+    use crate::common::ListOptions;
+    use crate::errors::ListError;
+    use crate::test_utils::fake_borg;
+
+    #[tokio::test]
+    async fn test_list_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo '{"type": "d", "mode": "drwxr-xr-x", "user": "root", "group": "root", "uid": 0, "gid": 0, "path": "etc", "healthy": true, "source": "", "linktarget": "", "flags": null, "mtime": "2023-01-02T10:00:00.123456", "size": 0}'
+echo '{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg", "message": "listing"}' >&2
+echo '{"type": "-", "mode": "-rw-r--r--", "user": null, "group": null, "uid": 1000, "gid": 1000, "path": "etc/hosts", "healthy": true, "source": "", "linktarget": "", "flags": null, "mtime": "2023-01-02T10:00:00.000000", "size": 220}'
+"#,
+        );
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        list_stream(
+            &ListOptions::new(Path::new("/tmp/repo")),
+            "archive",
+            &common_options,
+            tx,
+        )
+        .await
+        .unwrap();
+
+        let item = rx.recv().await.unwrap();
+        assert_eq!((item.item_type.as_str(), item.path.as_str()), ("d", "etc"));
+        let item = rx.recv().await.unwrap();
+        assert_eq!(item.path, "etc/hosts");
+        assert_eq!(item.size, 220);
+        assert_eq!(item.user, None);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_list_stream_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo '{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg", "message": "no repo", "msgid": "Repository.DoesNotExist"}' >&2
+exit 2
+"#,
+        );
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let res = list_stream(
+            &ListOptions::new(Path::new("/tmp/repo")),
+            "archive",
+            &common_options,
+            tx,
+        )
+        .await;
+        assert!(matches!(res, Err(ListError::RepositoryDoesNotExist)));
+    }
+
+    #[tokio::test]
+    async fn test_list_cancelled_kills_borg() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let common_options = fake_borg(
+            &dir,
+            &format!(
+                "#!/bin/sh\necho $$ > '{}'\nexec sleep 30\n",
+                pid_file.display()
+            ),
+        );
+
+        let options = ListOptions::new(Path::new("/tmp/repo"));
+        let pid = tokio::select! {
+            _ = list(&options, &common_options) => panic!("borg should still be running"),
+            pid = async {
+                loop {
+                    if let Ok(pid) = fs::read_to_string(&pid_file) {
+                        if !pid.is_empty() {
+                            return pid.trim().to_string();
+                        }
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                }
+            } => pid,
+        };
+
+        // The list future has been dropped, the child must have been killed
+        let stat = format!("/proc/{pid}/stat");
+        for _ in 0..100 {
+            match fs::read_to_string(&stat) {
+                // Either reaped or a zombie waiting to be reaped
+                Err(_) => return,
+                Ok(stat) if stat.split_whitespace().nth(2) == Some("Z") => return,
+                Ok(_) => tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+            }
+        }
+        panic!("borg process {pid} is still running");
+    }
+
+    #[tokio::test]
+    async fn test_list_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut common_options = fake_borg(&dir, "#!/bin/sh\nexec sleep 30\n");
+        common_options.timeout = Some(std::time::Duration::from_millis(200));
+
+        let options = ListOptions::new(Path::new("/tmp/repo"));
+        let res = list(&options, &common_options).await;
+        assert!(matches!(res, Err(ListError::Timeout)), "{res:?}");
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let res = list_stream(&options, "archive", &common_options, tx).await;
+        assert!(matches!(res, Err(ListError::Timeout)), "{res:?}");
+    }
 }
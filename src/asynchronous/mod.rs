@@ -1,37 +1,165 @@
 //! The asynchronous version of the borg commands are defined in this module
+//!
+//! The `*_raw` variants of the commands are an escape hatch for output that the
+//! commands fail to parse. They return the raw [Output] of borg, parsing it is up to the caller.
 
+use std::future::Future;
 use std::io;
-use std::process::Output;
+use std::process::{Output, Stdio};
+use std::time::Duration;
 
-pub use compact::compact;
-pub use create::{create, create_progress, CreateProgress};
-pub use init::init;
-pub use list::list;
-pub use mount::{mount, umount};
-pub use prune::prune;
+use tokio::io::AsyncRead;
 
+use crate::common::CommonOptions;
+use crate::utils::{borg_command, stdin_output, timeout_error};
+
+pub use crate::output::create::CreateProgress;
+pub use benchmark::benchmark_crud;
+pub use compact::{compact, compact_progress, compact_raw, CompactProgress};
+pub use config::config;
+pub use create::{
+    create, create_idempotent, create_progress, create_progress_cancellable,
+    create_progress_with_handle, create_raw, create_stdin, create_stream, CreateHandle,
+};
+pub use delete::{delete, delete_raw};
+pub use extract::extract;
+pub use info::{get_comment, info};
+pub use init::{init, init_raw};
+pub use list::{list, list_raw, list_since, list_stream};
+pub use mount::{
+    mount, mount_foreground, mount_guarded, mount_raw, umount, MountGuard, MountHandle,
+};
+pub use prune::{prune, prune_raw};
+pub use raw::run_raw;
+pub use recreate::set_comment;
+pub use serve::{serve, ServeHandle};
+pub use transfer::{transfer, transfer_progress, TransferProgress};
+pub use version::version;
+pub use workflow::backup;
+
+mod benchmark;
 mod compact;
+mod config;
 mod create;
+mod delete;
+mod extract;
+mod info;
 mod init;
 mod list;
 mod mount;
 mod prune;
+mod raw;
+mod recreate;
+mod serve;
+mod transfer;
+mod version;
+mod workflow;
 
+/// Execute borg and collect its output.
+///
+/// The child is killed if the returned future is dropped before it completes,
+/// e.g. when it is cancelled by [tokio::select!] or a timeout.
 pub(crate) async fn execute_borg(
     local_path: &str,
     args: Vec<String>,
     passphrase: &Option<String>,
+    common_options: &CommonOptions,
 ) -> Result<Output, io::Error> {
-    Ok(if let Some(passphrase) = passphrase {
-        tokio::process::Command::new(local_path)
-            .env("BORG_PASSPHRASE", passphrase)
-            .args(args)
-            .output()
-            .await?
-    } else {
-        tokio::process::Command::new(local_path)
+    let mut command =
+        tokio::process::Command::from(borg_command(local_path, passphrase, common_options));
+    command.args(args).kill_on_drop(true);
+    output_with_retry(command, common_options).await
+}
+
+/// Execute borg with `current_dir` as its working directory.
+///
+/// Only the working directory of the child is changed,
+/// so this is safe to use concurrently.
+pub(crate) async fn execute_borg_in_dir(
+    local_path: &str,
+    args: Vec<String>,
+    passphrase: &Option<String>,
+    common_options: &CommonOptions,
+    current_dir: &str,
+) -> Result<Output, io::Error> {
+    let mut command =
+        tokio::process::Command::from(borg_command(local_path, passphrase, common_options));
+    command
+        .current_dir(current_dir)
+        .args(args)
+        .kill_on_drop(true);
+    output_with_retry(command, common_options).await
+}
+
+/// Execute the command and collect its output, retrying it according to
+/// [CommonOptions::retry]
+///
+/// [CommonOptions::timeout] applies to each attempt.
+pub(crate) async fn output_with_retry(
+    mut command: tokio::process::Command,
+    common_options: &CommonOptions,
+) -> Result<Output, io::Error> {
+    let mut attempt = 1;
+    loop {
+        let output = with_timeout(common_options.timeout, command.output()).await?;
+        let Some(delay) = common_options
+            .retry
+            .as_ref()
+            .and_then(|retry| retry.retry_delay(attempt, &output))
+        else {
+            return Ok(output);
+        };
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+pub(crate) async fn execute_borg_with_stdin(
+    local_path: &str,
+    args: Vec<String>,
+    passphrase: &Option<String>,
+    common_options: &CommonOptions,
+    mut stdin: impl AsyncRead + Unpin,
+) -> Result<Output, io::Error> {
+    let mut child =
+        tokio::process::Command::from(borg_command(local_path, passphrase, common_options))
             .args(args)
-            .output()
-            .await?
-    })
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+    let mut child_stdin = child.stdin.take().ok_or(io::ErrorKind::BrokenPipe)?;
+
+    let output = async {
+        let (written, output) = tokio::join!(
+            async move {
+                let written = tokio::io::copy(&mut stdin, &mut child_stdin).await;
+                // Close stdin to signal EOF to borg
+                drop(child_stdin);
+                written
+            },
+            child.wait_with_output()
+        );
+        stdin_output(written, output?)
+    };
+    with_timeout(common_options.timeout, output).await
+}
+
+/// Run the future, failing with an [io::ErrorKind::TimedOut] error if it
+/// didn't complete within `timeout`.
+///
+/// On timeout the future is dropped, which kills borg
+/// as long as it was spawned with `kill_on_drop`.
+pub(crate) async fn with_timeout<T, E: From<io::Error>>(
+    timeout: Option<Duration>,
+    future: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future)
+            .await
+            .map_err(|_| E::from(timeout_error()))?,
+        None => future.await,
+    }
 }
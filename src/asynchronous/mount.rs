@@ -1,30 +1,265 @@
-use log::{debug, info};
+use std::io;
+use std::path::Path;
+use std::process::{ExitStatus, Output, Stdio};
+use std::time::Duration;
 
-use crate::asynchronous::execute_borg;
+use log::{debug, error, info};
+use tokio::io::AsyncReadExt;
+use tokio::process::Child;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::asynchronous::{execute_borg, with_timeout};
 use crate::common::{mount_fmt_args, mount_parse_output, CommonOptions, MountOptions};
 use crate::errors::MountError;
+use crate::utils::{borg_command, find_borg};
+
+/// The interval in which the mountpoint is checked while waiting for the mount
+const MOUNT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The maximum duration the mountpoint may take to appear after borg daemonized,
+/// unless [CommonOptions::timeout] is set
+const MOUNT_READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Check whether a filesystem is mounted at `mountpoint`.
+///
+/// A mountpoint resides on a different device than its parent directory.
+/// The metadata is retrieved on the blocking thread pool, as FUSE may block it.
+#[cfg(unix)]
+async fn is_mounted(mountpoint: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = tokio::fs::metadata(mountpoint).await?;
+    let parent = tokio::fs::metadata(mountpoint.join("..")).await?;
+    Ok(metadata.dev() != parent.dev())
+}
+
+/// Without device ids, the mountpoint can't be told apart from its parent directory,
+/// so it is considered mounted as soon as it exists.
+#[cfg(not(unix))]
+async fn is_mounted(mountpoint: &Path) -> io::Result<bool> {
+    tokio::fs::try_exists(mountpoint).await
+}
 
 /// Mount an archive or repo as a FUSE filesystem.
 ///
+/// Unless [MountOptions::foreground] is set, this returns once the mountpoint
+/// is usable. Older versions of borg daemonize before the filesystem is mounted,
+/// so the mountpoint is polled until it appears. The mountpoint has to appear within
+/// [CommonOptions::timeout], or 10 seconds if no timeout is set.
+///
 /// **Parameter**:
 /// - `options`: Reference to [MountOptions]
 /// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.mount_source.repository(),
+            archive = options.mount_source.archive(),
+        )
+    )
+)]
 pub async fn mount(
     options: &MountOptions,
     common_options: &CommonOptions,
 ) -> Result<(), MountError> {
-    let local_path = common_options.local_path.as_ref().map_or("borg", |x| x);
+    let res = mount_raw(options, common_options).await?;
+
+    mount_parse_output(res, common_options)?;
+
+    // In foreground mode, borg only exits after the mountpoint was unmounted
+    if !options.foreground {
+        let deadline = Instant::now() + common_options.timeout.unwrap_or(MOUNT_READY_TIMEOUT);
+        while !is_mounted(Path::new(&options.mountpoint)).await? {
+            if Instant::now() >= deadline {
+                return Err(MountError::NotReady(options.mountpoint.clone()));
+            }
+            tokio::time::sleep(MOUNT_POLL_INTERVAL).await;
+        }
+    }
+
+    info!("Finished mounting");
+
+    Ok(())
+}
+
+/// Run the borg mount command without parsing its output, see [mount].
+///
+/// **Parameter**:
+/// - `options`: Reference to [MountOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+pub async fn mount_raw(
+    options: &MountOptions,
+    common_options: &CommonOptions,
+) -> Result<Output, MountError> {
+    let local_path = &find_borg(common_options)?;
 
     let args = mount_fmt_args(options, common_options);
     debug!("Calling borg: {local_path} {args}");
     let args = shlex::split(&args).ok_or(MountError::ShlexError)?;
-    let res = execute_borg(local_path, args, &options.passphrase).await?;
+    let res = execute_borg(local_path, args, &options.passphrase, common_options).await?;
 
-    mount_parse_output(res)?;
+    Ok(res)
+}
 
-    info!("Finished mounting");
+/// The stdout and stderr of borg
+type PipeOutput = (Vec<u8>, Vec<u8>);
 
-    Ok(())
+/// A handle to a borg mount process that is running in the foreground.
+///
+/// The process exits after the mountpoint was unmounted, e.g. by using [umount].
+/// Use [MountHandle::ready] to wait until the mountpoint is usable.
+///
+/// If borg is still running when the handle is dropped, the mountpoint is unmounted
+/// using [crate::sync::umount], which blocks the current thread, and borg is killed.
+#[derive(Debug)]
+pub struct MountHandle {
+    child: Child,
+    /// stdout and stderr of borg, which are drained in the background,
+    /// as borg blocks if the pipes are full
+    output: Option<JoinHandle<io::Result<PipeOutput>>>,
+    mountpoint: String,
+    common_options: CommonOptions,
+}
+
+impl MountHandle {
+    /// The process id of the borg process.
+    ///
+    /// Returns [None] if the process has already exited.
+    pub fn id(&self) -> Option<u32> {
+        self.child.id()
+    }
+
+    /// Wait until the mountpoint is usable.
+    ///
+    /// If borg exits before the filesystem was mounted, its error is returned.
+    /// The wait is limited by [CommonOptions::timeout], if set.
+    pub async fn ready(&mut self) -> Result<(), MountError> {
+        with_timeout(self.common_options.timeout, async {
+            loop {
+                if is_mounted(Path::new(&self.mountpoint)).await? {
+                    info!("Mountpoint is ready");
+                    return Ok(());
+                }
+
+                if let Some(status) = self.child.try_wait()? {
+                    let res = self.output(status).await?;
+                    mount_parse_output(res, &self.common_options)?;
+                    return Err(MountError::NotReady(self.mountpoint.clone()));
+                }
+
+                tokio::time::sleep(MOUNT_POLL_INTERVAL).await;
+            }
+        })
+        .await
+    }
+
+    /// Wait for the borg process to exit.
+    ///
+    /// The process only exits after the mountpoint was unmounted.
+    pub async fn wait(mut self) -> Result<(), MountError> {
+        let status = self.child.wait().await?;
+        let res = self.output(status).await?;
+
+        mount_parse_output(res, &self.common_options)?;
+
+        info!("Finished mounting");
+
+        Ok(())
+    }
+
+    /// Collect the output of borg after it exited
+    async fn output(&mut self, status: ExitStatus) -> Result<Output, MountError> {
+        let (stdout, stderr) = match self.output.take() {
+            Some(output) => output
+                .await
+                .map_err(|_| MountError::PipeFailed)?
+                .map_err(MountError::InvalidBorgOutput)?,
+            None => Default::default(),
+        };
+        Ok(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+impl Drop for MountHandle {
+    fn drop(&mut self) {
+        // borg already exited, e.g. after the mountpoint was unmounted
+        if !matches!(self.child.try_wait(), Ok(None)) {
+            return;
+        }
+        if let Err(err) = crate::sync::umount(self.mountpoint.clone(), &self.common_options) {
+            error!("Could not unmount {}: {err}", self.mountpoint);
+        }
+    }
+}
+
+/// Mount an archive or repo as a FUSE filesystem with borg staying in the foreground.
+///
+/// [MountOptions::foreground] is ignored, as borg is always started in foreground.
+/// The returned [MountHandle] can be used to wait for the borg process to exit
+/// after the mountpoint was unmounted using [umount].
+///
+/// **Parameter**:
+/// - `options`: Reference to [MountOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.mount_source.repository(),
+            archive = options.mount_source.archive(),
+        )
+    )
+)]
+pub async fn mount_foreground(
+    options: &MountOptions,
+    common_options: &CommonOptions,
+) -> Result<MountHandle, MountError> {
+    let local_path = &find_borg(common_options)?;
+
+    let options = MountOptions {
+        foreground: true,
+        ..options.clone()
+    };
+    let args = mount_fmt_args(&options, common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(MountError::ShlexError)?;
+    let mut child = tokio::process::Command::from(borg_command(
+        local_path,
+        &options.passphrase,
+        common_options,
+    ))
+    .args(args)
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .kill_on_drop(true)
+    .spawn()?;
+
+    let mut stdout = child.stdout.take().ok_or(MountError::PipeFailed)?;
+    let mut stderr = child.stderr.take().ok_or(MountError::PipeFailed)?;
+    let output = tokio::spawn(async move {
+        let mut stdout_buf = Vec::new();
+        let mut stderr_buf = Vec::new();
+        tokio::try_join!(
+            stdout.read_to_end(&mut stdout_buf),
+            stderr.read_to_end(&mut stderr_buf)
+        )?;
+        Ok((stdout_buf, stderr_buf))
+    });
+
+    Ok(MountHandle {
+        child,
+        output: Some(output),
+        mountpoint: options.mountpoint,
+        common_options: common_options.clone(),
+    })
 }
 
 /// Unmount a previously mounted archive or repository.
@@ -32,15 +267,191 @@ pub async fn mount(
 /// **Parameter**:
 /// - `mountpoint`: The mountpoint to be unmounted.
 /// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(mountpoint = %mountpoint)))]
 pub async fn umount(mountpoint: String, common_options: &CommonOptions) -> Result<(), MountError> {
-    let local_path = common_options.local_path.as_ref().map_or("borg", |x| x);
+    let local_path = &find_borg(common_options)?;
 
     let args = vec!["umount".to_string(), mountpoint];
-    let res = execute_borg(local_path, args, &None).await?;
+    let res = execute_borg(local_path, args, &None, common_options).await?;
 
-    mount_parse_output(res)?;
+    mount_parse_output(res, common_options)?;
 
     info!("Finished mounting");
 
     Ok(())
 }
+
+/// A mounted archive or repository that is unmounted when the guard is dropped.
+///
+/// As [Drop] can't be async, dropping the guard runs [crate::sync::umount],
+/// which blocks the current thread until borg unmounted the mountpoint.
+/// Use [MountGuard::umount] to unmount without blocking and to receive the error,
+/// as unmounting on drop is best-effort and errors are only logged.
+/// If the process exits without running destructors, the mountpoint stays mounted.
+#[derive(Debug)]
+pub struct MountGuard {
+    mountpoint: String,
+    common_options: CommonOptions,
+    mounted: bool,
+}
+
+impl MountGuard {
+    /// The mountpoint of the archive or repository
+    pub fn mountpoint(&self) -> &str {
+        &self.mountpoint
+    }
+
+    /// Unmount the archive or repository.
+    ///
+    /// Unlike dropping the guard, this doesn't block and returns the error of [umount].
+    pub async fn umount(mut self) -> Result<(), MountError> {
+        self.mounted = false;
+        umount(self.mountpoint.clone(), &self.common_options).await
+    }
+}
+
+impl Drop for MountGuard {
+    fn drop(&mut self) {
+        if !self.mounted {
+            return;
+        }
+        if let Err(err) = crate::sync::umount(self.mountpoint.clone(), &self.common_options) {
+            error!("Could not unmount {}: {err}", self.mountpoint);
+        }
+    }
+}
+
+/// Mount an archive or repo as a FUSE filesystem, which is unmounted when
+/// the returned [MountGuard] is dropped.
+///
+/// The returned future resolves once the mountpoint is usable, see [mount].
+/// [MountOptions::foreground] is ignored, as borg has to daemonize to return.
+///
+/// **Parameter**:
+/// - `options`: Reference to [MountOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.mount_source.repository(),
+            archive = options.mount_source.archive(),
+        )
+    )
+)]
+pub async fn mount_guarded(
+    options: &MountOptions,
+    common_options: &CommonOptions,
+) -> Result<MountGuard, MountError> {
+    let options = MountOptions {
+        foreground: false,
+        ..options.clone()
+    };
+    mount(&options, common_options).await?;
+
+    Ok(MountGuard {
+        mountpoint: options.mountpoint,
+        common_options: common_options.clone(),
+        mounted: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::time::Duration;
+
+    use crate::asynchronous::mount_foreground;
+    use crate::common::{CommonOptions, MountOptions, MountSource};
+    use crate::errors::MountError;
+    use crate::test_utils::fake_borg;
+
+    #[tokio::test]
+    async fn test_mount_foreground_ready() {
+        let dir = tempfile::tempdir().unwrap();
+        let mountpoint = dir.path().join("mnt");
+        fs::create_dir(&mountpoint).unwrap();
+        let common_options = CommonOptions {
+            timeout: Some(Duration::from_millis(500)),
+            ..fake_borg(
+                &dir,
+                r#"#!/bin/sh
+echo '{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "fuse: failed"}' >&2
+exit 2
+"#,
+            )
+        };
+        let options = MountOptions::new(
+            MountSource::Repository {
+                name: Path::new("/tmp/repo").into(),
+                first_n_archives: None,
+                last_n_archives: None,
+                glob_archives: None,
+            },
+            mountpoint.to_str().unwrap().to_string(),
+        );
+
+        // borg exited without mounting
+        let mut handle = mount_foreground(&options, &common_options).await.unwrap();
+        let res = handle.ready().await;
+        assert!(
+            matches!(&res, Err(MountError::Unknown(output)) if output.contains("fuse: failed")),
+            "{res:?}"
+        );
+
+        // borg doesn't block on a full pipe before it exits
+        fake_borg(
+            &dir,
+            r#"#!/bin/sh
+i=0
+while [ $i -lt 2000 ]; do
+    echo '{"type": "log_message", "time": 1.0, "levelname": "DEBUG", "name": "borg.fuse", "message": "a long output that does not fit into the pipe"}' >&2
+    i=$((i + 1))
+done
+echo '{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "fuse: failed"}' >&2
+exit 2
+"#,
+        );
+        let mut handle = mount_foreground(&options, &common_options).await.unwrap();
+        let res = handle.ready().await;
+        assert!(
+            matches!(&res, Err(MountError::Unknown(output)) if output.contains("fuse: failed")),
+            "{res:?}"
+        );
+
+        // borg is running, but the mountpoint doesn't appear
+        fake_borg(&dir, "#!/bin/sh\nexec sleep 30\n");
+        let mut handle = mount_foreground(&options, &common_options).await.unwrap();
+        let res = handle.ready().await;
+        assert!(matches!(res, Err(MountError::Timeout)), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn test_mount_foreground_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let mountpoint = dir.path().join("mnt");
+        let unmounted = dir.path().join("unmounted");
+        let common_options = fake_borg(
+            &dir,
+            &format!(
+                "#!/bin/sh\ncase \"$*\" in\n*umount*) touch {} ;;\n*) exec sleep 30 ;;\nesac\n",
+                unmounted.display()
+            ),
+        );
+        let options = MountOptions::new(
+            MountSource::Repository {
+                name: Path::new("/tmp/repo").into(),
+                first_n_archives: None,
+                last_n_archives: None,
+                glob_archives: None,
+            },
+            mountpoint.to_str().unwrap().to_string(),
+        );
+
+        let handle = mount_foreground(&options, &common_options).await.unwrap();
+        drop(handle);
+        assert!(unmounted.exists());
+    }
+}
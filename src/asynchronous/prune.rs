@@ -1,28 +1,58 @@
+use std::process::Output;
+
 use log::{debug, info};
 
 use crate::asynchronous::execute_borg;
-use crate::common::{prune_fmt_args, prune_parse_output, CommonOptions, PruneOptions};
+use crate::common::{
+    prune_check_keep_rules, prune_check_match_archives, prune_fmt_args, prune_parse_output,
+    CommonOptions, PruneOptions,
+};
 use crate::errors::PruneError;
+use crate::output::prune::PruneReport;
+use crate::utils::find_borg;
 
 /// The entry point for the borg init command
 ///
 /// **Parameter**:
 /// - `options`: Reference to [PruneOptions]
 /// - `common_options`: The [CommonOptions] that can be applied to any command
+///
+/// The kept and pruned archives are only reported if [PruneOptions::list] is set.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %options.repository))
+)]
 pub async fn prune(
     options: &PruneOptions,
     common_options: &CommonOptions,
-) -> Result<(), PruneError> {
-    let local_path = common_options.local_path.as_ref().map_or("borg", |x| x);
+) -> Result<PruneReport, PruneError> {
+    let res = prune_raw(options, common_options).await?;
+
+    let report = prune_parse_output(res, common_options)?;
+
+    info!("Finished pruning");
+
+    Ok(report)
+}
+
+/// Run the borg prune command without parsing its output, see [prune].
+///
+/// **Parameter**:
+/// - `options`: Reference to [PruneOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+pub async fn prune_raw(
+    options: &PruneOptions,
+    common_options: &CommonOptions,
+) -> Result<Output, PruneError> {
+    prune_check_keep_rules(options)?;
+    prune_check_match_archives(options, common_options)?;
+
+    let local_path = &find_borg(common_options)?;
 
     let args = prune_fmt_args(options, common_options);
     debug!("Calling borg: {local_path} {args}");
     let args = shlex::split(&args).ok_or(PruneError::ShlexError)?;
-    let res = execute_borg(local_path, args, &options.passphrase).await?;
-
-    prune_parse_output(res)?;
-
-    info!("Finished pruning");
+    let res = execute_borg(local_path, args, &options.passphrase, common_options).await?;
 
-    Ok(())
+    Ok(res)
 }
@@ -0,0 +1,39 @@
+use std::process::Output;
+
+use log::debug;
+
+use crate::asynchronous::execute_borg;
+use crate::common::{raw_fmt_args, CommonOptions};
+use crate::errors::RawError;
+use crate::utils::find_borg;
+
+/// Run an arbitrary borg command.
+///
+/// This is an escape hatch for commands that are not supported by this crate.
+/// `--log-json` and the [CommonOptions] are prepended to `args`,
+/// e.g. `vec!["key".to_string(), "migrate-to-repokey".to_string(), repository]`.
+///
+/// The raw [Output] of borg is returned, parsing it is the responsibility of the caller.
+///
+/// **Parameter**:
+/// - `args`: The arguments of the borg command
+/// - `passphrase`: The passphrase of the repository, if required
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub async fn run_raw(
+    args: Vec<String>,
+    passphrase: Option<&str>,
+    common_options: &CommonOptions,
+) -> Result<Output, RawError> {
+    let local_path = &find_borg(common_options)?;
+
+    let common_args = raw_fmt_args(common_options);
+    debug!("Calling borg: {local_path} {common_args} {args:?}");
+    let mut borg_args = shlex::split(&common_args).ok_or(RawError::ShlexError)?;
+    borg_args.extend(args);
+
+    let passphrase = passphrase.map(str::to_string);
+    let res = execute_borg(local_path, borg_args, &passphrase, common_options).await?;
+
+    Ok(res)
+}
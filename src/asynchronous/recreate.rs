@@ -0,0 +1,42 @@
+use log::{debug, info};
+
+use crate::asynchronous::execute_borg;
+use crate::common::{recreate_parse_output, set_comment_fmt_args, CommonOptions, Repository};
+use crate::errors::RecreateError;
+use crate::utils::find_borg;
+
+/// Set the comment of an existing archive.
+///
+/// This uses borg recreate, but only the metadata of the archive is rewritten,
+/// so it is cheap even for large archives. The previous comment is replaced.
+///
+/// **Parameter**:
+/// - `repository`: The repository that contains the archive
+/// - `archive`: The name of the archive
+/// - `comment`: The new comment of the archive
+/// - `passphrase`: The passphrase for the repository
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %repository, archive = archive))
+)]
+pub async fn set_comment(
+    repository: &Repository,
+    archive: &str,
+    comment: &str,
+    passphrase: &Option<String>,
+    common_options: &CommonOptions,
+) -> Result<(), RecreateError> {
+    let local_path = &find_borg(common_options)?;
+
+    let args = set_comment_fmt_args(repository, archive, comment, common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(RecreateError::ShlexError)?;
+    let res = execute_borg(local_path, args, passphrase, common_options).await?;
+
+    recreate_parse_output(res, common_options)?;
+
+    info!("Finished setting the comment");
+
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use std::process::{Output, Stdio};
+
+use log::{debug, info};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout};
+use tokio::sync::mpsc;
+
+use crate::common::{serve_fmt_args, serve_parse_output, CommonOptions, ServeOptions};
+use crate::errors::ServeError;
+use crate::utils::{borg_command, find_borg};
+
+/// A handle to a running borg serve process.
+///
+/// borg serve speaks the borg RPC protocol on stdin and stdout and sends its log
+/// messages to stderr, all of which have to be forwarded to the client.
+/// The process exits once its stdin is closed, i.e. the client disconnected.
+/// Dropping the handle kills the process.
+#[derive(Debug)]
+pub struct ServeHandle {
+    child: Child,
+    /// The lines of stderr, which is drained in the background, as borg blocks if the pipe is full
+    stderr: Option<mpsc::UnboundedReceiver<Vec<u8>>>,
+    common_options: CommonOptions,
+}
+
+impl ServeHandle {
+    /// The process id of the borg process.
+    ///
+    /// Returns [None] if the process has already exited.
+    pub fn id(&self) -> Option<u32> {
+        self.child.id()
+    }
+
+    /// Take the stdin of borg serve, to which the requests of the client are written.
+    ///
+    /// Returns [None] if it was already taken.
+    pub fn take_stdin(&mut self) -> Option<ChildStdin> {
+        self.child.stdin.take()
+    }
+
+    /// Take the stdout of borg serve, from which the responses to the client are read.
+    ///
+    /// Returns [None] if it was already taken.
+    pub fn take_stdout(&mut self) -> Option<ChildStdout> {
+        self.child.stdout.take()
+    }
+
+    /// Take the stderr of borg serve, from which the log messages for the client are received.
+    ///
+    /// Every line is received as soon as borg wrote it, including its newline.
+    /// If it isn't taken, the log messages are passed to the `log` crate by [ServeHandle::wait].
+    /// Returns [None] if it was already taken.
+    pub fn take_stderr(&mut self) -> Option<mpsc::UnboundedReceiver<Vec<u8>>> {
+        self.stderr.take()
+    }
+
+    /// Kill the borg process and wait for it to exit.
+    pub async fn kill(&mut self) -> Result<(), ServeError> {
+        Ok(self.child.kill().await?)
+    }
+
+    /// Wait for the borg process to exit.
+    ///
+    /// If stdin wasn't taken, it is closed first, which makes borg serve exit.
+    pub async fn wait(mut self) -> Result<(), ServeError> {
+        drop(self.child.stdin.take());
+
+        let mut stdout = Vec::new();
+        if let Some(mut pipe) = self.child.stdout.take() {
+            pipe.read_to_end(&mut stdout)
+                .await
+                .map_err(ServeError::InvalidBorgOutput)?;
+        }
+        let status = self.child.wait().await?;
+        // The lines end once borg closed stderr
+        let mut stderr = Vec::new();
+        if let Some(mut lines) = self.stderr.take() {
+            while let Some(line) = lines.recv().await {
+                stderr.extend(line);
+            }
+        }
+
+        serve_parse_output(
+            Output {
+                status,
+                stdout,
+                stderr,
+            },
+            &self.common_options,
+        )?;
+
+        info!("Finished serving");
+
+        Ok(())
+    }
+}
+
+/// Run borg serve as the server side of a remote repository.
+///
+/// This allows to build a custom endpoint for borg clients, e.g. by bridging the
+/// pipes of the returned [ServeHandle] to an SSH channel.
+/// [CommonOptions::timeout] is ignored, as borg serve runs until the client disconnects.
+///
+/// **Parameter**:
+/// - `options`: Reference to [ServeOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub async fn serve(
+    options: &ServeOptions,
+    common_options: &CommonOptions,
+) -> Result<ServeHandle, ServeError> {
+    let local_path = &find_borg(common_options)?;
+
+    let args = serve_fmt_args(options, common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(ServeError::ShlexError)?;
+    let mut child = tokio::process::Command::from(borg_command(local_path, &None, common_options))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let stderr = child.stderr.take().ok_or(ServeError::PipeFailed)?;
+    let (line_tx, line_rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(stderr);
+        loop {
+            let mut line = Vec::new();
+            match reader.read_until(b'\n', &mut line).await {
+                Ok(0) | Err(_) => break,
+                // The receiver may be gone, but the pipe still has to be drained
+                Ok(_) => drop(line_tx.send(line)),
+            }
+        }
+    });
+
+    Ok(ServeHandle {
+        child,
+        stderr: Some(line_rx),
+        common_options: common_options.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::asynchronous::serve;
+    use crate::common::ServeOptions;
+    use crate::test_utils::fake_borg;
+
+    #[tokio::test]
+    async fn test_serve_stderr() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+i=0
+while [ $i -lt 2000 ]; do
+    echo "Remote: $i of a long output that doesn't fit into the pipe" >&2
+    i=$((i + 1))
+done
+cat
+"#,
+        );
+
+        // borg doesn't block on stderr while the client is served
+        let mut handle = serve(&ServeOptions::default(), &common_options)
+            .await
+            .unwrap();
+        let mut stdin = handle.take_stdin().unwrap();
+        let mut stdout = handle.take_stdout().unwrap();
+        stdin.write_all(b"request\n").await.unwrap();
+        drop(stdin);
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).await.unwrap();
+        assert_eq!(output, "request\n");
+
+        let mut stderr = handle.take_stderr().unwrap();
+        handle.wait().await.unwrap();
+        let mut lines = Vec::new();
+        while let Some(line) = stderr.recv().await {
+            lines.push(line);
+        }
+        assert_eq!(lines.len(), 2000);
+        assert_eq!(
+            lines[0],
+            b"Remote: 0 of a long output that doesn't fit into the pipe\n"
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt::{Display, Formatter};
+use std::process::{Output, Stdio};
+
+use log::{debug, error, info, trace};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::asynchronous::{output_with_retry, with_timeout};
+use crate::common::{
+    transfer_check_version, transfer_fmt_args, transfer_parse_output, CommonOptions,
+    TransferOptions,
+};
+use crate::errors::TransferError;
+use crate::output::logging::LoggingMessage;
+use crate::utils::{borg_transfer_command, find_borg};
+
+/// Transfer archives from one repository to another.
+///
+/// This requires borg >= 2.0.
+///
+/// **Parameter**:
+/// - `options`: Reference to [TransferOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.destination,
+            source = %options.source,
+        )
+    )
+)]
+pub async fn transfer(
+    options: &TransferOptions,
+    common_options: &CommonOptions,
+) -> Result<(), TransferError> {
+    transfer_check_version(common_options)?;
+
+    let local_path = &find_borg(common_options)?;
+
+    let args = transfer_fmt_args(options, common_options, false);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(TransferError::ShlexError)?;
+    let mut command =
+        tokio::process::Command::from(borg_transfer_command(local_path, options, common_options));
+    command.args(args).kill_on_drop(true);
+    let res = output_with_retry(command, common_options).await?;
+
+    transfer_parse_output(res, common_options)?;
+
+    info!("Finished transferring archives");
+
+    Ok(())
+}
+
+/// The progress of a borg transfer command.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum TransferProgress {
+    /// Progress with a current and a total value
+    Percent {
+        /// Current value, absent if the operation has finished
+        current: Option<u64>,
+        /// Total value, absent if the operation has finished
+        total: Option<u64>,
+        /// Indicating whether the operation has finished
+        finished: bool,
+    },
+    /// Progress that only describes what is currently being worked on
+    Message {
+        /// The current progress message, may be absent
+        message: Option<String>,
+        /// Indicating whether the operation has finished
+        finished: bool,
+    },
+}
+
+impl Display for TransferProgress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransferProgress::Percent {
+                current: Some(current),
+                total: Some(total),
+                finished: false,
+            } => write!(f, "{current}/{total}"),
+            TransferProgress::Message {
+                message: Some(message),
+                finished: false,
+            } => write!(f, "{message}"),
+            _ => write!(f, "Finished"),
+        }
+    }
+}
+
+/// Transfer archives from one repository to another.
+///
+/// This requires borg >= 2.0.
+///
+/// The progress will be sent back through the provided channel.
+///
+/// **Parameter**:
+/// - `options`: Reference to [TransferOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+/// - `progress_channel`: A [tokio::sync::mpsc::Sender] of [TransferProgress]. On every progress
+///   update, a message will be sent to this channel
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.destination,
+            source = %options.source,
+        )
+    )
+)]
+pub async fn transfer_progress(
+    options: &TransferOptions,
+    common_options: &CommonOptions,
+    progress_channel: tokio::sync::mpsc::Sender<TransferProgress>,
+) -> Result<(), TransferError> {
+    transfer_check_version(common_options)?;
+
+    let local_path = &find_borg(common_options)?;
+
+    let args = transfer_fmt_args(options, common_options, true);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(TransferError::ShlexError)?;
+    let mut child =
+        tokio::process::Command::from(borg_transfer_command(local_path, options, common_options))
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+    with_timeout(common_options.timeout, async move {
+        let stderr = child.stderr.take().ok_or(TransferError::PipeFailed)?;
+        let mut stderr_reader = BufReader::new(stderr).lines();
+
+        // Everything except the progress is kept to be parsed after borg exited
+        let mut stderr = Vec::new();
+
+        while let Some(line) = stderr_reader
+            .next_line()
+            .await
+            .map_err(TransferError::InvalidBorgOutput)?
+        {
+            let progress = match LoggingMessage::from_str(&line) {
+                Ok(LoggingMessage::ProgressPercent {
+                    current,
+                    total,
+                    finished,
+                    ..
+                }) => TransferProgress::Percent {
+                    current,
+                    total,
+                    finished,
+                },
+                Ok(LoggingMessage::ProgressMessage {
+                    message, finished, ..
+                }) => TransferProgress::Message { message, finished },
+                _ => {
+                    stderr.extend_from_slice(line.as_bytes());
+                    stderr.push(b'\n');
+                    continue;
+                }
+            };
+
+            trace!("Progress: {progress}");
+            if let Err(err) = progress_channel.send(progress).await {
+                error!("Could not send to progress channel: {err}");
+            }
+        }
+
+        let status = child.wait().await?;
+        debug!("Child process exited with {status}");
+
+        transfer_parse_output(
+            Output {
+                status,
+                stdout: Vec::new(),
+                stderr,
+            },
+            common_options,
+        )?;
+
+        info!("Finished transferring archives");
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,41 @@
+use log::info;
+
+use crate::asynchronous::{create, prune};
+use crate::common::{CommonOptions, CreateOptions, PruneOptions};
+use crate::errors::BackupError;
+use crate::output::create::Create;
+use crate::output::prune::PruneReport;
+
+/// Create an archive and prune the repository afterwards.
+///
+/// Prune is only run if the archive was created successfully,
+/// the first error is returned.
+///
+/// **Parameter**:
+/// - `create_options`: Reference to [CreateOptions]
+/// - `prune_options`: Reference to [PruneOptions], usually for the same repository
+/// - `common_options`: The [CommonOptions] that are used for both commands
+///
+/// Returns the results of [create] and [prune].
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %create_options.archive.repository(),
+            archive = create_options.archive.archive(),
+        )
+    )
+)]
+pub async fn backup(
+    create_options: &CreateOptions,
+    prune_options: &PruneOptions,
+    common_options: &CommonOptions,
+) -> Result<(Option<Create>, PruneReport), BackupError> {
+    let create = create(create_options, common_options).await?;
+    let report = prune(prune_options, common_options).await?;
+
+    info!("Finished backup");
+
+    Ok((create, report))
+}
@@ -1,18 +1,39 @@
 //! The common options of borg commands are defined here
 
-use std::fmt::{Display, Formatter, Write};
-use std::io::BufRead;
-use std::num::NonZeroU16;
+use std::fmt::{Debug, Display, Formatter};
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, Write};
+use std::num::{NonZeroU16, NonZeroU32};
+use std::path::{Path, PathBuf};
 use std::process::Output;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
-use log::{debug, error, info, trace, warn};
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use log::{trace, warn};
 use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
 
-use crate::errors::{CompactError, CreateError, InitError, ListError, MountError, PruneError};
+use crate::errors::{
+    ArchiveRefError, BenchmarkError, CompactError, CompressionError, ConfigError, CreateError,
+    DeleteError, ExtractError, FilesCacheModeError, InfoError, InitError, KeyfileError, ListError,
+    MountError, PatternListError, PruneError, RecreateError, RepositoryError, ServeError,
+    TransferError, VersionError,
+};
+use crate::output::benchmark::{BenchmarkMeasurement, BenchmarkResult};
+use crate::output::common::{timestamp_to_local, timestamp_to_utc};
+use crate::output::compact::CompactReport;
 use crate::output::create::Create;
-use crate::output::list::ListRepository;
+use crate::output::delete::DeleteReport;
+use crate::output::extract::SizeMismatch;
+use crate::output::info::{Info, InfoArchivesOutput, InfoRepositoryOutput};
+use crate::output::list::{ListItem, ListRepository};
 use crate::output::logging::{LevelName, LoggingMessage, MessageId};
-use crate::utils::shell_escape;
+use crate::output::prune::PruneReport;
+use crate::output::version::BorgVersion;
+use crate::utils::{shell_escape, ErrorContext};
 
 /// A pattern instruction.
 /// These instructions will be used for the `--pattern` command line parameter.
@@ -135,6 +156,125 @@ impl Display for Pattern {
     }
 }
 
+impl Pattern {
+    /// Check whether every path matched by `other` is matched by this pattern as well.
+    ///
+    /// This only detects the obvious cases, i.e. equal patterns and nested path prefixes.
+    /// [Pattern::PathFullMatch] is ignored, as it doesn't respect the order of the patterns.
+    fn covers(&self, other: &Pattern) -> bool {
+        match (self, other) {
+            (Pattern::PathFullMatch(_), _) | (_, Pattern::PathFullMatch(_)) => false,
+            (Pattern::PathPrefix(prefix), Pattern::PathPrefix(path)) => {
+                let prefix = prefix.trim_matches('/');
+                let path = path.trim_matches('/');
+                prefix.is_empty()
+                    || path == prefix
+                    || path
+                        .strip_prefix(prefix)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            }
+            (Pattern::FnMatch(a), Pattern::FnMatch(b))
+            | (Pattern::Shell(a), Pattern::Shell(b))
+            | (Pattern::Regex(a), Pattern::Regex(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// A builder for a list of [PatternInstruction]
+///
+/// As borg uses the first matching pattern, the order of the rules is important.
+/// [PatternList::build] rejects lists with rules before the first root path,
+/// as well as rules that are shadowed by an earlier rule and therefore never used.
+///
+/// ```
+/// use borgbackup::common::{Pattern, PatternList};
+///
+/// // backup pics, but not the ones from 2018, except the good ones
+/// let patterns = PatternList::new()
+///     .root("/home/user/pics".to_string())
+///     .include(Pattern::PathPrefix("/home/user/pics/2018/good".to_string()))
+///     .exclude(Pattern::PathPrefix("/home/user/pics/2018".to_string()))
+///     .build()
+///     .unwrap();
+/// assert_eq!(patterns.len(), 3);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PatternList {
+    instructions: Vec<PatternInstruction>,
+}
+
+impl PatternList {
+    /// Create an empty [PatternList]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a root path to use as a starting point
+    pub fn root(mut self, path: String) -> Self {
+        self.instructions.push(PatternInstruction::Root(path));
+        self
+    }
+
+    /// Include the files matching the pattern
+    pub fn include(mut self, pattern: Pattern) -> Self {
+        self.instructions.push(PatternInstruction::Include(pattern));
+        self
+    }
+
+    /// Exclude the files matching the pattern
+    pub fn exclude(mut self, pattern: Pattern) -> Self {
+        self.instructions.push(PatternInstruction::Exclude(pattern));
+        self
+    }
+
+    /// Exclude the files matching the pattern without recursing into matching directories
+    pub fn exclude_no_recurse(mut self, pattern: Pattern) -> Self {
+        self.instructions
+            .push(PatternInstruction::ExcludeNoRecurse(pattern));
+        self
+    }
+
+    /// Validate the order of the rules and return the [PatternInstruction]s
+    pub fn build(self) -> Result<Vec<PatternInstruction>, PatternListError> {
+        let mut has_root = false;
+        let mut rules: Vec<&PatternInstruction> = Vec::new();
+
+        for instruction in &self.instructions {
+            let pattern = match instruction {
+                PatternInstruction::Root(_) => {
+                    has_root = true;
+                    continue;
+                }
+                PatternInstruction::Include(pattern)
+                | PatternInstruction::Exclude(pattern)
+                | PatternInstruction::ExcludeNoRecurse(pattern) => pattern,
+            };
+
+            if !has_root {
+                return Err(PatternListError::MissingRoot(instruction.to_string()));
+            }
+
+            let shadowed_by = rules.iter().find(|rule| match rule {
+                PatternInstruction::Include(earlier)
+                | PatternInstruction::Exclude(earlier)
+                | PatternInstruction::ExcludeNoRecurse(earlier) => earlier.covers(pattern),
+                PatternInstruction::Root(_) => false,
+            });
+            if let Some(shadowed_by) = shadowed_by {
+                return Err(PatternListError::ShadowedRule {
+                    rule: instruction.to_string(),
+                    shadowed_by: shadowed_by.to_string(),
+                });
+            }
+
+            rules.push(instruction);
+        }
+
+        Ok(self.instructions)
+    }
+}
+
 /// The compression modes of an archive.
 ///
 /// The compression modes `auto` and `obfuscate` are currently not supported by this library.
@@ -165,6 +305,32 @@ pub enum CompressionMode {
     Lzma(u8),
 }
 
+impl CompressionMode {
+    /// Check that the compression level is in the range that borg accepts.
+    ///
+    /// - [CompressionMode::Zstd]: 1 to 22
+    /// - [CompressionMode::Zlib]: 0 to 9
+    /// - [CompressionMode::Lzma]: 0 to 9
+    pub fn validate(&self) -> Result<(), CompressionError> {
+        let (level, min, max) = match self {
+            CompressionMode::None | CompressionMode::Lz4 => return Ok(()),
+            CompressionMode::Zstd(x) => (*x, 1, 22),
+            CompressionMode::Zlib(x) => (*x, 0, 9),
+            CompressionMode::Lzma(x) => (*x, 0, 9),
+        };
+
+        if level < min || level > max {
+            return Err(CompressionError::InvalidLevel {
+                mode: *self,
+                min,
+                max,
+            });
+        }
+
+        Ok(())
+    }
+}
+
 impl Display for CompressionMode {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -177,6 +343,424 @@ impl Display for CompressionMode {
     }
 }
 
+/// The parameters of the chunker used to split files into chunks.
+///
+/// See <https://borgbackup.readthedocs.io/en/stable/internals/data-structures.html#chunks>
+/// for further information about the chunker.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum ChunkerParams {
+    /// Use the default chunker parameters of borg
+    Default,
+    /// Cut the input into blocks of a fixed size.
+    ///
+    /// This is useful for block devices and raw disk or VM images.
+    /// It is also required for [CreateOptions::sparse] to be effective.
+    Fixed {
+        /// The size of the blocks in bytes
+        block_size: u64,
+        /// The size of an optional header in bytes, which is cut into its own chunk
+        header_size: Option<u64>,
+    },
+    /// Content defined chunking using a rolling hash
+    Buzhash {
+        /// Minimum chunk size as 2 ^ `min_exp` bytes
+        min_exp: u8,
+        /// Maximum chunk size as 2 ^ `max_exp` bytes
+        max_exp: u8,
+        /// Number of bits of the hash that must be zero to cut a chunk.
+        ///
+        /// The resulting statistical medium chunk size is 2 ^ `mask_bits` bytes.
+        mask_bits: u8,
+        /// The size of the rolling hash window in bytes (borg defaults to 4095)
+        window_size: u32,
+    },
+}
+
+impl Display for ChunkerParams {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkerParams::Default => write!(f, "default"),
+            ChunkerParams::Fixed {
+                block_size,
+                header_size: None,
+            } => write!(f, "fixed,{block_size}"),
+            ChunkerParams::Fixed {
+                block_size,
+                header_size: Some(header_size),
+            } => write!(f, "fixed,{block_size},{header_size}"),
+            ChunkerParams::Buzhash {
+                min_exp,
+                max_exp,
+                mask_bits,
+                window_size,
+            } => write!(f, "buzhash,{min_exp},{max_exp},{mask_bits},{window_size}"),
+        }
+    }
+}
+
+/// The mode of the files cache, which is used to detect unchanged files.
+///
+/// These are all combinations that borg accepts.
+///
+/// See <https://borgbackup.readthedocs.io/en/stable/usage/create.html> for further information.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilesCacheMode {
+    /// `ctime,size,inode`, the default of borg
+    CtimeSizeInode,
+    /// `mtime,size,inode`
+    MtimeSizeInode,
+    /// `ctime,size`, useful if inode numbers are not stable
+    CtimeSize,
+    /// `mtime,size`, useful if inode numbers are not stable
+    MtimeSize,
+    /// `rechunk,ctime`, all files are considered modified and rechunked
+    RechunkCtime,
+    /// `rechunk,mtime`, all files are considered modified and rechunked
+    RechunkMtime,
+    /// `size`, only the size is used to detect modifications
+    Size,
+    /// `disabled`, all files are considered modified
+    Disabled,
+}
+
+impl Display for FilesCacheMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilesCacheMode::CtimeSizeInode => write!(f, "ctime,size,inode"),
+            FilesCacheMode::MtimeSizeInode => write!(f, "mtime,size,inode"),
+            FilesCacheMode::CtimeSize => write!(f, "ctime,size"),
+            FilesCacheMode::MtimeSize => write!(f, "mtime,size"),
+            FilesCacheMode::RechunkCtime => write!(f, "rechunk,ctime"),
+            FilesCacheMode::RechunkMtime => write!(f, "rechunk,mtime"),
+            FilesCacheMode::Size => write!(f, "size"),
+            FilesCacheMode::Disabled => write!(f, "disabled"),
+        }
+    }
+}
+
+impl FromStr for FilesCacheMode {
+    type Err = FilesCacheModeError;
+
+    /// Parse a comma separated mode like `mtime,size`.
+    ///
+    /// The order of the entries doesn't matter,
+    /// but only combinations that borg accepts are valid.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut entries: Vec<&str> = s.split(',').map(str::trim).collect();
+        entries.sort_unstable();
+
+        Ok(match entries.as_slice() {
+            ["ctime", "inode", "size"] => FilesCacheMode::CtimeSizeInode,
+            ["inode", "mtime", "size"] => FilesCacheMode::MtimeSizeInode,
+            ["ctime", "size"] => FilesCacheMode::CtimeSize,
+            ["mtime", "size"] => FilesCacheMode::MtimeSize,
+            ["ctime", "rechunk"] => FilesCacheMode::RechunkCtime,
+            ["mtime", "rechunk"] => FilesCacheMode::RechunkMtime,
+            ["size"] => FilesCacheMode::Size,
+            ["disabled"] => FilesCacheMode::Disabled,
+            _ => return Err(FilesCacheModeError::InvalidMode(s.to_string())),
+        })
+    }
+}
+
+/// The location of a borg repository
+///
+/// Borg accepts three forms:
+/// - a local path: `/tmp/foo`
+/// - a path on a remote host in scp style: `user@example.com:/opt/repo`
+/// - an ssh URL: `ssh://user@example.com:2323/opt/repo`
+///
+/// Local paths can be converted infallibly with [From], all other forms
+/// have to be parsed with [FromStr] or [TryFrom], which validates them.
+/// The repository is (de)serialized in its string form.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct Repository {
+    location: String,
+    user: Option<String>,
+    host: Option<String>,
+    port: Option<u16>,
+    path: String,
+}
+
+impl Repository {
+    /// The repository as passed to borg
+    pub fn as_str(&self) -> &str {
+        &self.location
+    }
+
+    /// Whether the repository is on a remote host and accessed via ssh
+    pub fn is_remote(&self) -> bool {
+        self.host.is_some()
+    }
+
+    /// The user to log in as on the remote host
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    /// The remote host, [None] for local repositories
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    /// The ssh port of the remote host, if it is specified
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// The path of the repository, on the remote host for remote repositories
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn local(location: String) -> Self {
+        Self {
+            path: location.clone(),
+            location,
+            user: None,
+            host: None,
+            port: None,
+        }
+    }
+}
+
+/// Split off the user of `[user@]host...`
+///
+/// The `@` is only considered if it comes before the host.
+fn split_user(s: &str) -> (Option<&str>, &str) {
+    match s.find(['@', ':', '/']) {
+        Some(index) if s[index..].starts_with('@') => (Some(&s[..index]), &s[index + 1..]),
+        _ => (None, s),
+    }
+}
+
+/// Split off the host of `host...`
+///
+/// IPv6 addresses are enclosed in brackets, e.g. `[::1]`, and returned without them.
+fn split_host(s: &str) -> Option<(&str, &str)> {
+    match s.strip_prefix('[') {
+        Some(bracketed) => bracketed.split_once(']'),
+        None => Some(s.split_at(s.find([':', '/']).unwrap_or(s.len()))),
+    }
+}
+
+impl FromStr for Repository {
+    type Err = RepositoryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(RepositoryError::Empty);
+        }
+        let invalid = || RepositoryError::InvalidUrl(s.to_string());
+        let missing_path = || RepositoryError::MissingPath(s.to_string());
+        let contains_archive = || RepositoryError::ContainsArchive(s.to_string());
+
+        if let Some(url) = s.strip_prefix("ssh://") {
+            let (user, rest) = split_user(url);
+            let (host, rest) = split_host(rest).ok_or_else(invalid)?;
+            let (port, path) = match rest.strip_prefix(':') {
+                Some(rest) => {
+                    let (port, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+                    let port = port
+                        .parse::<u16>()
+                        .map_err(|_| RepositoryError::InvalidPort(s.to_string()))?;
+                    (Some(port), path)
+                }
+                None => (None, rest),
+            };
+
+            if host.is_empty() || user == Some("") || !(path.is_empty() || path.starts_with('/')) {
+                return Err(invalid());
+            }
+            if path.len() <= 1 {
+                return Err(missing_path());
+            }
+            if path.contains("::") {
+                return Err(contains_archive());
+            }
+
+            return Ok(Self {
+                location: s.to_string(),
+                user: user.map(str::to_string),
+                host: Some(host.to_string()),
+                port,
+                path: path.to_string(),
+            });
+        }
+
+        if let Some(path) = s.strip_prefix("file://") {
+            if path.is_empty() {
+                return Err(missing_path());
+            }
+            if path.contains("::") {
+                return Err(contains_archive());
+            }
+            return Ok(Self {
+                path: path.to_string(),
+                ..Self::local(s.to_string())
+            });
+        }
+
+        // In scp style, the host is separated by a colon, e.g. user@host:/path
+        let (user, rest) = split_user(s);
+        if let Some((host, rest)) = split_host(rest) {
+            if let Some(path) = rest.strip_prefix(':') {
+                if rest.contains("::") {
+                    return Err(contains_archive());
+                }
+                if host.is_empty() || user == Some("") {
+                    return Err(invalid());
+                }
+                if path.is_empty() {
+                    return Err(missing_path());
+                }
+
+                return Ok(Self {
+                    location: s.to_string(),
+                    user: user.map(str::to_string),
+                    host: Some(host.to_string()),
+                    port: None,
+                    path: path.to_string(),
+                });
+            }
+        }
+
+        if s.contains("::") {
+            return Err(contains_archive());
+        }
+        Ok(Self::local(s.to_string()))
+    }
+}
+
+impl TryFrom<&str> for Repository {
+    type Error = RepositoryError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl TryFrom<String> for Repository {
+    type Error = RepositoryError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<&Path> for Repository {
+    fn from(value: &Path) -> Self {
+        Self::local(value.to_string_lossy().into_owned())
+    }
+}
+
+impl From<PathBuf> for Repository {
+    fn from(value: PathBuf) -> Self {
+        Self::from(value.as_path())
+    }
+}
+
+impl From<Repository> for String {
+    fn from(value: Repository) -> Self {
+        value.location
+    }
+}
+
+impl Display for Repository {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.location)
+    }
+}
+
+/// A reference to an archive within a repository
+///
+/// Borg refers to archives as `repository::archive`, e.g. `/tmp/foo::my-archive`.
+/// An [ArchiveRef] can be constructed from both parts with [ArchiveRef::new]
+/// or parsed from the combined form with [FromStr] or [TryFrom].
+/// It is displayed and (de)serialized in the combined form.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct ArchiveRef {
+    repository: Repository,
+    archive: String,
+}
+
+impl ArchiveRef {
+    /// Create a new [ArchiveRef]
+    pub fn new(repository: impl Into<Repository>, archive: String) -> Self {
+        Self {
+            repository: repository.into(),
+            archive,
+        }
+    }
+
+    /// The repository that contains the archive
+    pub fn repository(&self) -> &Repository {
+        &self.repository
+    }
+
+    /// The name of the archive
+    pub fn archive(&self) -> &str {
+        &self.archive
+    }
+
+    /// The shell escaped `repository::archive` argument of borg
+    pub(crate) fn fmt_arg(&self) -> String {
+        format!(
+            "{}::{}",
+            shell_escape(self.repository.as_str()),
+            shell_escape(&self.archive)
+        )
+    }
+}
+
+impl FromStr for ArchiveRef {
+    type Err = ArchiveRefError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((repository, archive)) = s.split_once("::") else {
+            return Err(ArchiveRefError::MissingArchive(s.to_string()));
+        };
+        if archive.is_empty() {
+            return Err(ArchiveRefError::MissingArchive(s.to_string()));
+        }
+
+        Ok(Self {
+            repository: repository.parse()?,
+            archive: archive.to_string(),
+        })
+    }
+}
+
+impl TryFrom<&str> for ArchiveRef {
+    type Error = ArchiveRefError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl TryFrom<String> for ArchiveRef {
+    type Error = ArchiveRefError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ArchiveRef> for String {
+    fn from(value: ArchiveRef) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for ArchiveRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}::{}", self.repository, self.archive)
+    }
+}
+
 /// The encryption mode of the repository.
 ///
 /// See <https://borgbackup.readthedocs.io/en/stable/usage/init.html#more-encryption-modes>
@@ -249,98 +833,570 @@ impl Display for EncryptionMode {
     }
 }
 
-/// The common options that can be used for every borg command
-#[derive(Deserialize, Serialize, Debug, Clone, Default)]
-pub struct CommonOptions {
-    /// The local path to the borg executable. (default = "borg")
-    pub local_path: Option<String>,
-    /// The remote path to the borg executable. (default = "borg")
-    pub remote_path: Option<String>,
-    /// set network upload rate limit in kiByte/s (0 = unlimited)
-    pub upload_ratelimit: Option<u64>,
-    /// Use this command to connect to the ‘borg serve’ process (default: "ssh")
+/// The log level of borg.
+///
+/// Only log messages with at least the specified level are emitted by borg.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum LogLevel {
+    /// Only emit critical messages
+    Critical,
+    /// Emit error messages and above
+    Error,
+    /// Emit warning messages and above (borg's default)
+    Warning,
+    /// Emit info messages and above
+    Info,
+    /// Emit all messages including debug messages.
     ///
-    /// This can be useful to specify an alternative ssh key: "ssh -i /path/to/privkey"
-    pub rsh: Option<String>,
+    /// This dramatically increases the amount of output borg produces,
+    /// which has to be parsed and logged.
+    Debug,
 }
 
-impl From<&CommonOptions> for String {
-    fn from(value: &CommonOptions) -> Self {
-        let mut s = String::new();
-
-        if let Some(rsh) = &value.rsh {
-            s = format!("{s} --rsh {} ", shell_escape(rsh));
+impl Display for LogLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogLevel::Critical => write!(f, "critical"),
+            LogLevel::Error => write!(f, "error"),
+            LogLevel::Warning => write!(f, "warning"),
+            LogLevel::Info => write!(f, "info"),
+            LogLevel::Debug => write!(f, "debug"),
         }
+    }
+}
 
-        if let Some(remote_path) = &value.remote_path {
-            s = format!("{s} --remote-path {} ", shell_escape(remote_path));
-        }
+/// The command borg uses to connect to the ‘borg serve’ process, see [CommonOptions::rsh]
+///
+/// Borg receives the command as a single value and splits it into
+/// the program and its arguments itself.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Rsh {
+    /// A command line that is split like a shell would,
+    /// e.g. `ssh -i /path/to/privkey -o StrictHostKeyChecking=no`
+    Command(String),
+    /// The program and its arguments, which are passed through verbatim.
+    ///
+    /// Use this if an argument contains whitespace or quotes,
+    /// e.g. `["ssh", "-i", "/path/to/my key"]`
+    Args(Vec<String>),
+}
 
-        if let Some(upload_ratelimit) = &value.upload_ratelimit {
-            s = format!("{s} --upload-ratelimit {upload_ratelimit} ");
+impl Rsh {
+    /// The command line that is passed to borg
+    fn command_line(&self) -> String {
+        match self {
+            Rsh::Command(command) => command.clone(),
+            // Quote the arguments, so they survive the split by borg.
+            // Quoting only fails on nul bytes, which can't be passed to borg anyway.
+            Rsh::Args(args) => {
+                shlex::try_join(args.iter().map(String::as_str)).unwrap_or_else(|_| args.join(" "))
+            }
         }
+    }
+}
 
-        s
+impl From<String> for Rsh {
+    fn from(value: String) -> Self {
+        Rsh::Command(value)
     }
 }
 
-/// The quantifier for [PruneWithin]
-#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
-pub enum PruneWithinTime {
-    /// Hour quantifier
-    Hour,
-    /// Day quantifier
-    Day,
-    /// Week quantifier (7 days)
-    Week,
-    /// Month quantifier (31 days)
-    Month,
-    /// Year quantifier
-    Year,
+impl From<&str> for Rsh {
+    fn from(value: &str) -> Self {
+        Rsh::Command(value.to_string())
+    }
 }
 
-impl Display for PruneWithinTime {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            PruneWithinTime::Hour => write!(f, "H"),
-            PruneWithinTime::Day => write!(f, "d"),
-            PruneWithinTime::Week => write!(f, "w"),
-            PruneWithinTime::Month => write!(f, "m"),
-            PruneWithinTime::Year => write!(f, "y"),
-        }
+impl From<Vec<String>> for Rsh {
+    fn from(value: Vec<String>) -> Self {
+        Rsh::Args(value)
     }
 }
 
-/// The definition to specify an interval in which backups
-/// should not be pruned.
-#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
-pub struct PruneWithin {
-    /// quantifier
-    pub quantifier: NonZeroU16,
-    /// The time quantifier
-    pub time: PruneWithinTime,
+/// The policy for retrying borg on transient errors, see [CommonOptions::retry]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one
+    pub attempts: NonZeroU32,
+    /// The delay before the first retry, it is doubled for every further retry
+    pub backoff: Duration,
+    /// The message ids of the errors that are retried
+    ///
+    /// Errors that are not listed, e.g. a wrong passphrase, are returned immediately.
+    pub transient: Vec<MessageId>,
 }
 
-impl Display for PruneWithin {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}{}", self.quantifier, self.time)
+impl RetryPolicy {
+    /// Create a new [RetryPolicy], which retries closed connections and lock errors
+    pub fn new(attempts: NonZeroU32, backoff: Duration) -> Self {
+        Self {
+            attempts,
+            backoff,
+            transient: vec![
+                MessageId::ConnectionClosed,
+                MessageId::ConnectionClosedWithHint,
+                MessageId::LockError,
+                MessageId::LockErrorT,
+                MessageId::LockTimeout,
+            ],
+        }
     }
-}
 
-/// Options for [crate::sync::prune]
-///
-/// A good procedure is to thin out more and more the older your backups get.
-/// As an example, `keep_daily` 7 means to keep the latest backup on each day, up to 7 most
-/// recent days with backups (days without backups do not count). The rules are applied from
-/// secondly to yearly, and backups selected by previous rules do not count towards those of
-/// later rules. The time that each backup starts is used for pruning purposes.
-/// Dates and times are interpreted in the local timezone, and weeks go from Monday to Sunday.
+    /// The transient error borg failed with, if any
+    ///
+    /// The message ids of the log messages are checked, as well as the exit code,
+    /// see [CommonOptions::modern_exit_codes].
+    pub(crate) fn transient_error(&self, output: &Output) -> Option<MessageId> {
+        let exit_code = output.status.code()?;
+        if !is_error_exit_code(exit_code) {
+            return None;
+        }
+
+        BufRead::lines(output.stderr.as_slice())
+            .map_while(Result::ok)
+            .filter_map(|line| match LoggingMessage::from_str(&line) {
+                Ok(LoggingMessage::LogMessage { msg_id, .. }) => msg_id,
+                _ => None,
+            })
+            .chain(MessageId::from_exit_code(exit_code))
+            .find(|msg_id| self.transient.contains(msg_id))
+    }
+
+    /// The delay before the next attempt, or [None] if borg shouldn't be retried
+    ///
+    /// `attempt` is the number of the attempt that produced `output`, starting at 1.
+    pub(crate) fn retry_delay(&self, attempt: u32, output: &Output) -> Option<Duration> {
+        if attempt >= self.attempts.get() {
+            return None;
+        }
+        let msg_id = self.transient_error(output)?;
+
+        let delay = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(attempt - 1));
+        warn!(
+            "borg failed with {msg_id} (attempt {attempt}/{}), retrying in {delay:?}",
+            self.attempts
+        );
+        Some(delay)
+    }
+}
+
+/// A callback that is invoked with the messages of borg, see [CommonOptions::log_callback]
+pub type LogCallback = Arc<dyn Fn(&LoggingMessage) + Send + Sync>;
+
+/// The common options that can be used for every borg command
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct CommonOptions {
+    /// The local path to the borg executable.
+    ///
+    /// If not set, borg is searched in `PATH`. If it isn't found,
+    /// the `BorgNotFound` variant of the error of the command is returned.
+    pub local_path: Option<String>,
+    /// The remote path to the borg executable. (default = "borg")
+    pub remote_path: Option<String>,
+    /// Restrict the remote `borg serve` process to append-only mode,
+    /// by appending `--append-only` to its arguments.
+    ///
+    /// In append-only mode, the session can't delete or overwrite data in the repository,
+    /// regardless of whether the repository was initialized with `--append-only`.
+    /// It only applies to remote repositories, as local repositories don't use `borg serve`.
+    ///
+    /// **Note**: This restriction is requested by the client, so it only protects against
+    /// mistakes of the client itself. A compromised client can simply omit it.
+    /// To protect the repository against a compromised client, the restriction has to be
+    /// enforced on the server, e.g. with a forced command in `authorized_keys`:
+    /// `command="borg serve --append-only --restrict-to-repository /opt/repo",restrict ssh-ed25519 ...`.
+    /// If the server enforces a command, the remote command of the client is ignored.
+    pub append_only_remote: bool,
+    /// set network upload rate limit in kiByte/s (0 = unlimited)
+    pub upload_ratelimit: Option<u64>,
+    /// set network upload buffer size in MiB (0 = no buffer)
+    pub upload_buffer: Option<u64>,
+    /// set network download rate limit in kiByte/s (0 = unlimited)
+    pub download_ratelimit: Option<u64>,
+    /// Use this command to connect to the ‘borg serve’ process (default: "ssh")
+    ///
+    /// This can be useful to specify an alternative ssh key: "ssh -i /path/to/privkey",
+    /// see [Rsh] for the accepted forms.
+    pub rsh: Option<Rsh>,
+    /// The identity file ssh uses to connect to the ‘borg serve’ process
+    ///
+    /// Together with [CommonOptions::ssh_known_hosts_file], this is a shorthand for
+    /// `ssh -i <identity file> -o UserKnownHostsFile=<known hosts file>`.
+    /// It is ignored if [CommonOptions::rsh] is set, which takes precedence.
+    pub ssh_identity_file: Option<PathBuf>,
+    /// The known hosts file ssh uses to connect to the ‘borg serve’ process
+    ///
+    /// See [CommonOptions::ssh_identity_file], it is ignored if [CommonOptions::rsh] is set.
+    pub ssh_known_hosts_file: Option<PathBuf>,
+    /// Wait at most SECONDS for acquiring a repository/cache lock (borg defaults to 1)
+    ///
+    /// This is useful if multiple borg processes may access the same repository concurrently.
+    pub lock_wait: Option<u64>,
+    /// Set umask to the given value, e.g. `0o077` (borg defaults to `0o077`)
+    ///
+    /// This controls the permissions of the files borg creates in the repository.
+    pub umask: Option<u32>,
+    /// The log level of borg (borg defaults to [LogLevel::Warning])
+    ///
+    /// Raise it to [LogLevel::Info] to receive more detailed log messages.
+    /// Note that [LogLevel::Debug] dramatically increases the output volume.
+    pub log_level: Option<LogLevel>,
+    /// The base directory of borg, set as `BORG_BASE_DIR` for the borg process.
+    ///
+    /// It is used as the base for the default cache, config and security directories.
+    pub base_dir: Option<String>,
+    /// The cache directory of borg, set as `BORG_CACHE_DIR` for the borg process.
+    pub cache_dir: Option<String>,
+    /// The config directory of borg, set as `BORG_CONFIG_DIR` for the borg process.
+    pub config_dir: Option<String>,
+    /// The key file of a repository with `keyfile` encryption,
+    /// set as `BORG_KEY_FILE` for the borg process.
+    ///
+    /// If not set, borg searches the keys directory for the key file of the repository.
+    pub key_file: Option<PathBuf>,
+    /// The directory borg searches for key files, set as `BORG_KEYS_DIR` for the borg process.
+    pub keys_dir: Option<PathBuf>,
+    /// The security directory of borg, set as `BORG_SECURITY_DIR` for the borg process.
+    ///
+    /// borg keeps the state of its replay protection in this directory, e.g. the last seen
+    /// manifest timestamp and the nonces of each repository. Isolating it, e.g. per tenant,
+    /// keeps the state of unrelated setups apart. The state has to be kept though, as borg
+    /// can only detect a rolled back repository by comparing it to this state.
+    pub security_dir: Option<PathBuf>,
+    /// Allow access to a repository that was previously located at a different location,
+    /// by setting `BORG_RELOCATED_REPO_ACCESS_IS_OK=yes` for the borg process.
+    ///
+    /// Without it, borg asks for confirmation, which can't be answered by this library.
+    ///
+    /// **Note**: This suppresses a safety prompt, only use it deliberately.
+    pub relocated_repo_access_ok: bool,
+    /// Allow access to an unknown unencrypted repository,
+    /// by setting `BORG_UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK=yes` for the borg process.
+    ///
+    /// Without it, borg asks for confirmation, which can't be answered by this library.
+    ///
+    /// **Note**: This suppresses a safety prompt, only use it deliberately.
+    pub unknown_unencrypted_repo_access_ok: bool,
+    /// Use the modern exit codes of borg (borg >= 1.4),
+    /// by setting `BORG_EXIT_CODES=modern` for the borg process.
+    ///
+    /// With modern exit codes, borg uses a distinct exit code for each error,
+    /// which allows to map errors to typed errors even if borg didn't emit a log message.
+    /// See [MessageId::from_exit_code] for further information.
+    pub modern_exit_codes: bool,
+    /// The maximum number of bytes of borg's log output that are kept
+    /// for the `Unknown` variant of an error.
+    ///
+    /// Only the most recent output is kept.
+    /// If not set, [DEFAULT_MAX_ERROR_CONTEXT] is used.
+    pub max_error_context: Option<usize>,
+    /// The version of the used borg binary.
+    ///
+    /// If set, arguments that were renamed between borg versions are emitted in the
+    /// spelling of this version, otherwise the spelling of the latest version is used.
+    /// The version can be retrieved with [crate::sync::version].
+    pub borg_version: Option<BorgVersion>,
+    /// The maximum duration a borg command may run.
+    ///
+    /// If borg didn't finish in time, it is killed and a `Timeout` error is returned.
+    /// This prevents hanging forever, e.g. on an unresponsive ssh connection.
+    pub timeout: Option<Duration>,
+    /// Retry borg if it failed with a transient error, e.g. a closed connection.
+    ///
+    /// This applies to commands whose output is collected after borg exited.
+    /// Commands that stream their progress or read from `stdin` are not retried.
+    /// [CommonOptions::timeout] applies to each attempt.
+    pub retry: Option<RetryPolicy>,
+    /// A callback that is invoked for every message borg emitted.
+    ///
+    /// This allows to route the messages of borg into another logging pipeline,
+    /// with the `msg_id`, level and timestamp intact.
+    /// The log messages are passed to the `log` crate regardless.
+    ///
+    /// As a closure can't be serialized, it is skipped by serde.
+    #[serde(skip)]
+    pub log_callback: Option<LogCallback>,
+    /// Interpret the timestamps in the output of borg as UTC instead of local time.
+    ///
+    /// The timestamps of archives and repositories, e.g. `start` and `end` of an archive,
+    /// are emitted with `--json` in ISO 8601 format without an offset, e.g.
+    /// `2023-01-02T10:00:00.302352`. They are in the local time of the host running borg,
+    /// which is the client for remote repositories, and parsed as [NaiveDateTime].
+    /// Timezone-aware timestamps, which borg >= 2.0 emits, are converted to UTC while parsing.
+    /// The `time` of log messages is seconds since the epoch and not affected.
+    ///
+    /// Set this if borg runs with `TZ=UTC` or emits timezone-aware timestamps.
+    /// This only affects [CommonOptions::timestamp_utc] and [CommonOptions::timestamp_local],
+    /// the parsed timestamps are left as emitted.
+    pub assume_timestamps_utc: bool,
+    /// A command borg is run with, e.g. `["nice", "-n", "19", "ionice", "-c3"]`.
+    ///
+    /// The first element is executed with the remaining elements, followed by
+    /// the path of borg and its arguments, e.g. `nice -n 19 ionice -c3 /usr/bin/borg create ...`.
+    /// This allows to run borg in a sandbox or with a lower priority, e.g. with
+    /// `systemd-run` or `unshare`. An empty wrapper is ignored.
+    ///
+    /// Signals, e.g. to kill borg on [CommonOptions::timeout], are sent to the wrapper,
+    /// so it should either execute borg in its place, like `nice` does, or forward them.
+    pub command_wrapper: Option<Vec<String>>,
+}
+
+impl Debug for CommonOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommonOptions")
+            .field("local_path", &self.local_path)
+            .field("remote_path", &self.remote_path)
+            .field("append_only_remote", &self.append_only_remote)
+            .field("upload_ratelimit", &self.upload_ratelimit)
+            .field("upload_buffer", &self.upload_buffer)
+            .field("download_ratelimit", &self.download_ratelimit)
+            .field("rsh", &self.rsh)
+            .field("ssh_identity_file", &self.ssh_identity_file)
+            .field("ssh_known_hosts_file", &self.ssh_known_hosts_file)
+            .field("lock_wait", &self.lock_wait)
+            .field("umask", &self.umask)
+            .field("log_level", &self.log_level)
+            .field("base_dir", &self.base_dir)
+            .field("cache_dir", &self.cache_dir)
+            .field("config_dir", &self.config_dir)
+            .field("key_file", &self.key_file)
+            .field("keys_dir", &self.keys_dir)
+            .field("security_dir", &self.security_dir)
+            .field("relocated_repo_access_ok", &self.relocated_repo_access_ok)
+            .field(
+                "unknown_unencrypted_repo_access_ok",
+                &self.unknown_unencrypted_repo_access_ok,
+            )
+            .field("modern_exit_codes", &self.modern_exit_codes)
+            .field("max_error_context", &self.max_error_context)
+            .field("borg_version", &self.borg_version)
+            .field("timeout", &self.timeout)
+            .field("retry", &self.retry)
+            .field(
+                "log_callback",
+                &self.log_callback.as_ref().map(|_| "Fn(&LoggingMessage)"),
+            )
+            .field("assume_timestamps_utc", &self.assume_timestamps_utc)
+            .field("command_wrapper", &self.command_wrapper)
+            .finish()
+    }
+}
+
+impl CommonOptions {
+    /// Convert a timestamp from the output of borg, e.g. [ListArchive::start](crate::output::list::ListArchive::start),
+    /// to UTC, see [CommonOptions::assume_timestamps_utc]
+    ///
+    /// Use this to compare timestamps of borg running on hosts in different timezones
+    /// or across changes of daylight saving time.
+    pub fn timestamp_utc(&self, timestamp: NaiveDateTime) -> DateTime<Utc> {
+        timestamp_to_utc(timestamp, self.assume_timestamps_utc)
+    }
+
+    /// Convert a timestamp from the output of borg to local time,
+    /// see [CommonOptions::timestamp_utc]
+    pub fn timestamp_local(&self, timestamp: NaiveDateTime) -> DateTime<Local> {
+        timestamp_to_local(timestamp, self.assume_timestamps_utc)
+    }
+
+    /// Pass a message of borg to [CommonOptions::log_callback], if set
+    pub(crate) fn notify_log_callback(&self, message: &LoggingMessage) {
+        if let Some(log_callback) = &self.log_callback {
+            log_callback(message);
+        }
+    }
+
+    /// The rsh command borg is called with
+    ///
+    /// If [CommonOptions::rsh] isn't set, it is synthesized from
+    /// [CommonOptions::ssh_identity_file] and [CommonOptions::ssh_known_hosts_file].
+    fn effective_rsh(&self) -> Option<Rsh> {
+        if let Some(rsh) = &self.rsh {
+            return Some(rsh.clone());
+        }
+        if self.ssh_identity_file.is_none() && self.ssh_known_hosts_file.is_none() {
+            return None;
+        }
+
+        let mut args = vec!["ssh".to_string()];
+        if let Some(identity_file) = &self.ssh_identity_file {
+            args.push("-i".to_string());
+            args.push(identity_file.to_string_lossy().into_owned());
+        }
+        if let Some(known_hosts_file) = &self.ssh_known_hosts_file {
+            args.push("-o".to_string());
+            args.push(format!(
+                "UserKnownHostsFile={}",
+                known_hosts_file.to_string_lossy()
+            ));
+        }
+        Some(Rsh::Args(args))
+    }
+
+    /// The remote path borg is called with
+    ///
+    /// borg runs `<remote path> serve <options>` on the remote host, so `--append-only`
+    /// can't be appended to the remote path directly. If [CommonOptions::append_only_remote]
+    /// is set, the remote path is wrapped in a shell that appends it to the arguments instead.
+    fn effective_remote_path(&self) -> Option<String> {
+        if !self.append_only_remote {
+            return self.remote_path.clone();
+        }
+
+        let remote_path = self.remote_path.as_deref().unwrap_or("borg");
+        Some(format!(
+            r#"sh -c 'exec "$0" "$@" --append-only' {remote_path}"#
+        ))
+    }
+
+    /// The flags of the options as a fragment of the arguments of borg.
+    ///
+    /// Like the other optional flags, the fragment starts with a space if it isn't empty.
+    pub(crate) fn fmt_args(&self) -> String {
+        let flags = self.to_string();
+        if flags.is_empty() {
+            flags
+        } else {
+            format!(" {flags}")
+        }
+    }
+}
+
+/// The default value of [CommonOptions::max_error_context]: 64 KiB
+pub const DEFAULT_MAX_ERROR_CONTEXT: usize = 64 * 1024;
+
+/// Renders the command line flags of the [CommonOptions], separated by single spaces.
+///
+/// The flags are always emitted in this order, unset options are left out:
+/// 1. [CommonOptions::log_level]
+/// 2. `--rsh`, see [CommonOptions::rsh]
+/// 3. `--remote-path`, see [CommonOptions::remote_path]
+/// 4. [CommonOptions::upload_ratelimit]
+/// 5. [CommonOptions::upload_buffer]
+/// 6. [CommonOptions::download_ratelimit]
+/// 7. [CommonOptions::lock_wait]
+/// 8. [CommonOptions::umask]
+///
+/// The values are quoted, so splitting the string with `shlex` yields the arguments
+/// that are passed to borg. Options that are passed as environment variables,
+/// like [CommonOptions::cache_dir], are not part of it.
+impl Display for CommonOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut flags = Vec::new();
+
+        if let Some(log_level) = &self.log_level {
+            flags.push(format!("--{log_level}"));
+        }
+
+        if let Some(rsh) = self.effective_rsh() {
+            let rsh = rsh.command_line();
+            // The command line contains quotes of its own, which shell_escape doesn't preserve
+            let rsh = shlex::try_quote(&rsh)
+                .map(|rsh| rsh.into_owned())
+                .unwrap_or_else(|_| shell_escape(&rsh));
+            flags.push(format!("--rsh {rsh}"));
+        }
+
+        if let Some(remote_path) = self.effective_remote_path() {
+            // The remote path may contain quotes of its own, which shell_escape doesn't preserve
+            let remote_path = shlex::try_quote(&remote_path)
+                .map(|remote_path| remote_path.into_owned())
+                .unwrap_or_else(|_| shell_escape(&remote_path));
+            flags.push(format!("--remote-path {remote_path}"));
+        }
+
+        if let Some(upload_ratelimit) = &self.upload_ratelimit {
+            flags.push(format!("--upload-ratelimit {upload_ratelimit}"));
+        }
+
+        if let Some(upload_buffer) = &self.upload_buffer {
+            flags.push(format!("--upload-buffer {upload_buffer}"));
+        }
+
+        if let Some(download_ratelimit) = &self.download_ratelimit {
+            flags.push(format!("--download-ratelimit {download_ratelimit}"));
+        }
+
+        if let Some(lock_wait) = &self.lock_wait {
+            flags.push(format!("--lock-wait {lock_wait}"));
+        }
+
+        if let Some(umask) = &self.umask {
+            flags.push(format!("--umask {umask:04o}"));
+        }
+
+        write!(f, "{}", flags.join(" "))
+    }
+}
+
+impl From<&CommonOptions> for String {
+    fn from(value: &CommonOptions) -> Self {
+        value.to_string()
+    }
+}
+
+/// The quantifier for [PruneWithin]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub enum PruneWithinTime {
+    /// Hour quantifier
+    Hour,
+    /// Day quantifier
+    Day,
+    /// Week quantifier (7 days)
+    Week,
+    /// Month quantifier (31 days)
+    Month,
+    /// Year quantifier
+    Year,
+}
+
+impl Display for PruneWithinTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PruneWithinTime::Hour => write!(f, "H"),
+            PruneWithinTime::Day => write!(f, "d"),
+            PruneWithinTime::Week => write!(f, "w"),
+            PruneWithinTime::Month => write!(f, "m"),
+            PruneWithinTime::Year => write!(f, "y"),
+        }
+    }
+}
+
+/// The definition to specify an interval in which backups
+/// should not be pruned.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct PruneWithin {
+    /// quantifier
+    pub quantifier: NonZeroU16,
+    /// The time quantifier
+    pub time: PruneWithinTime,
+}
+
+impl Display for PruneWithin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.quantifier, self.time)
+    }
+}
+
+/// Options for [crate::sync::prune]
+///
+/// A good procedure is to thin out more and more the older your backups get.
+/// As an example, `keep_daily` 7 means to keep the latest backup on each day, up to 7 most
+/// recent days with backups (days without backups do not count). The rules are applied from
+/// secondly to yearly, and backups selected by previous rules do not count towards those of
+/// later rules. The time that each backup starts is used for pruning purposes.
+/// Dates and times are interpreted in the local timezone, and weeks go from Monday to Sunday.
 /// Specifying a negative number of archives to keep means that there is no limit.
 ///
 /// As of borg 1.2.0, borg will retain the oldest archive if any of the secondly, minutely,
 /// hourly, daily, weekly, monthly, or yearly rules was not otherwise able to meet
 /// its retention target. This enables the first chronological archive to continue aging until
 /// it is replaced by a newer archive that meets the retention criteria.
+///
+/// At least one keep rule must be given, otherwise [PruneError::NoKeepRules] is returned.
+/// Use [crate::sync::delete] to delete all archives.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PruneOptions {
     /// Path to the repository
@@ -348,8 +1404,8 @@ pub struct PruneOptions {
     /// Example values:
     /// - `/tmp/foo`
     /// - `user@example.com:/opt/repo`
-    /// - `ssh://user@example.com:2323:/opt/repo`
-    pub repository: String,
+    /// - `ssh://user@example.com:2323/opt/repo`
+    pub repository: Repository,
     /// The passphrase for the repository
     ///
     /// If using a repository with [EncryptionMode::None],
@@ -358,6 +1414,8 @@ pub struct PruneOptions {
     /// The archives kept with this option do not count towards the totals specified
     /// by any other options.
     pub keep_within: Option<PruneWithin>,
+    /// number of the most recent archives to keep, regardless of their age
+    pub keep_last: Option<NonZeroU16>,
     /// number of secondly archives to keep
     pub keep_secondly: Option<NonZeroU16>,
     /// number of minutely archives to keep
@@ -378,15 +1436,35 @@ pub struct PruneOptions {
     ///
     /// The pattern can use [Pattern::Shell]
     pub glob_archives: Option<String>,
+    /// only consider archive names matching the pattern.
+    ///
+    /// Only [Pattern::Shell] and [Pattern::Regex] can be used to match archive names.
+    /// Borg >= 2.0 receives the pattern with `--match-archives`, older versions only
+    /// support [Pattern::Shell] with `--glob-archives`.
+    /// Unless [CommonOptions::borg_version] is set, borg < 2.0 is assumed for [Pattern::Shell].
+    ///
+    /// This can't be combined with [PruneOptions::glob_archives].
+    pub match_archives: Option<Pattern>,
+    /// Output a verbose list of the archives it keeps / prunes.
+    ///
+    /// The decisions are returned as [PruneReport].
+    pub list: bool,
+    /// Do not change the repository, only report what would be pruned.
+    ///
+    /// Use this in combination with [PruneOptions::list] to preview the decisions.
+    pub dry_run: bool,
+    /// Work slower, but using less space and memory
+    pub save_space: bool,
 }
 
 impl PruneOptions {
     /// Create an new [PruneOptions]
-    pub fn new(repository: String) -> Self {
+    pub fn new(repository: impl Into<Repository>) -> Self {
         Self {
-            repository,
+            repository: repository.into(),
             passphrase: None,
             keep_within: None,
+            keep_last: None,
             keep_secondly: None,
             keep_minutely: None,
             keep_hourly: None,
@@ -396,6 +1474,41 @@ impl PruneOptions {
             keep_yearly: None,
             checkpoint_interval: None,
             glob_archives: None,
+            match_archives: None,
+            list: false,
+            dry_run: false,
+            save_space: false,
+        }
+    }
+
+    /// Whether any of the `keep_*` rules is set
+    pub fn has_keep_rules(&self) -> bool {
+        self.keep_within.is_some()
+            || self.keep_last.is_some()
+            || self.keep_secondly.is_some()
+            || self.keep_minutely.is_some()
+            || self.keep_hourly.is_some()
+            || self.keep_daily.is_some()
+            || self.keep_weekly.is_some()
+            || self.keep_monthly.is_some()
+            || self.keep_yearly.is_some()
+    }
+}
+
+impl MountSource {
+    /// The repository that is mounted, or which contains the mounted archive
+    pub fn repository(&self) -> &Repository {
+        match self {
+            MountSource::Repository { name, .. } => name,
+            MountSource::Archive { archive } => archive.repository(),
+        }
+    }
+
+    /// The name of the mounted archive, if an archive is mounted
+    pub fn archive(&self) -> Option<&str> {
+        match self {
+            MountSource::Repository { .. } => None,
+            MountSource::Archive { archive } => Some(archive.archive()),
         }
     }
 }
@@ -413,8 +1526,8 @@ pub enum MountSource {
         /// Example values:
         /// - `/tmp/foo`
         /// - `user@example.com:/opt/repo`
-        /// - `ssh://user@example.com:2323:/opt/repo`
-        name: String,
+        /// - `ssh://user@example.com:2323/opt/repo`
+        name: Repository,
         /// Obtain the first N archives
         first_n_archives: Option<NonZeroU16>,
         /// Obtain the last N archives
@@ -424,13 +1537,13 @@ pub enum MountSource {
     },
     /// Mount an archive (repo_name::archive_name)
     Archive {
-        /// Path to the borg archive you wish to mount
+        /// The borg archive you wish to mount
         ///
         /// Example values:
         /// - `/tmp/foo::my-archive`
         /// - `user@example.com:/opt/repo::archive`
-        /// - `ssh://user@example.com:2323:/opt/repo`
-        archive_name: String,
+        /// - `ssh://user@example.com:2323/opt/repo::archive`
+        archive: ArchiveRef,
     },
 }
 
@@ -459,6 +1572,20 @@ pub struct MountOptions {
     /// - `/a/path/I/actually/care/about`
     /// - `**/some/intermediate/folder/*`
     pub select_paths: Vec<Pattern>,
+    /// Stay in the foreground, do not daemonize.
+    ///
+    /// The command won't return until the mountpoint is unmounted.
+    /// Use [crate::asynchronous::mount_foreground] to retrieve a handle to the running process.
+    pub foreground: bool,
+    /// Extra mount options, passed to FUSE.
+    ///
+    /// Every option is passed with its own `-o`.
+    ///
+    /// Example values:
+    /// - `allow_other`
+    /// - `versions`
+    /// - `uid=1000`
+    pub fuse_options: Vec<String>,
 }
 
 impl MountOptions {
@@ -469,6 +1596,8 @@ impl MountOptions {
             mountpoint,
             passphrase: None,
             select_paths: vec![],
+            foreground: false,
+            fuse_options: vec![],
         }
     }
 }
@@ -481,21 +1610,77 @@ pub struct CompactOptions {
     /// Example values:
     /// - `/tmp/foo`
     /// - `user@example.com:/opt/repo`
-    /// - `ssh://user@example.com:2323:/opt/repo`
-    pub repository: String,
+    /// - `ssh://user@example.com:2323/opt/repo`
+    pub repository: Repository,
+    /// Do not change the repository, only report how much space would be freed.
+    ///
+    /// This requires borg >= 2.0, if [CommonOptions::borg_version] is set to an older
+    /// version, [CompactError::DryRunNotSupported] is returned.
+    pub dry_run: bool,
+    /// Remove the commit-only segment files left behind by borg < 1.2.
+    ///
+    /// This is only supported by borg >= 1.2 and < 2.0, if [CommonOptions::borg_version]
+    /// is set to another version, [CompactError::CleanupCommitsNotSupported] is returned.
+    pub cleanup_commits: bool,
+}
+
+impl CompactOptions {
+    /// Create an new [CompactOptions]
+    pub fn new(repository: impl Into<Repository>) -> Self {
+        Self {
+            repository: repository.into(),
+            dry_run: false,
+            cleanup_commits: false,
+        }
+    }
+}
+
+/// The source of the data that should be archived by a borg create command
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum CreateSource {
+    /// The paths to archive.
+    ///
+    /// All given paths will be recursively traversed.
+    Paths(Vec<String>),
+    /// Read the content of a single file from stdin.
+    ///
+    /// Use [crate::sync::create_stdin] or [crate::asynchronous::create_stdin] to provide the data.
+    Stdin {
+        /// The file name of the content in the archive (borg defaults to "stdin")
+        name: String,
+        /// The file mode of the content in the archive (borg defaults to 0o660)
+        mode: Option<u32>,
+        /// The user name of the content in the archive (borg defaults to "root")
+        user: Option<String>,
+        /// The group name of the content in the archive (borg defaults to "root")
+        group: Option<String>,
+    },
+    /// Execute a command and archive its stdout as a single file.
+    ///
+    /// If the command exits with a non-zero exit code, borg will fail to create the archive.
+    Command {
+        /// The command and its arguments
+        command: Vec<String>,
+        /// The file name of the content in the archive (borg defaults to "stdin")
+        name: String,
+        /// The file mode of the content in the archive (borg defaults to 0o660)
+        mode: Option<u32>,
+        /// The user name of the content in the archive (borg defaults to "root")
+        user: Option<String>,
+        /// The group name of the content in the archive (borg defaults to "root")
+        group: Option<String>,
+    },
 }
 
+/// The combined length of the paths in bytes, above which the paths are passed to borg
+/// in a temporary file, see [CreateOptions::paths_from_file]
+pub const PATHS_FROM_FILE_THRESHOLD: usize = 64 * 1024;
+
 /// The options for a borg create command
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CreateOptions {
-    /// Path to the repository
-    ///
-    /// Example values:
-    /// - `/tmp/foo`
-    /// - `user@example.com:/opt/repo`
-    /// - `ssh://user@example.com:2323:/opt/repo`
-    pub repository: String,
-    /// Name of archive to create (must be also a valid directory name)
+    /// The repository and the name of archive to create
+    /// (must be also a valid directory name)
     ///
     /// The archive name needs to be unique.
     /// It must not end in ‘.checkpoint’ or ‘.checkpoint.N’ (with N being a number),
@@ -506,7 +1691,7 @@ pub struct CreateOptions {
     ///
     /// See <https://borgbackup.readthedocs.io/en/stable/usage/help.html#borg-patterns> for further
     /// information.
-    pub archive: String,
+    pub archive: ArchiveRef,
     /// The passphrase for the repository
     ///
     /// If using a repository with [EncryptionMode::None],
@@ -514,23 +1699,82 @@ pub struct CreateOptions {
     pub passphrase: Option<String>,
     /// Add a comment text to the archive
     pub comment: Option<String>,
+    /// Manually specify the archive creation date/time (interpreted as UTC).
+    ///
+    /// If not set, the current time is used.
+    pub timestamp: Option<NaiveDateTime>,
     /// Specify the compression mode that should be used.
     ///
     /// Defaults to [CompressionMode::Lz4].
     pub compression: Option<CompressionMode>,
-    /// The paths to archive.
+    /// Specify the chunker parameters.
     ///
-    /// All given paths will be recursively traversed.
-    pub paths: Vec<String>,
+    /// Defaults to [ChunkerParams::Default].
+    pub chunker_params: Option<ChunkerParams>,
+    /// Specify the mode of the files cache.
+    ///
+    /// Defaults to [FilesCacheMode::CtimeSizeInode].
+    pub files_cache: Option<FilesCacheMode>,
+    /// Write a checkpoint every N seconds (borg defaults to 1800)
+    ///
+    /// If borg create is aborted, only the files since the last checkpoint have to be
+    /// backed up again, so shorter intervals lose less work on unreliable connections.
+    pub checkpoint_interval: Option<NonZeroU16>,
+    /// Write a checkpoint every N bytes of written data (requires borg >= 1.4)
+    ///
+    /// In contrast to [CreateOptions::checkpoint_interval], the amount of work that is lost
+    /// on an abort doesn't depend on the throughput. `0` disables it, which is the default of borg.
+    ///
+    /// If [CommonOptions::borg_version] is set to an older version,
+    /// [CreateError::CheckpointVolumeNotSupported] is returned,
+    /// otherwise older versions fail with an unknown argument.
+    pub checkpoint_volume: Option<u64>,
+    /// Pass `--stats` to borg.
+    ///
+    /// As the output of borg create is requested with `--json`, which implies `--stats`,
+    /// this has no effect: the statistics are always returned in
+    /// [Create::archive](crate::output::create::Create::archive).
+    /// It is accepted for parity with scripts that pass `--stats` out of habit,
+    /// a statistics table on stderr is tolerated while parsing the output.
+    pub stats: bool,
+    /// The source of the data to archive.
+    ///
+    /// See [CreateSource] for further information.
+    pub source: CreateSource,
+    /// Pass the paths of [CreateSource::Paths] to borg in a temporary file
+    /// instead of the command line (requires borg >= 1.2).
+    ///
+    /// This avoids exceeding the length limit of the command line for large lists of paths.
+    /// It is enabled automatically if the paths exceed [PATHS_FROM_FILE_THRESHOLD] bytes.
+    /// The paths must not contain newlines.
+    pub paths_from_file: bool,
     /// Exclude directories that contain a CACHEDIR.TAG file
     /// (<http://www.bford.info/cachedir/spec.html>)
     pub exclude_caches: bool,
+    /// Exclude directories that contain a file with one of the given names
+    pub exclude_if_present: Vec<String>,
+    /// Keep tag files of excluded directories.
+    ///
+    /// The contents of the tagged directory are still excluded,
+    /// but the tag file itself (e.g. `CACHEDIR.TAG`) is kept in the archive.
+    ///
+    /// This only has an effect in combination with [CreateOptions::exclude_caches]
+    /// or [CreateOptions::exclude_if_present].
+    pub keep_exclude_tags: bool,
+    /// Exclude files flagged NODUMP
+    pub exclude_nodump: bool,
+    /// Do not create a backup archive, only simulate it.
+    ///
+    /// In combination with `--list`, borg reports which files would be archived.
+    /// As no archive is created, no statistics are returned.
+    pub dry_run: bool,
     /// The patterns to apply
     ///
     /// Using these, you may specify the backup roots (starting points)
     /// and patterns for inclusion/exclusion.
     ///
-    /// See [PatternInstruction] for further information.
+    /// See [PatternInstruction] for further information,
+    /// [PatternList] helps to build the instructions in a valid order.
     ///
     /// Note that the order of the instructions is important:
     ///
@@ -539,23 +1783,33 @@ pub struct CreateOptions {
     /// borg create --pattern=+pics/2018/good --pattern=-pics/2018 repo::arch pics
     /// ```
     pub patterns: Vec<PatternInstruction>,
-    /// read include/exclude patterns from the given path, one per line
+    /// Patterns that are passed to borg in a temporary file instead of the command line
+    ///
+    /// This is useful for large lists of patterns, which could exceed the length limit
+    /// of the command line. The file is removed after borg exited.
+    ///
+    /// The patterns are applied after [CreateOptions::patterns] and
+    /// [CreateOptions::pattern_files].
+    pub inline_patterns: Vec<PatternInstruction>,
+    /// read include/exclude patterns from the given paths, one per line
+    ///
+    /// The files are passed to borg in order, as the first matching pattern is used.
     ///
     /// Refer to <https://borgbackup.readthedocs.io/en/stable/usage/help.html#borg-help-patterns>
     /// for further information how to use pattern / exclude files.
-    pub pattern_file: Option<String>,
+    pub pattern_files: Vec<String>,
     /// Exclude paths matching PATTERN.
     ///
     /// See [Pattern] for further information.
     pub excludes: Vec<Pattern>,
-    /// read exclude patterns from the given path, one per line
+    /// read exclude patterns from the given paths, one per line
     ///
     /// Refer to <https://borgbackup.readthedocs.io/en/stable/usage/help.html#borg-help-patterns>
     /// for further information how to use pattern / exclude files.
-    pub exclude_file: Option<String>,
+    pub exclude_files: Vec<String>,
     /// Only store numeric user and group identifiers
     pub numeric_ids: bool,
-    /// Detect sparse holes in input (supported only by fixed chunker)
+    /// Detect sparse holes in input (supported only by [ChunkerParams::Fixed])
     pub sparse: bool,
     /// Open and read block and char device files as well as FIFOs as if they were regular files.
     ///
@@ -567,608 +1821,4979 @@ pub struct CreateOptions {
     pub no_acls: bool,
     /// Do not read and store flags (e.g. NODUMP, IMMUTABLE) into archive
     pub no_flags: bool,
+    /// Do store atime into archive
+    pub atime: bool,
+    /// Do not store birthtime (creation date) into archive
+    pub nobirthtime: bool,
 }
 
 impl CreateOptions {
     /// Create an new [CreateOptions]
     pub fn new(
-        repository: String,
+        repository: impl Into<Repository>,
         archive: String,
         paths: Vec<String>,
         patterns: Vec<PatternInstruction>,
     ) -> Self {
         Self {
-            repository,
-            archive,
+            archive: ArchiveRef::new(repository, archive),
             passphrase: None,
             comment: None,
+            timestamp: None,
             compression: None,
-            paths,
+            chunker_params: None,
+            files_cache: None,
+            checkpoint_interval: None,
+            checkpoint_volume: None,
+            stats: false,
+            source: CreateSource::Paths(paths),
+            paths_from_file: false,
             exclude_caches: false,
+            exclude_if_present: vec![],
+            keep_exclude_tags: false,
+            exclude_nodump: false,
+            dry_run: false,
             patterns,
-            pattern_file: None,
+            inline_patterns: vec![],
+            pattern_files: vec![],
             excludes: vec![],
-            exclude_file: None,
+            exclude_files: vec![],
             numeric_ids: false,
             sparse: false,
             read_special: false,
             no_xattrs: false,
             no_acls: false,
             no_flags: false,
+            atime: false,
+            nobirthtime: false,
         }
     }
-}
 
-/// The options to provide to the [crate::sync::init] command
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct InitOptions {
-    /// Path to the repository
+    /// Create a new [CreateOptionsBuilder]
     ///
-    /// Example values:
-    /// - `/tmp/foo`
-    /// - `user@example.com:/opt/repo`
-    /// - `ssh://user@example.com:2323:/opt/repo`
-    pub repository: String,
-    /// The mode to use for encryption
-    pub encryption_mode: EncryptionMode,
-    /// Set the repository to append_only mode.
-    /// Note that this only affects the low level structure of the repository,
-    /// and running delete or prune will still be allowed.
+    /// All options that are not specified on the builder are set to their defaults.
     ///
-    /// See [Append-only mode (forbid compaction)](https://borgbackup.readthedocs.io/en/stable/usage/notes.html#append-only-mode)
-    /// in Additional Notes for more details.
-    pub append_only: bool,
-    /// Create the parent directories of the repo, if they are missing.
+    /// ```
+    /// use std::path::Path;
     ///
-    /// Defaults to false
-    pub make_parent_dirs: bool,
-    /// Set storage quota of the new repository (e.g. 5G, 1.5T)
+    /// use borgbackup::common::{CompressionMode, CreateOptions};
     ///
-    /// No quota is set by default
-    pub storage_quota: Option<String>,
-}
-
-impl InitOptions {
-    /// Create new [InitOptions].
-    ///
-    /// `append_only`, `make_parent_dirs` and `storage_quota` are set to their defaults.
-    pub fn new(repository: String, encryption_mode: EncryptionMode) -> Self {
-        Self {
-            repository,
-            encryption_mode,
-            append_only: false,
-            make_parent_dirs: false,
-            storage_quota: None,
+    /// let options = CreateOptions::builder(
+    ///     Path::new("/tmp/repo"),
+    ///     "{now}".to_string(),
+    ///     vec!["/home".to_string()],
+    /// )
+    /// .compression(CompressionMode::Zstd(9))
+    /// .exclude_caches(true)
+    /// .build();
+    /// ```
+    pub fn builder(
+        repository: impl Into<Repository>,
+        archive: String,
+        paths: Vec<String>,
+    ) -> CreateOptionsBuilder {
+        CreateOptionsBuilder {
+            options: Self::new(repository, archive, paths, vec![]),
         }
     }
 }
 
-/// The options for the [crate::sync::list] command
-#[derive(Deserialize, Serialize, Debug, Clone)]
-pub struct ListOptions {
-    /// Path to the repository
-    ///
-    /// Example values:
-    /// - `/tmp/foo`
-    /// - `user@example.com:/opt/repo`
-    /// - `ssh://user@example.com:2323:/opt/repo`
-    pub repository: String,
-    /// The passphrase for the repository
-    ///
-    /// If using a repository with [EncryptionMode::None],
-    /// you can leave this option empty
-    pub passphrase: Option<String>,
+/// Builder for [CreateOptions]
+///
+/// Use [CreateOptions::builder] to create a new builder.
+#[derive(Debug, Clone)]
+pub struct CreateOptionsBuilder {
+    options: CreateOptions,
 }
 
-pub(crate) fn init_fmt_args(options: &InitOptions, common_options: &CommonOptions) -> String {
-    format!(
-        "--log-json {common_options}init -e {e}{append_only}{make_parent_dirs}{storage_quota} {repository}",
-        common_options = String::from(common_options),
-        e = options.encryption_mode,
-        append_only = if options.append_only {
-            " --append-only"
-        } else {
-            ""
-        },
-        make_parent_dirs = if options.make_parent_dirs {
-            " --make-parent-dirs"
-        } else {
-            ""
-        },
-        storage_quota = options
-            .storage_quota
-            .as_ref()
-            .map_or("".to_string(), |x| format!(
-                " --storage-quota {quota}",
-                quota = shell_escape(x)
-            )),
-        repository = shell_escape(&options.repository),
-    )
-}
+impl CreateOptionsBuilder {
+    /// Set the passphrase for the repository
+    pub fn passphrase(mut self, passphrase: String) -> Self {
+        self.options.passphrase = Some(passphrase);
+        self
+    }
 
-fn log_message(level_name: LevelName, time: f64, name: String, message: String) {
-    match level_name {
-        LevelName::Debug => debug!("{time} {name}: {message}"),
-        LevelName::Info => info!("{time} {name}: {message}"),
-        LevelName::Warning => warn!("{time} {name}: {message}"),
-        LevelName::Error => error!("{time} {name}: {message}"),
-        LevelName::Critical => error!("{time} {name}: {message}"),
+    /// Add a comment text to the archive
+    pub fn comment(mut self, comment: String) -> Self {
+        self.options.comment = Some(comment);
+        self
     }
-}
 
-pub(crate) fn init_parse_result(res: Output) -> Result<(), InitError> {
-    let Some(exit_code) = res.status.code() else {
-        warn!("borg process was terminated by signal");
-        return Err(InitError::TerminatedBySignal);
-    };
+    /// Set the source of the data to archive
+    pub fn source(mut self, source: CreateSource) -> Self {
+        self.options.source = source;
+        self
+    }
 
-    let mut output = String::new();
+    /// Manually specify the archive creation date/time (interpreted as UTC)
+    pub fn timestamp(mut self, timestamp: NaiveDateTime) -> Self {
+        self.options.timestamp = Some(timestamp);
+        self
+    }
 
-    for line in res.stderr.lines() {
-        let line = line.map_err(InitError::InvalidBorgOutput)?;
-        writeln!(output, "{line}").unwrap();
+    /// Specify the compression mode that should be used
+    pub fn compression(mut self, compression: CompressionMode) -> Self {
+        self.options.compression = Some(compression);
+        self
+    }
 
-        trace!("borg output: {line}");
+    /// Specify the chunker parameters
+    pub fn chunker_params(mut self, chunker_params: ChunkerParams) -> Self {
+        self.options.chunker_params = Some(chunker_params);
+        self
+    }
 
-        let log_msg = LoggingMessage::from_str(&line)?;
+    /// Specify the mode of the files cache
+    pub fn files_cache(mut self, files_cache: FilesCacheMode) -> Self {
+        self.options.files_cache = Some(files_cache);
+        self
+    }
 
-        if let LoggingMessage::LogMessage {
-            name,
-            message,
-            level_name,
-            time,
-            msg_id,
-        } = log_msg
-        {
-            log_message(level_name, time, name, message);
+    /// Write a checkpoint every N seconds
+    pub fn checkpoint_interval(mut self, checkpoint_interval: NonZeroU16) -> Self {
+        self.options.checkpoint_interval = Some(checkpoint_interval);
+        self
+    }
 
-            if let Some(msg_id) = msg_id {
-                match msg_id {
-                    MessageId::RepositoryAlreadyExists => {
-                        return Err(InitError::RepositoryAlreadyExists)
-                    }
-                    _ => {
-                        if exit_code > 1 {
-                            return Err(InitError::UnexpectedMessageId(msg_id));
-                        }
-                    }
-                }
-            }
+    /// Write a checkpoint every N bytes of written data (requires borg >= 1.4)
+    pub fn checkpoint_volume(mut self, checkpoint_volume: u64) -> Self {
+        self.options.checkpoint_volume = Some(checkpoint_volume);
+        self
+    }
 
-            if let Some(MessageId::RepositoryAlreadyExists) = msg_id {
-                return Err(InitError::RepositoryAlreadyExists);
-            } else if let Some(msg_id) = msg_id {
-                return Err(InitError::UnexpectedMessageId(msg_id));
-            }
-        }
+    /// Pass `--stats` to borg, see [CreateOptions::stats]
+    pub fn stats(mut self, stats: bool) -> Self {
+        self.options.stats = stats;
+        self
     }
 
-    if exit_code > 1 {
-        return Err(InitError::Unknown(output));
+    /// Pass the paths to borg in a temporary file.
+    ///
+    /// See [CreateOptions::paths_from_file] for further information.
+    pub fn paths_from_file(mut self, paths_from_file: bool) -> Self {
+        self.options.paths_from_file = paths_from_file;
+        self
     }
 
-    Ok(())
-}
+    /// Exclude directories that contain a CACHEDIR.TAG file
+    pub fn exclude_caches(mut self, exclude_caches: bool) -> Self {
+        self.options.exclude_caches = exclude_caches;
+        self
+    }
 
-pub(crate) fn prune_fmt_args(options: &PruneOptions, common_options: &CommonOptions) -> String {
-    format!(
-        "--log-json {common_options} prune{keep_within}{keep_secondly}{keep_minutely}{keep_hourly}{keep_daily}{keep_weekly}{keep_monthly}{keep_yearly} {repository}",
-        common_options = String::from(common_options),
-        keep_within = options.keep_within.as_ref().map_or("".to_string(), |x| format!(" --keep-within {x}")),
-        keep_secondly = options.keep_secondly.as_ref().map_or("".to_string(), |x| format!(" --keep-secondly {x}")),
-        keep_minutely = options.keep_minutely.map_or("".to_string(), |x| format!(" --keep-minutely {x}")),
-        keep_hourly = options.keep_hourly.map_or("".to_string(), |x| format!(" --keep-hourly {x}")),
-        keep_daily = options.keep_daily.map_or("".to_string(), |x| format!(" --keep-daily {x}")),
-        keep_weekly = options.keep_weekly.map_or("".to_string(), |x| format!(" --keep-weekly {x}")),
-        keep_monthly = options.keep_monthly.map_or("".to_string(), |x| format!(" --keep-monthly {x}")),
-        keep_yearly = options.keep_yearly.map_or("".to_string(), |x| format!(" --keep-yearly {x}")),
-        repository = shell_escape(&options.repository)
-    )
-}
+    /// Set the names of the files that exclude the directories containing them
+    pub fn exclude_if_present(mut self, exclude_if_present: Vec<String>) -> Self {
+        self.options.exclude_if_present = exclude_if_present;
+        self
+    }
 
-pub(crate) fn prune_parse_output(res: Output) -> Result<(), PruneError> {
-    let Some(exit_code) = res.status.code() else {
-        warn!("borg process was terminated by signal");
-        return Err(PruneError::TerminatedBySignal);
-    };
+    /// Append a single name of a file that excludes the directories containing it
+    pub fn exclude_if_present_name(mut self, name: String) -> Self {
+        self.options.exclude_if_present.push(name);
+        self
+    }
 
-    let mut output = String::new();
+    /// Keep tag files of excluded directories
+    pub fn keep_exclude_tags(mut self, keep_exclude_tags: bool) -> Self {
+        self.options.keep_exclude_tags = keep_exclude_tags;
+        self
+    }
 
-    for line in BufRead::lines(res.stderr.as_slice()) {
-        let line = line.map_err(PruneError::InvalidBorgOutput)?;
-        writeln!(output, "{line}").unwrap();
+    /// Exclude files flagged NODUMP
+    pub fn exclude_nodump(mut self, exclude_nodump: bool) -> Self {
+        self.options.exclude_nodump = exclude_nodump;
+        self
+    }
 
-        trace!("borg output: {line}");
+    /// Only simulate the creation of the archive
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.options.dry_run = dry_run;
+        self
+    }
 
-        let log_msg = LoggingMessage::from_str(&line)?;
+    /// Set the patterns to apply.
+    ///
+    /// See [CreateOptions::patterns] for further information.
+    pub fn patterns(mut self, patterns: Vec<PatternInstruction>) -> Self {
+        self.options.patterns = patterns;
+        self
+    }
 
-        if let LoggingMessage::LogMessage {
-            name,
-            message,
-            level_name,
-            time,
-            msg_id,
-        } = log_msg
-        {
-            log_message(level_name, time, name, message);
+    /// Append a single pattern to the patterns to apply
+    pub fn pattern(mut self, pattern: PatternInstruction) -> Self {
+        self.options.patterns.push(pattern);
+        self
+    }
 
-            if let Some(msg_id) = msg_id {
-                if exit_code > 1 {
-                    return Err(PruneError::UnexpectedMessageId(msg_id));
-                }
-            }
-        }
+    /// Set the patterns that are passed to borg in a temporary file.
+    ///
+    /// See [CreateOptions::inline_patterns] for further information.
+    pub fn inline_patterns(mut self, inline_patterns: Vec<PatternInstruction>) -> Self {
+        self.options.inline_patterns = inline_patterns;
+        self
     }
 
-    if exit_code > 1 {
-        return Err(PruneError::Unknown(output));
+    /// Set the paths to read include/exclude patterns from.
+    ///
+    /// See [CreateOptions::pattern_files] for further information.
+    pub fn pattern_files(mut self, pattern_files: Vec<String>) -> Self {
+        self.options.pattern_files = pattern_files;
+        self
     }
 
-    Ok(())
-}
+    /// Append a single path to read include/exclude patterns from
+    pub fn pattern_file(mut self, pattern_file: String) -> Self {
+        self.options.pattern_files.push(pattern_file);
+        self
+    }
 
-pub(crate) fn mount_fmt_args(options: &MountOptions, common_options: &CommonOptions) -> String {
-    let mount_source_formatted = match &options.mount_source {
-        MountSource::Repository {
-            name,
-            first_n_archives,
-            last_n_archives,
-            glob_archives,
-        } => {
-            format!(
-                "{name}{first_n_archives}{last_n_archives}{glob_archives}",
-                name = name.clone(),
-                first_n_archives = first_n_archives
-                    .map(|first_n| format!(" --first {}", first_n))
-                    .unwrap_or_default(),
-                last_n_archives = last_n_archives
-                    .map(|last_n| format!(" --last {}", last_n))
-                    .unwrap_or_default(),
-                glob_archives = glob_archives
-                    .as_ref()
-                    .map(|glob| format!(" --glob-archives {}", glob))
-                    .unwrap_or_default(),
-            )
-        }
-        MountSource::Archive { archive_name } => archive_name.clone(),
-    };
-    format!(
-        "--log-json {common_options} mount {mount_source} {mountpoint} {select_paths}",
-        common_options = String::from(common_options),
-        mount_source = mount_source_formatted,
-        mountpoint = options.mountpoint,
-        select_paths = options
-            .select_paths
-            .iter()
-            .map(|x| format!("--pattern={}", shell_escape(&x.to_string())))
-            .collect::<Vec<String>>()
-            .join(" "),
-    )
-    .trim()
-    .to_string()
-}
+    /// Set the paths to exclude
+    pub fn excludes(mut self, excludes: Vec<Pattern>) -> Self {
+        self.options.excludes = excludes;
+        self
+    }
 
-pub(crate) fn mount_parse_output(res: Output) -> Result<(), MountError> {
-    let Some(exit_code) = res.status.code() else {
-        warn!("borg process was terminated by signal");
-        return Err(MountError::TerminatedBySignal);
-    };
+    /// Append a single pattern to the paths to exclude
+    pub fn exclude(mut self, exclude: Pattern) -> Self {
+        self.options.excludes.push(exclude);
+        self
+    }
 
-    let mut output = String::new();
+    /// Set the paths to read exclude patterns from
+    pub fn exclude_files(mut self, exclude_files: Vec<String>) -> Self {
+        self.options.exclude_files = exclude_files;
+        self
+    }
 
-    for line in BufRead::lines(res.stderr.as_slice()) {
-        let line = line.map_err(MountError::InvalidBorgOutput)?;
-        writeln!(output, "{line}").unwrap();
+    /// Append a single path to read exclude patterns from
+    pub fn exclude_file(mut self, exclude_file: String) -> Self {
+        self.options.exclude_files.push(exclude_file);
+        self
+    }
 
-        trace!("borg output: {line}");
+    /// Only store numeric user and group identifiers
+    pub fn numeric_ids(mut self, numeric_ids: bool) -> Self {
+        self.options.numeric_ids = numeric_ids;
+        self
+    }
 
-        let log_msg = LoggingMessage::from_str(&line)?;
+    /// Detect sparse holes in input (supported only by [ChunkerParams::Fixed])
+    pub fn sparse(mut self, sparse: bool) -> Self {
+        self.options.sparse = sparse;
+        self
+    }
 
-        if let LoggingMessage::UMountError(message) = log_msg {
-            return Err(MountError::UMountError(message));
-        };
+    /// Open and read block and char device files as well as FIFOs as if they were regular files
+    pub fn read_special(mut self, read_special: bool) -> Self {
+        self.options.read_special = read_special;
+        self
+    }
 
-        if let LoggingMessage::LogMessage {
-            name,
-            message,
-            level_name,
-            time,
-            msg_id,
-        } = log_msg
-        {
-            log_message(level_name, time, name, message);
+    /// Do not read and store xattrs into archive
+    pub fn no_xattrs(mut self, no_xattrs: bool) -> Self {
+        self.options.no_xattrs = no_xattrs;
+        self
+    }
+
+    /// Do not read and store ACLs into archive
+    pub fn no_acls(mut self, no_acls: bool) -> Self {
+        self.options.no_acls = no_acls;
+        self
+    }
+
+    /// Do not read and store flags (e.g. NODUMP, IMMUTABLE) into archive
+    pub fn no_flags(mut self, no_flags: bool) -> Self {
+        self.options.no_flags = no_flags;
+        self
+    }
+
+    /// Do store atime into archive
+    pub fn atime(mut self, atime: bool) -> Self {
+        self.options.atime = atime;
+        self
+    }
+
+    /// Do not store birthtime (creation date) into archive
+    pub fn nobirthtime(mut self, nobirthtime: bool) -> Self {
+        self.options.nobirthtime = nobirthtime;
+        self
+    }
+
+    /// Build the [CreateOptions]
+    pub fn build(self) -> CreateOptions {
+        self.options
+    }
+}
+
+/// The options to provide to the [crate::sync::init] command
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InitOptions {
+    /// Path to the repository
+    ///
+    /// Example values:
+    /// - `/tmp/foo`
+    /// - `user@example.com:/opt/repo`
+    /// - `ssh://user@example.com:2323/opt/repo`
+    pub repository: Repository,
+    /// The mode to use for encryption
+    pub encryption_mode: EncryptionMode,
+    /// Set the repository to append_only mode.
+    /// Note that this only affects the low level structure of the repository,
+    /// and running delete or prune will still be allowed.
+    ///
+    /// See [Append-only mode (forbid compaction)](https://borgbackup.readthedocs.io/en/stable/usage/notes.html#append-only-mode)
+    /// in Additional Notes for more details.
+    pub append_only: bool,
+    /// Create the parent directories of the repo, if they are missing.
+    ///
+    /// Defaults to false
+    pub make_parent_dirs: bool,
+    /// Set storage quota of the new repository (e.g. 5G, 1.5T)
+    ///
+    /// No quota is set by default
+    pub storage_quota: Option<String>,
+}
+
+impl InitOptions {
+    /// Create new [InitOptions].
+    ///
+    /// `append_only`, `make_parent_dirs` and `storage_quota` are set to their defaults.
+    pub fn new(repository: impl Into<Repository>, encryption_mode: EncryptionMode) -> Self {
+        Self {
+            repository: repository.into(),
+            encryption_mode,
+            append_only: false,
+            make_parent_dirs: false,
+            storage_quota: None,
+        }
+    }
+}
+
+/// The options for the [crate::sync::extract] command
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ExtractOptions {
+    /// The archive to extract
+    ///
+    /// Example values:
+    /// - `/tmp/foo::my-archive`
+    /// - `user@example.com:/opt/repo::archive`
+    /// - `ssh://user@example.com:2323/opt/repo::archive`
+    pub archive: ArchiveRef,
+    /// The passphrase for the repository
+    ///
+    /// If using a repository with [EncryptionMode::None],
+    /// you can leave this option empty
+    pub passphrase: Option<String>,
+    /// The directory the archive is extracted into
+    pub destination: String,
+    /// Only extract these paths of the archive.
+    ///
+    /// If empty, the whole archive is extracted.
+    pub paths: Vec<String>,
+    /// Only extract the files that are matched by the patterns
+    pub patterns: Vec<PatternInstruction>,
+    /// Remove the specified number of leading path elements.
+    ///
+    /// Paths with fewer elements will be silently skipped.
+    pub strip_components: Option<u32>,
+    /// Only obtain user and group ids from the archive instead of their names
+    pub numeric_ids: bool,
+    /// Create holes in the output sparse file from all-zero chunks
+    ///
+    /// This saves space when restoring mostly empty files like VM images.
+    pub sparse: bool,
+    /// Report the paths of the extracted files.
+    ///
+    /// The paths are returned by [crate::sync::extract], e.g. to keep a record of a restore.
+    pub list_files: bool,
+    /// Verify the sizes of the extracted files after extracting.
+    ///
+    /// The files of the archive are listed with a second invocation of borg and their
+    /// sizes are compared to the extracted files, see [ExtractOptions::size_mismatches].
+    /// This catches truncated files, e.g. if the disk ran full,
+    /// which borg may only report as a warning.
+    pub verify_sizes: bool,
+}
+
+impl ExtractOptions {
+    /// Create new [ExtractOptions]
+    pub fn new(repository: impl Into<Repository>, archive: String, destination: String) -> Self {
+        Self {
+            archive: ArchiveRef::new(repository, archive),
+            passphrase: None,
+            destination,
+            paths: vec![],
+            patterns: vec![],
+            strip_components: None,
+            numeric_ids: false,
+            sparse: false,
+            list_files: false,
+            verify_sizes: false,
+        }
+    }
+
+    /// Compare the sizes of the regular files of the archive with the extracted files
+    /// in [ExtractOptions::destination].
+    ///
+    /// [ExtractOptions::paths] and [ExtractOptions::strip_components] are applied to the
+    /// items of the archive. As [ExtractOptions::patterns] are not evaluated, missing files
+    /// are only reported if no patterns are set.
+    ///
+    /// **Parameter**:
+    /// - `items`: The items of the archive, e.g. from [crate::asynchronous::list_stream]
+    pub fn size_mismatches(&self, items: &[ListItem]) -> Vec<SizeMismatch> {
+        let destination = Path::new(&self.destination);
+        let strip_components = self.strip_components.unwrap_or(0) as usize;
+
+        items
+            .iter()
+            .filter(|item| item.item_type == "-")
+            .filter(|item| {
+                self.paths.is_empty()
+                    || self.paths.iter().any(|path| {
+                        let path = path.trim_end_matches('/');
+                        item.path == path || item.path.starts_with(&format!("{path}/"))
+                    })
+            })
+            .filter_map(|item| {
+                let components: Vec<_> = item.path.split('/').skip(strip_components).collect();
+                if components.is_empty() {
+                    return None;
+                }
+                let path = components.join("/");
+
+                let actual = std::fs::symlink_metadata(destination.join(&path))
+                    .ok()
+                    .map(|metadata| metadata.len());
+                match actual {
+                    Some(actual) if actual == item.size => None,
+                    None if !self.patterns.is_empty() => None,
+                    _ => Some(SizeMismatch {
+                        path,
+                        expected: item.size,
+                        actual,
+                    }),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The options for the [crate::sync::delete] command
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DeleteOptions {
+    /// Path to the repository
+    ///
+    /// Example values:
+    /// - `/tmp/foo`
+    /// - `user@example.com:/opt/repo`
+    /// - `ssh://user@example.com:2323/opt/repo`
+    pub repository: Repository,
+    /// The passphrase for the repository
+    ///
+    /// If using a repository with [EncryptionMode::None],
+    /// you can leave this option empty
+    pub passphrase: Option<String>,
+    /// The archive to delete, which has to be in [DeleteOptions::repository]
+    ///
+    /// Either this or [DeleteOptions::glob_archives] has to be set, as borg deletes the whole
+    /// repository otherwise. Deleting the repository is not supported.
+    pub archive: Option<ArchiveRef>,
+    /// Delete all archives with names matching the glob.
+    ///
+    /// The pattern can use [Pattern::Shell].
+    /// As a typo may match more archives than intended, use [DeleteOptions::dry_run] to check
+    /// which archives are matched.
+    pub glob_archives: Option<String>,
+    /// Do not delete anything, only report which archives would be deleted
+    pub dry_run: bool,
+    /// Report the statistics of the deleted data,
+    /// see [DeleteStats](crate::output::delete::DeleteStats)
+    pub stats: bool,
+}
+
+impl DeleteOptions {
+    /// Create new [DeleteOptions]
+    pub fn new(repository: impl Into<Repository>) -> Self {
+        Self {
+            repository: repository.into(),
+            passphrase: None,
+            archive: None,
+            glob_archives: None,
+            dry_run: false,
+            stats: false,
+        }
+    }
+}
+
+/// The options for the [crate::sync::info] command
+///
+/// If neither an archive nor any of the archive filters are set,
+/// only the information about the repository is retrieved, see [crate::output::info::Info].
+/// Calculating the statistics of archives is expensive, so the filters should be used
+/// to select only the archives of interest.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct InfoOptions {
+    /// Path to the repository
+    ///
+    /// Example values:
+    /// - `/tmp/foo`
+    /// - `user@example.com:/opt/repo`
+    /// - `ssh://user@example.com:2323/opt/repo`
+    pub repository: Repository,
+    /// The passphrase for the repository
+    ///
+    /// If using a repository with [EncryptionMode::None],
+    /// you can leave this option empty
+    pub passphrase: Option<String>,
+    /// Name of the archive to retrieve information about
+    pub archive: Option<String>,
+    /// Only consider the first N archives
+    pub first_n: Option<NonZeroU16>,
+    /// Only consider the last N archives
+    pub last_n: Option<NonZeroU16>,
+    /// Only consider archives with names matching the glob.
+    ///
+    /// The pattern can use [Pattern::Shell]
+    pub glob_archives: Option<String>,
+}
+
+impl InfoOptions {
+    /// Create new [InfoOptions]
+    pub fn new(repository: impl Into<Repository>) -> Self {
+        Self {
+            repository: repository.into(),
+            passphrase: None,
+            archive: None,
+            first_n: None,
+            last_n: None,
+            glob_archives: None,
+        }
+    }
+
+    /// Whether the information of archives instead of the repository is requested
+    pub(crate) fn selects_archives(&self) -> bool {
+        self.archive.is_some()
+            || self.first_n.is_some()
+            || self.last_n.is_some()
+            || self.glob_archives.is_some()
+    }
+}
+
+/// The options for the [crate::sync::benchmark_crud] command
+///
+/// Measures the throughput of creating, reading, updating and deleting archives.
+/// This creates and deletes archives in the repository, use a test repository.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct BenchmarkOptions {
+    /// Path to the repository
+    ///
+    /// Example values:
+    /// - `/tmp/foo`
+    /// - `user@example.com:/opt/repo`
+    /// - `ssh://user@example.com:2323/opt/repo`
+    pub repository: Repository,
+    /// Path to a directory in which borg writes the input files of the benchmark
+    pub path: String,
+    /// The passphrase for the repository
+    ///
+    /// If using a repository with [EncryptionMode::None],
+    /// you can leave this option empty
+    pub passphrase: Option<String>,
+}
+
+impl BenchmarkOptions {
+    /// Create new [BenchmarkOptions]
+    pub fn new(repository: impl Into<Repository>, path: String) -> Self {
+        Self {
+            repository: repository.into(),
+            path,
+            passphrase: None,
+        }
+    }
+}
+
+/// The options for the [crate::sync::serve] command
+///
+/// `borg serve` is the server side of a remote repository, which is usually started by ssh.
+/// The restrictions are enforced by the server, so unlike
+/// [CommonOptions::append_only_remote], a client can't lift them.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ServeOptions {
+    /// Restrict repository access to these paths and their subdirectories
+    ///
+    /// If empty, all paths are accessible.
+    pub restrict_to_paths: Vec<String>,
+    /// Restrict repository access to exactly these repositories
+    ///
+    /// Unlike [ServeOptions::restrict_to_paths], subdirectories are not accessible.
+    /// If empty, all repositories are accessible.
+    pub restrict_to_repositories: Vec<String>,
+    /// Only allow appending to the repositories, deleting or overwriting data is denied
+    pub append_only: bool,
+    /// Override the storage quota of the repositories, e.g. `5G` or `1.5T`
+    pub storage_quota: Option<String>,
+}
+
+/// The options for the [crate::sync::config] command
+///
+/// Reads or sets a setting of the config of a repository, e.g. `append_only`,
+/// `additional_free_space` or `max_segment_size`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ConfigOptions {
+    /// Path to the repository
+    ///
+    /// Example values:
+    /// - `/tmp/foo`
+    /// - `user@example.com:/opt/repo`
+    /// - `ssh://user@example.com:2323/opt/repo`
+    pub repository: Repository,
+    /// The passphrase for the repository
+    ///
+    /// If using a repository with [EncryptionMode::None],
+    /// you can leave this option empty
+    pub passphrase: Option<String>,
+    /// The key of the setting, e.g. `append_only`
+    pub key: String,
+    /// The value to set
+    ///
+    /// If [None], the current value is read instead.
+    pub value: Option<String>,
+}
+
+impl ConfigOptions {
+    /// Create new [ConfigOptions] that read the setting `key`
+    pub fn get(repository: impl Into<Repository>, key: String) -> Self {
+        Self {
+            repository: repository.into(),
+            passphrase: None,
+            key,
+            value: None,
+        }
+    }
+
+    /// Create new [ConfigOptions] that set the setting `key` to `value`
+    pub fn set(repository: impl Into<Repository>, key: String, value: String) -> Self {
+        Self {
+            repository: repository.into(),
+            passphrase: None,
+            key,
+            value: Some(value),
+        }
+    }
+}
+
+/// The options for the [crate::sync::transfer] command
+///
+/// `borg transfer` requires borg >= 2.0. If [CommonOptions::borg_version] is set to an older
+/// version, [TransferError::TransferNotSupported] is returned.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TransferOptions {
+    /// Path to the repository the archives are transferred from
+    ///
+    /// Example values:
+    /// - `/tmp/foo`
+    /// - `user@example.com:/opt/repo`
+    /// - `ssh://user@example.com:2323/opt/repo`
+    pub source: Repository,
+    /// Path to the repository the archives are transferred to
+    pub destination: Repository,
+    /// The passphrase for the destination repository.
+    ///
+    /// Borg reads it from `BORG_PASSPHRASE`.
+    pub passphrase: Option<String>,
+    /// The passphrase for the source repository.
+    ///
+    /// Borg reads it from `BORG_OTHER_PASSPHRASE`.
+    pub other_passphrase: Option<String>,
+    /// Only transfer archives with names matching the glob.
+    ///
+    /// The pattern can use [Pattern::Shell]
+    pub glob_archives: Option<String>,
+    /// Do not change the destination repository, only report what would be transferred.
+    pub dry_run: bool,
+}
+
+impl TransferOptions {
+    /// Create new [TransferOptions]
+    pub fn new(source: impl Into<Repository>, destination: impl Into<Repository>) -> Self {
+        Self {
+            source: source.into(),
+            destination: destination.into(),
+            passphrase: None,
+            other_passphrase: None,
+            glob_archives: None,
+            dry_run: false,
+        }
+    }
+}
+
+/// The options for the [crate::sync::list] command
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ListOptions {
+    /// Path to the repository
+    ///
+    /// Example values:
+    /// - `/tmp/foo`
+    /// - `user@example.com:/opt/repo`
+    /// - `ssh://user@example.com:2323/opt/repo`
+    pub repository: Repository,
+    /// The passphrase for the repository
+    ///
+    /// If using a repository with [EncryptionMode::None],
+    /// you can leave this option empty
+    pub passphrase: Option<String>,
+    /// Specify the format for the archive listing.
+    ///
+    /// As the output is requested as json, the format only controls which keys are
+    /// added to each archive entry. `archive`, `name`, `id`, `start` and `time` are always
+    /// present.
+    ///
+    /// Placeholders that are parsed into [ListArchive](crate::output::list::ListArchive):
+    /// - `{archive}` / `{name}`
+    /// - `{id}`
+    /// - `{start}` / `{time}`
+    /// - `{end}`
+    /// - `{comment}`
+    /// - `{hostname}`
+    /// - `{username}`
+    ///
+    /// Other placeholders are accepted by borg but ignored while parsing.
+    ///
+    /// Example value: `{archive} {start} {hostname}`
+    pub format: Option<String>,
+    /// Only list the first N archives after sorting
+    pub first_n: Option<NonZeroU16>,
+    /// Only list the last N archives after sorting
+    pub last_n: Option<NonZeroU16>,
+    /// Only list archives with names matching the glob.
+    ///
+    /// The pattern can use [Pattern::Shell]
+    pub glob_archives: Option<String>,
+    /// Comma-separated list of sorting keys (borg defaults to `timestamp`)
+    ///
+    /// Valid keys are: `timestamp`, `name` and `id`
+    pub sort_by: Option<String>,
+    /// Include checkpoint archives in the listing.
+    ///
+    /// Checkpoints are written while borg create is running and represent incomplete backups,
+    /// see [ListArchive::is_checkpoint](crate::output::list::ListArchive::is_checkpoint).
+    /// If not set, they are excluded, which borg >= 1.2 does by itself.
+    pub consider_checkpoints: bool,
+}
+
+impl ListOptions {
+    /// Create new [ListOptions]
+    pub fn new(repository: impl Into<Repository>) -> Self {
+        Self {
+            repository: repository.into(),
+            passphrase: None,
+            format: None,
+            first_n: None,
+            last_n: None,
+            glob_archives: None,
+            sort_by: None,
+            consider_checkpoints: false,
+        }
+    }
+}
+
+pub(crate) fn init_fmt_args(options: &InitOptions, common_options: &CommonOptions) -> String {
+    format!(
+        "--log-json{common_options} init -e {e}{append_only}{make_parent_dirs}{storage_quota} {repository}",
+        common_options = common_options.fmt_args(),
+        e = options.encryption_mode,
+        append_only = if options.append_only {
+            " --append-only"
+        } else {
+            ""
+        },
+        make_parent_dirs = if options.make_parent_dirs {
+            " --make-parent-dirs"
+        } else {
+            ""
+        },
+        storage_quota = options
+            .storage_quota
+            .as_ref()
+            .map_or("".to_string(), |x| format!(
+                " --storage-quota {quota}",
+                quota = shell_escape(x)
+            )),
+        repository = shell_escape(options.repository.as_str()),
+    )
+}
+
+/// Check whether an exit code of borg signals an error.
+///
+/// The modern exit codes of borg use 1 and 100 to 127 for warnings,
+/// while the legacy exit codes only use 1.
+pub(crate) fn is_error_exit_code(exit_code: i32) -> bool {
+    exit_code > 1 && !(100..128).contains(&exit_code)
+}
+
+/// Log a message of borg
+///
+/// If the `tracing` feature is enabled, the message is emitted as a [tracing] event
+/// with the `msg_id`, `level`, `time` and `name` of the message as fields,
+/// otherwise it is passed to the `log` crate.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn log_message(
+    level_name: LevelName,
+    time: f64,
+    name: &str,
+    message: &str,
+    msg_id: Option<MessageId>,
+) {
+    #[cfg(feature = "tracing")]
+    {
+        macro_rules! event {
+            ($level:expr) => {
+                tracing::event!(
+                    $level,
+                    msg_id = msg_id.map(tracing::field::display),
+                    level = ?level_name,
+                    time,
+                    name,
+                    "{message}"
+                )
+            };
+        }
+        match level_name {
+            LevelName::Debug => event!(tracing::Level::DEBUG),
+            LevelName::Info => event!(tracing::Level::INFO),
+            LevelName::Warning => event!(tracing::Level::WARN),
+            LevelName::Error | LevelName::Critical => event!(tracing::Level::ERROR),
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        match level_name {
+            LevelName::Debug => log::debug!("{time} {name}: {message}"),
+            LevelName::Info => log::info!("{time} {name}: {message}"),
+            LevelName::Warning => log::warn!("{time} {name}: {message}"),
+            LevelName::Error => log::error!("{time} {name}: {message}"),
+            LevelName::Critical => log::error!("{time} {name}: {message}"),
+        }
+    }
+}
+
+pub(crate) fn init_parse_result(
+    res: Output,
+    common_options: &CommonOptions,
+) -> Result<(), InitError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(InitError::TerminatedBySignal);
+    };
+
+    let mut output = ErrorContext::new(common_options.max_error_context);
+
+    for line in res.stderr.lines() {
+        let line = line.map_err(InitError::InvalidBorgOutput)?;
+        output.push_line(&line);
+
+        trace!("borg output: {line}");
+
+        let log_msg = LoggingMessage::from_str(&line)?;
+        common_options.notify_log_callback(&log_msg);
+
+        if let LoggingMessage::QuestionPrompt { message, .. } = log_msg {
+            return Err(InitError::InteractivePromptRequired { prompt: message });
+        }
+
+        if let LoggingMessage::LogMessage {
+            name,
+            message,
+            level_name,
+            time,
+            msg_id,
+        } = log_msg
+        {
+            log_message(level_name, time, &name, &message, msg_id);
+
+            if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                match msg_id {
+                    MessageId::RepositoryAlreadyExists => {
+                        return Err(InitError::RepositoryAlreadyExists)
+                    }
+                    _ => {
+                        if is_error_exit_code(exit_code) {
+                            return Err(InitError::UnexpectedMessageId(msg_id));
+                        }
+                    }
+                }
+            }
+
+            if let Some(MessageId::RepositoryAlreadyExists) = msg_id {
+                return Err(InitError::RepositoryAlreadyExists);
+            } else if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                return Err(InitError::UnexpectedMessageId(msg_id));
+            }
+        }
+    }
+
+    if is_error_exit_code(exit_code) {
+        if let Some(msg_id) = MessageId::from_exit_code(exit_code) {
+            return Err(match msg_id {
+                MessageId::RepositoryAlreadyExists => InitError::RepositoryAlreadyExists,
+                _ => InitError::UnexpectedMessageId(msg_id),
+            });
+        }
+        return Err(InitError::Unknown(output.into_string()));
+    }
+
+    Ok(())
+}
+
+/// Refuse to prune without keep rules, as borg rejects the command
+pub(crate) fn prune_check_keep_rules(options: &PruneOptions) -> Result<(), PruneError> {
+    if !options.has_keep_rules() {
+        return Err(PruneError::NoKeepRules);
+    }
+    Ok(())
+}
+
+/// Refuse archive selections borg can't express, see [PruneOptions::match_archives]
+pub(crate) fn prune_check_match_archives(
+    options: &PruneOptions,
+    common_options: &CommonOptions,
+) -> Result<(), PruneError> {
+    let Some(pattern) = &options.match_archives else {
+        return Ok(());
+    };
+    if options.glob_archives.is_some() {
+        return Err(PruneError::InvalidMatchArchives(
+            "glob_archives and match_archives can't be combined".to_string(),
+        ));
+    }
+    match pattern {
+        Pattern::Shell(_) => Ok(()),
+        Pattern::Regex(_) => match common_options.borg_version {
+            Some(version) if !version.supports_match_archives() => {
+                Err(PruneError::InvalidMatchArchives(format!(
+                    "borg {version} doesn't support regular expressions"
+                )))
+            }
+            _ => Ok(()),
+        },
+        _ => Err(PruneError::InvalidMatchArchives(format!(
+            "{pattern} is neither a shell pattern nor a regular expression"
+        ))),
+    }
+}
+
+/// Select the archives to prune with the flag the borg version accepts
+fn prune_match_archives_fmt_args(options: &PruneOptions, common_options: &CommonOptions) -> String {
+    let supports_match_archives = common_options
+        .borg_version
+        .is_some_and(|x| x.supports_match_archives());
+    match (&options.match_archives, &options.glob_archives) {
+        (Some(Pattern::Shell(x)), _) | (None, Some(x)) if !supports_match_archives => {
+            format!(" --glob-archives {}", shell_escape(x))
+        }
+        (Some(pattern), _) => format!(" --match-archives {}", shell_escape(&pattern.to_string())),
+        (None, Some(x)) => format!(" --match-archives {}", shell_escape(&format!("sh:{x}"))),
+        (None, None) => "".to_string(),
+    }
+}
+
+pub(crate) fn prune_fmt_args(options: &PruneOptions, common_options: &CommonOptions) -> String {
+    format!(
+        "--log-json{common_options} prune{dry_run}{list}{save_space}{checkpoint_interval}{keep_within}{keep_last}{keep_secondly}{keep_minutely}{keep_hourly}{keep_daily}{keep_weekly}{keep_monthly}{keep_yearly}{match_archives} {repository}",
+        common_options = common_options.fmt_args(),
+        dry_run = if options.dry_run { " --dry-run" } else { "" },
+        list = if options.list { " --list" } else { "" },
+        save_space = if options.save_space { " --save-space" } else { "" },
+        checkpoint_interval = options.checkpoint_interval.map_or("".to_string(), |x| format!(" --checkpoint-interval {x}")),
+        keep_within = options.keep_within.as_ref().map_or("".to_string(), |x| format!(" --keep-within {x}")),
+        keep_last = options.keep_last.map_or("".to_string(), |x| format!(" --keep-last {x}")),
+        keep_secondly = options.keep_secondly.as_ref().map_or("".to_string(), |x| format!(" --keep-secondly {x}")),
+        keep_minutely = options.keep_minutely.map_or("".to_string(), |x| format!(" --keep-minutely {x}")),
+        keep_hourly = options.keep_hourly.map_or("".to_string(), |x| format!(" --keep-hourly {x}")),
+        keep_daily = options.keep_daily.map_or("".to_string(), |x| format!(" --keep-daily {x}")),
+        keep_weekly = options.keep_weekly.map_or("".to_string(), |x| format!(" --keep-weekly {x}")),
+        keep_monthly = options.keep_monthly.map_or("".to_string(), |x| format!(" --keep-monthly {x}")),
+        keep_yearly = options.keep_yearly.map_or("".to_string(), |x| format!(" --keep-yearly {x}")),
+        match_archives = prune_match_archives_fmt_args(options, common_options),
+        repository = shell_escape(options.repository.as_str())
+    )
+}
+
+pub(crate) fn prune_parse_output(
+    res: Output,
+    common_options: &CommonOptions,
+) -> Result<PruneReport, PruneError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(PruneError::TerminatedBySignal);
+    };
+
+    let mut output = ErrorContext::new(common_options.max_error_context);
+    let mut report = PruneReport::default();
+
+    for line in BufRead::lines(res.stderr.as_slice()) {
+        let line = line.map_err(PruneError::InvalidBorgOutput)?;
+        output.push_line(&line);
+
+        trace!("borg output: {line}");
+
+        let log_msg = LoggingMessage::from_str(&line)?;
+        common_options.notify_log_callback(&log_msg);
+
+        if let LoggingMessage::QuestionPrompt { message, .. } = log_msg {
+            return Err(PruneError::InteractivePromptRequired { prompt: message });
+        }
+
+        if let LoggingMessage::LogMessage {
+            name,
+            message,
+            level_name,
+            time,
+            msg_id,
+        } = log_msg
+        {
+            if name == "borg.output.list" {
+                report.add_list_message(&message);
+            }
+
+            log_message(level_name, time, &name, &message, msg_id);
+
+            if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                    return Err(PruneError::Keyfile(err));
+                }
+                if is_error_exit_code(exit_code) {
+                    return Err(PruneError::UnexpectedMessageId(msg_id));
+                }
+            }
+        }
+    }
+
+    if is_error_exit_code(exit_code) {
+        if let Some(msg_id) = MessageId::from_exit_code(exit_code) {
+            if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                return Err(PruneError::Keyfile(err));
+            }
+            return Err(PruneError::UnexpectedMessageId(msg_id));
+        }
+        return Err(PruneError::Unknown(output.into_string()));
+    }
+
+    Ok(report)
+}
+
+pub(crate) fn mount_fmt_args(options: &MountOptions, common_options: &CommonOptions) -> String {
+    let mount_source_formatted = match &options.mount_source {
+        MountSource::Repository {
+            name,
+            first_n_archives,
+            last_n_archives,
+            glob_archives,
+        } => {
+            format!(
+                "{name}{first_n_archives}{last_n_archives}{glob_archives}",
+                name = shell_escape(name.as_str()),
+                first_n_archives = first_n_archives
+                    .map(|first_n| format!(" --first {}", first_n))
+                    .unwrap_or_default(),
+                last_n_archives = last_n_archives
+                    .map(|last_n| format!(" --last {}", last_n))
+                    .unwrap_or_default(),
+                glob_archives = glob_archives
+                    .as_ref()
+                    .map(|glob| format!(" --glob-archives {}", glob))
+                    .unwrap_or_default(),
+            )
+        }
+        MountSource::Archive { archive } => archive.fmt_arg(),
+    };
+    format!(
+        "--log-json{common_options} mount{foreground}{fuse_options} {mount_source} {mountpoint}{select_paths}",
+        common_options = common_options.fmt_args(),
+        foreground = if options.foreground { " -f" } else { "" },
+        fuse_options = options
+            .fuse_options
+            .iter()
+            .map(|x| format!(" -o {}", shell_escape(x)))
+            .collect::<String>(),
+        mount_source = mount_source_formatted,
+        mountpoint = shell_escape(&options.mountpoint),
+        select_paths = options
+            .select_paths
+            .iter()
+            .map(|x| format!(" --pattern={}", shell_escape(&x.to_string())))
+            .collect::<String>(),
+    )
+}
+
+pub(crate) fn mount_parse_output(
+    res: Output,
+    common_options: &CommonOptions,
+) -> Result<(), MountError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(MountError::TerminatedBySignal);
+    };
+
+    let mut output = ErrorContext::new(common_options.max_error_context);
+
+    for line in BufRead::lines(res.stderr.as_slice()) {
+        let line = line.map_err(MountError::InvalidBorgOutput)?;
+        output.push_line(&line);
+
+        trace!("borg output: {line}");
+
+        let log_msg = LoggingMessage::from_str(&line)?;
+        common_options.notify_log_callback(&log_msg);
+
+        if let LoggingMessage::QuestionPrompt { message, .. } = log_msg {
+            return Err(MountError::InteractivePromptRequired { prompt: message });
+        }
+
+        if let LoggingMessage::UMountError(message) = log_msg {
+            // fusermount: entry for <mountpoint> not found in /etc/mtab
+            if let Some((mountpoint, _)) = message
+                .split_once(" entry for ")
+                .and_then(|(_, x)| x.rsplit_once(" not found in "))
+            {
+                return Err(MountError::NotMounted(mountpoint.to_string()));
+            }
+            return Err(MountError::UMountError(message));
+        };
+
+        if let LoggingMessage::LogMessage {
+            name,
+            message,
+            level_name,
+            time,
+            msg_id,
+        } = log_msg
+        {
+            log_message(level_name, time, &name, &message, msg_id);
+
+            if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                    return Err(MountError::Keyfile(err));
+                }
+                if is_error_exit_code(exit_code) {
+                    return Err(MountError::UnexpectedMessageId(msg_id));
+                }
+            }
+        }
+    }
+
+    if is_error_exit_code(exit_code) {
+        if let Some(msg_id) = MessageId::from_exit_code(exit_code) {
+            if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                return Err(MountError::Keyfile(err));
+            }
+            return Err(MountError::UnexpectedMessageId(msg_id));
+        }
+        return Err(MountError::Unknown(output.into_string()));
+    }
+    Ok(())
+}
+
+pub(crate) fn list_fmt_args(options: &ListOptions, common_options: &CommonOptions) -> String {
+    format!(
+        "--log-json{common_options} list --json{format}{first_n}{last_n}{glob_archives}{sort_by}{consider_checkpoints} {repository}",
+        common_options = common_options.fmt_args(),
+        format = options
+            .format
+            .as_ref()
+            .map_or("".to_string(), |x| format!(" --format {}", shell_escape(x))),
+        first_n = options
+            .first_n
+            .map(|first_n| format!(" --first {first_n}"))
+            .unwrap_or_default(),
+        last_n = options
+            .last_n
+            .map(|last_n| format!(" --last {last_n}"))
+            .unwrap_or_default(),
+        glob_archives = options
+            .glob_archives
+            .as_ref()
+            .map(|glob| format!(" --glob-archives {}", shell_escape(glob)))
+            .unwrap_or_default(),
+        sort_by = options
+            .sort_by
+            .as_ref()
+            .map(|sort_by| format!(" --sort-by {}", shell_escape(sort_by)))
+            .unwrap_or_default(),
+        consider_checkpoints = match common_options.borg_version {
+            _ if !options.consider_checkpoints => "",
+            // Older versions list checkpoints anyway
+            Some(version) if !version.supports_consider_checkpoints() => "",
+            _ => " --consider-checkpoints",
+        },
+        repository = shell_escape(options.repository.as_str())
+    )
+}
+
+pub(crate) fn list_stream_fmt_args(
+    options: &ListOptions,
+    archive: &str,
+    common_options: &CommonOptions,
+) -> String {
+    format!(
+        "--log-json{common_options} list --json-lines{format} {repository}::{archive}",
+        common_options = common_options.fmt_args(),
+        format = options
+            .format
+            .as_ref()
+            .map_or("".to_string(), |x| format!(" --format {}", shell_escape(x))),
+        repository = shell_escape(options.repository.as_str()),
+        archive = shell_escape(archive),
+    )
+}
+
+pub(crate) fn list_parse_output(
+    res: Output,
+    options: &ListOptions,
+    common_options: &CommonOptions,
+) -> Result<ListRepository, ListError> {
+    list_parse_log(&res, common_options)?;
+
+    trace!("Parsing output");
+    let mut list_repo: ListRepository = serde_json::from_slice(&res.stdout)?;
+
+    // borg < 1.2 lists checkpoints regardless
+    if !options.consider_checkpoints {
+        list_repo
+            .archives
+            .retain(|archive| !archive.is_checkpoint());
+    }
+
+    Ok(list_repo)
+}
+
+/// Check the log output and exit code of a borg list command for errors
+/// Parse the output of [list_stream_fmt_args], which contains an item per line
+pub(crate) fn list_items_parse_output(
+    res: Output,
+    common_options: &CommonOptions,
+) -> Result<Vec<ListItem>, ListError> {
+    list_parse_log(&res, common_options)?;
+
+    trace!("Parsing output");
+    BufRead::lines(res.stdout.as_slice())
+        .map(|line| {
+            let line = line.map_err(ListError::InvalidBorgOutput)?;
+            Ok(serde_json::from_str(&line)?)
+        })
+        .collect()
+}
+
+pub(crate) fn list_parse_log(
+    res: &Output,
+    common_options: &CommonOptions,
+) -> Result<(), ListError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(ListError::TerminatedBySignal);
+    };
+
+    let mut output = ErrorContext::new(common_options.max_error_context);
+
+    for line in BufRead::lines(res.stderr.as_slice()) {
+        let line = line.map_err(ListError::InvalidBorgOutput)?;
+        output.push_line(&line);
+
+        trace!("borg output: {line}");
+
+        let log_msg = LoggingMessage::from_str(&line)?;
+        common_options.notify_log_callback(&log_msg);
+
+        if let LoggingMessage::QuestionPrompt { message, .. } = log_msg {
+            return Err(ListError::InteractivePromptRequired { prompt: message });
+        }
+
+        if let LoggingMessage::LogMessage {
+            name,
+            message,
+            level_name,
+            time,
+            msg_id,
+        } = log_msg
+        {
+            log_message(level_name, time, &name, &message, msg_id);
+
+            if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                    return Err(ListError::Keyfile(err));
+                }
+                match msg_id {
+                    MessageId::RepositoryDoesNotExist => {
+                        return Err(ListError::RepositoryDoesNotExist);
+                    }
+                    MessageId::PassphraseWrong => {
+                        return Err(ListError::PassphraseWrong);
+                    }
+                    _ => {
+                        if is_error_exit_code(exit_code) {
+                            return Err(ListError::UnexpectedMessageId(msg_id));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if is_error_exit_code(exit_code) {
+        if let Some(msg_id) = MessageId::from_exit_code(exit_code) {
+            if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                return Err(ListError::Keyfile(err));
+            }
+            return Err(match msg_id {
+                MessageId::RepositoryDoesNotExist => ListError::RepositoryDoesNotExist,
+                MessageId::PassphraseWrong => ListError::PassphraseWrong,
+                _ => ListError::UnexpectedMessageId(msg_id),
+            });
+        }
+        return Err(ListError::Unknown(output.into_string()));
+    }
+
+    Ok(())
+}
+
+/// The temporary files that are passed to borg create
+///
+/// The files are removed on drop, so they have to be kept until borg exited.
+#[derive(Default)]
+pub(crate) struct CreateTempFiles {
+    /// The file with [CreateOptions::inline_patterns]
+    pub(crate) inline_patterns: Option<NamedTempFile>,
+    /// The file with the paths to archive, see [CreateOptions::paths_from_file]
+    pub(crate) paths: Option<NamedTempFile>,
+}
+
+impl CreateTempFiles {
+    /// Write the temporary files required by the options
+    pub(crate) fn new(options: &CreateOptions) -> Result<Self, CreateError> {
+        let mut files = Self::default();
+
+        if !options.inline_patterns.is_empty() {
+            let lines = options.inline_patterns.iter().map(|x| x.to_string());
+            files.inline_patterns = Some(
+                write_temp_file("borg-patterns-", lines)
+                    .map_err(CreateError::InlinePatternsFailed)?,
+            );
+        }
+
+        if let CreateSource::Paths(paths) = &options.source {
+            let len: usize = paths.iter().map(|x| x.len() + 1).sum();
+            if options.paths_from_file || len > PATHS_FROM_FILE_THRESHOLD {
+                if let Some(path) = paths.iter().find(|x| x.contains('\n')) {
+                    return Err(CreateError::InvalidPath(path.clone()));
+                }
+                files.paths = Some(
+                    write_temp_file("borg-paths-", paths.iter())
+                        .map_err(CreateError::PathsFileFailed)?,
+                );
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Open the file with the paths to archive, which is passed to borg as stdin
+    pub(crate) fn open_paths(&self) -> Result<Option<File>, CreateError> {
+        self.paths
+            .as_ref()
+            .map(|x| x.reopen())
+            .transpose()
+            .map_err(CreateError::PathsFileFailed)
+    }
+}
+
+/// Write a temporary file with one line per item
+fn write_temp_file(
+    prefix: &str,
+    lines: impl Iterator<Item = impl Display>,
+) -> io::Result<NamedTempFile> {
+    let mut file = tempfile::Builder::new().prefix(prefix).tempfile()?;
+    for line in lines {
+        writeln!(file, "{line}")?;
+    }
+    file.flush()?;
+    Ok(file)
+}
+
+/// Format the arguments of borg create
+///
+/// `temp_files` are the files written by [CreateTempFiles::new].
+pub(crate) fn create_fmt_args(
+    options: &CreateOptions,
+    common_options: &CommonOptions,
+    progress: bool,
+    temp_files: &CreateTempFiles,
+) -> Result<String, CreateError> {
+    if let Some(compression) = &options.compression {
+        compression.validate()?;
+    }
+    match common_options.borg_version {
+        Some(version)
+            if options.checkpoint_volume.is_some() && !version.supports_checkpoint_volume() =>
+        {
+            return Err(CreateError::CheckpointVolumeNotSupported(version));
+        }
+        _ => {}
+    }
+
+    Ok(format!(
+        "--log-json{p}{common_options} create --json{stats}{dry_run}{comment}{timestamp}{compression}{chunker_params}{files_cache}{checkpoint_interval}{checkpoint_volume}{num_ids}{sparse}{read_special}{no_xattr}{no_acls}{no_flags}{atime}{nobirthtime}{ex_caches}{ex_if_present}{keep_exclude_tags}{ex_nodump}{patterns}{excludes}{pattern_files}{inline_patterns}{exclude_files}{stdin} {archive}{source}",
+        common_options = common_options.fmt_args(),
+        p = if progress { " --progress" } else { "" },
+        stats = if options.stats { " --stats" } else { "" },
+        dry_run = if options.dry_run { " --dry-run" } else { "" },
+        comment = options.comment.as_ref().map_or("".to_string(), |x| format!(
+            " --comment {}",
+            shell_escape(x)
+        )),
+        timestamp = options.timestamp.as_ref().map_or("".to_string(), |x| format!(
+            " --timestamp {}",
+            x.format("%Y-%m-%dT%H:%M:%S")
+        )),
+        compression = options.compression.as_ref().map_or("".to_string(), |x| format!(" --compression {x}")),
+        chunker_params = options.chunker_params.as_ref().map_or("".to_string(), |x| format!(" --chunker-params {x}")),
+        files_cache = options.files_cache.as_ref().map_or("".to_string(), |x| format!(" --files-cache={x}")),
+        checkpoint_interval = options.checkpoint_interval.map_or("".to_string(), |x| format!(" --checkpoint-interval {x}")),
+        checkpoint_volume = options.checkpoint_volume.map_or("".to_string(), |x| format!(" --checkpoint-volume {x}")),
+        num_ids = if options.numeric_ids { " --numeric-ids" } else { "" },
+        sparse = if options.sparse { " --sparse" } else { "" },
+        read_special = if options.read_special { " --read-special" } else { "" },
+        no_xattr = if options.no_xattrs { " --noxattrs" } else { "" },
+        no_acls = if options.no_acls { " --noacls" } else { "" },
+        no_flags = match common_options.borg_version {
+            _ if !options.no_flags => "",
+            Some(version) if !version.supports_noflags() => " --nobsdflags",
+            _ => " --noflags",
+        },
+        atime = if options.atime { " --atime" } else { "" },
+        nobirthtime = if options.nobirthtime { " --nobirthtime" } else { "" },
+        ex_caches = if options.exclude_caches { " --exclude-caches" } else {""},
+        ex_if_present = options.exclude_if_present.iter().map(|x| format!(
+            " --exclude-if-present {}",
+            shell_escape(x),
+        )).collect::<String>(),
+        keep_exclude_tags = if options.keep_exclude_tags { " --keep-exclude-tags" } else { "" },
+        ex_nodump = if options.exclude_nodump { " --exclude-nodump" } else { "" },
+        patterns = options.patterns.iter().map(|x| format!(
+            " --pattern={}",
+            shell_escape(&x.to_string()),
+        )).collect::<String>(),
+        excludes = options.excludes.iter().map(|x| format!(
+            " --exclude={}",
+            shell_escape(&x.to_string()),
+        )).collect::<String>(),
+        pattern_files = options.pattern_files.iter().map(|x| format!(
+            " --patterns-from {}",
+            shell_escape(x),
+        )).collect::<String>(),
+        inline_patterns = temp_files.inline_patterns.as_ref().map_or("".to_string(), |x| format!(
+            " --patterns-from {}",
+            shell_escape(&x.path().to_string_lossy()),
+        )),
+        exclude_files = options.exclude_files.iter().map(|x| format!(
+            " --exclude-from {}",
+            shell_escape(x),
+        )).collect::<String>(),
+        archive = options.archive.fmt_arg(),
+        stdin = match &options.source {
+            CreateSource::Paths(_) if temp_files.paths.is_some() => " --paths-from-stdin".to_string(),
+            CreateSource::Paths(_) => "".to_string(),
+            CreateSource::Stdin { name, mode, user, group } => {
+                stdin_fmt_args(name, mode, user, group)
+            }
+            CreateSource::Command { name, mode, user, group, .. } => format!(
+                " --content-from-command{}",
+                stdin_fmt_args(name, mode, user, group)
+            ),
+        },
+        source = match &options.source {
+            CreateSource::Paths(_) if temp_files.paths.is_some() => "".to_string(),
+            CreateSource::Paths(paths) => paths.iter().map(|x| format!(" {x}")).collect(),
+            CreateSource::Stdin { .. } => " -".to_string(),
+            CreateSource::Command { command, .. } => format!(
+                " -- {}",
+                command.iter().map(|x| shell_escape(x)).collect::<Vec<String>>().join(" ")
+            ),
+        },
+    ))
+}
+
+fn stdin_fmt_args(
+    name: &str,
+    mode: &Option<u32>,
+    user: &Option<String>,
+    group: &Option<String>,
+) -> String {
+    format!(
+        " --stdin-name {name}{user}{group}{mode}",
+        name = shell_escape(name),
+        user = user.as_ref().map_or("".to_string(), |x| format!(
+            " --stdin-user {}",
+            shell_escape(x)
+        )),
+        group = group.as_ref().map_or("".to_string(), |x| format!(
+            " --stdin-group {}",
+            shell_escape(x)
+        )),
+        mode = mode.map_or("".to_string(), |x| format!(" --stdin-mode {x:04o}")),
+    )
+}
+
+pub(crate) fn create_parse_output(
+    res: Output,
+    options: &CreateOptions,
+    common_options: &CommonOptions,
+) -> Result<Option<Create>, CreateError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(CreateError::TerminatedBySignal);
+    };
+
+    let mut output = ErrorContext::new(common_options.max_error_context);
+
+    for line in BufRead::lines(res.stderr.as_slice()) {
+        let line = line.map_err(CreateError::InvalidBorgOutput)?;
+        output.push_line(&line);
+
+        trace!("borg output: {line}");
+
+        // Unexpected output like tracebacks is kept as context, but must not abort parsing
+        let log_msg = match LoggingMessage::from_str(&line) {
+            Ok(log_msg) => log_msg,
+            Err(err) => {
+                warn!("Could not parse borg output ({err}): {line}");
+                continue;
+            }
+        };
+        common_options.notify_log_callback(&log_msg);
+
+        if let LoggingMessage::QuestionPrompt { message, .. } = log_msg {
+            return Err(CreateError::InteractivePromptRequired { prompt: message });
+        }
+
+        if let LoggingMessage::LogMessage {
+            name,
+            message,
+            level_name,
+            time,
+            msg_id,
+        } = log_msg
+        {
+            log_message(level_name, time, &name, &message, msg_id);
+
+            if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                    return Err(CreateError::Keyfile(err));
+                }
+                match msg_id {
+                    MessageId::ArchiveAlreadyExists => {
+                        return Err(CreateError::ArchiveAlreadyExists)
+                    }
+                    MessageId::PassphraseWrong => {
+                        return Err(CreateError::PassphraseWrong);
+                    }
+                    _ => {
+                        if is_error_exit_code(exit_code) {
+                            return Err(CreateError::UnexpectedMessageId(msg_id));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if is_error_exit_code(exit_code) {
+        if let Some(msg_id) = MessageId::from_exit_code(exit_code) {
+            if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                return Err(CreateError::Keyfile(err));
+            }
+            return Err(match msg_id {
+                MessageId::ArchiveAlreadyExists => CreateError::ArchiveAlreadyExists,
+                MessageId::PassphraseWrong => CreateError::PassphraseWrong,
+                _ => CreateError::UnexpectedMessageId(msg_id),
+            });
+        }
+        return Err(CreateError::Unknown(output.into_string()));
+    }
+
+    // borg doesn't emit stats if no archive was created
+    if options.dry_run {
+        return Ok(None);
+    }
+
+    trace!("Parsing stats");
+    let stats: Create = serde_json::from_slice(&res.stdout)?;
+
+    Ok(Some(stats))
+}
+
+pub(crate) fn compact_fmt_args(
+    options: &CompactOptions,
+    common_options: &CommonOptions,
+    progress: bool,
+) -> String {
+    format!(
+        "--log-json{p}{common_options} compact{info}{dry_run}{cleanup_commits} {repository}",
+        common_options = common_options.fmt_args(),
+        p = if progress { " --progress" } else { "" },
+        // borg only reports the freed space on the info level
+        info = if common_options.log_level.is_none() {
+            " --info"
+        } else {
+            ""
+        },
+        dry_run = if options.dry_run { " --dry-run" } else { "" },
+        cleanup_commits = if options.cleanup_commits {
+            " --cleanup-commits"
+        } else {
+            ""
+        },
+        repository = shell_escape(options.repository.as_str())
+    )
+}
+
+/// Refuse options the borg version doesn't support, see [CompactOptions::dry_run]
+/// and [CompactOptions::cleanup_commits]
+pub(crate) fn compact_check_version(
+    options: &CompactOptions,
+    common_options: &CommonOptions,
+) -> Result<(), CompactError> {
+    match common_options.borg_version {
+        Some(version) if options.dry_run && !version.supports_compact_dry_run() => {
+            Err(CompactError::DryRunNotSupported(version))
+        }
+        Some(version) if options.cleanup_commits && !version.supports_cleanup_commits() => {
+            Err(CompactError::CleanupCommitsNotSupported(version))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn compact_parse_output(
+    res: Output,
+    common_options: &CommonOptions,
+) -> Result<CompactReport, CompactError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(CompactError::TerminatedBySignal);
+    };
+
+    let mut output = ErrorContext::new(common_options.max_error_context);
+    let mut report = CompactReport::default();
+
+    for line in BufRead::lines(res.stderr.as_slice()) {
+        let line = line.map_err(CompactError::InvalidBorgOutput)?;
+        output.push_line(&line);
+
+        trace!("borg output: {line}");
+
+        let log_msg = LoggingMessage::from_str(&line)?;
+        common_options.notify_log_callback(&log_msg);
+
+        if let LoggingMessage::QuestionPrompt { message, .. } = log_msg {
+            return Err(CompactError::InteractivePromptRequired { prompt: message });
+        }
+
+        if let LoggingMessage::LogMessage {
+            name,
+            message,
+            level_name,
+            time,
+            msg_id,
+        } = log_msg
+        {
+            if name == "borg.repository" {
+                report.add_log_message(&message);
+            }
+
+            log_message(level_name, time, &name, &message, msg_id);
+
+            if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                    return Err(CompactError::Keyfile(err));
+                }
+                if is_error_exit_code(exit_code) {
+                    return Err(CompactError::UnexpectedMessageId(msg_id));
+                }
+            }
+        }
+    }
+
+    if is_error_exit_code(exit_code) {
+        if let Some(msg_id) = MessageId::from_exit_code(exit_code) {
+            if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                return Err(CompactError::Keyfile(err));
+            }
+            return Err(CompactError::UnexpectedMessageId(msg_id));
+        }
+        return Err(CompactError::Unknown(output.into_string()));
+    }
+
+    Ok(report)
+}
+
+pub(crate) fn extract_fmt_args(options: &ExtractOptions, common_options: &CommonOptions) -> String {
+    format!(
+        "--log-json{common_options} extract{list}{num_ids}{sparse}{strip_components}{patterns} {archive}{paths}",
+        common_options = common_options.fmt_args(),
+        list = if options.list_files { " --list" } else { "" },
+        num_ids = if options.numeric_ids { " --numeric-ids" } else { "" },
+        sparse = if options.sparse { " --sparse" } else { "" },
+        strip_components = options
+            .strip_components
+            .map_or("".to_string(), |x| format!(" --strip-components {x}")),
+        patterns = options
+            .patterns
+            .iter()
+            .map(|x| format!(" --pattern={}", shell_escape(&x.to_string())))
+            .collect::<String>(),
+        archive = options.archive.fmt_arg(),
+        paths = options
+            .paths
+            .iter()
+            .map(|x| format!(" {}", shell_escape(x)))
+            .collect::<String>(),
+    )
+}
+
+pub(crate) fn extract_parse_output(
+    res: Output,
+    common_options: &CommonOptions,
+) -> Result<Vec<String>, ExtractError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(ExtractError::TerminatedBySignal);
+    };
+
+    let mut output = ErrorContext::new(common_options.max_error_context);
+    let mut files = vec![];
+
+    for line in BufRead::lines(res.stderr.as_slice()) {
+        let line = line.map_err(ExtractError::InvalidBorgOutput)?;
+        output.push_line(&line);
+
+        trace!("borg output: {line}");
+
+        let log_msg = LoggingMessage::from_str(&line)?;
+        common_options.notify_log_callback(&log_msg);
+
+        if let LoggingMessage::QuestionPrompt { message, .. } = log_msg {
+            return Err(ExtractError::InteractivePromptRequired { prompt: message });
+        }
+
+        if let LoggingMessage::LogMessage {
+            name,
+            message,
+            level_name,
+            time,
+            msg_id,
+        } = log_msg
+        {
+            log_message(level_name, time, &name, &message, msg_id);
+
+            // With --list, borg logs the path of every extracted item
+            if name == "borg.output.list" {
+                files.push(message);
+            }
+
+            if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                    return Err(ExtractError::Keyfile(err));
+                }
+                match msg_id {
+                    MessageId::RepositoryDoesNotExist => {
+                        return Err(ExtractError::RepositoryDoesNotExist);
+                    }
+                    MessageId::ArchiveDoesNotExist => {
+                        return Err(ExtractError::ArchiveDoesNotExist);
+                    }
+                    MessageId::PassphraseWrong => {
+                        return Err(ExtractError::PassphraseWrong);
+                    }
+                    _ => {
+                        if is_error_exit_code(exit_code) {
+                            return Err(ExtractError::UnexpectedMessageId(msg_id));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if is_error_exit_code(exit_code) {
+        if let Some(msg_id) = MessageId::from_exit_code(exit_code) {
+            if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                return Err(ExtractError::Keyfile(err));
+            }
+            return Err(match msg_id {
+                MessageId::RepositoryDoesNotExist => ExtractError::RepositoryDoesNotExist,
+                MessageId::ArchiveDoesNotExist => ExtractError::ArchiveDoesNotExist,
+                MessageId::PassphraseWrong => ExtractError::PassphraseWrong,
+                _ => ExtractError::UnexpectedMessageId(msg_id),
+            });
+        }
+        return Err(ExtractError::Unknown(output.into_string()));
+    }
+
+    Ok(files)
+}
+
+pub(crate) fn info_fmt_args(options: &InfoOptions, common_options: &CommonOptions) -> String {
+    format!(
+        "--log-json{common_options} info --json{first_n}{last_n}{glob_archives} {repository}{archive}",
+        common_options = common_options.fmt_args(),
+        first_n = options
+            .first_n
+            .map(|first_n| format!(" --first {first_n}"))
+            .unwrap_or_default(),
+        last_n = options
+            .last_n
+            .map(|last_n| format!(" --last {last_n}"))
+            .unwrap_or_default(),
+        glob_archives = options
+            .glob_archives
+            .as_ref()
+            .map(|glob| format!(" --glob-archives {}", shell_escape(glob)))
+            .unwrap_or_default(),
+        repository = shell_escape(options.repository.as_str()),
+        archive = options
+            .archive
+            .as_ref()
+            .map(|archive| format!("::{}", shell_escape(archive)))
+            .unwrap_or_default(),
+    )
+}
+
+pub(crate) fn info_parse_output(
+    res: Output,
+    options: &InfoOptions,
+    common_options: &CommonOptions,
+) -> Result<Info, InfoError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(InfoError::TerminatedBySignal);
+    };
+
+    let mut output = ErrorContext::new(common_options.max_error_context);
+
+    for line in BufRead::lines(res.stderr.as_slice()) {
+        let line = line.map_err(InfoError::InvalidBorgOutput)?;
+        output.push_line(&line);
+
+        trace!("borg output: {line}");
+
+        let log_msg = LoggingMessage::from_str(&line)?;
+        common_options.notify_log_callback(&log_msg);
+
+        if let LoggingMessage::QuestionPrompt { message, .. } = log_msg {
+            return Err(InfoError::InteractivePromptRequired { prompt: message });
+        }
+
+        if let LoggingMessage::LogMessage {
+            name,
+            message,
+            level_name,
+            time,
+            msg_id,
+        } = log_msg
+        {
+            log_message(level_name, time, &name, &message, msg_id);
+
+            if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                    return Err(InfoError::Keyfile(err));
+                }
+                match msg_id {
+                    MessageId::RepositoryDoesNotExist => {
+                        return Err(InfoError::RepositoryDoesNotExist);
+                    }
+                    MessageId::ArchiveDoesNotExist => {
+                        return Err(InfoError::ArchiveDoesNotExist);
+                    }
+                    MessageId::PassphraseWrong => {
+                        return Err(InfoError::PassphraseWrong);
+                    }
+                    _ => {
+                        if is_error_exit_code(exit_code) {
+                            return Err(InfoError::UnexpectedMessageId(msg_id));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if is_error_exit_code(exit_code) {
+        if let Some(msg_id) = MessageId::from_exit_code(exit_code) {
+            if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                return Err(InfoError::Keyfile(err));
+            }
+            return Err(match msg_id {
+                MessageId::RepositoryDoesNotExist => InfoError::RepositoryDoesNotExist,
+                MessageId::ArchiveDoesNotExist => InfoError::ArchiveDoesNotExist,
+                MessageId::PassphraseWrong => InfoError::PassphraseWrong,
+                _ => InfoError::UnexpectedMessageId(msg_id),
+            });
+        }
+        return Err(InfoError::Unknown(output.into_string()));
+    }
+
+    trace!("Parsing output");
+    let info = if options.selects_archives() {
+        serde_json::from_slice::<InfoArchivesOutput>(&res.stdout)?.into()
+    } else {
+        serde_json::from_slice::<InfoRepositoryOutput>(&res.stdout)?.into()
+    };
+
+    Ok(info)
+}
+
+/// Refuse to delete without selecting archives, as borg would delete the whole repository.
+///
+/// A dry run only reports what would be deleted, so it is allowed.
+pub(crate) fn delete_check_selection(options: &DeleteOptions) -> Result<(), DeleteError> {
+    match (&options.archive, &options.glob_archives) {
+        (None, None) if !options.dry_run => Err(DeleteError::NoArchivesSelected),
+        (Some(_), Some(_)) => Err(DeleteError::ConflictingSelection),
+        (Some(archive), None) if archive.repository() != &options.repository => {
+            Err(DeleteError::ArchiveNotInRepository(archive.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn delete_fmt_args(options: &DeleteOptions, common_options: &CommonOptions) -> String {
+    format!(
+        "--log-json{common_options} delete{dry_run} --list{stats}{glob_archives} {archive}",
+        common_options = common_options.fmt_args(),
+        dry_run = if options.dry_run { " --dry-run" } else { "" },
+        stats = if options.stats { " --stats" } else { "" },
+        glob_archives = options
+            .glob_archives
+            .as_ref()
+            .map(|glob| format!(" --glob-archives {}", shell_escape(glob)))
+            .unwrap_or_default(),
+        archive = options.archive.as_ref().map_or_else(
+            || shell_escape(options.repository.as_str()),
+            ArchiveRef::fmt_arg
+        ),
+    )
+}
+
+pub(crate) fn delete_parse_output(
+    res: Output,
+    common_options: &CommonOptions,
+) -> Result<DeleteReport, DeleteError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(DeleteError::TerminatedBySignal);
+    };
+
+    let mut output = ErrorContext::new(common_options.max_error_context);
+    let mut report = DeleteReport::default();
+
+    for line in BufRead::lines(res.stderr.as_slice()) {
+        let line = line.map_err(DeleteError::InvalidBorgOutput)?;
+        output.push_line(&line);
+
+        trace!("borg output: {line}");
+
+        let log_msg = LoggingMessage::from_str(&line)?;
+        common_options.notify_log_callback(&log_msg);
+
+        if let LoggingMessage::QuestionPrompt { message, .. } = log_msg {
+            return Err(DeleteError::InteractivePromptRequired { prompt: message });
+        }
+
+        if let LoggingMessage::LogMessage {
+            name,
+            message,
+            level_name,
+            time,
+            msg_id,
+        } = log_msg
+        {
+            match name.as_str() {
+                "borg.output.list" => report.add_list_message(&message),
+                "borg.output.stats" => report.add_stats_message(&message),
+                _ => {}
+            }
+
+            log_message(level_name, time, &name, &message, msg_id);
+
+            if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                    return Err(DeleteError::Keyfile(err));
+                }
+                match msg_id {
+                    MessageId::RepositoryDoesNotExist => {
+                        return Err(DeleteError::RepositoryDoesNotExist);
+                    }
+                    MessageId::ArchiveDoesNotExist => {
+                        return Err(DeleteError::ArchiveDoesNotExist);
+                    }
+                    MessageId::PassphraseWrong => {
+                        return Err(DeleteError::PassphraseWrong);
+                    }
+                    _ => {
+                        if is_error_exit_code(exit_code) {
+                            return Err(DeleteError::UnexpectedMessageId(msg_id));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if is_error_exit_code(exit_code) {
+        if let Some(msg_id) = MessageId::from_exit_code(exit_code) {
+            if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                return Err(DeleteError::Keyfile(err));
+            }
+            return Err(match msg_id {
+                MessageId::RepositoryDoesNotExist => DeleteError::RepositoryDoesNotExist,
+                MessageId::ArchiveDoesNotExist => DeleteError::ArchiveDoesNotExist,
+                MessageId::PassphraseWrong => DeleteError::PassphraseWrong,
+                _ => DeleteError::UnexpectedMessageId(msg_id),
+            });
+        }
+        return Err(DeleteError::Unknown(output.into_string()));
+    }
+
+    Ok(report)
+}
+
+pub(crate) fn set_comment_fmt_args(
+    repository: &Repository,
+    archive: &str,
+    comment: &str,
+    common_options: &CommonOptions,
+) -> String {
+    format!(
+        "--log-json{common_options} recreate --comment {comment} {repository}::{archive}",
+        common_options = common_options.fmt_args(),
+        comment = shell_escape(comment),
+        repository = shell_escape(repository.as_str()),
+        archive = shell_escape(archive),
+    )
+}
+
+pub(crate) fn recreate_parse_output(
+    res: Output,
+    common_options: &CommonOptions,
+) -> Result<(), RecreateError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(RecreateError::TerminatedBySignal);
+    };
+
+    let mut output = ErrorContext::new(common_options.max_error_context);
+
+    for line in BufRead::lines(res.stderr.as_slice()) {
+        let line = line.map_err(RecreateError::InvalidBorgOutput)?;
+        output.push_line(&line);
+
+        trace!("borg output: {line}");
+
+        let log_msg = LoggingMessage::from_str(&line)?;
+        common_options.notify_log_callback(&log_msg);
+
+        if let LoggingMessage::QuestionPrompt { message, .. } = log_msg {
+            return Err(RecreateError::InteractivePromptRequired { prompt: message });
+        }
+
+        if let LoggingMessage::LogMessage {
+            name,
+            message,
+            level_name,
+            time,
+            msg_id,
+        } = log_msg
+        {
+            log_message(level_name, time, &name, &message, msg_id);
+
+            if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                    return Err(RecreateError::Keyfile(err));
+                }
+                match msg_id {
+                    MessageId::RepositoryDoesNotExist => {
+                        return Err(RecreateError::RepositoryDoesNotExist);
+                    }
+                    MessageId::ArchiveDoesNotExist => {
+                        return Err(RecreateError::ArchiveDoesNotExist);
+                    }
+                    MessageId::PassphraseWrong => {
+                        return Err(RecreateError::PassphraseWrong);
+                    }
+                    _ => {
+                        if is_error_exit_code(exit_code) {
+                            return Err(RecreateError::UnexpectedMessageId(msg_id));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if is_error_exit_code(exit_code) {
+        if let Some(msg_id) = MessageId::from_exit_code(exit_code) {
+            if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                return Err(RecreateError::Keyfile(err));
+            }
+            return Err(match msg_id {
+                MessageId::RepositoryDoesNotExist => RecreateError::RepositoryDoesNotExist,
+                MessageId::ArchiveDoesNotExist => RecreateError::ArchiveDoesNotExist,
+                MessageId::PassphraseWrong => RecreateError::PassphraseWrong,
+                _ => RecreateError::UnexpectedMessageId(msg_id),
+            });
+        }
+        return Err(RecreateError::Unknown(output.into_string()));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn benchmark_crud_fmt_args(
+    options: &BenchmarkOptions,
+    common_options: &CommonOptions,
+) -> String {
+    format!(
+        "--log-json{common_options} benchmark crud {repository} {path}",
+        common_options = common_options.fmt_args(),
+        repository = shell_escape(options.repository.as_str()),
+        path = shell_escape(&options.path),
+    )
+}
+
+pub(crate) fn benchmark_crud_parse_output(
+    res: Output,
+    common_options: &CommonOptions,
+) -> Result<BenchmarkResult, BenchmarkError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(BenchmarkError::TerminatedBySignal);
+    };
+
+    let mut output = ErrorContext::new(common_options.max_error_context);
+
+    for line in BufRead::lines(res.stderr.as_slice()) {
+        let line = line.map_err(BenchmarkError::InvalidBorgOutput)?;
+        output.push_line(&line);
+
+        trace!("borg output: {line}");
+
+        let Ok(log_msg) = LoggingMessage::from_str(&line) else {
+            warn!("Could not parse borg output: {line}");
+            continue;
+        };
+        common_options.notify_log_callback(&log_msg);
+
+        if let LoggingMessage::QuestionPrompt { message, .. } = log_msg {
+            return Err(BenchmarkError::InteractivePromptRequired { prompt: message });
+        }
+
+        if let LoggingMessage::LogMessage {
+            name,
+            message,
+            level_name,
+            time,
+            msg_id,
+        } = log_msg
+        {
+            log_message(level_name, time, &name, &message, msg_id);
+
+            if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                    return Err(BenchmarkError::Keyfile(err));
+                }
+                match msg_id {
+                    MessageId::RepositoryDoesNotExist => {
+                        return Err(BenchmarkError::RepositoryDoesNotExist);
+                    }
+                    MessageId::PassphraseWrong => {
+                        return Err(BenchmarkError::PassphraseWrong);
+                    }
+                    MessageId::PathNotAllowed => {
+                        return Err(BenchmarkError::PathNotAllowed);
+                    }
+                    _ => {
+                        if is_error_exit_code(exit_code) {
+                            return Err(BenchmarkError::UnexpectedMessageId(msg_id));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if is_error_exit_code(exit_code) {
+        if let Some(msg_id) = MessageId::from_exit_code(exit_code) {
+            if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                return Err(BenchmarkError::Keyfile(err));
+            }
+            return Err(match msg_id {
+                MessageId::RepositoryDoesNotExist => BenchmarkError::RepositoryDoesNotExist,
+                MessageId::PassphraseWrong => BenchmarkError::PassphraseWrong,
+                MessageId::PathNotAllowed => BenchmarkError::PathNotAllowed,
+                _ => BenchmarkError::UnexpectedMessageId(msg_id),
+            });
+        }
+        return Err(BenchmarkError::Unknown(output.into_string()));
+    }
+
+    // The table isn't available as JSON, so it is parsed line by line
+    let measurements = String::from_utf8_lossy(&res.stdout)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            BenchmarkMeasurement::parse(line)
+                .ok_or_else(|| BenchmarkError::InvalidBenchmarkOutput(line.to_string()))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(BenchmarkResult { measurements })
+}
+
+pub(crate) fn serve_fmt_args(options: &ServeOptions, common_options: &CommonOptions) -> String {
+    format!(
+        "{common_options}serve{restrict_to_paths}{restrict_to_repositories}{append_only}{storage_quota}",
+        // Unlike the other commands, serve is the first argument
+        common_options = match common_options.to_string() {
+            flags if flags.is_empty() => flags,
+            flags => format!("{flags} "),
+        },
+        restrict_to_paths = options
+            .restrict_to_paths
+            .iter()
+            .map(|path| format!(" --restrict-to-path {}", shell_escape(path)))
+            .collect::<String>(),
+        restrict_to_repositories = options
+            .restrict_to_repositories
+            .iter()
+            .map(|repository| format!(" --restrict-to-repository {}", shell_escape(repository)))
+            .collect::<String>(),
+        append_only = if options.append_only {
+            " --append-only"
+        } else {
+            ""
+        },
+        storage_quota = options
+            .storage_quota
+            .as_ref()
+            .map(|quota| format!(" --storage-quota {}", shell_escape(quota)))
+            .unwrap_or_default(),
+    )
+}
+
+pub(crate) fn serve_parse_output(
+    res: Output,
+    common_options: &CommonOptions,
+) -> Result<(), ServeError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(ServeError::TerminatedBySignal);
+    };
+
+    let mut output = ErrorContext::new(common_options.max_error_context);
+
+    for line in BufRead::lines(res.stderr.as_slice()) {
+        let line = line.map_err(ServeError::InvalidBorgOutput)?;
+        output.push_line(&line);
+
+        trace!("borg output: {line}");
+
+        // The output is only collected if the caller didn't take stderr,
+        // it may contain anything the client would have received
+        let Ok(log_msg) = LoggingMessage::from_str(&line) else {
+            continue;
+        };
+        common_options.notify_log_callback(&log_msg);
+
+        if let LoggingMessage::LogMessage {
+            name,
+            message,
+            level_name,
+            time,
+            msg_id,
+        } = log_msg
+        {
+            log_message(level_name, time, &name, &message, msg_id);
+
+            if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                if is_error_exit_code(exit_code) {
+                    return Err(ServeError::UnexpectedMessageId(msg_id));
+                }
+            }
+        }
+    }
+
+    if is_error_exit_code(exit_code) {
+        if let Some(msg_id) = MessageId::from_exit_code(exit_code) {
+            return Err(ServeError::UnexpectedMessageId(msg_id));
+        }
+        return Err(ServeError::Unknown(output.into_string()));
+    }
+    Ok(())
+}
+
+pub(crate) fn config_fmt_args(options: &ConfigOptions, common_options: &CommonOptions) -> String {
+    format!(
+        "--log-json{common_options} config {repository} {key}{value}",
+        common_options = common_options.fmt_args(),
+        repository = shell_escape(options.repository.as_str()),
+        key = shell_escape(&options.key),
+        value = options
+            .value
+            .as_ref()
+            .map(|value| format!(" {}", shell_escape(value)))
+            .unwrap_or_default(),
+    )
+}
+
+pub(crate) fn config_parse_output(
+    res: Output,
+    options: &ConfigOptions,
+    common_options: &CommonOptions,
+) -> Result<Option<String>, ConfigError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(ConfigError::TerminatedBySignal);
+    };
+
+    let mut output = ErrorContext::new(common_options.max_error_context);
+
+    for line in BufRead::lines(res.stderr.as_slice()) {
+        let line = line.map_err(ConfigError::InvalidBorgOutput)?;
+        output.push_line(&line);
+
+        trace!("borg output: {line}");
+
+        // A missing key is reported as plain text instead of a log message
+        let Ok(log_msg) = LoggingMessage::from_str(&line) else {
+            continue;
+        };
+        common_options.notify_log_callback(&log_msg);
+
+        if let LoggingMessage::QuestionPrompt { message, .. } = log_msg {
+            return Err(ConfigError::InteractivePromptRequired { prompt: message });
+        }
+
+        if let LoggingMessage::LogMessage {
+            name,
+            message,
+            level_name,
+            time,
+            msg_id,
+        } = log_msg
+        {
+            log_message(level_name, time, &name, &message, msg_id);
+
+            if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                    return Err(ConfigError::Keyfile(err));
+                }
+                match msg_id {
+                    MessageId::RepositoryDoesNotExist => {
+                        return Err(ConfigError::RepositoryDoesNotExist);
+                    }
+                    MessageId::PassphraseWrong => {
+                        return Err(ConfigError::PassphraseWrong);
+                    }
+                    MessageId::PathNotAllowed => {
+                        return Err(ConfigError::PathNotAllowed);
+                    }
+                    _ => {
+                        if is_error_exit_code(exit_code) {
+                            return Err(ConfigError::UnexpectedMessageId(msg_id));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if is_error_exit_code(exit_code) {
+        if let Some(msg_id) = MessageId::from_exit_code(exit_code) {
+            if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                return Err(ConfigError::Keyfile(err));
+            }
+            return Err(match msg_id {
+                MessageId::RepositoryDoesNotExist => ConfigError::RepositoryDoesNotExist,
+                MessageId::PassphraseWrong => ConfigError::PassphraseWrong,
+                MessageId::PathNotAllowed => ConfigError::PathNotAllowed,
+                _ => ConfigError::UnexpectedMessageId(msg_id),
+            });
+        }
+        return Err(ConfigError::Unknown(output.into_string()));
+    }
+
+    if options.value.is_some() {
+        return Ok(None);
+    }
+
+    // borg exits with a warning if the key isn't set
+    if exit_code == 1 {
+        return Err(ConfigError::KeyNotFound(options.key.clone()));
+    }
+
+    let value = String::from_utf8_lossy(&res.stdout);
+    Ok(Some(value.trim_end_matches('\n').to_string()))
+}
+
+/// Refuse to transfer if the borg version has no transfer command
+pub(crate) fn transfer_check_version(common_options: &CommonOptions) -> Result<(), TransferError> {
+    match common_options.borg_version {
+        Some(version) if !version.supports_transfer() => {
+            Err(TransferError::TransferNotSupported(version))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn transfer_fmt_args(
+    options: &TransferOptions,
+    common_options: &CommonOptions,
+    progress: bool,
+) -> String {
+    format!(
+        "--log-json{p}{common_options} --repo {destination} transfer{dry_run} --other-repo {source}{glob_archives}",
+        common_options = common_options.fmt_args(),
+        p = if progress { " --progress" } else { "" },
+        destination = shell_escape(options.destination.as_str()),
+        dry_run = if options.dry_run { " --dry-run" } else { "" },
+        source = shell_escape(options.source.as_str()),
+        glob_archives = options.glob_archives.as_ref().map_or("".to_string(), |x| format!(
+            " --match-archives {}",
+            shell_escape(&format!("sh:{x}"))
+        )),
+    )
+}
+
+pub(crate) fn transfer_parse_output(
+    res: Output,
+    common_options: &CommonOptions,
+) -> Result<(), TransferError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(TransferError::TerminatedBySignal);
+    };
+
+    let mut output = ErrorContext::new(common_options.max_error_context);
+
+    for line in BufRead::lines(res.stderr.as_slice()) {
+        let line = line.map_err(TransferError::InvalidBorgOutput)?;
+        output.push_line(&line);
+
+        trace!("borg output: {line}");
+
+        let log_msg = LoggingMessage::from_str(&line)?;
+        common_options.notify_log_callback(&log_msg);
+
+        if let LoggingMessage::QuestionPrompt { message, .. } = log_msg {
+            return Err(TransferError::InteractivePromptRequired { prompt: message });
+        }
+
+        if let LoggingMessage::LogMessage {
+            name,
+            message,
+            level_name,
+            time,
+            msg_id,
+        } = log_msg
+        {
+            log_message(level_name, time, &name, &message, msg_id);
+
+            if let Some(msg_id) = msg_id.filter(MessageId::is_known) {
+                if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                    return Err(TransferError::Keyfile(err));
+                }
+                match msg_id {
+                    MessageId::RepositoryDoesNotExist => {
+                        return Err(TransferError::RepositoryDoesNotExist);
+                    }
+                    MessageId::PassphraseWrong => {
+                        return Err(TransferError::PassphraseWrong);
+                    }
+                    _ => {
+                        if is_error_exit_code(exit_code) {
+                            return Err(TransferError::UnexpectedMessageId(msg_id));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if is_error_exit_code(exit_code) {
+        if let Some(msg_id) = MessageId::from_exit_code(exit_code) {
+            if let Some(err) = KeyfileError::from_message_id(msg_id) {
+                return Err(TransferError::Keyfile(err));
+            }
+            return Err(match msg_id {
+                MessageId::RepositoryDoesNotExist => TransferError::RepositoryDoesNotExist,
+                MessageId::PassphraseWrong => TransferError::PassphraseWrong,
+                _ => TransferError::UnexpectedMessageId(msg_id),
+            });
+        }
+        return Err(TransferError::Unknown(output.into_string()));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn raw_fmt_args(common_options: &CommonOptions) -> String {
+    format!(
+        "--log-json{common_options}",
+        common_options = common_options.fmt_args(),
+    )
+}
+
+pub(crate) fn version_parse_output(res: Output) -> Result<BorgVersion, VersionError> {
+    let Some(exit_code) = res.status.code() else {
+        warn!("borg process was terminated by signal");
+        return Err(VersionError::TerminatedBySignal);
+    };
+
+    if exit_code != 0 {
+        return Err(VersionError::Unknown(
+            String::from_utf8_lossy(&res.stderr).to_string(),
+        ));
+    }
+
+    // Output has the format "borg 1.2.4" or "borg 2.0.0b5"
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    let invalid_version = || VersionError::InvalidVersion(stdout.trim().to_string());
+
+    let version = stdout
+        .split_whitespace()
+        .last()
+        .ok_or_else(invalid_version)?;
+    let mut parts = version.splitn(3, '.').map(|part| {
+        let digits = part
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(part, |end| &part[..end]);
+        digits.parse::<u32>()
+    });
+    let mut next = || {
+        parts
+            .next()
+            .and_then(Result::ok)
+            .ok_or_else(invalid_version)
+    };
+
+    Ok(BorgVersion {
+        major: next()?,
+        minor: next()?,
+        patch: next()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::{NonZeroU16, NonZeroU32};
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::Path;
+    use std::process::{ExitStatus, Output};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use crate::common::{
+        benchmark_crud_fmt_args, benchmark_crud_parse_output, compact_check_version,
+        compact_fmt_args, config_fmt_args, config_parse_output, create_fmt_args,
+        create_parse_output, delete_check_selection, delete_fmt_args, delete_parse_output,
+        extract_fmt_args, extract_parse_output, info_fmt_args, info_parse_output,
+        is_error_exit_code, list_fmt_args, list_items_parse_output, list_parse_output,
+        list_stream_fmt_args, mount_fmt_args, mount_parse_output, prune_check_keep_rules,
+        prune_check_match_archives, prune_fmt_args, prune_parse_output, raw_fmt_args,
+        recreate_parse_output, serve_fmt_args, set_comment_fmt_args, transfer_check_version,
+        transfer_fmt_args, version_parse_output, ArchiveRef, BenchmarkOptions, ChunkerParams,
+        CommonOptions, CompactOptions, CompressionMode, ConfigOptions, CreateOptions, CreateSource,
+        CreateTempFiles, DeleteOptions, ExtractOptions, FilesCacheMode, InfoOptions, ListOptions,
+        LogLevel, MountOptions, MountSource, Pattern, PatternInstruction, PatternList,
+        PruneOptions, PruneWithin, PruneWithinTime, Repository, RetryPolicy, Rsh, ServeOptions,
+        TransferOptions,
+    };
+    use crate::errors::{
+        BenchmarkError, CompactError, ConfigError, CreateError, DeleteError, KeyfileError,
+        ListError, MountError, PatternListError, PruneError, RecreateError, TransferError,
+    };
+    use crate::output::benchmark::BenchmarkOperation;
+    use crate::output::extract::SizeMismatch;
+    use crate::output::info::Info;
+    use crate::output::logging::{LoggingMessage, MessageId};
+    use crate::output::prune::PruneReport;
+    use crate::output::version::BorgVersion;
+
+    #[test]
+    fn test_prune_fmt_args() {
+        let mut prune_option = PruneOptions::new(Path::new("prune_option_repo"));
+        prune_option.keep_last = NonZeroU16::new(10);
+        prune_option.keep_secondly = NonZeroU16::new(1);
+        prune_option.keep_minutely = NonZeroU16::new(2);
+        prune_option.keep_hourly = NonZeroU16::new(3);
+        prune_option.keep_daily = NonZeroU16::new(4);
+        prune_option.keep_weekly = NonZeroU16::new(5);
+        prune_option.keep_monthly = NonZeroU16::new(6);
+        prune_option.keep_yearly = NonZeroU16::new(7);
+        let args = prune_fmt_args(&prune_option, &CommonOptions::default());
+        assert_eq!("--log-json prune --keep-last 10 --keep-secondly 1 --keep-minutely 2 --keep-hourly 3 --keep-daily 4 --keep-weekly 5 --keep-monthly 6 --keep-yearly 7 'prune_option_repo'", args);
+    }
+    #[test]
+    fn test_mount_fmt_args() {
+        let mount_option = MountOptions::new(
+            MountSource::Archive {
+                archive: "/tmp/borg-repo::archive".parse().unwrap(),
+            },
+            String::from("/mnt/borg-mount"),
+        );
+        let args = mount_fmt_args(&mount_option, &CommonOptions::default());
+        assert_eq!(
+            "--log-json mount '/tmp/borg-repo'::'archive' '/mnt/borg-mount'",
+            args
+        );
+    }
+    #[test]
+    fn test_mount_fmt_args_patterns() {
+        let mut mount_option = MountOptions::new(
+            MountSource::Archive {
+                archive: "/my-borg-repo::archive".parse().unwrap(),
+            },
+            String::from("/borg-mount"),
+        );
+        mount_option.select_paths = vec![
+            Pattern::Shell("**/test/*".to_string()),
+            Pattern::Regex("^[A-Z]{3}".to_string()),
+        ];
+        let args = mount_fmt_args(&mount_option, &CommonOptions::default());
+        assert_eq!(
+            "--log-json mount '/my-borg-repo'::'archive' '/borg-mount' --pattern='sh:**/test/*' --pattern='re:^[A-Z]{3}'",
+            args
+        );
+    }
+    #[test]
+    fn test_mount_fmt_args_repo() {
+        let mut mount_option = MountOptions::new(
+            MountSource::Repository {
+                name: Path::new("/my-repo").into(),
+                first_n_archives: Some(NonZeroU16::new(10).unwrap()),
+                last_n_archives: Some(NonZeroU16::new(5).unwrap()),
+                glob_archives: Some("archive-name*12-2022*".to_string()),
+            },
+            String::from("/borg-mount"),
+        );
+        mount_option.select_paths = vec![Pattern::Shell("**/foobar/*".to_string())];
+        let args = mount_fmt_args(&mount_option, &CommonOptions::default());
+        assert_eq!(
+            "--log-json mount '/my-repo' --first 10 --last 5 --glob-archives archive-name*12-2022* '/borg-mount' --pattern='sh:**/foobar/*'",
+            args
+        );
+    }
+    #[test]
+    fn test_list_fmt_args_format() {
+        let mut list_option = ListOptions::new(Path::new("/my-repo"));
+        list_option.format = Some("{archive} {start} {hostname}".to_string());
+        let args = list_fmt_args(&list_option, &CommonOptions::default());
+        assert_eq!(
+            "--log-json list --json --format '{archive} {start} {hostname}' '/my-repo'",
+            args
+        );
+    }
+    #[test]
+    fn test_create_options_builder() {
+        let built = CreateOptions::builder(
+            Path::new("/my-repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+        )
+        .compression(CompressionMode::Zstd(9))
+        .exclude(Pattern::Shell("**/.cache".to_string()))
+        .numeric_ids(true)
+        .no_acls(true)
+        .build();
+
+        let mut expected = CreateOptions::new(
+            Path::new("/my-repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+            vec![],
+        );
+        expected.compression = Some(CompressionMode::Zstd(9));
+        expected.excludes = vec![Pattern::Shell("**/.cache".to_string())];
+        expected.numeric_ids = true;
+        expected.no_acls = true;
+
+        let common_options = CommonOptions::default();
+        assert_eq!(
+            create_fmt_args(
+                &expected,
+                &common_options,
+                false,
+                &CreateTempFiles::default()
+            )
+            .unwrap(),
+            create_fmt_args(&built, &common_options, false, &CreateTempFiles::default()).unwrap()
+        );
+    }
+    #[test]
+    fn test_compression_validate() {
+        assert!(CompressionMode::Zstd(0).validate().is_err());
+        assert!(CompressionMode::Zstd(1).validate().is_ok());
+        assert!(CompressionMode::Zstd(22).validate().is_ok());
+        assert!(CompressionMode::Zstd(23).validate().is_err());
+        assert!(CompressionMode::Zlib(0).validate().is_ok());
+        assert!(CompressionMode::Zlib(9).validate().is_ok());
+        assert!(CompressionMode::Zlib(10).validate().is_err());
+        assert!(CompressionMode::Lzma(0).validate().is_ok());
+        assert!(CompressionMode::Lzma(9).validate().is_ok());
+        assert!(CompressionMode::Lzma(10).validate().is_err());
+        assert!(CompressionMode::None.validate().is_ok());
+        assert!(CompressionMode::Lz4.validate().is_ok());
+    }
+    #[test]
+    fn test_create_fmt_args_invalid_compression() {
+        let options = CreateOptions::builder(
+            Path::new("/my-repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+        )
+        .compression(CompressionMode::Zstd(23))
+        .build();
+        assert!(matches!(
+            create_fmt_args(
+                &options,
+                &CommonOptions::default(),
+                false,
+                &CreateTempFiles::default()
+            ),
+            Err(CreateError::InvalidCompression(_))
+        ));
+    }
+    #[test]
+    fn test_create_fmt_args_keep_exclude_tags() {
+        let options = CreateOptions::builder(
+            Path::new("/my-repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+        )
+        .exclude_caches(true)
+        .keep_exclude_tags(true)
+        .build();
+        let args = create_fmt_args(
+            &options,
+            &CommonOptions::default(),
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            "--log-json create --json --exclude-caches --keep-exclude-tags '/my-repo'::'archive' /home",
+            args
+        );
+    }
+    #[test]
+    fn test_create_fmt_args_exclude_if_present() {
+        let options = CreateOptions::builder(
+            Path::new("/my-repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+        )
+        .exclude_if_present_name(".nobackup".to_string())
+        .exclude_if_present_name("no backup".to_string())
+        .keep_exclude_tags(true)
+        .build();
+        let args = create_fmt_args(
+            &options,
+            &CommonOptions::default(),
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            "--log-json create --json --exclude-if-present '.nobackup' --exclude-if-present 'no backup' --keep-exclude-tags '/my-repo'::'archive' /home",
+            args
+        );
+    }
+    #[test]
+    fn test_create_fmt_args_exclude_nodump() {
+        let options = CreateOptions::builder(
+            Path::new("/my-repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+        )
+        .exclude_nodump(true)
+        .build();
+        let args = create_fmt_args(
+            &options,
+            &CommonOptions::default(),
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            "--log-json create --json --exclude-nodump '/my-repo'::'archive' /home",
+            args
+        );
+    }
+    #[test]
+    fn test_create_fmt_args_pattern_files() {
+        let options = CreateOptions::builder(
+            Path::new("/my-repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+        )
+        .pattern_file("/etc/borg/system.lst".to_string())
+        .pattern_file("/etc/borg/home.lst".to_string())
+        .exclude_files(vec!["/etc/borg/exclude.lst".to_string()])
+        .build();
+        let args = create_fmt_args(
+            &options,
+            &CommonOptions::default(),
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            "--log-json create --json --patterns-from '/etc/borg/system.lst' --patterns-from '/etc/borg/home.lst' --exclude-from '/etc/borg/exclude.lst' '/my-repo'::'archive' /home",
+            args
+        );
+    }
+    #[test]
+    fn test_create_fmt_args_timestamp() {
+        let options = CreateOptions::builder(
+            Path::new("/my-repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+        )
+        .timestamp(
+            NaiveDate::from_ymd_opt(2022, 3, 4)
+                .unwrap()
+                .and_hms_milli_opt(5, 6, 7, 890)
+                .unwrap(),
+        )
+        .build();
+        let args = create_fmt_args(
+            &options,
+            &CommonOptions::default(),
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            "--log-json create --json --timestamp 2022-03-04T05:06:07 '/my-repo'::'archive' /home",
+            args
+        );
+    }
+    #[test]
+    fn test_create_fmt_args_stdin() {
+        let options = CreateOptions::builder(Path::new("/my-repo"), "archive".to_string(), vec![])
+            .source(CreateSource::Stdin {
+                name: "dump.sql".to_string(),
+                mode: Some(0o640),
+                user: Some("postgres".to_string()),
+                group: None,
+            })
+            .build();
+        let args = create_fmt_args(
+            &options,
+            &CommonOptions::default(),
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            "--log-json create --json --stdin-name 'dump.sql' --stdin-user 'postgres' --stdin-mode 0640 '/my-repo'::'archive' -",
+            args
+        );
+        assert_eq!(
+            shlex::split(&args).unwrap().last().map(String::as_str),
+            Some("-")
+        );
+    }
+    #[test]
+    fn test_create_fmt_args_content_from_command() {
+        let options = CreateOptions::builder(Path::new("/my-repo"), "archive".to_string(), vec![])
+            .source(CreateSource::Command {
+                command: vec!["pg_dump".to_string(), "my db".to_string()],
+                name: "dump.sql".to_string(),
+                mode: None,
+                user: None,
+                group: None,
+            })
+            .build();
+        let args = create_fmt_args(
+            &options,
+            &CommonOptions::default(),
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            "--log-json create --json --content-from-command --stdin-name 'dump.sql' '/my-repo'::'archive' -- 'pg_dump' 'my db'",
+            args
+        );
+    }
+    #[test]
+    fn test_create_fmt_args_chunker_params() {
+        let chunker_params = [
+            (ChunkerParams::Default, "default"),
+            (
+                ChunkerParams::Fixed {
+                    block_size: 4194304,
+                    header_size: None,
+                },
+                "fixed,4194304",
+            ),
+            (
+                ChunkerParams::Fixed {
+                    block_size: 4194304,
+                    header_size: Some(4096),
+                },
+                "fixed,4194304,4096",
+            ),
+            (
+                ChunkerParams::Buzhash {
+                    min_exp: 19,
+                    max_exp: 23,
+                    mask_bits: 21,
+                    window_size: 4095,
+                },
+                "buzhash,19,23,21,4095",
+            ),
+        ];
+
+        for (params, expected) in chunker_params {
+            let options = CreateOptions::builder(
+                Path::new("/my-repo"),
+                "archive".to_string(),
+                vec!["/home".to_string()],
+            )
+            .chunker_params(params)
+            .build();
+            let args = create_fmt_args(
+                &options,
+                &CommonOptions::default(),
+                false,
+                &CreateTempFiles::default(),
+            )
+            .unwrap();
+            assert_eq!(
+                format!("--log-json create --json --chunker-params {expected} '/my-repo'::'archive' /home"),
+                args
+            );
+        }
+    }
+    #[test]
+    fn test_common_options_ratelimits() {
+        let common_options = CommonOptions {
+            upload_ratelimit: Some(0),
+            upload_buffer: Some(16),
+            download_ratelimit: Some(2048),
+            ..CommonOptions::default()
+        };
+        assert_eq!(
+            "--upload-ratelimit 0 --upload-buffer 16 --download-ratelimit 2048",
+            String::from(&common_options)
+        );
+
+        let common_options = CommonOptions {
+            download_ratelimit: Some(2048),
+            ..CommonOptions::default()
+        };
+        assert_eq!("--download-ratelimit 2048", String::from(&common_options));
+    }
+    #[test]
+    fn test_common_options_lock_wait() {
+        let common_options = CommonOptions {
+            lock_wait: Some(600),
+            ..CommonOptions::default()
+        };
+        assert_eq!("--lock-wait 600", String::from(&common_options));
+
+        let mut prune_option = PruneOptions::new(Path::new("/my-repo"));
+        prune_option.keep_daily = NonZeroU16::new(7);
+        let args = prune_fmt_args(&prune_option, &common_options);
+        assert_eq!(
+            "--log-json --lock-wait 600 prune --keep-daily 7 '/my-repo'",
+            args
+        );
+    }
+    #[test]
+    fn test_common_options_log_level() {
+        let levels = [
+            (LogLevel::Critical, "--critical"),
+            (LogLevel::Error, "--error"),
+            (LogLevel::Warning, "--warning"),
+            (LogLevel::Info, "--info"),
+            (LogLevel::Debug, "--debug"),
+        ];
+
+        for (level, expected) in levels {
+            let common_options = CommonOptions {
+                log_level: Some(level),
+                lock_wait: Some(10),
+                ..CommonOptions::default()
+            };
+            assert_eq!(
+                format!("{expected} --lock-wait 10"),
+                String::from(&common_options)
+            );
+        }
+    }
+    #[test]
+    fn test_is_error_exit_code() {
+        assert!(!is_error_exit_code(0));
+        assert!(!is_error_exit_code(1));
+        assert!(is_error_exit_code(2));
+        assert!(is_error_exit_code(30));
+        assert!(!is_error_exit_code(100));
+        assert!(!is_error_exit_code(107));
+        assert!(is_error_exit_code(130));
+    }
+    #[test]
+    fn test_create_parse_output_modern_exit_code() {
+        let create_options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec![],
+            vec![],
+        );
+        let output = |exit_code: i32| Output {
+            status: ExitStatus::from_raw(exit_code << 8),
+            stdout: vec![],
+            stderr: vec![],
+        };
+        assert!(matches!(
+            create_parse_output(output(30), &create_options, &CommonOptions::default()),
+            Err(CreateError::ArchiveAlreadyExists)
+        ));
+        assert!(matches!(
+            create_parse_output(output(52), &create_options, &CommonOptions::default()),
+            Err(CreateError::PassphraseWrong)
+        ));
+        assert!(matches!(
+            create_parse_output(output(70), &create_options, &CommonOptions::default()),
+            Err(CreateError::UnexpectedMessageId(MessageId::LockError))
+        ));
+        assert!(matches!(
+            create_parse_output(output(2), &create_options, &CommonOptions::default()),
+            Err(CreateError::Unknown(_))
+        ));
+    }
+    #[test]
+    fn test_mount_fmt_args_spaces() {
+        let mount_option = MountOptions::new(
+            MountSource::Archive {
+                archive: "/tmp/My Repo::my archive".parse().unwrap(),
+            },
+            String::from("/mnt/My Backups"),
+        );
+        let args = mount_fmt_args(&mount_option, &CommonOptions::default());
+        assert_eq!(
+            shlex::split(&args).unwrap(),
+            vec![
+                "--log-json",
+                "mount",
+                "/tmp/My Repo::my archive",
+                "/mnt/My Backups"
+            ]
+        );
+
+        let mount_option = MountOptions::new(
+            MountSource::Repository {
+                name: Path::new("/tmp/My Repo").into(),
+                first_n_archives: None,
+                last_n_archives: None,
+                glob_archives: None,
+            },
+            String::from("/mnt/My Backups"),
+        );
+        let args = mount_fmt_args(&mount_option, &CommonOptions::default());
+        assert_eq!(
+            shlex::split(&args).unwrap(),
+            vec!["--log-json", "mount", "/tmp/My Repo", "/mnt/My Backups"]
+        );
+    }
+    #[test]
+    fn test_mount_fmt_args_foreground_fuse_options() {
+        let mut mount_option = MountOptions::new(
+            MountSource::Archive {
+                archive: "/tmp/borg-repo::archive".parse().unwrap(),
+            },
+            String::from("/mnt/borg-mount"),
+        );
+        mount_option.foreground = true;
+        mount_option.fuse_options = vec!["allow_other".to_string(), "uid=1000".to_string()];
+        let args = mount_fmt_args(&mount_option, &CommonOptions::default());
+        assert_eq!(
+            "--log-json mount -f -o 'allow_other' -o 'uid=1000' '/tmp/borg-repo'::'archive' '/mnt/borg-mount'",
+            args
+        );
+
+        mount_option.foreground = false;
+        mount_option.fuse_options = vec!["versions".to_string()];
+        let args = mount_fmt_args(&mount_option, &CommonOptions::default());
+        assert_eq!(
+            "--log-json mount -o 'versions' '/tmp/borg-repo'::'archive' '/mnt/borg-mount'",
+            args
+        );
+    }
+    #[test]
+    fn test_mount_parse_output_not_mounted() {
+        let output = |stderr: &str| Output {
+            status: ExitStatus::from_raw(1 << 8),
+            stdout: vec![],
+            stderr: stderr.as_bytes().to_vec(),
+        };
+
+        let res = mount_parse_output(
+            output("fusermount: entry for /mnt/My Backups not found in /etc/mtab\n"),
+            &CommonOptions::default(),
+        );
+        assert!(matches!(res, Err(MountError::NotMounted(x)) if x == "/mnt/My Backups"));
+
+        let res = mount_parse_output(
+            output("fusermount: failed to unmount /mnt/borg: Device or resource busy\n"),
+            &CommonOptions::default(),
+        );
+        assert!(matches!(res, Err(MountError::UMountError(_))));
+    }
+
+    #[test]
+    fn test_create_parse_output_error_context() {
+        let create_options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec![],
+            vec![],
+        );
+        let stderr: String = (0..1000)
+            .map(|i| {
+                format!(
+                    r#"{{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg", "message": "line {i}"}}"#
+                ) + "\n"
+            })
+            .collect();
+        let output = Output {
+            status: ExitStatus::from_raw(2 << 8),
+            stdout: vec![],
+            stderr: stderr.into_bytes(),
+        };
+        let common_options = CommonOptions {
+            max_error_context: Some(256),
+            ..CommonOptions::default()
+        };
+
+        let Err(CreateError::Unknown(context)) =
+            create_parse_output(output, &create_options, &common_options)
+        else {
+            panic!("Expected CreateError::Unknown");
+        };
+        assert!(context.len() <= 256);
+        assert!(context.ends_with("\"message\": \"line 999\"}\n"));
+    }
+
+    #[test]
+    fn test_create_dry_run() {
+        let options = CreateOptions::builder(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+        )
+        .dry_run(true)
+        .build();
+        let args = create_fmt_args(
+            &options,
+            &CommonOptions::default(),
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            "--log-json create --json --dry-run '/tmp/repo'::'archive' /home"
+        );
+
+        let output = Output {
+            status: ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        };
+        assert!(matches!(
+            create_parse_output(output, &options, &CommonOptions::default()),
+            Ok(None)
+        ));
+    }
+
+    #[test]
+    fn test_prune_list() {
+        let mut options = PruneOptions::new(Path::new("/tmp/repo"));
+        options.list = true;
+        options.keep_daily = NonZeroU16::new(1);
+        let args = prune_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(args, "--log-json prune --list --keep-daily 1 '/tmp/repo'");
+
+        let stderr = [
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.list", "message": "Keeping archive (rule: daily #1):        host-2023-01-02                      Mon, 2023-01-02 10:00:00 [0b1e1ac2a3fbe6f3]"}"#,
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.list", "message": "Pruning archive (1/1):                   host-2023-01-01                      Sun, 2023-01-01 10:00:00 [3a2cde5e7b0b7d61]"}"#,
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.repository", "message": "Pruning archive: not from the list logger"}"#,
+        ]
+        .join("\n");
+        let output = Output {
+            status: ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: stderr.into_bytes(),
+        };
+
+        let report = prune_parse_output(output, &CommonOptions::default()).unwrap();
+        assert_eq!(
+            report,
+            PruneReport {
+                kept: vec!["host-2023-01-02".to_string()],
+                pruned: vec!["host-2023-01-01".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_prune_dry_run() {
+        let mut options = PruneOptions::new(Path::new("/tmp/repo"));
+        options.dry_run = true;
+        options.list = true;
+        options.keep_daily = NonZeroU16::new(1);
+        let args = prune_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(
+            args,
+            "--log-json prune --dry-run --list --keep-daily 1 '/tmp/repo'"
+        );
+
+        let stderr = [
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.list", "message": "Keeping archive (rule: daily #1):        host-2023-01-02                      Mon, 2023-01-02 10:00:00 [0b1e1ac2a3fbe6f3]"}"#,
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.list", "message": "Would prune:                             host-2023-01-01                      Sun, 2023-01-01 10:00:00 [3a2cde5e7b0b7d61]"}"#,
+        ]
+        .join("\n");
+        let output = Output {
+            status: ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: stderr.into_bytes(),
+        };
+
+        let report = prune_parse_output(output, &CommonOptions::default()).unwrap();
+        assert_eq!(
+            report,
+            PruneReport {
+                kept: vec!["host-2023-01-02".to_string()],
+                pruned: vec!["host-2023-01-01".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_version_parse_output() {
+        let output = |stdout: &str| Output {
+            status: ExitStatus::from_raw(0),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: vec![],
+        };
+
+        let version = version_parse_output(output("borg 1.2.4\n")).unwrap();
+        assert_eq!(version, BorgVersion::new(1, 2, 4));
+        assert!(version.supports_compact());
+        assert!(version.supports_noflags());
+        assert!(!version.supports_modern_exit_codes());
+
+        let version = version_parse_output(output("borg 1.1.18\n")).unwrap();
+        assert_eq!(version, BorgVersion::new(1, 1, 18));
+        assert!(!version.supports_noflags());
+
+        assert_eq!(
+            version_parse_output(output("borg 2.0.0b5\n")).unwrap(),
+            BorgVersion::new(2, 0, 0)
+        );
+        assert_eq!(
+            version_parse_output(output("borg 1.2.1.dev42+g2f5e3d1\n")).unwrap(),
+            BorgVersion::new(1, 2, 1)
+        );
+        assert!(BorgVersion::new(1, 10, 0) > BorgVersion::new(1, 4, 2));
+        assert!(version_parse_output(output("borg\n")).is_err());
+        assert!(version_parse_output(output("borg 1.x\n")).is_err());
+    }
+
+    #[test]
+    fn test_create_no_flags_borg_version() {
+        let options = CreateOptions::builder(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+        )
+        .no_flags(true)
+        .build();
+
+        let args = create_fmt_args(
+            &options,
+            &CommonOptions::default(),
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            "--log-json create --json --noflags '/tmp/repo'::'archive' /home"
+        );
+
+        let common_options = CommonOptions {
+            borg_version: Some(BorgVersion::new(1, 2, 0)),
+            ..CommonOptions::default()
+        };
+        let args = create_fmt_args(
+            &options,
+            &common_options,
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            "--log-json create --json --noflags '/tmp/repo'::'archive' /home"
+        );
+
+        let common_options = CommonOptions {
+            borg_version: Some(BorgVersion::new(1, 1, 18)),
+            ..CommonOptions::default()
+        };
+        let args = create_fmt_args(
+            &options,
+            &common_options,
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            "--log-json create --json --nobsdflags '/tmp/repo'::'archive' /home"
+        );
+    }
+
+    #[test]
+    fn test_create_files_cache() {
+        let options = CreateOptions::builder(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+        )
+        .files_cache(FilesCacheMode::MtimeSize)
+        .build();
+        let args = create_fmt_args(
+            &options,
+            &CommonOptions::default(),
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            "--log-json create --json --files-cache=mtime,size '/tmp/repo'::'archive' /home"
+        );
+
+        assert_eq!(
+            "size,inode,ctime".parse::<FilesCacheMode>().unwrap(),
+            FilesCacheMode::CtimeSizeInode
+        );
+        assert_eq!(
+            "rechunk,mtime".parse::<FilesCacheMode>().unwrap(),
+            FilesCacheMode::RechunkMtime
+        );
+        assert_eq!(
+            "disabled".parse::<FilesCacheMode>().unwrap(),
+            FilesCacheMode::Disabled
+        );
+        assert!("ctime,mtime,size".parse::<FilesCacheMode>().is_err());
+        assert!("disabled,size".parse::<FilesCacheMode>().is_err());
+        assert!("inode".parse::<FilesCacheMode>().is_err());
+    }
+
+    #[test]
+    fn test_create_atime_nobirthtime() {
+        let options = CreateOptions::builder(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+        )
+        .atime(true)
+        .nobirthtime(true)
+        .build();
+        let args = create_fmt_args(
+            &options,
+            &CommonOptions::default(),
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            "--log-json create --json --atime --nobirthtime '/tmp/repo'::'archive' /home"
+        );
+    }
+
+    #[test]
+    fn test_common_options_umask() {
+        let common_options = CommonOptions {
+            umask: Some(0o077),
+            ..CommonOptions::default()
+        };
+        assert_eq!("--umask 0077", String::from(&common_options));
+
+        let common_options = CommonOptions {
+            umask: Some(0o002),
+            ..CommonOptions::default()
+        };
+        assert_eq!("--umask 0002", String::from(&common_options));
+    }
+
+    #[test]
+    fn test_transfer_fmt_args() {
+        let mut options = TransferOptions::new(Path::new("/tmp/old repo"), Path::new("/tmp/new"));
+        let args = transfer_fmt_args(&options, &CommonOptions::default(), false);
+        assert_eq!(
+            args,
+            "--log-json --repo '/tmp/new' transfer --other-repo '/tmp/old repo'"
+        );
+
+        options.dry_run = true;
+        options.glob_archives = Some("host-*".to_string());
+        let args = transfer_fmt_args(&options, &CommonOptions::default(), true);
+        assert_eq!(
+            args,
+            "--log-json --progress --repo '/tmp/new' transfer --dry-run --other-repo '/tmp/old repo' --match-archives 'sh:host-*'"
+        );
+    }
+
+    #[test]
+    fn test_transfer_check_version() {
+        assert!(transfer_check_version(&CommonOptions::default()).is_ok());
+
+        let common_options = CommonOptions {
+            borg_version: Some(BorgVersion::new(1, 2, 8)),
+            ..CommonOptions::default()
+        };
+        let res = transfer_check_version(&common_options);
+        assert!(
+            matches!(res, Err(TransferError::TransferNotSupported(_))),
+            "{res:?}"
+        );
+
+        let common_options = CommonOptions {
+            borg_version: Some(BorgVersion::new(2, 0, 0)),
+            ..CommonOptions::default()
+        };
+        assert!(transfer_check_version(&common_options).is_ok());
+    }
+
+    #[test]
+    fn test_extract_fmt_args() {
+        let mut options = ExtractOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            "/tmp/restore".to_string(),
+        );
+        let args = extract_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(args, "--log-json extract '/tmp/repo'::'archive'");
+
+        options.paths = vec!["home/user/My Documents".to_string(), "etc".to_string()];
+        options.patterns = vec![
+            PatternInstruction::Exclude(Pattern::Shell("**/*.tmp".to_string())),
+            PatternInstruction::Include(Pattern::PathPrefix("home/user".to_string())),
+        ];
+        let args = extract_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(
+            args,
+            "--log-json extract --pattern='- sh:**/*.tmp' --pattern='+ pp:home/user' '/tmp/repo'::'archive' 'home/user/My Documents' 'etc'"
+        );
+    }
+
+    #[test]
+    fn test_extract_strip_components_numeric_ids() {
+        let mut options = ExtractOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            "/tmp/restore".to_string(),
+        );
+        options.strip_components = Some(2);
+        let args = extract_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(
+            args,
+            "--log-json extract --strip-components 2 '/tmp/repo'::'archive'"
+        );
+
+        options.numeric_ids = true;
+        options.paths = vec!["home/user".to_string()];
+        let args = extract_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(
+            args,
+            "--log-json extract --numeric-ids --strip-components 2 '/tmp/repo'::'archive' 'home/user'"
+        );
+    }
+
+    #[test]
+    fn test_extract_sparse() {
+        let mut options = ExtractOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            "/tmp/restore".to_string(),
+        );
+        options.sparse = true;
+        let args = extract_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(args, "--log-json extract --sparse '/tmp/repo'::'archive'");
+    }
+
+    #[test]
+    fn test_list_stream_fmt_args() {
+        let options = ListOptions::new(Path::new("/tmp/repo"));
+        let args = list_stream_fmt_args(&options, "my archive", &CommonOptions::default());
+        assert_eq!(
+            args,
+            "--log-json list --json-lines '/tmp/repo'::'my archive'"
+        );
+    }
+
+    #[test]
+    fn test_list_filter_fmt_args() {
+        let mut options = ListOptions::new(Path::new("/tmp/repo"));
+        options.last_n = NonZeroU16::new(5);
+        options.sort_by = Some("timestamp".to_string());
+        let args = list_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(
+            args,
+            "--log-json list --json --last 5 --sort-by 'timestamp' '/tmp/repo'"
+        );
+
+        let mut options = ListOptions::new(Path::new("/tmp/repo"));
+        options.first_n = NonZeroU16::new(1);
+        options.glob_archives = Some("host-*".to_string());
+        let args = list_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(
+            args,
+            "--log-json list --json --first 1 --glob-archives 'host-*' '/tmp/repo'"
+        );
+    }
+
+    #[test]
+    fn test_info_fmt_args() {
+        let mut options = InfoOptions::new(Path::new("/tmp/repo"));
+        let args = info_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(args, "--log-json info --json '/tmp/repo'");
+
+        options.last_n = NonZeroU16::new(2);
+        options.glob_archives = Some("host-*".to_string());
+        let args = info_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(
+            args,
+            "--log-json info --json --last 2 --glob-archives 'host-*' '/tmp/repo'"
+        );
+
+        let mut options = InfoOptions::new(Path::new("/tmp/repo"));
+        options.archive = Some("my archive".to_string());
+        let args = info_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(args, "--log-json info --json '/tmp/repo'::'my archive'");
+    }
+
+    #[test]
+    fn test_info_parse_output() {
+        let repository = r#""repository": {"id": "e2ffc4d6e1d5e4a5e0a1e2b8c7d9f0a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6e7", "last_modified": "2023-01-02T10:00:00.000000", "location": "/tmp/repo"}, "encryption": {"mode": "repokey"}"#;
+        let output = |stdout: String| Output {
+            status: ExitStatus::from_raw(0),
+            stdout: stdout.into_bytes(),
+            stderr: vec![],
+        };
+
+        let options = InfoOptions::new(Path::new("/tmp/repo"));
+        let info = info_parse_output(
+            output(format!(
+                r#"{{{repository}, "security_dir": "/root/.config/borg/security/e2ff"}}"#
+            )),
+            &options,
+            &CommonOptions::default(),
+        )
+        .unwrap();
+        assert!(matches!(
+            info,
+            Info::Repository {
+                security_dir: Some(_),
+                ..
+            }
+        ));
+
+        let mut options = InfoOptions::new(Path::new("/tmp/repo"));
+        options.last_n = NonZeroU16::new(1);
+        let info = info_parse_output(
+            output(format!(r#"{{{repository}, "archives": []}}"#)),
+            &options,
+            &CommonOptions::default(),
+        )
+        .unwrap();
+        assert!(matches!(info, Info::Archives { archives, .. } if archives.is_empty()));
+    }
+
+    #[test]
+    fn test_common_options_rsh() {
+        // Split the arguments like this crate and then the value of --rsh like borg does
+        let borg_rsh = |rsh: Rsh| {
+            let common_options = CommonOptions {
+                rsh: Some(rsh),
+                ..CommonOptions::default()
+            };
+            let args = shlex::split(&String::from(&common_options)).unwrap();
+            assert_eq!(args.len(), 2);
+            assert_eq!(args[0], "--rsh");
+            shlex::split(&args[1]).unwrap()
+        };
+
+        assert_eq!(
+            borg_rsh("ssh -i /path/key -o StrictHostKeyChecking=no".into()),
+            ["ssh", "-i", "/path/key", "-o", "StrictHostKeyChecking=no"]
+        );
+        assert_eq!(
+            borg_rsh("ssh -i '/path/my key'".into()),
+            ["ssh", "-i", "/path/my key"]
+        );
+        assert_eq!(
+            borg_rsh(
+                vec![
+                    "ssh".to_string(),
+                    "-i".to_string(),
+                    "/path/it's my key".to_string(),
+                    "-o".to_string(),
+                    "StrictHostKeyChecking=no".to_string(),
+                ]
+                .into()
+            ),
+            [
+                "ssh",
+                "-i",
+                "/path/it's my key",
+                "-o",
+                "StrictHostKeyChecking=no"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rsh_deserialize() {
+        let rsh: Rsh = serde_json::from_str(r#""ssh -i /path/key""#).unwrap();
+        assert_eq!(rsh, Rsh::Command("ssh -i /path/key".to_string()));
+        let rsh: Rsh = serde_json::from_str(r#"["ssh", "-i", "/path/key"]"#).unwrap();
+        assert_eq!(
+            rsh,
+            Rsh::Args(vec![
+                "ssh".to_string(),
+                "-i".to_string(),
+                "/path/key".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_common_options_ssh_files() {
+        let mut common_options = CommonOptions {
+            ssh_identity_file: Some("/root/.ssh/id_borg".into()),
+            ..CommonOptions::default()
+        };
+        assert_eq!(
+            String::from(&common_options),
+            "--rsh 'ssh -i /root/.ssh/id_borg'"
+        );
+
+        common_options.ssh_known_hosts_file = Some("/etc/borg/known hosts".into());
+        let args = shlex::split(&String::from(&common_options)).unwrap();
+        assert_eq!(
+            shlex::split(&args[1]).unwrap(),
+            [
+                "ssh",
+                "-i",
+                "/root/.ssh/id_borg",
+                "-o",
+                "UserKnownHostsFile=/etc/borg/known hosts"
+            ]
+        );
+
+        let common_options = CommonOptions {
+            ssh_known_hosts_file: Some("/etc/borg/known_hosts".into()),
+            ..CommonOptions::default()
+        };
+        let args = shlex::split(&String::from(&common_options)).unwrap();
+        assert_eq!(
+            shlex::split(&args[1]).unwrap(),
+            ["ssh", "-o", "UserKnownHostsFile=/etc/borg/known_hosts"]
+        );
+
+        // An explicit rsh takes precedence
+        let common_options = CommonOptions {
+            rsh: Some("ssh -p 2222".into()),
+            ssh_identity_file: Some("/root/.ssh/id_borg".into()),
+            ..CommonOptions::default()
+        };
+        assert_eq!(String::from(&common_options), "--rsh 'ssh -p 2222'");
+    }
+
+    #[test]
+    fn test_repository_parse() {
+        let repo: Repository = "/tmp/foo".parse().unwrap();
+        assert!(!repo.is_remote());
+        assert_eq!(repo.path(), "/tmp/foo");
+        assert_eq!(repo.host(), None);
+
+        let repo: Repository = "relative/repo:1".parse().unwrap();
+        assert!(!repo.is_remote());
+        assert_eq!(repo.path(), "relative/repo:1");
+
+        let repo: Repository = "user@[::1]:repo".parse().unwrap();
+        assert_eq!(repo.user(), Some("user"));
+        assert_eq!(repo.host(), Some("::1"));
+        assert_eq!(repo.path(), "repo");
+
+        let repo: Repository = "user@example.com:/opt/repo".parse().unwrap();
+        assert!(repo.is_remote());
+        assert_eq!(repo.user(), Some("user"));
+        assert_eq!(repo.host(), Some("example.com"));
+        assert_eq!(repo.port(), None);
+        assert_eq!(repo.path(), "/opt/repo");
+
+        let repo: Repository = "example.com:repo".parse().unwrap();
+        assert_eq!(repo.user(), None);
+        assert_eq!(repo.host(), Some("example.com"));
+        assert_eq!(repo.path(), "repo");
+
+        let repo: Repository = "ssh://user@example.com:2323/opt/repo".parse().unwrap();
+        assert!(repo.is_remote());
+        assert_eq!(repo.user(), Some("user"));
+        assert_eq!(repo.host(), Some("example.com"));
+        assert_eq!(repo.port(), Some(2323));
+        assert_eq!(repo.path(), "/opt/repo");
+        assert_eq!(repo.to_string(), "ssh://user@example.com:2323/opt/repo");
+
+        let repo: Repository = "ssh://[::1]:22/./repo".parse().unwrap();
+        assert_eq!(repo.host(), Some("::1"));
+        assert_eq!(repo.port(), Some(22));
+        assert_eq!(repo.path(), "/./repo");
+
+        let repo = Repository::try_from("file:///tmp/foo").unwrap();
+        assert!(!repo.is_remote());
+        assert_eq!(repo.path(), "/tmp/foo");
+    }
+
+    #[test]
+    fn test_repository_parse_invalid() {
+        for repo in [
+            "",
+            "/tmp/foo::archive",
+            "repo::archive",
+            "user@example.com:/opt/repo::archive",
+            "ssh://example.com/repo::archive",
+            "ssh://example.com",
+            "ssh://example.com/",
+            "ssh://example.com:port/repo",
+            "ssh://example.com:99999/repo",
+            "ssh://@example.com/repo",
+            "ssh://[::1/repo",
+            "user@example.com:",
+            ":/opt/repo",
+        ] {
+            assert!(repo.parse::<Repository>().is_err(), "{repo}");
+        }
+    }
+
+    #[test]
+    fn test_repository_serde() {
+        let repo: Repository = serde_json::from_str(r#""user@example.com:/opt/repo""#).unwrap();
+        assert_eq!(repo.host(), Some("example.com"));
+        assert_eq!(
+            serde_json::to_string(&repo).unwrap(),
+            r#""user@example.com:/opt/repo""#
+        );
+        assert!(serde_json::from_str::<Repository>(r#""ssh://example.com""#).is_err());
+    }
+
+    #[test]
+    fn test_archive_ref() {
+        let archive: ArchiveRef = "user@example.com:/opt/repo::my archive".parse().unwrap();
+        assert_eq!(archive.repository().host(), Some("example.com"));
+        assert_eq!(archive.repository().path(), "/opt/repo");
+        assert_eq!(archive.archive(), "my archive");
+        assert_eq!(
+            archive.to_string(),
+            "user@example.com:/opt/repo::my archive"
+        );
+        assert_eq!(
+            archive.fmt_arg(),
+            "'user@example.com:/opt/repo'::'my archive'"
+        );
+
+        let archive = ArchiveRef::new(Path::new("/tmp/repo"), "{now}".to_string());
+        assert_eq!(archive.to_string(), "/tmp/repo::{now}");
+        assert_eq!(
+            serde_json::to_string(&archive).unwrap(),
+            r#""/tmp/repo::{now}""#
+        );
+        let deserialized: ArchiveRef = serde_json::from_str(r#""/tmp/repo::{now}""#).unwrap();
+        assert_eq!(deserialized, archive);
+
+        for archive in [
+            "/tmp/repo",
+            "/tmp/repo::",
+            "::archive",
+            "ssh://host::archive",
+        ] {
+            assert!(archive.parse::<ArchiveRef>().is_err(), "{archive}");
+        }
+    }
+
+    #[test]
+    fn test_log_callback() {
+        let create_options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec![],
+            vec![],
+        );
+        let output = Output {
+            status: ExitStatus::from_raw(2 << 8),
+            stdout: vec![],
+            stderr: br#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg", "message": "working"}
+{"type": "log_message", "time": 2.0, "levelname": "ERROR", "name": "borg", "message": "exists", "msgid": "Archive.AlreadyExists"}
+"#
+            .to_vec(),
+        };
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let common_options = CommonOptions {
+            log_callback: Some(Arc::new({
+                let messages = messages.clone();
+                move |message: &LoggingMessage| messages.lock().unwrap().push(message.clone())
+            })),
+            ..CommonOptions::default()
+        };
+        assert!(format!("{common_options:?}").contains("log_callback"));
+
+        let res = create_parse_output(output, &create_options, &common_options);
+        assert!(matches!(res, Err(CreateError::ArchiveAlreadyExists)));
+
+        let messages = messages.lock().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(
+            &messages[1],
+            LoggingMessage::LogMessage {
+                time,
+                msg_id: Some(MessageId::ArchiveAlreadyExists),
+                ..
+            } if *time == 2.0
+        ));
+    }
+
+    #[test]
+    fn test_pattern_list() {
+        let patterns = PatternList::new()
+            .root("/home".to_string())
+            .exclude_no_recurse(Pattern::Shell("**/.cache".to_string()))
+            .include(Pattern::PathPrefix("/home/user/pics/good".to_string()))
+            .exclude(Pattern::PathPrefix("/home/user/pics".to_string()))
+            .exclude(Pattern::PathPrefix("/home/user/pictures".to_string()))
+            .build()
+            .unwrap();
+        assert_eq!(
+            patterns.iter().map(|x| x.to_string()).collect::<Vec<_>>(),
+            [
+                "P /home",
+                "! sh:**/.cache",
+                "+ pp:/home/user/pics/good",
+                "- pp:/home/user/pics",
+                "- pp:/home/user/pictures",
+            ]
+        );
+
+        let res = PatternList::new()
+            .exclude(Pattern::Shell("*.tmp".to_string()))
+            .root("/home".to_string())
+            .build();
+        assert!(matches!(res, Err(PatternListError::MissingRoot(rule)) if rule == "- sh:*.tmp"));
+
+        let res = PatternList::new()
+            .root("/home".to_string())
+            .exclude(Pattern::PathPrefix("/home/user/pics/".to_string()))
+            .include(Pattern::PathPrefix("/home/user/pics/good".to_string()))
+            .build();
+        assert!(matches!(
+            res,
+            Err(PatternListError::ShadowedRule { rule, shadowed_by })
+                if rule == "+ pp:/home/user/pics/good" && shadowed_by == "- pp:/home/user/pics/"
+        ));
+
+        let res = PatternList::new()
+            .root("/home".to_string())
+            .exclude(Pattern::Shell("**/*.tmp".to_string()))
+            .exclude_no_recurse(Pattern::Shell("**/*.tmp".to_string()))
+            .build();
+        assert!(matches!(res, Err(PatternListError::ShadowedRule { .. })));
+
+        // Full path matches don't depend on the order
+        PatternList::new()
+            .root("/home".to_string())
+            .exclude(Pattern::PathPrefix("/home/user".to_string()))
+            .include(Pattern::PathFullMatch("/home/user/.bashrc".to_string()))
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_create_fmt_args_paths_from_file() {
+        let paths: Vec<String> = (0..10000).map(|i| format!("/home/user/file-{i}")).collect();
+        let options =
+            CreateOptions::new(Path::new("/my-repo"), "archive".to_string(), paths, vec![]);
+        let temp_files = CreateTempFiles::new(&options).unwrap();
+        let args =
+            create_fmt_args(&options, &CommonOptions::default(), false, &temp_files).unwrap();
+        assert_eq!(
+            "--log-json create --json --paths-from-stdin '/my-repo'::'archive'",
+            args
+        );
+        let content = std::fs::read_to_string(temp_files.paths.unwrap().path()).unwrap();
+        assert_eq!(content.lines().count(), 10000);
+        assert_eq!(content.lines().next(), Some("/home/user/file-0"));
+
+        // Short lists of paths are passed on the command line, unless requested otherwise
+        let mut options = CreateOptions::new(
+            Path::new("/my-repo"),
+            "archive".to_string(),
+            vec!["/home\n/etc".to_string()],
+            vec![],
+        );
+        assert!(CreateTempFiles::new(&options).unwrap().paths.is_none());
+        options.paths_from_file = true;
+        assert!(matches!(
+            CreateTempFiles::new(&options),
+            Err(CreateError::InvalidPath(_))
+        ));
+
+        let err = CreateError::from(std::io::Error::from(
+            std::io::ErrorKind::ArgumentListTooLong,
+        ));
+        assert!(matches!(err, CreateError::ArgumentListTooLong));
+    }
+
+    #[test]
+    fn test_config_fmt_args() {
+        let options = ConfigOptions::get(Path::new("/tmp/repo"), "append_only".to_string());
+        let args = config_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(args, "--log-json config '/tmp/repo' 'append_only'");
+
+        let options = ConfigOptions::set(
+            Path::new("/tmp/repo"),
+            "append_only".to_string(),
+            "1".to_string(),
+        );
+        let args = config_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(args, "--log-json config '/tmp/repo' 'append_only' '1'");
+    }
+
+    #[test]
+    fn test_config_parse_output() {
+        let output = |code: i32, stdout: &str, stderr: &str| Output {
+            status: ExitStatus::from_raw(code << 8),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        };
+        let get = ConfigOptions::get(Path::new("/tmp/repo"), "append_only".to_string());
+        let common_options = CommonOptions::default();
+
+        let value = config_parse_output(output(0, "0\n", ""), &get, &common_options).unwrap();
+        assert_eq!(value.as_deref(), Some("0"));
+
+        let res = config_parse_output(
+            output(1, "", "No option 'append_only' in section: 'repository'\n"),
+            &get,
+            &common_options,
+        );
+        assert!(matches!(res, Err(ConfigError::KeyNotFound(key)) if key == "append_only"));
+
+        let res = config_parse_output(
+            output(
+                2,
+                "",
+                r#"{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "not allowed", "msgid": "PathNotAllowed"}"#,
+            ),
+            &get,
+            &common_options,
+        );
+        assert!(matches!(res, Err(ConfigError::PathNotAllowed)));
+
+        let set = ConfigOptions::set(
+            Path::new("/tmp/repo"),
+            "append_only".to_string(),
+            "1".to_string(),
+        );
+        let value = config_parse_output(output(0, "", ""), &set, &common_options).unwrap();
+        assert!(value.is_none());
+    }
+
+    #[test]
+    fn test_benchmark_crud_fmt_args() {
+        let options = BenchmarkOptions::new(Path::new("/tmp/repo"), "/tmp/bench dir".to_string());
+        let args = benchmark_crud_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(
+            args,
+            "--log-json benchmark crud '/tmp/repo' '/tmp/bench dir'"
+        );
+    }
+
+    #[test]
+    fn test_benchmark_crud_parse_output() {
+        let output = |code: i32, stdout: &str, stderr: &str| Output {
+            status: ExitStatus::from_raw(code << 8),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        };
+        let common_options = CommonOptions::default();
+
+        let stdout = "\
+C-Z-BIG         116.06 MB/s (10 * 100.00 MB all-zero files: 8.62s)
+R-Z-BIG         197.00 MB/s (10 * 100.00 MB all-zero files: 5.08s)
+U-Z-BIG         418.07 MB/s (10 * 100.00 MB all-zero files: 2.39s)
+D-Z-BIG         724.94 MB/s (10 * 100.00 MB all-zero files: 1.38s)
+C-R-BIG          42.21 MB/s (10 * 100.00 MB random files: 23.69s)
+R-R-BIG         134.45 MB/s (10 * 100.00 MB random files: 7.44s)
+U-R-BIG         316.83 MB/s (10 * 100.00 MB random files: 3.16s)
+D-R-BIG         251.10 MB/s (10 * 100.00 MB random files: 3.98s)
+C-Z-MEDIUM      118.53 MB/s (1000 * 1.00 MB all-zero files: 8.44s)
+R-Z-MEDIUM      218.49 MB/s (1000 * 1.00 MB all-zero files: 4.58s)
+U-Z-MEDIUM      591.59 MB/s (1000 * 1.00 MB all-zero files: 1.69s)
+D-Z-MEDIUM      730.04 MB/s (1000 * 1.00 MB all-zero files: 1.37s)
+C-R-MEDIUM       31.46 MB/s (1000 * 1.00 MB random files: 31.79s)
+R-R-MEDIUM      129.64 MB/s (1000 * 1.00 MB random files: 7.71s)
+U-R-MEDIUM      621.86 MB/s (1000 * 1.00 MB random files: 1.61s)
+D-R-MEDIUM      234.82 MB/s (1000 * 1.00 MB random files: 4.26s)
+C-Z-SMALL        19.81 MB/s (10000 * 10.00 kB all-zero files: 5.05s)
+R-Z-SMALL        97.69 MB/s (10000 * 10.00 kB all-zero files: 1.02s)
+U-Z-SMALL        36.35 MB/s (10000 * 10.00 kB all-zero files: 2.75s)
+D-Z-SMALL        57.04 MB/s (10000 * 10.00 kB all-zero files: 1.75s)
+C-R-SMALL         9.81 MB/s (10000 * 10.00 kB random files: 10.19s)
+R-R-SMALL        92.21 MB/s (10000 * 10.00 kB random files: 1.08s)
+U-R-SMALL        36.87 MB/s (10000 * 10.00 kB random files: 2.71s)
+D-R-SMALL        57.20 MB/s (10000 * 10.00 kB random files: 1.75s)
+";
+
+        let result = benchmark_crud_parse_output(output(0, stdout, ""), &common_options).unwrap();
+        assert_eq!(result.measurements.len(), 24);
+
+        let create = result.get(BenchmarkOperation::Create, "R-BIG").unwrap();
+        assert!(create.random);
+        assert_eq!(create.throughput, 42.21);
+        assert_eq!(create.file_count, 10);
+        assert_eq!(create.file_size, "100.00 MB");
+        assert_eq!(create.duration, 23.69);
+
+        let delete = result.get(BenchmarkOperation::Delete, "Z-SMALL").unwrap();
+        assert!(!delete.random);
+        assert_eq!(delete.file_count, 10000);
+        assert_eq!(delete.file_size, "10.00 kB");
+
+        let res = benchmark_crud_parse_output(output(0, "C-Z-BIG garbage\n", ""), &common_options);
+        assert!(
+            matches!(res, Err(BenchmarkError::InvalidBenchmarkOutput(line)) if line == "C-Z-BIG garbage")
+        );
+
+        let res = benchmark_crud_parse_output(
+            output(
+                2,
+                "",
+                r#"{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "does not exist", "msgid": "Repository.DoesNotExist"}"#,
+            ),
+            &common_options,
+        );
+        assert!(matches!(res, Err(BenchmarkError::RepositoryDoesNotExist)));
+    }
+
+    #[test]
+    fn test_common_options_append_only_remote() {
+        let common_options = CommonOptions {
+            remote_path: Some("/usr/local/bin/borg".to_string()),
+            ..CommonOptions::default()
+        };
+        assert_eq!(
+            String::from(&common_options),
+            "--remote-path /usr/local/bin/borg"
+        );
+
+        // Split the arguments like this crate, then emulate borg joining the remote command
+        // and the remote shell splitting it again
+        let remote_command = |common_options: &CommonOptions| {
+            let args = shlex::split(&String::from(common_options)).unwrap();
+            assert_eq!(args[0], "--remote-path");
+            shlex::split(&format!("{} serve --umask=077", args[1])).unwrap()
+        };
+
+        let common_options = CommonOptions {
+            append_only_remote: true,
+            ..CommonOptions::default()
+        };
+        assert_eq!(
+            remote_command(&common_options),
+            [
+                "sh",
+                "-c",
+                r#"exec "$0" "$@" --append-only"#,
+                "borg",
+                "serve",
+                "--umask=077"
+            ]
+        );
+
+        let common_options = CommonOptions {
+            remote_path: Some("/usr/local/bin/borg".to_string()),
+            append_only_remote: true,
+            ..CommonOptions::default()
+        };
+        assert_eq!(remote_command(&common_options)[3], "/usr/local/bin/borg");
+    }
+
+    #[test]
+    fn test_serve_fmt_args() {
+        let args = serve_fmt_args(&ServeOptions::default(), &CommonOptions::default());
+        assert_eq!(args, "serve");
+
+        let options = ServeOptions {
+            restrict_to_paths: vec!["/backups/a".to_string(), "/backups/b c".to_string()],
+            restrict_to_repositories: vec!["/backups/repo".to_string()],
+            append_only: true,
+            storage_quota: Some("5G".to_string()),
+        };
+        let args = serve_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(
+            shlex::split(&args).unwrap(),
+            [
+                "serve",
+                "--restrict-to-path",
+                "/backups/a",
+                "--restrict-to-path",
+                "/backups/b c",
+                "--restrict-to-repository",
+                "/backups/repo",
+                "--append-only",
+                "--storage-quota",
+                "5G"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_output_unknown_message_id() {
+        let stderr = r#"{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "something new", "msgid": "Repository.SomethingNew"}"#;
+        let output = Output {
+            status: ExitStatus::from_raw(2 << 8),
+            stdout: vec![],
+            stderr: stderr.as_bytes().to_vec(),
+        };
+
+        // An unknown message id doesn't abort parsing, the exit code decides
+        let res = prune_parse_output(output, &CommonOptions::default());
+        assert!(
+            matches!(&res, Err(PruneError::Unknown(output)) if output.contains("something new")),
+            "{res:?}"
+        );
+    }
+
+    #[test]
+    fn test_list_consider_checkpoints() {
+        let mut options = ListOptions::new(Path::new("/tmp/repo"));
+        let args = list_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(args, "--log-json list --json '/tmp/repo'");
+
+        options.consider_checkpoints = true;
+        let args = list_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(
+            args,
+            "--log-json list --json --consider-checkpoints '/tmp/repo'"
+        );
+        let common_options = CommonOptions {
+            borg_version: Some(BorgVersion::new(1, 1, 18)),
+            ..CommonOptions::default()
+        };
+        let args = list_fmt_args(&options, &common_options);
+        assert_eq!(args, "--log-json list --json '/tmp/repo'");
+
+        let stdout = r#"{
+    "archives": [
+        {"archive": "host-2023-01-01", "name": "host-2023-01-01", "id": "3a2c", "start": "2023-01-01T10:00:00.000000", "time": "2023-01-01T10:00:00.000000"},
+        {"archive": "host-2023-01-02.checkpoint", "name": "host-2023-01-02.checkpoint", "id": "0b1e", "start": "2023-01-02T10:00:00.000000", "time": "2023-01-02T10:00:00.000000"},
+        {"archive": "host-2023-01-02.checkpoint.3", "name": "host-2023-01-02.checkpoint.3", "id": "0b1f", "start": "2023-01-02T11:00:00.000000", "time": "2023-01-02T11:00:00.000000"}
+    ],
+    "encryption": {"mode": "repokey"},
+    "repository": {"id": "e2ff", "last_modified": "2023-01-02T11:00:00.000000", "location": "/tmp/repo"}
+}"#;
+        let output = || Output {
+            status: ExitStatus::from_raw(0),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: vec![],
+        };
+
+        let list = list_parse_output(output(), &options, &common_options).unwrap();
+        assert_eq!(list.archives.len(), 3);
+
+        options.consider_checkpoints = false;
+        let list = list_parse_output(output(), &options, &common_options).unwrap();
+        let names: Vec<_> = list.archives.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, ["host-2023-01-01"]);
+    }
+
+    #[test]
+    fn test_prune_keep_last() {
+        let mut options = PruneOptions::new(Path::new("/tmp/repo"));
+        options.keep_last = NonZeroU16::new(10);
+        options.keep_daily = NonZeroU16::new(7);
+        let args = prune_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(
+            args,
+            "--log-json prune --keep-last 10 --keep-daily 7 '/tmp/repo'"
+        );
+    }
+
+    #[test]
+    fn test_prune_no_keep_rules() {
+        let mut options = PruneOptions::new(Path::new("/tmp/repo"));
+        let res = prune_check_keep_rules(&options);
+        assert!(matches!(res, Err(PruneError::NoKeepRules)), "{res:?}");
+
+        options.keep_within = Some(PruneWithin {
+            quantifier: NonZeroU16::new(2).unwrap(),
+            time: PruneWithinTime::Day,
+        });
+        assert!(prune_check_keep_rules(&options).is_ok());
+    }
+
+    #[test]
+    fn test_prune_save_space_checkpoint_interval() {
+        let mut options = PruneOptions::new(Path::new("/tmp/repo"));
+        options.keep_daily = NonZeroU16::new(7);
+        options.save_space = true;
+        options.checkpoint_interval = NonZeroU16::new(600);
+        let args = prune_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(
+            args,
+            "--log-json prune --save-space --checkpoint-interval 600 --keep-daily 7 '/tmp/repo'"
+        );
+
+        options.list = true;
+        let args = prune_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(
+            args,
+            "--log-json prune --list --save-space --checkpoint-interval 600 --keep-daily 7 '/tmp/repo'"
+        );
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::new(NonZeroU32::new(3).unwrap(), Duration::from_secs(1));
+        let output = |exit_code: i32, stderr: &str| Output {
+            status: ExitStatus::from_raw(exit_code << 8),
+            stdout: vec![],
+            stderr: stderr.as_bytes().to_vec(),
+        };
+
+        let closed = output(
+            2,
+            r#"{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "Connection closed by remote host", "msgid": "ConnectionClosed"}"#,
+        );
+        assert_eq!(
+            policy.transient_error(&closed),
+            Some(MessageId::ConnectionClosed)
+        );
+        assert_eq!(policy.retry_delay(1, &closed), Some(Duration::from_secs(1)));
+        assert_eq!(policy.retry_delay(2, &closed), Some(Duration::from_secs(2)));
+        assert_eq!(policy.retry_delay(3, &closed), None);
+
+        // modern exit code of LockTimeout
+        assert_eq!(
+            policy.transient_error(&output(73, "")),
+            Some(MessageId::LockTimeout)
+        );
+
+        let wrong_passphrase = output(
+            2,
+            r#"{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "passphrase supplied in BORG_PASSPHRASE is incorrect", "msgid": "PassphraseWrong"}"#,
+        );
+        assert_eq!(policy.transient_error(&wrong_passphrase), None);
+        assert_eq!(policy.retry_delay(1, &wrong_passphrase), None);
+
+        // warnings are not retried
+        assert_eq!(policy.transient_error(&output(1, "")), None);
+    }
+
+    #[test]
+    fn test_extract_list_files() {
+        let mut options = ExtractOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            "/tmp/restore".to_string(),
+        );
+        options.list_files = true;
+        let args = extract_fmt_args(&options, &CommonOptions::default());
+        assert_eq!(args, "--log-json extract --list '/tmp/repo'::'archive'");
+
+        let stderr = [
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.list", "message": "etc"}"#,
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.list", "message": "etc/my hosts"}"#,
+            r#"{"type": "log_message", "time": 1.0, "levelname": "WARNING", "name": "borg.archive", "message": "etc/shadow: open: [Errno 13] Permission denied"}"#,
+        ]
+        .join("\n");
+        let output = Output {
+            status: ExitStatus::from_raw(1 << 8),
+            stdout: vec![],
+            stderr: stderr.into_bytes(),
+        };
+
+        let files = extract_parse_output(output, &CommonOptions::default()).unwrap();
+        assert_eq!(files, vec!["etc".to_string(), "etc/my hosts".to_string()]);
+    }
+
+    #[test]
+    fn test_extract_size_mismatches() {
+        let destination = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(destination.path().join("user")).unwrap();
+        std::fs::write(destination.path().join("user/complete"), "12345").unwrap();
+        std::fs::write(destination.path().join("user/truncated"), "12").unwrap();
+
+        let output = Output {
+            status: ExitStatus::from_raw(0),
+            stdout: [
+                r#"{"type": "d", "mode": "drwxr-xr-x", "user": "root", "group": "root", "uid": 0, "gid": 0, "path": "home/user", "mtime": "2023-01-02T10:00:00.000000", "size": 0}"#,
+                r#"{"type": "-", "mode": "-rw-r--r--", "user": "root", "group": "root", "uid": 0, "gid": 0, "path": "home/user/complete", "mtime": "2023-01-02T10:00:00.000000", "size": 5}"#,
+                r#"{"type": "-", "mode": "-rw-r--r--", "user": "root", "group": "root", "uid": 0, "gid": 0, "path": "home/user/truncated", "mtime": "2023-01-02T10:00:00.000000", "size": 5}"#,
+                r#"{"type": "-", "mode": "-rw-r--r--", "user": "root", "group": "root", "uid": 0, "gid": 0, "path": "home/user/missing", "mtime": "2023-01-02T10:00:00.000000", "size": 5}"#,
+                r#"{"type": "-", "mode": "-rw-r--r--", "user": "root", "group": "root", "uid": 0, "gid": 0, "path": "etc/hosts", "mtime": "2023-01-02T10:00:00.000000", "size": 220}"#,
+            ]
+            .join("\n")
+            .into_bytes(),
+            stderr: vec![],
+        };
+        let items = list_items_parse_output(output, &CommonOptions::default()).unwrap();
+        assert_eq!(items.len(), 5);
+
+        let mut options = ExtractOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            destination.path().to_str().unwrap().to_string(),
+        );
+        options.paths = vec!["home/user/".to_string()];
+        options.strip_components = Some(1);
+        assert_eq!(
+            options.size_mismatches(&items),
+            vec![
+                SizeMismatch {
+                    path: "user/truncated".to_string(),
+                    expected: 5,
+                    actual: Some(2),
+                },
+                SizeMismatch {
+                    path: "user/missing".to_string(),
+                    expected: 5,
+                    actual: None,
+                },
+            ]
+        );
+
+        // Missing files may have been excluded by a pattern
+        options.patterns = vec![PatternInstruction::Exclude(Pattern::Shell(
+            "**/missing".to_string(),
+        ))];
+        assert_eq!(options.size_mismatches(&items).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_output_keyfile_errors() {
+        let output = |exit_code: i32, stderr: String| Output {
+            status: ExitStatus::from_raw(exit_code << 8),
+            stdout: vec![],
+            stderr: stderr.into_bytes(),
+        };
+        let log_line = |msg_id: &str, message: &str| {
+            format!(
+                r#"{{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "{message}", "msgid": "{msg_id}"}}"#
+            )
+        };
+
+        let res = list_parse_output(
+            output(
+                2,
+                log_line(
+                    "KeyfileNotFoundError",
+                    "No key file for repository /tmp/repo found in /root/.config/borg/keys.",
+                ),
+            ),
+            &ListOptions::new(Path::new("/tmp/repo")),
+            &CommonOptions::default(),
+        );
+        assert!(
+            matches!(res, Err(ListError::Keyfile(KeyfileError::NotFound))),
+            "{res:?}"
+        );
+
+        let res = prune_parse_output(
+            output(
+                2,
+                log_line(
+                    "KeyfileInvalidError",
+                    "Invalid key file for repository /tmp/repo found in /root/.config/borg/keys/repo.",
+                ),
+            ),
+            &CommonOptions::default(),
+        );
+        assert!(
+            matches!(res, Err(PruneError::Keyfile(KeyfileError::Invalid))),
+            "{res:?}"
+        );
+
+        let options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/etc".to_string()],
+            vec![],
+        );
+        let res = create_parse_output(
+            output(
+                2,
+                log_line(
+                    "KeyfileMismatchError",
+                    "Mismatch between repository /tmp/repo and key file /root/.config/borg/keys/repo.",
+                ),
+            ),
+            &options,
+            &CommonOptions::default(),
+        );
+        assert!(
+            matches!(res, Err(CreateError::Keyfile(KeyfileError::Mismatch))),
+            "{res:?}"
+        );
 
-            if let Some(msg_id) = msg_id {
-                if exit_code > 1 {
-                    return Err(MountError::UnexpectedMessageId(msg_id));
-                }
-            }
-        }
+        // Modern exit code without a log message
+        let res = create_parse_output(
+            output(42, String::new()),
+            &options,
+            &CommonOptions::default(),
+        );
+        assert!(
+            matches!(res, Err(CreateError::Keyfile(KeyfileError::NotFound))),
+            "{res:?}"
+        );
     }
 
-    if exit_code > 1 {
-        return Err(MountError::Unknown(output));
+    #[test]
+    fn test_create_checkpoint_interval() {
+        let options = CreateOptions::builder(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+        )
+        .files_cache(FilesCacheMode::MtimeSize)
+        .checkpoint_interval(NonZeroU16::new(300).unwrap())
+        .build();
+        let args = create_fmt_args(
+            &options,
+            &CommonOptions::default(),
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            "--log-json create --json --files-cache=mtime,size --checkpoint-interval 300 '/tmp/repo'::'archive' /home"
+        );
     }
-    Ok(())
-}
 
-pub(crate) fn list_fmt_args(options: &ListOptions, common_options: &CommonOptions) -> String {
-    format!(
-        "--log-json {common_options} list --json {repository}",
-        common_options = String::from(common_options),
-        repository = shell_escape(&options.repository)
-    )
-}
+    #[test]
+    fn test_create_checkpoint_volume() {
+        let options = CreateOptions::builder(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+        )
+        .checkpoint_interval(NonZeroU16::new(300).unwrap())
+        .checkpoint_volume(1 << 30)
+        .build();
+        let args = create_fmt_args(
+            &options,
+            &CommonOptions::default(),
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            "--log-json create --json --checkpoint-interval 300 --checkpoint-volume 1073741824 '/tmp/repo'::'archive' /home"
+        );
 
-pub(crate) fn list_parse_output(res: Output) -> Result<ListRepository, ListError> {
-    let Some(exit_code) = res.status.code() else {
-        warn!("borg process was terminated by signal");
-        return Err(ListError::TerminatedBySignal);
-    };
+        let common_options = CommonOptions {
+            borg_version: Some(BorgVersion::new(1, 2, 8)),
+            ..CommonOptions::default()
+        };
+        let res = create_fmt_args(
+            &options,
+            &common_options,
+            false,
+            &CreateTempFiles::default(),
+        );
+        assert!(
+            matches!(res, Err(CreateError::CheckpointVolumeNotSupported(version)) if version == BorgVersion::new(1, 2, 8)),
+            "{res:?}"
+        );
+    }
 
-    let mut output = String::new();
+    #[test]
+    fn test_set_comment() {
+        let repository = Repository::from(Path::new("/tmp/repo"));
+        assert_eq!(
+            set_comment_fmt_args(
+                &repository,
+                "archive",
+                r#"{"verified": true}"#,
+                &CommonOptions::default()
+            ),
+            r#"--log-json recreate --comment '{"verified": true}' '/tmp/repo'::'archive'"#
+        );
 
-    for line in BufRead::lines(res.stderr.as_slice()) {
-        let line = line.map_err(ListError::InvalidBorgOutput)?;
-        writeln!(output, "{line}").unwrap();
+        let output = |exit_code: i32, stderr: &str| Output {
+            status: ExitStatus::from_raw(exit_code << 8),
+            stdout: vec![],
+            stderr: stderr.as_bytes().to_vec(),
+        };
+        assert!(recreate_parse_output(output(0, ""), &CommonOptions::default()).is_ok());
 
-        trace!("borg output: {line}");
+        let res = recreate_parse_output(
+            output(
+                2,
+                r#"{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "Archive missing does not exist", "msgid": "Archive.DoesNotExist"}"#,
+            ),
+            &CommonOptions::default(),
+        );
+        assert!(
+            matches!(res, Err(RecreateError::ArchiveDoesNotExist)),
+            "{res:?}"
+        );
+    }
 
-        let log_msg = LoggingMessage::from_str(&line)?;
+    #[test]
+    fn test_delete_dry_run() {
+        let mut options = DeleteOptions::new(Path::new("/tmp/repo"));
+        options.glob_archives = Some("web-*".to_string());
+        options.dry_run = true;
+        assert_eq!(
+            delete_fmt_args(&options, &CommonOptions::default()),
+            "--log-json delete --dry-run --list --glob-archives 'web-*' '/tmp/repo'"
+        );
 
-        if let LoggingMessage::LogMessage {
-            name,
-            message,
-            level_name,
-            time,
-            msg_id,
-        } = log_msg
-        {
-            log_message(level_name, time, name, message);
+        let stderr = [
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.list", "message": "Would delete archive: web-2023-01-01                       Sun, 2023-01-01 10:00:00 [3a2cde5e7b0b7d61] (1/2)"}"#,
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.list", "message": "Would delete archive: web-2023-01-02                       Mon, 2023-01-02 10:00:00 [0b1e1ac2a3fbe6f3] (2/2)"}"#,
+        ]
+        .join("\n");
+        let output = Output {
+            status: ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: stderr.into_bytes(),
+        };
+        let report = delete_parse_output(output, &CommonOptions::default()).unwrap();
+        assert_eq!(report.deleted, ["web-2023-01-01", "web-2023-01-02"]);
 
-            if let Some(msg_id) = msg_id {
-                match msg_id {
-                    MessageId::RepositoryDoesNotExist => {
-                        return Err(ListError::RepositoryDoesNotExist);
-                    }
-                    MessageId::PassphraseWrong => {
-                        return Err(ListError::PassphraseWrong);
-                    }
-                    _ => {
-                        if exit_code > 1 {
-                            return Err(ListError::UnexpectedMessageId(msg_id));
-                        }
-                    }
-                }
-            }
-        }
+        let mut options = DeleteOptions::new(Path::new("/tmp/repo"));
+        options.archive = Some(ArchiveRef::new(
+            Path::new("/tmp/repo"),
+            "web-2023-01-01".to_string(),
+        ));
+        assert_eq!(
+            delete_fmt_args(&options, &CommonOptions::default()),
+            "--log-json delete --list '/tmp/repo'::'web-2023-01-01'"
+        );
     }
 
-    if exit_code > 1 {
-        return Err(ListError::Unknown(output));
-    }
+    #[test]
+    fn test_delete_check_selection() {
+        let mut options = DeleteOptions::new(Path::new("/tmp/repo"));
+        assert!(matches!(
+            delete_check_selection(&options),
+            Err(DeleteError::NoArchivesSelected)
+        ));
 
-    trace!("Parsing output");
-    let list_repo: ListRepository = serde_json::from_slice(&res.stdout)?;
+        options.dry_run = true;
+        assert!(delete_check_selection(&options).is_ok());
 
-    Ok(list_repo)
-}
+        options.dry_run = false;
+        options.glob_archives = Some("web-*".to_string());
+        assert!(delete_check_selection(&options).is_ok());
 
-pub(crate) fn create_fmt_args(
-    options: &CreateOptions,
-    common_options: &CommonOptions,
-    progress: bool,
-) -> String {
-    format!(
-        "--log-json{p} {common_options}create --json{comment}{compression}{num_ids}{sparse}{read_special}{no_xattr}{no_acls}{no_flags}{ex_caches}{patterns}{excludes}{pattern_file}{exclude_file} {repo}::{archive} {paths}",
-        common_options = String::from(common_options),
-        p = if progress { " --progress" } else { "" },
-        comment = options.comment.as_ref().map_or("".to_string(), |x| format!(
-            " --comment {}",
-            shell_escape(x)
-        )),
-        compression = options.compression.as_ref().map_or("".to_string(), |x| format!(" --compression {x}")),
-        num_ids = if options.numeric_ids { " --numeric-ids" } else { "" },
-        sparse = if options.sparse { " --sparse" } else { "" },
-        read_special = if options.read_special { " --read-special" } else { "" },
-        no_xattr = if options.no_xattrs { " --noxattrs" } else { "" },
-        no_acls = if options.no_acls { " --noacls" } else { "" },
-        no_flags = if options.no_flags { " --noflags" } else { "" },
-        ex_caches = if options.exclude_caches { " --exclude-caches" } else {""},
-        patterns = options.patterns.iter().map(|x| format!(
-            " --pattern={}",
-            shell_escape(&x.to_string()),
-        )).collect::<Vec<String>>().join(" "),
-        excludes = options.excludes.iter().map(|x| format!(
-            " --exclude={}",
-            shell_escape(&x.to_string()),
-        )).collect::<Vec<String>>().join(" "),
-        pattern_file = options.pattern_file.as_ref().map_or(
-            "".to_string(),
-            |x| format!(" --patterns-from {}", shell_escape(x)),
-        ),
-        exclude_file = options.exclude_file.as_ref().map_or(
-            "".to_string(),
-            |x| format!(" --exclude-from {}", shell_escape(x)),
-        ),
-        repo = shell_escape(&options.repository),
-        archive = shell_escape(&options.archive),
-        paths = options.paths.join(" "),
-    )
-}
+        options.archive = Some(ArchiveRef::new(
+            Path::new("/tmp/repo"),
+            "web-2023-01-01".to_string(),
+        ));
+        assert!(matches!(
+            delete_check_selection(&options),
+            Err(DeleteError::ConflictingSelection)
+        ));
 
-pub(crate) fn create_parse_output(res: Output) -> Result<Create, CreateError> {
-    let Some(exit_code) = res.status.code() else {
-        warn!("borg process was terminated by signal");
-        return Err(CreateError::TerminatedBySignal);
-    };
+        options.glob_archives = None;
+        assert!(delete_check_selection(&options).is_ok());
 
-    let mut output = String::new();
+        options.archive = Some(ArchiveRef::new(
+            Path::new("/tmp/other"),
+            "web-2023-01-01".to_string(),
+        ));
+        assert!(matches!(
+            delete_check_selection(&options),
+            Err(DeleteError::ArchiveNotInRepository(archive)) if archive == "/tmp/other::web-2023-01-01"
+        ));
+    }
 
-    for line in BufRead::lines(res.stderr.as_slice()) {
-        let line = line.map_err(CreateError::InvalidBorgOutput)?;
-        writeln!(output, "{line}").unwrap();
+    #[test]
+    fn test_delete_stats() {
+        let mut options = DeleteOptions::new(Path::new("/tmp/repo"));
+        options.archive = Some(ArchiveRef::new(
+            Path::new("/tmp/repo"),
+            "web-2023-01-01".to_string(),
+        ));
+        options.stats = true;
+        assert_eq!(
+            delete_fmt_args(&options, &CommonOptions::default()),
+            "--log-json delete --list --stats '/tmp/repo'::'web-2023-01-01'"
+        );
 
-        trace!("borg output: {line}");
+        let stderr = [
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.list", "message": "Deleting archive: web-2023-01-01                       Sun, 2023-01-01 10:00:00 [3a2cde5e7b0b7d61] (1/1)"}"#,
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.stats", "message": "------------------------------------------------------------------------------"}"#,
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.stats", "message": "                       Original size      Compressed size    Deduplicated size"}"#,
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.stats", "message": "Deleted data:                 -1.50 GB            -750.00 MB             -1.25 MB"}"#,
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.stats", "message": "All archives:                 10.00 GB              5.00 GB              2.00 GB"}"#,
+        ]
+        .join("\n");
+        let output = Output {
+            status: ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: stderr.into_bytes(),
+        };
+        let report = delete_parse_output(output, &CommonOptions::default()).unwrap();
+        assert_eq!(report.deleted, ["web-2023-01-01"]);
+        let stats = report.stats.unwrap();
+        assert_eq!(stats.deleted_archives, 1);
+        assert_eq!(stats.freed_size, 1_250_000);
+    }
 
-        let log_msg = LoggingMessage::from_str(&line)?;
+    #[test]
+    fn test_create_stats() {
+        let options = CreateOptions::builder(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/home".to_string()],
+        )
+        .stats(true)
+        .build();
+        let args = create_fmt_args(
+            &options,
+            &CommonOptions::default(),
+            false,
+            &CreateTempFiles::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            "--log-json create --json --stats '/tmp/repo'::'archive' /home"
+        );
 
-        if let LoggingMessage::LogMessage {
-            name,
-            message,
-            level_name,
-            time,
-            msg_id,
-        } = log_msg
-        {
-            log_message(level_name, time, name, message);
+        // The statistics table, both as log messages and as plain text
+        let stderr = [
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.stats", "message": "------------------------------------------------------------------------------"}"#,
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.stats", "message": "Archive name: archive"}"#,
+            r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.stats", "message": "This archive:                  100 B                 50 B                 25 B"}"#,
+            "                       Original size      Compressed size    Deduplicated size",
+            "This archive:                  100 B                 50 B                 25 B",
+            r#"{"type": "log_message", "time": 1.0, "levelname": "WARNING", "name": "borg.archiver", "message": "/home/file: file changed while we backed it up", "msgid": "FileChangedWarning"}"#,
+        ]
+        .join("\n");
+        let output = Output {
+            status: ExitStatus::from_raw(1 << 8),
+            stdout: r#"{"archive": {"command_line": ["borg"], "duration": 0.1, "end": "2023-01-02T10:00:01.000000", "id": "0b1e", "limits": {"max_archive_size": 0.0}, "name": "archive", "start": "2023-01-02T10:00:00.000000", "stats": {"compressed_size": 50, "deduplicated_size": 25, "nfiles": 1, "original_size": 100}}, "repository": {"id": "e2ff", "last_modified": "2023-01-02T10:00:01.000000", "location": "/tmp/repo"}}"#.as_bytes().to_vec(),
+            stderr: stderr.into_bytes(),
+        };
 
-            if let Some(msg_id) = msg_id {
-                match msg_id {
-                    MessageId::ArchiveAlreadyExists => {
-                        return Err(CreateError::ArchiveAlreadyExists)
-                    }
-                    MessageId::PassphraseWrong => {
-                        return Err(CreateError::PassphraseWrong);
-                    }
-                    _ => {
-                        if exit_code > 1 {
-                            return Err(CreateError::UnexpectedMessageId(msg_id));
-                        }
-                    }
-                }
-            }
-        }
+        let create = create_parse_output(output, &options, &CommonOptions::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(create.archive.stats.original_size, 100);
+        assert_eq!(create.archive.stats.compressed_size, 50);
+        assert_eq!(create.archive.stats.deduplicated_size, 25);
     }
 
-    if exit_code > 1 {
-        return Err(CreateError::Unknown(output));
+    #[test]
+    fn test_common_options_display() {
+        assert_eq!(CommonOptions::default().to_string(), "");
+
+        let common_options = CommonOptions {
+            remote_path: Some("/opt/borg/bin/borg".to_string()),
+            upload_ratelimit: Some(1024),
+            upload_buffer: Some(16),
+            download_ratelimit: Some(2048),
+            rsh: Some("ssh -p 2222".into()),
+            lock_wait: Some(30),
+            umask: Some(0o077),
+            log_level: Some(LogLevel::Debug),
+            ..CommonOptions::default()
+        };
+        let flags = "--debug --rsh 'ssh -p 2222' --remote-path /opt/borg/bin/borg --upload-ratelimit 1024 --upload-buffer 16 --download-ratelimit 2048 --lock-wait 30 --umask 0077";
+        assert_eq!(common_options.to_string(), flags);
+        assert_eq!(String::from(&common_options), flags);
+        assert_eq!(
+            shlex::split(flags).unwrap(),
+            [
+                "--debug",
+                "--rsh",
+                "ssh -p 2222",
+                "--remote-path",
+                "/opt/borg/bin/borg",
+                "--upload-ratelimit",
+                "1024",
+                "--upload-buffer",
+                "16",
+                "--download-ratelimit",
+                "2048",
+                "--lock-wait",
+                "30",
+                "--umask",
+                "0077"
+            ]
+        );
+
+        let mut options = PruneOptions::new(Path::new("/tmp/repo"));
+        options.keep_daily = NonZeroU16::new(7);
+        assert_eq!(
+            prune_fmt_args(&options, &common_options),
+            format!("--log-json {flags} prune --keep-daily 7 '/tmp/repo'")
+        );
+        assert_eq!(
+            serve_fmt_args(&ServeOptions::default(), &common_options),
+            format!("{flags} serve")
+        );
     }
 
-    trace!("Parsing stats");
-    let stats: Create = serde_json::from_slice(&res.stdout)?;
+    #[test]
+    fn test_fmt_args_whitespace() {
+        let assert_normalized = |args: String| {
+            assert!(!args.contains("  "), "double space in {args:?}");
+            assert_eq!(args.trim(), args, "leading or trailing space in {args:?}");
+        };
 
-    Ok(stats)
-}
+        for common_options in [
+            CommonOptions::default(),
+            CommonOptions {
+                log_level: Some(LogLevel::Info),
+                lock_wait: Some(10),
+                ..CommonOptions::default()
+            },
+        ] {
+            let mut options = CreateOptions::new(
+                Path::new("/tmp/repo"),
+                "archive".to_string(),
+                vec!["/home".to_string(), "/etc".to_string()],
+                vec![
+                    PatternInstruction::Root("/home".to_string()),
+                    PatternInstruction::Exclude(Pattern::Shell("*.tmp".to_string())),
+                ],
+            );
+            options.excludes = vec![
+                Pattern::Shell("*.cache".to_string()),
+                Pattern::Shell("*.log".to_string()),
+            ];
+            let temp_files = CreateTempFiles::default();
+            assert_normalized(
+                create_fmt_args(&options, &common_options, true, &temp_files).unwrap(),
+            );
 
-pub(crate) fn compact_fmt_args(options: &CompactOptions, common_options: &CommonOptions) -> String {
-    format!(
-        "--log-json {common_options}compact {repository}",
-        common_options = String::from(common_options),
-        repository = shell_escape(&options.repository)
-    )
-}
+            options.source = CreateSource::Paths(vec![]);
+            assert_normalized(
+                create_fmt_args(&options, &common_options, false, &temp_files).unwrap(),
+            );
 
-pub(crate) fn compact_parse_output(res: Output) -> Result<(), CompactError> {
-    let Some(exit_code) = res.status.code() else {
-        warn!("borg process was terminated by signal");
-        return Err(CompactError::TerminatedBySignal);
-    };
+            let mut options = MountOptions::new(
+                MountSource::Archive {
+                    archive: ArchiveRef::new(Path::new("/tmp/repo"), "archive".to_string()),
+                },
+                "/mnt".to_string(),
+            );
+            assert_normalized(mount_fmt_args(&options, &common_options));
+            options.select_paths = vec![
+                Pattern::Shell("home/user".to_string()),
+                Pattern::Shell("etc".to_string()),
+            ];
+            assert_normalized(mount_fmt_args(&options, &common_options));
 
-    let mut output = String::new();
+            let mut options = PruneOptions::new(Path::new("/tmp/repo"));
+            options.keep_daily = NonZeroU16::new(7);
+            assert_normalized(prune_fmt_args(&options, &common_options));
 
-    for line in BufRead::lines(res.stderr.as_slice()) {
-        let line = line.map_err(CompactError::InvalidBorgOutput)?;
-        writeln!(output, "{line}").unwrap();
+            let options = ListOptions::new(Path::new("/tmp/repo"));
+            assert_normalized(list_fmt_args(&options, &common_options));
+            assert_normalized(list_stream_fmt_args(&options, "archive", &common_options));
 
-        trace!("borg output: {line}");
+            let options = InfoOptions::new(Path::new("/tmp/repo"));
+            assert_normalized(info_fmt_args(&options, &common_options));
 
-        let log_msg = LoggingMessage::from_str(&line)?;
+            let options = ExtractOptions::new(
+                Path::new("/tmp/repo"),
+                "archive".to_string(),
+                "/tmp/restore".to_string(),
+            );
+            assert_normalized(extract_fmt_args(&options, &common_options));
 
-        if let LoggingMessage::LogMessage {
-            name,
-            message,
-            level_name,
-            time,
-            msg_id,
-        } = log_msg
-        {
-            log_message(level_name, time, name, message);
+            let mut options = DeleteOptions::new(Path::new("/tmp/repo"));
+            options.archive = Some(ArchiveRef::new(
+                Path::new("/tmp/repo"),
+                "archive".to_string(),
+            ));
+            assert_normalized(delete_fmt_args(&options, &common_options));
 
-            if let Some(msg_id) = msg_id {
-                if exit_code > 1 {
-                    return Err(CompactError::UnexpectedMessageId(msg_id));
-                }
-            }
+            let options = TransferOptions::new(Path::new("/tmp/old"), Path::new("/tmp/new"));
+            assert_normalized(transfer_fmt_args(&options, &common_options, true));
+
+            assert_normalized(serve_fmt_args(&ServeOptions::default(), &common_options));
+            assert_normalized(raw_fmt_args(&common_options));
         }
     }
 
-    if exit_code > 1 {
-        return Err(CompactError::Unknown(output));
-    }
+    #[test]
+    fn test_prune_match_archives() {
+        let mut options = PruneOptions::new(Path::new("/tmp/repo"));
+        options.keep_last = NonZeroU16::new(3);
+        let borg_1 = CommonOptions {
+            borg_version: Some(BorgVersion::new(1, 2, 8)),
+            ..CommonOptions::default()
+        };
+        let borg_2 = CommonOptions {
+            borg_version: Some(BorgVersion::new(2, 0, 0)),
+            ..CommonOptions::default()
+        };
 
-    Ok(())
-}
+        options.glob_archives = Some("web-*".to_string());
+        assert_eq!(
+            prune_fmt_args(&options, &CommonOptions::default()),
+            "--log-json prune --keep-last 3 --glob-archives 'web-*' '/tmp/repo'"
+        );
+        assert_eq!(
+            prune_fmt_args(&options, &borg_2),
+            "--log-json prune --keep-last 3 --match-archives 'sh:web-*' '/tmp/repo'"
+        );
 
-#[cfg(test)]
-mod tests {
-    use std::num::NonZeroU16;
+        options.match_archives = Some(Pattern::Shell("db-*".to_string()));
+        let res = prune_check_match_archives(&options, &borg_2);
+        assert!(
+            matches!(res, Err(PruneError::InvalidMatchArchives(_))),
+            "{res:?}"
+        );
 
-    use crate::common::{
-        mount_fmt_args, prune_fmt_args, CommonOptions, MountOptions, MountSource, Pattern,
-        PruneOptions,
-    };
-    #[test]
-    fn test_prune_fmt_args() {
-        let mut prune_option = PruneOptions::new("prune_option_repo".to_string());
-        prune_option.keep_secondly = NonZeroU16::new(1);
-        prune_option.keep_minutely = NonZeroU16::new(2);
-        prune_option.keep_hourly = NonZeroU16::new(3);
-        prune_option.keep_daily = NonZeroU16::new(4);
-        prune_option.keep_weekly = NonZeroU16::new(5);
-        prune_option.keep_monthly = NonZeroU16::new(6);
-        prune_option.keep_yearly = NonZeroU16::new(7);
-        let args = prune_fmt_args(&prune_option, &CommonOptions::default());
-        assert_eq!("--log-json  prune --keep-secondly 1 --keep-minutely 2 --keep-hourly 3 --keep-daily 4 --keep-weekly 5 --keep-monthly 6 --keep-yearly 7 'prune_option_repo'", args);
-    }
-    #[test]
-    fn test_mount_fmt_args() {
-        let mount_option = MountOptions::new(
-            MountSource::Archive {
-                archive_name: "/tmp/borg-repo::archive".to_string(),
-            },
-            String::from("/mnt/borg-mount"),
+        options.glob_archives = None;
+        assert!(prune_check_match_archives(&options, &borg_1).is_ok());
+        assert_eq!(
+            prune_fmt_args(&options, &borg_1),
+            "--log-json prune --keep-last 3 --glob-archives 'db-*' '/tmp/repo'"
         );
-        let args = mount_fmt_args(&mount_option, &CommonOptions::default());
         assert_eq!(
-            "--log-json  mount /tmp/borg-repo::archive /mnt/borg-mount",
-            args
+            prune_fmt_args(&options, &borg_2),
+            "--log-json prune --keep-last 3 --match-archives 'sh:db-*' '/tmp/repo'"
+        );
+        assert_eq!(
+            prune_fmt_args(&options, &CommonOptions::default()),
+            "--log-json prune --keep-last 3 --glob-archives 'db-*' '/tmp/repo'"
+        );
+
+        options.match_archives = Some(Pattern::Regex("^db-[0-9]+$".to_string()));
+        let res = prune_check_match_archives(&options, &borg_1);
+        assert!(
+            matches!(res, Err(PruneError::InvalidMatchArchives(_))),
+            "{res:?}"
+        );
+        assert!(prune_check_match_archives(&options, &borg_2).is_ok());
+        assert!(prune_check_match_archives(&options, &CommonOptions::default()).is_ok());
+        assert_eq!(
+            prune_fmt_args(&options, &borg_2),
+            "--log-json prune --keep-last 3 --match-archives 're:^db-[0-9]+$' '/tmp/repo'"
+        );
+
+        options.match_archives = Some(Pattern::PathPrefix("db".to_string()));
+        let res = prune_check_match_archives(&options, &borg_2);
+        assert!(
+            matches!(res, Err(PruneError::InvalidMatchArchives(_))),
+            "{res:?}"
         );
     }
+
     #[test]
-    fn test_mount_fmt_args_patterns() {
-        let mut mount_option = MountOptions::new(
-            MountSource::Archive {
-                archive_name: "/my-borg-repo".to_string(),
-            },
-            String::from("/borg-mount"),
+    fn test_compact_dry_run() {
+        let mut options = CompactOptions::new(Path::new("/tmp/repo"));
+        assert_eq!(
+            compact_fmt_args(&options, &CommonOptions::default(), false),
+            "--log-json compact --info '/tmp/repo'"
         );
-        mount_option.select_paths = vec![
-            Pattern::Shell("**/test/*".to_string()),
-            Pattern::Regex("^[A-Z]{3}".to_string()),
-        ];
-        let args = mount_fmt_args(&mount_option, &CommonOptions::default());
+
+        options.dry_run = true;
+        let common_options = CommonOptions {
+            log_level: Some(LogLevel::Debug),
+            ..CommonOptions::default()
+        };
         assert_eq!(
-            "--log-json  mount /my-borg-repo /borg-mount --pattern='sh:**/test/*' --pattern='re:^[A-Z]{3}'",
-            args
+            compact_fmt_args(&options, &common_options, true),
+            "--log-json --progress --debug compact --dry-run '/tmp/repo'"
+        );
+        assert!(compact_check_version(&options, &CommonOptions::default()).is_ok());
+
+        let common_options = CommonOptions {
+            borg_version: Some(BorgVersion::new(1, 2, 8)),
+            ..CommonOptions::default()
+        };
+        let res = compact_check_version(&options, &common_options);
+        assert!(
+            matches!(res, Err(CompactError::DryRunNotSupported(_))),
+            "{res:?}"
         );
+
+        options.dry_run = false;
+        assert!(compact_check_version(&options, &common_options).is_ok());
     }
+
     #[test]
-    fn test_mount_fmt_args_repo() {
-        let mut mount_option = MountOptions::new(
-            MountSource::Repository {
-                name: "/my-repo".to_string(),
-                first_n_archives: Some(NonZeroU16::new(10).unwrap()),
-                last_n_archives: Some(NonZeroU16::new(5).unwrap()),
-                glob_archives: Some("archive-name*12-2022*".to_string()),
-            },
-            String::from("/borg-mount"),
-        );
-        mount_option.select_paths = vec![Pattern::Shell("**/foobar/*".to_string())];
-        let args = mount_fmt_args(&mount_option, &CommonOptions::default());
+    fn test_compact_cleanup_commits() {
+        let mut options = CompactOptions::new(Path::new("/tmp/repo"));
+        options.cleanup_commits = true;
         assert_eq!(
-            "--log-json  mount /my-repo --first 10 --last 5 --glob-archives archive-name*12-2022* /borg-mount --pattern='sh:**/foobar/*'",
-            args
+            compact_fmt_args(&options, &CommonOptions::default(), false),
+            "--log-json compact --info --cleanup-commits '/tmp/repo'"
         );
+        assert!(compact_check_version(&options, &CommonOptions::default()).is_ok());
+
+        let common_options = CommonOptions {
+            borg_version: Some(BorgVersion::new(1, 2, 8)),
+            ..CommonOptions::default()
+        };
+        assert!(compact_check_version(&options, &common_options).is_ok());
+
+        for version in [BorgVersion::new(1, 1, 18), BorgVersion::new(2, 0, 0)] {
+            let common_options = CommonOptions {
+                borg_version: Some(version),
+                ..CommonOptions::default()
+            };
+            let res = compact_check_version(&options, &common_options);
+            assert!(
+                matches!(res, Err(CompactError::CleanupCommitsNotSupported(_))),
+                "{res:?}"
+            );
+        }
     }
 }
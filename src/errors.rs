@@ -4,7 +4,132 @@ use std::io;
 
 use thiserror::Error;
 
+use crate::common::CompressionMode;
+use crate::output::extract::SizeMismatch;
 use crate::output::logging::MessageId;
+use crate::output::version::BorgVersion;
+use crate::utils::is_borg_not_found_error;
+
+/// Implement [From<io::Error>] for errors with a `CommandFailed`, a `Timeout`
+/// and a `BorgNotFound` variant
+///
+/// An [io::ErrorKind::TimedOut] is raised by the execution of borg
+/// if [CommonOptions::timeout](crate::common::CommonOptions::timeout) elapsed.
+macro_rules! impl_from_io_error {
+    ($($error:ty),+) => {
+        $(
+            impl From<io::Error> for $error {
+                fn from(value: io::Error) -> Self {
+                    if value.kind() == io::ErrorKind::TimedOut {
+                        Self::Timeout
+                    } else if is_borg_not_found_error(&value) {
+                        Self::BorgNotFound
+                    } else {
+                        Self::CommandFailed(value)
+                    }
+                }
+            }
+        )+
+    };
+}
+
+/// The errors that can be returned from [crate::common::CompressionMode::validate]
+#[derive(Error, Debug)]
+pub enum CompressionError {
+    /// The compression level is not in the allowed range of the compression mode
+    #[error("Invalid compression level {mode}, allowed range is {min} to {max}")]
+    InvalidLevel {
+        /// The invalid compression mode
+        mode: CompressionMode,
+        /// The minimal allowed level
+        min: u8,
+        /// The maximal allowed level
+        max: u8,
+    },
+}
+
+/// The errors that can be returned when parsing a [crate::common::FilesCacheMode]
+#[derive(Error, Debug)]
+pub enum FilesCacheModeError {
+    /// The mode is not a combination that borg accepts
+    #[error("Invalid files cache mode: {0}")]
+    InvalidMode(String),
+}
+
+/// The errors that can be returned when parsing a [crate::common::Repository]
+#[derive(Error, Debug)]
+pub enum RepositoryError {
+    /// The repository is empty
+    #[error("The repository must not be empty")]
+    Empty,
+    /// The repository contains an archive, separated by `::`
+    #[error("The repository must not contain an archive: {0}")]
+    ContainsArchive(String),
+    /// The repository is a malformed `ssh://` URL
+    #[error("Invalid repository URL: {0}")]
+    InvalidUrl(String),
+    /// The port of the repository URL is not a valid port
+    #[error("Invalid port in repository URL: {0}")]
+    InvalidPort(String),
+    /// The repository on a remote host is missing the path
+    #[error("The repository is missing a path: {0}")]
+    MissingPath(String),
+}
+
+/// The errors that can be returned when parsing a [crate::common::ArchiveRef]
+#[derive(Error, Debug)]
+pub enum ArchiveRefError {
+    /// The archive is missing, it has to be separated from the repository by `::`
+    #[error("The archive is missing: {0}")]
+    MissingArchive(String),
+    /// The repository part is invalid
+    #[error("Invalid repository: {0}")]
+    InvalidRepository(#[from] RepositoryError),
+}
+
+/// The errors that can be returned when building a [crate::common::PatternList]
+#[derive(Error, Debug)]
+pub enum PatternListError {
+    /// A rule was added before any root path
+    #[error("The rule {0} must be preceded by a root path")]
+    MissingRoot(String),
+    /// A rule can never match, as an earlier rule matches all of its paths
+    #[error("The rule {rule} is never used, as it is shadowed by {shadowed_by}")]
+    ShadowedRule {
+        /// The rule that is never used
+        rule: String,
+        /// The earlier rule that matches first
+        shadowed_by: String,
+    },
+}
+
+/// The errors of the key file of a repository using `keyfile` encryption
+///
+/// These are returned as the `Keyfile` variant of the errors of the commands.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum KeyfileError {
+    /// No key file for the repository was found, it has to be imported first
+    #[error("No key file for the repository was found")]
+    NotFound,
+    /// The key file is invalid
+    #[error("The key file is invalid")]
+    Invalid,
+    /// The key file belongs to a different repository
+    #[error("The key file does not match the repository")]
+    Mismatch,
+}
+
+impl KeyfileError {
+    /// The keyfile error corresponding to the message id, if any
+    pub(crate) fn from_message_id(msg_id: MessageId) -> Option<Self> {
+        match msg_id {
+            MessageId::KeyfileNotFoundError => Some(Self::NotFound),
+            MessageId::KeyfileInvalidError => Some(Self::Invalid),
+            MessageId::KeyfileMismatchError => Some(Self::Mismatch),
+            _ => None,
+        }
+    }
+}
 
 /// The errors that can be returned from [crate::sync::compact]
 #[derive(Error, Debug)]
@@ -17,7 +142,13 @@ pub enum CompactError {
     ShlexError,
     /// The command failed to execute
     #[error("The command failed to execute: {0}")]
-    CommandFailed(#[from] io::Error),
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
     /// Invalid borg output found
     #[error("Error while deserializing borg output: {0}")]
     InvalidBorgOutput(io::Error),
@@ -33,6 +164,28 @@ pub enum CompactError {
     /// An unexpected message id was received
     #[error("An unexpected message id was received: {0}")]
     UnexpectedMessageId(MessageId),
+    /// Borg asked a question, which requires an interactive answer.
+    ///
+    /// Some questions can be answered in advance, e.g. with
+    /// [CommonOptions::relocated_repo_access_ok](crate::common::CommonOptions::relocated_repo_access_ok).
+    #[error("Borg asked a question that requires an interactive answer: {prompt}")]
+    InteractivePromptRequired {
+        /// The question borg asked
+        prompt: String,
+    },
+    /// The key file of the repository is missing, invalid or doesn't match
+    #[error("{0}")]
+    Keyfile(KeyfileError),
+    /// [CompactOptions::dry_run](crate::common::CompactOptions::dry_run) was set,
+    /// but the borg version doesn't support it.
+    ///
+    /// Without it, borg would compact the repository.
+    #[error("borg {0} doesn't support a dry run of compact")]
+    DryRunNotSupported(BorgVersion),
+    /// [CompactOptions::cleanup_commits](crate::common::CompactOptions::cleanup_commits) was set,
+    /// but the borg version doesn't support it.
+    #[error("borg {0} doesn't support --cleanup-commits")]
+    CleanupCommitsNotSupported(BorgVersion),
 }
 
 /// The errors that can be returned from [crate::sync::prune]
@@ -46,7 +199,13 @@ pub enum PruneError {
     ShlexError,
     /// The command failed to execute
     #[error("The command failed to execute: {0}")]
-    CommandFailed(#[from] io::Error),
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
     /// Invalid borg output found
     #[error("Error while deserializing borg output: {0}")]
     InvalidBorgOutput(io::Error),
@@ -65,6 +224,27 @@ pub enum PruneError {
     /// An unexpected message id was received
     #[error("An unexpected message id was received: {0}")]
     UnexpectedMessageId(MessageId),
+    /// Borg asked a question, which requires an interactive answer.
+    ///
+    /// Some questions can be answered in advance, e.g. with
+    /// [CommonOptions::relocated_repo_access_ok](crate::common::CommonOptions::relocated_repo_access_ok).
+    #[error("Borg asked a question that requires an interactive answer: {prompt}")]
+    InteractivePromptRequired {
+        /// The question borg asked
+        prompt: String,
+    },
+    /// No keep rule was specified, which borg refuses.
+    ///
+    /// Use [crate::sync::delete] to delete all archives.
+    #[error("No keep rule was specified")]
+    NoKeepRules,
+    /// The archive selection of [PruneOptions::match_archives](crate::common::PruneOptions::match_archives)
+    /// can't be expressed with the borg version
+    #[error("Invalid archive selection: {0}")]
+    InvalidMatchArchives(String),
+    /// The key file of the repository is missing, invalid or doesn't match
+    #[error("{0}")]
+    Keyfile(KeyfileError),
 }
 
 /// The errors that can be returned from [crate::sync::mount]
@@ -78,7 +258,13 @@ pub enum MountError {
     ShlexError,
     /// The command failed to execute
     #[error("The command failed to execute: {0}")]
-    CommandFailed(#[from] io::Error),
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
     /// Invalid borg output found
     #[error("Error while deserializing borg output: {0}")]
     InvalidBorgOutput(io::Error),
@@ -88,12 +274,33 @@ pub enum MountError {
     /// Borg was terminated by a signal
     #[error("Borg was terminated by a signal")]
     TerminatedBySignal,
-    /// An unexpected message id was received
+    /// Piping from stdout or stderr failed
+    #[error("Piping from stdout or stderr failed")]
+    PipeFailed,
+    /// Failed to umount
     #[error("Failed to umount: {0}")]
     UMountError(String),
+    /// The mountpoint is not mounted
+    #[error("The mountpoint {0} is not mounted")]
+    NotMounted(String),
+    /// Borg didn't mount the mountpoint, although it reported success
+    #[error("The mountpoint {0} did not become ready")]
+    NotReady(String),
     /// An unexpected message id was received
     #[error("An unexpected message id was received: {0}")]
     UnexpectedMessageId(MessageId),
+    /// Borg asked a question, which requires an interactive answer.
+    ///
+    /// Some questions can be answered in advance, e.g. with
+    /// [CommonOptions::relocated_repo_access_ok](crate::common::CommonOptions::relocated_repo_access_ok).
+    #[error("Borg asked a question that requires an interactive answer: {prompt}")]
+    InteractivePromptRequired {
+        /// The question borg asked
+        prompt: String,
+    },
+    /// The key file of the repository is missing, invalid or doesn't match
+    #[error("{0}")]
+    Keyfile(KeyfileError),
 }
 
 /// The errors that can be returned from [crate::sync::list]
@@ -107,7 +314,13 @@ pub enum ListError {
     ShlexError,
     /// The command failed to execute
     #[error("The command failed to execute: {0}")]
-    CommandFailed(#[from] io::Error),
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
     /// Invalid borg output found
     #[error("Could not read borg output: {0}")]
     InvalidBorgOutput(io::Error),
@@ -129,6 +342,18 @@ pub enum ListError {
     /// The provided passphrase was incorrect
     #[error("The provided passphrase was incorrect")]
     PassphraseWrong,
+    /// Borg asked a question, which requires an interactive answer.
+    ///
+    /// Some questions can be answered in advance, e.g. with
+    /// [CommonOptions::relocated_repo_access_ok](crate::common::CommonOptions::relocated_repo_access_ok).
+    #[error("Borg asked a question that requires an interactive answer: {prompt}")]
+    InteractivePromptRequired {
+        /// The question borg asked
+        prompt: String,
+    },
+    /// The key file of the repository is missing, invalid or doesn't match
+    #[error("{0}")]
+    Keyfile(KeyfileError),
 }
 
 /// The possible errors that can get returned from [crate::sync::init]
@@ -139,7 +364,13 @@ pub enum InitError {
     ShlexError,
     /// The command failed to execute
     #[error("The command failed to execute: {0}")]
-    CommandFailed(#[from] io::Error),
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
     /// Invalid borg output found
     #[error("Could not read borg output: {0}")]
     InvalidBorgOutput(io::Error),
@@ -158,17 +389,61 @@ pub enum InitError {
     /// Unknown error occurred
     #[error("Unknown error occurred: {0}")]
     Unknown(String),
+    /// Borg asked a question, which requires an interactive answer.
+    ///
+    /// Some questions can be answered in advance, e.g. with
+    /// [CommonOptions::relocated_repo_access_ok](crate::common::CommonOptions::relocated_repo_access_ok).
+    #[error("Borg asked a question that requires an interactive answer: {prompt}")]
+    InteractivePromptRequired {
+        /// The question borg asked
+        prompt: String,
+    },
 }
 
 /// The errors of a borg create command
 #[derive(Debug, Error)]
 pub enum CreateError {
+    /// The specified compression mode is invalid
+    #[error("Invalid compression: {0}")]
+    InvalidCompression(#[from] CompressionError),
+    /// The source of the archive is [crate::common::CreateSource::Stdin],
+    /// but the command doesn't provide any input
+    #[error("The create source is stdin, but no input was provided")]
+    MissingStdin,
     /// Piping from stdout or stderr failed
     #[error("Piping failed")]
     PipeFailed,
+    /// The file for [CreateOptions::inline_patterns](crate::common::CreateOptions::inline_patterns)
+    /// couldn't be written
+    #[error("Could not write the inline patterns: {0}")]
+    InlinePatternsFailed(io::Error),
+    /// The file for [CreateOptions::paths_from_file](crate::common::CreateOptions::paths_from_file)
+    /// couldn't be written
+    #[error("Could not write the paths file: {0}")]
+    PathsFileFailed(io::Error),
+    /// The path can't be passed in a file, as it contains a newline
+    #[error("The path contains a newline: {0}")]
+    InvalidPath(String),
+    /// The command line exceeded the length limit of the operating system
+    #[error("The argument list is too long, pass the paths in a file with CreateOptions::paths_from_file")]
+    ArgumentListTooLong,
     /// The specified archive name already exists
     #[error("Archive already exists")]
     ArchiveAlreadyExists,
+    /// Retrieving the information of the already existing archive failed,
+    /// see [crate::sync::create_idempotent]
+    #[error("Could not retrieve the information of the existing archive: {0}")]
+    ExistingArchiveInfo(InfoError),
+    /// borg create was cancelled, see [crate::asynchronous::create_progress_cancellable]
+    #[error("Creating the archive was cancelled")]
+    Cancelled {
+        /// Whether a checkpoint of the archive exists, which the next run can resume from
+        checkpoint_created: bool,
+    },
+    /// Listing the checkpoints of a cancelled archive failed,
+    /// see [crate::asynchronous::create_progress_cancellable]
+    #[error("Could not list the checkpoints of the cancelled archive: {0}")]
+    CheckpointList(ListError),
     /// The provided passphrase was incorrect
     #[error("Invalid passphrase")]
     PassphraseWrong,
@@ -177,7 +452,13 @@ pub enum CreateError {
     ShlexError,
     /// The command failed to execute
     #[error("The command failed to execute: {0}")]
-    CommandFailed(#[from] io::Error),
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
     /// Invalid borg output found
     #[error("Could not read borg output: {0}")]
     InvalidBorgOutput(io::Error),
@@ -193,4 +474,532 @@ pub enum CreateError {
     /// Unknown error occurred
     #[error("Unknown error occurred: {0}")]
     Unknown(String),
+    /// Borg asked a question, which requires an interactive answer.
+    ///
+    /// Some questions can be answered in advance, e.g. with
+    /// [CommonOptions::relocated_repo_access_ok](crate::common::CommonOptions::relocated_repo_access_ok).
+    #[error("Borg asked a question that requires an interactive answer: {prompt}")]
+    InteractivePromptRequired {
+        /// The question borg asked
+        prompt: String,
+    },
+    /// The key file of the repository is missing, invalid or doesn't match
+    #[error("{0}")]
+    Keyfile(KeyfileError),
+    /// [CreateOptions::checkpoint_volume](crate::common::CreateOptions::checkpoint_volume) was
+    /// set, but the borg version doesn't support it.
+    #[error("borg {0} doesn't support checkpoints by volume")]
+    CheckpointVolumeNotSupported(BorgVersion),
+}
+
+/// The errors that can be returned from [crate::sync::version]
+#[derive(Debug, Error)]
+pub enum VersionError {
+    /// An unknown error occurred
+    #[error("Unknown error occurred: {0}")]
+    Unknown(String),
+    /// The command failed to execute
+    #[error("The command failed to execute: {0}")]
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
+    /// Borg was terminated by a signal
+    #[error("Borg was terminated by a signal")]
+    TerminatedBySignal,
+    /// The version reported by borg could not be parsed
+    #[error("Invalid version: {0}")]
+    InvalidVersion(String),
+}
+
+/// The errors that can be returned from [crate::sync::run_raw]
+#[derive(Debug, Error)]
+pub enum RawError {
+    /// Error while splitting the arguments
+    #[error("error while splitting the arguments")]
+    ShlexError,
+    /// The command failed to execute
+    #[error("The command failed to execute: {0}")]
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
+}
+
+/// The errors that can be returned from [crate::sync::transfer]
+#[derive(Debug, Error)]
+pub enum TransferError {
+    /// An unknown error occurred
+    #[error("Unknown error occurred: {0}")]
+    Unknown(String),
+    /// Error while splitting the arguments
+    #[error("error while splitting the arguments")]
+    ShlexError,
+    /// The command failed to execute
+    #[error("The command failed to execute: {0}")]
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
+    /// Invalid borg output found
+    #[error("Could not read borg output: {0}")]
+    InvalidBorgOutput(io::Error),
+    /// Error while deserializing output of borg
+    #[error("Error while deserializing borg output: {0}")]
+    DeserializeError(#[from] serde_json::Error),
+    /// Borg was terminated by a signal
+    #[error("Borg was terminated by a signal")]
+    TerminatedBySignal,
+    /// Piping from stdout or stderr failed
+    #[error("Piping from stdout or stderr failed")]
+    PipeFailed,
+    /// An unexpected message id was received
+    #[error("An unexpected message id was received: {0}")]
+    UnexpectedMessageId(MessageId),
+    /// One of the repositories does not exist
+    #[error("The repository does not exist")]
+    RepositoryDoesNotExist,
+    /// One of the provided passphrases was incorrect
+    #[error("The provided passphrase was incorrect")]
+    PassphraseWrong,
+    /// Borg asked a question, which requires an interactive answer.
+    ///
+    /// Some questions can be answered in advance, e.g. with
+    /// [CommonOptions::relocated_repo_access_ok](crate::common::CommonOptions::relocated_repo_access_ok).
+    #[error("Borg asked a question that requires an interactive answer: {prompt}")]
+    InteractivePromptRequired {
+        /// The question borg asked
+        prompt: String,
+    },
+    /// The key file of the repository is missing, invalid or doesn't match
+    #[error("{0}")]
+    Keyfile(KeyfileError),
+    /// [CommonOptions::borg_version](crate::common::CommonOptions::borg_version) is set to a
+    /// version without the transfer command, which was added in borg 2.0
+    #[error("borg {0} doesn't support transferring archives")]
+    TransferNotSupported(BorgVersion),
+}
+
+/// The errors that can be returned from [crate::sync::extract]
+#[derive(Debug, Error)]
+pub enum ExtractError {
+    /// An unknown error occurred
+    #[error("Unknown error occurred: {0}")]
+    Unknown(String),
+    /// Error while splitting the arguments
+    #[error("error while splitting the arguments")]
+    ShlexError,
+    /// The command failed to execute
+    #[error("The command failed to execute: {0}")]
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
+    /// Invalid borg output found
+    #[error("Could not read borg output: {0}")]
+    InvalidBorgOutput(io::Error),
+    /// Error while deserializing output of borg
+    #[error("Error while deserializing borg output: {0}")]
+    DeserializeError(#[from] serde_json::Error),
+    /// Borg was terminated by a signal
+    #[error("Borg was terminated by a signal")]
+    TerminatedBySignal,
+    /// An unexpected message id was received
+    #[error("An unexpected message id was received: {0}")]
+    UnexpectedMessageId(MessageId),
+    /// The specified repository does not exist
+    #[error("The repository does not exist")]
+    RepositoryDoesNotExist,
+    /// The specified archive does not exist
+    #[error("The archive does not exist")]
+    ArchiveDoesNotExist,
+    /// The provided passphrase was incorrect
+    #[error("The provided passphrase was incorrect")]
+    PassphraseWrong,
+    /// Borg asked a question, which requires an interactive answer.
+    ///
+    /// Some questions can be answered in advance, e.g. with
+    /// [CommonOptions::relocated_repo_access_ok](crate::common::CommonOptions::relocated_repo_access_ok).
+    #[error("Borg asked a question that requires an interactive answer: {prompt}")]
+    InteractivePromptRequired {
+        /// The question borg asked
+        prompt: String,
+    },
+    /// Listing the files of the archive failed,
+    /// see [ExtractOptions::verify_sizes](crate::common::ExtractOptions::verify_sizes)
+    #[error("Could not list the files of the archive for verification: {0}")]
+    VerifyFailed(ListError),
+    /// The sizes of extracted files differ from the archive,
+    /// see [ExtractOptions::verify_sizes](crate::common::ExtractOptions::verify_sizes)
+    #[error("The sizes of {} extracted files differ from the archive", .0.len())]
+    SizeMismatch(Vec<SizeMismatch>),
+    /// The key file of the repository is missing, invalid or doesn't match
+    #[error("{0}")]
+    Keyfile(KeyfileError),
+}
+
+/// The errors that can be returned from [crate::sync::config]
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    /// An unknown error occurred
+    #[error("Unknown error occurred: {0}")]
+    Unknown(String),
+    /// Error while splitting the arguments
+    #[error("error while splitting the arguments")]
+    ShlexError,
+    /// The command failed to execute
+    #[error("The command failed to execute: {0}")]
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
+    /// Invalid borg output found
+    #[error("Could not read borg output: {0}")]
+    InvalidBorgOutput(io::Error),
+    /// Borg was terminated by a signal
+    #[error("Borg was terminated by a signal")]
+    TerminatedBySignal,
+    /// An unexpected message id was received
+    #[error("An unexpected message id was received: {0}")]
+    UnexpectedMessageId(MessageId),
+    /// The specified repository does not exist
+    #[error("The repository does not exist")]
+    RepositoryDoesNotExist,
+    /// The provided passphrase was incorrect
+    #[error("The provided passphrase was incorrect")]
+    PassphraseWrong,
+    /// The repository path is not allowed by the `borg serve` process
+    #[error("The repository path is not allowed")]
+    PathNotAllowed,
+    /// The key is not set in the config of the repository
+    #[error("The key is not set: {0}")]
+    KeyNotFound(String),
+    /// Borg asked a question, which requires an interactive answer.
+    ///
+    /// Some questions can be answered in advance, e.g. with
+    /// [CommonOptions::relocated_repo_access_ok](crate::common::CommonOptions::relocated_repo_access_ok).
+    #[error("Borg asked a question that requires an interactive answer: {prompt}")]
+    InteractivePromptRequired {
+        /// The question borg asked
+        prompt: String,
+    },
+    /// The key file of the repository is missing, invalid or doesn't match
+    #[error("{0}")]
+    Keyfile(KeyfileError),
+}
+
+/// The errors that can be returned from [crate::sync::benchmark_crud]
+#[derive(Debug, Error)]
+pub enum BenchmarkError {
+    /// An unknown error occurred
+    #[error("Unknown error occurred: {0}")]
+    Unknown(String),
+    /// Error while splitting the arguments
+    #[error("error while splitting the arguments")]
+    ShlexError,
+    /// The command failed to execute
+    #[error("The command failed to execute: {0}")]
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
+    /// Invalid borg output found
+    #[error("Could not read borg output: {0}")]
+    InvalidBorgOutput(io::Error),
+    /// A line of the benchmark table couldn't be parsed
+    #[error("Could not parse benchmark output: {0}")]
+    InvalidBenchmarkOutput(String),
+    /// Borg was terminated by a signal
+    #[error("Borg was terminated by a signal")]
+    TerminatedBySignal,
+    /// An unexpected message id was received
+    #[error("An unexpected message id was received: {0}")]
+    UnexpectedMessageId(MessageId),
+    /// The specified repository does not exist
+    #[error("The repository does not exist")]
+    RepositoryDoesNotExist,
+    /// The provided passphrase was incorrect
+    #[error("The provided passphrase was incorrect")]
+    PassphraseWrong,
+    /// The repository path is not allowed by the `borg serve` process
+    #[error("The repository path is not allowed")]
+    PathNotAllowed,
+    /// Borg asked a question, which requires an interactive answer.
+    ///
+    /// Some questions can be answered in advance, e.g. with
+    /// [CommonOptions::relocated_repo_access_ok](crate::common::CommonOptions::relocated_repo_access_ok).
+    #[error("Borg asked a question that requires an interactive answer: {prompt}")]
+    InteractivePromptRequired {
+        /// The question borg asked
+        prompt: String,
+    },
+    /// The key file of the repository is missing, invalid or doesn't match
+    #[error("{0}")]
+    Keyfile(KeyfileError),
+}
+
+/// The errors that can be returned from [crate::sync::serve]
+#[derive(Debug, Error)]
+pub enum ServeError {
+    /// An unknown error occurred
+    #[error("Unknown error occurred: {0}")]
+    Unknown(String),
+    /// Error while splitting the arguments
+    #[error("error while splitting the arguments")]
+    ShlexError,
+    /// The command failed to execute
+    #[error("The command failed to execute: {0}")]
+    CommandFailed(io::Error),
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
+    /// Invalid borg output found
+    #[error("Could not read borg output: {0}")]
+    InvalidBorgOutput(io::Error),
+    /// Borg was terminated by a signal
+    #[error("Borg was terminated by a signal")]
+    TerminatedBySignal,
+    /// Piping from stdout or stderr failed
+    #[error("Piping from stdout or stderr failed")]
+    PipeFailed,
+    /// An unexpected message id was received
+    #[error("An unexpected message id was received: {0}")]
+    UnexpectedMessageId(MessageId),
+}
+
+/// The errors that can be returned from [crate::sync::info]
+#[derive(Debug, Error)]
+pub enum InfoError {
+    /// An unknown error occurred
+    #[error("Unknown error occurred: {0}")]
+    Unknown(String),
+    /// Error while splitting the arguments
+    #[error("error while splitting the arguments")]
+    ShlexError,
+    /// The command failed to execute
+    #[error("The command failed to execute: {0}")]
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
+    /// Invalid borg output found
+    #[error("Could not read borg output: {0}")]
+    InvalidBorgOutput(io::Error),
+    /// Error while deserializing output of borg
+    #[error("Error while deserializing borg output: {0}")]
+    DeserializeError(#[from] serde_json::Error),
+    /// Borg was terminated by a signal
+    #[error("Borg was terminated by a signal")]
+    TerminatedBySignal,
+    /// An unexpected message id was received
+    #[error("An unexpected message id was received: {0}")]
+    UnexpectedMessageId(MessageId),
+    /// The specified repository does not exist
+    #[error("The repository does not exist")]
+    RepositoryDoesNotExist,
+    /// The specified archive does not exist
+    #[error("The archive does not exist")]
+    ArchiveDoesNotExist,
+    /// The provided passphrase was incorrect
+    #[error("The provided passphrase was incorrect")]
+    PassphraseWrong,
+    /// Borg asked a question, which requires an interactive answer.
+    ///
+    /// Some questions can be answered in advance, e.g. with
+    /// [CommonOptions::relocated_repo_access_ok](crate::common::CommonOptions::relocated_repo_access_ok).
+    #[error("Borg asked a question that requires an interactive answer: {prompt}")]
+    InteractivePromptRequired {
+        /// The question borg asked
+        prompt: String,
+    },
+    /// The key file of the repository is missing, invalid or doesn't match
+    #[error("{0}")]
+    Keyfile(KeyfileError),
+}
+
+/// The errors that can be returned from [crate::sync::delete]
+#[derive(Debug, Error)]
+pub enum DeleteError {
+    /// An unknown error occurred
+    #[error("Unknown error occurred: {0}")]
+    Unknown(String),
+    /// Error while splitting the arguments
+    #[error("error while splitting the arguments")]
+    ShlexError,
+    /// The command failed to execute
+    #[error("The command failed to execute: {0}")]
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
+    /// Invalid borg output found
+    #[error("Could not read borg output: {0}")]
+    InvalidBorgOutput(io::Error),
+    /// Error while deserializing output of borg
+    #[error("Error while deserializing borg output: {0}")]
+    DeserializeError(#[from] serde_json::Error),
+    /// Borg was terminated by a signal
+    #[error("Borg was terminated by a signal")]
+    TerminatedBySignal,
+    /// An unexpected message id was received
+    #[error("An unexpected message id was received: {0}")]
+    UnexpectedMessageId(MessageId),
+    /// The specified repository does not exist
+    #[error("The repository does not exist")]
+    RepositoryDoesNotExist,
+    /// The specified archive does not exist
+    #[error("The archive does not exist")]
+    ArchiveDoesNotExist,
+    /// The provided passphrase was incorrect
+    #[error("The provided passphrase was incorrect")]
+    PassphraseWrong,
+    /// Borg asked a question, which requires an interactive answer.
+    ///
+    /// Some questions can be answered in advance, e.g. with
+    /// [CommonOptions::relocated_repo_access_ok](crate::common::CommonOptions::relocated_repo_access_ok).
+    #[error("Borg asked a question that requires an interactive answer: {prompt}")]
+    InteractivePromptRequired {
+        /// The question borg asked
+        prompt: String,
+    },
+    /// The key file of the repository is missing, invalid or doesn't match
+    #[error("{0}")]
+    Keyfile(KeyfileError),
+    /// Neither an archive nor a glob was selected, so borg would delete the whole repository,
+    /// see [DeleteOptions::archive](crate::common::DeleteOptions::archive)
+    #[error("Refusing to delete without selecting archives")]
+    NoArchivesSelected,
+    /// Both an archive and a glob were selected, but borg only accepts one of them
+    #[error("Either an archive or a glob can be selected, not both")]
+    ConflictingSelection,
+    /// The archive to delete is not in [DeleteOptions::repository](crate::common::DeleteOptions::repository)
+    #[error("The archive {0} is not in the repository to delete from")]
+    ArchiveNotInRepository(String),
+}
+
+/// The errors that can be returned from [crate::sync::set_comment]
+#[derive(Debug, Error)]
+pub enum RecreateError {
+    /// An unknown error occurred
+    #[error("Unknown error occurred: {0}")]
+    Unknown(String),
+    /// Error while splitting the arguments
+    #[error("error while splitting the arguments")]
+    ShlexError,
+    /// The command failed to execute
+    #[error("The command failed to execute: {0}")]
+    CommandFailed(io::Error),
+    /// Borg did not finish within [CommonOptions::timeout](crate::common::CommonOptions::timeout)
+    #[error("Borg did not finish within the timeout")]
+    Timeout,
+    /// The borg executable wasn't found in `PATH`
+    #[error("The borg executable was not found in PATH")]
+    BorgNotFound,
+    /// Invalid borg output found
+    #[error("Could not read borg output: {0}")]
+    InvalidBorgOutput(io::Error),
+    /// Error while deserializing output of borg
+    #[error("Error while deserializing borg output: {0}")]
+    DeserializeError(#[from] serde_json::Error),
+    /// Borg was terminated by a signal
+    #[error("Borg was terminated by a signal")]
+    TerminatedBySignal,
+    /// An unexpected message id was received
+    #[error("An unexpected message id was received: {0}")]
+    UnexpectedMessageId(MessageId),
+    /// The specified repository does not exist
+    #[error("The repository does not exist")]
+    RepositoryDoesNotExist,
+    /// The specified archive does not exist
+    #[error("The archive does not exist")]
+    ArchiveDoesNotExist,
+    /// The provided passphrase was incorrect
+    #[error("The provided passphrase was incorrect")]
+    PassphraseWrong,
+    /// Borg asked a question, which requires an interactive answer.
+    ///
+    /// Some questions can be answered in advance, e.g. with
+    /// [CommonOptions::relocated_repo_access_ok](crate::common::CommonOptions::relocated_repo_access_ok).
+    #[error("Borg asked a question that requires an interactive answer: {prompt}")]
+    InteractivePromptRequired {
+        /// The question borg asked
+        prompt: String,
+    },
+    /// The key file of the repository is missing, invalid or doesn't match
+    #[error("{0}")]
+    Keyfile(KeyfileError),
+}
+
+/// The errors that can be returned from [crate::sync::backup]
+#[derive(Debug, Error)]
+pub enum BackupError {
+    /// Creating the archive failed, prune was not run
+    #[error("Creating the archive failed: {0}")]
+    Create(#[from] CreateError),
+    /// Pruning failed after the archive was created
+    #[error("Pruning failed: {0}")]
+    Prune(#[from] PruneError),
+}
+
+impl_from_io_error!(
+    CompactError,
+    PruneError,
+    DeleteError,
+    MountError,
+    ListError,
+    InitError,
+    VersionError,
+    RawError,
+    TransferError,
+    ExtractError,
+    InfoError,
+    RecreateError,
+    ConfigError,
+    BenchmarkError
+);
+
+// borg serve runs until the client disconnects, so there is no timeout
+impl From<io::Error> for ServeError {
+    fn from(value: io::Error) -> Self {
+        if is_borg_not_found_error(&value) {
+            Self::BorgNotFound
+        } else {
+            Self::CommandFailed(value)
+        }
+    }
+}
+
+impl From<io::Error> for CreateError {
+    fn from(value: io::Error) -> Self {
+        match value.kind() {
+            io::ErrorKind::TimedOut => Self::Timeout,
+            io::ErrorKind::ArgumentListTooLong => Self::ArgumentListTooLong,
+            _ if is_borg_not_found_error(&value) => Self::BorgNotFound,
+            _ => Self::CommandFailed(value),
+        }
+    }
 }
@@ -17,6 +17,11 @@
 //!
 //! ## Features
 //! - `tokio`: provides the [asynchronous] module
+//! - `metrics`: provides the `metrics` module with flat metrics derived from the output of borg
+//! - `tracing`: emits a span for every borg command and the messages of borg as structured
+//!   [tracing](https://docs.rs/tracing) events instead of passing them to the `log` crate.
+//!   The `*_raw` variants of the commands don't emit a span, as the commands parsing their
+//!   output call them and would emit the span twice otherwise.
 //!
 #![warn(missing_docs)]
 #![cfg_attr(all(doc, CHANNEL_NIGHTLY), feature(doc_auto_cfg))]
@@ -25,6 +30,10 @@
 pub mod asynchronous;
 pub mod common;
 pub mod errors;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod output;
 pub mod sync;
+#[cfg(test)]
+pub(crate) mod test_utils;
 pub(crate) mod utils;
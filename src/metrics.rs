@@ -0,0 +1,100 @@
+//! Flat metrics derived from the output of borg, e.g. to export them as Prometheus gauges
+
+use serde::{Deserialize, Serialize};
+
+use crate::output::create::Create;
+use crate::output::info::{ArchiveStats, Info, InfoArchive};
+
+/// The metrics of a single archive
+///
+/// The sizes are taken from the stats of the archive, not from the stats of the cache,
+/// which cover the whole repository.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct BackupMetrics {
+    /// Size of files and metadata before compression
+    pub original_bytes: u64,
+    /// Size after compression
+    pub compressed_bytes: u64,
+    /// Size after compression and deduplication, i.e. the size added to the repository
+    pub deduplicated_bytes: u64,
+    /// Number of regular files in the archive
+    pub nfiles: u64,
+    /// Duration between start and end of the archive in seconds
+    pub duration_seconds: f64,
+    /// The size after compression relative to the original size,
+    /// see [ArchiveStats::compression_ratio]
+    pub compression_ratio: f64,
+}
+
+impl BackupMetrics {
+    /// The metrics of the archive created by borg create
+    pub fn from_create(create: &Create) -> Self {
+        Self::from_stats(&create.archive.stats, create.archive.duration)
+    }
+
+    /// The metrics of an archive reported by borg info
+    pub fn from_info_archive(archive: &InfoArchive) -> Self {
+        Self::from_stats(&archive.stats, archive.duration)
+    }
+
+    /// The metrics of every archive reported by borg info
+    ///
+    /// Returns an empty list for [Info::Repository], as it doesn't contain archives.
+    pub fn from_info(info: &Info) -> Vec<Self> {
+        match info {
+            Info::Repository { .. } => Vec::new(),
+            Info::Archives { archives, .. } => {
+                archives.iter().map(Self::from_info_archive).collect()
+            }
+        }
+    }
+
+    fn from_stats(stats: &ArchiveStats, duration: f64) -> Self {
+        Self {
+            original_bytes: stats.original_size,
+            compressed_bytes: stats.compressed_size,
+            deduplicated_bytes: stats.deduplicated_size,
+            nfiles: stats.nfiles,
+            duration_seconds: duration,
+            compression_ratio: stats.compression_ratio(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::BackupMetrics;
+    use crate::output::create::Create;
+
+    #[test]
+    fn test_backup_metrics_from_create() {
+        let create: Create = serde_json::from_str(
+            r#"{
+    "archive": {
+        "command_line": ["borg", "create", "--json", "/tmp/repo::archive", "/etc"],
+        "duration": 0.5,
+        "end": "2023-01-02T10:00:00.500000",
+        "id": "0b1e",
+        "limits": {"max_archive_size": 0.00005},
+        "name": "archive",
+        "start": "2023-01-02T10:00:00.000000",
+        "stats": {"compressed_size": 1000, "deduplicated_size": 250, "nfiles": 3, "original_size": 4000}
+    },
+    "repository": {"id": "e2ff", "last_modified": "2023-01-02T10:00:00.000000", "location": "/tmp/repo"}
+}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            BackupMetrics::from_create(&create),
+            BackupMetrics {
+                original_bytes: 4000,
+                compressed_bytes: 1000,
+                deduplicated_bytes: 250,
+                nfiles: 3,
+                duration_seconds: 0.5,
+                compression_ratio: 0.25,
+            }
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! The definition of the borg benchmark crud output
+
+use serde::{Deserialize, Serialize};
+
+/// The operation measured by borg benchmark crud
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BenchmarkOperation {
+    /// Creating an archive (`C`)
+    Create,
+    /// Extracting an archive (`R`)
+    Read,
+    /// Creating an archive of unchanged files (`U`)
+    Update,
+    /// Deleting an archive (`D`)
+    Delete,
+}
+
+/// A single line of the benchmark, e.g.
+/// `C-Z-BIG         116.06 MB/s (10 * 100.00 MB all-zero files: 8.62s)`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BenchmarkMeasurement {
+    /// The measured operation
+    pub operation: BenchmarkOperation,
+    /// The name of the test, e.g. `Z-BIG`
+    pub test: String,
+    /// Whether the files had random content, otherwise they only contained zeros
+    pub random: bool,
+    /// The throughput in MB/s
+    pub throughput: f64,
+    /// The number of files
+    pub file_count: u64,
+    /// The size of each file as formatted by borg, e.g. `100.00 MB`
+    pub file_size: String,
+    /// The duration of the operation in seconds
+    pub duration: f64,
+}
+
+impl BenchmarkMeasurement {
+    /// Parse a line of the output of borg benchmark crud
+    pub(crate) fn parse(line: &str) -> Option<Self> {
+        let (name, rest) = line.trim().split_once(' ')?;
+        let (operation, test) = name.split_once('-')?;
+        let operation = match operation {
+            "C" => BenchmarkOperation::Create,
+            "R" => BenchmarkOperation::Read,
+            "U" => BenchmarkOperation::Update,
+            "D" => BenchmarkOperation::Delete,
+            _ => return None,
+        };
+
+        let (throughput, rest) = rest.trim_start().split_once(" MB/s (")?;
+        let (file_count, rest) = rest.split_once(" * ")?;
+        let (rest, duration) = rest.strip_suffix("s)")?.rsplit_once(" files: ")?;
+        let (file_size, content) = rest.rsplit_once(' ')?;
+        let random = match content {
+            "random" => true,
+            "all-zero" => false,
+            _ => return None,
+        };
+
+        Some(Self {
+            operation,
+            test: test.to_string(),
+            random,
+            throughput: throughput.parse().ok()?,
+            file_count: file_count.parse().ok()?,
+            file_size: file_size.to_string(),
+            duration: duration.parse().ok()?,
+        })
+    }
+}
+
+/// The result of borg benchmark crud
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BenchmarkResult {
+    /// The measurements in the order reported by borg
+    pub measurements: Vec<BenchmarkMeasurement>,
+}
+
+impl BenchmarkResult {
+    /// Retrieve the measurement of an operation of a test, e.g. `Z-BIG`
+    pub fn get(&self, operation: BenchmarkOperation, test: &str) -> Option<&BenchmarkMeasurement> {
+        self.measurements
+            .iter()
+            .find(|x| x.operation == operation && x.test == test)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::benchmark::{BenchmarkMeasurement, BenchmarkOperation};
+
+    #[test]
+    fn test_parse_measurement() {
+        let measurement = BenchmarkMeasurement::parse(
+            "C-Z-BIG         116.06 MB/s (10 * 100.00 MB all-zero files: 8.62s)",
+        )
+        .unwrap();
+        assert_eq!(
+            measurement,
+            BenchmarkMeasurement {
+                operation: BenchmarkOperation::Create,
+                test: "Z-BIG".to_string(),
+                random: false,
+                throughput: 116.06,
+                file_count: 10,
+                file_size: "100.00 MB".to_string(),
+                duration: 8.62,
+            }
+        );
+
+        let measurement = BenchmarkMeasurement::parse(
+            "D-R-SMALL        36.47 MB/s (10000 * 10.00 kB random files: 2.74s)",
+        )
+        .unwrap();
+        assert_eq!(measurement.operation, BenchmarkOperation::Delete);
+        assert_eq!(measurement.test, "R-SMALL");
+        assert!(measurement.random);
+        assert_eq!(measurement.file_size, "10.00 kB");
+
+        assert!(BenchmarkMeasurement::parse("").is_none());
+        assert!(
+            BenchmarkMeasurement::parse("X-Z-BIG 1.00 MB/s (1 * 1 B all-zero files: 1.00s)")
+                .is_none()
+        );
+        assert!(BenchmarkMeasurement::parse("C-Z-BIG 1.00 MB/s").is_none());
+    }
+}
@@ -1,7 +1,9 @@
 //! Common types that are used throughout the API are defined in this module
 
-use chrono::NaiveDateTime;
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::output::info::ratio;
 
 /// Information about the repository
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -12,6 +14,7 @@ pub struct Repository {
     /// from what is specified on the command line
     pub location: String,
     /// Date when the repository was last modified by the Borg client
+    #[serde(deserialize_with = "deserialize_timestamp")]
     pub last_modified: NaiveDateTime,
 }
 
@@ -103,6 +106,16 @@ pub struct CacheStats {
     pub unique_size: u64,
 }
 
+impl CacheStats {
+    /// The uncompressed size of all unique chunks relative to the total size
+    /// of all chunks multiplied with their reference counts
+    ///
+    /// Returns `0.0` if the total size is zero.
+    pub fn dedup_ratio(&self) -> f64 {
+        ratio(self.unique_size, self.total_size)
+    }
+}
+
 /// Object describing the utilization of Borg limits
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub struct Limits {
@@ -110,3 +123,183 @@ pub struct Limits {
     /// to the maximum size allowed by Borg
     pub max_archive_size: f64,
 }
+
+/// Parse a size formatted by borg, e.g. `-1.23 MB`, into bytes.
+///
+/// borg uses decimal prefixes, the sign is ignored.
+pub(crate) fn parse_file_size(size: &str, unit: &str) -> Option<u64> {
+    let size: f64 = size.trim_start_matches('-').parse().ok()?;
+    let exponent = match unit.strip_suffix('B')? {
+        "" => 0,
+        "k" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        "P" => 5,
+        "E" => 6,
+        _ => return None,
+    };
+    Some((size * 1000f64.powi(exponent)).round() as u64)
+}
+
+/// Parse a timestamp emitted by borg
+///
+/// Borg emits local timestamps with or without fractional seconds,
+/// e.g. `2023-01-02T10:00:00` or `2023-01-02T10:00:00.302352`.
+/// Timezone-aware timestamps like `2023-01-02T10:00:00.302352+00:00` are
+/// converted to UTC.
+pub(crate) fn parse_timestamp(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+        .ok()
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|timestamp| timestamp.naive_utc())
+        })
+}
+
+/// Convert a timestamp parsed by [parse_timestamp] to UTC
+///
+/// If `assume_utc` isn't set, the timestamp is interpreted as local time,
+/// see [timestamp_to_local].
+pub(crate) fn timestamp_to_utc(timestamp: NaiveDateTime, assume_utc: bool) -> DateTime<Utc> {
+    if assume_utc {
+        return Utc.from_utc_datetime(&timestamp);
+    }
+    timestamp_to_local(timestamp, false).with_timezone(&Utc)
+}
+
+/// Convert a timestamp parsed by [parse_timestamp] to local time
+///
+/// If `assume_utc` isn't set, the timestamp is interpreted as local time.
+/// Local times that occur twice, as the clock was set back at the end of daylight saving time,
+/// resolve to the earlier one. Local times that were skipped, as the clock was set forward,
+/// can't be emitted by a borg running in the same timezone and are interpreted as UTC.
+pub(crate) fn timestamp_to_local(timestamp: NaiveDateTime, assume_utc: bool) -> DateTime<Local> {
+    if assume_utc {
+        return Local.from_utc_datetime(&timestamp);
+    }
+    Local
+        .from_local_datetime(&timestamp)
+        .earliest()
+        .unwrap_or_else(|| Local.from_utc_datetime(&timestamp))
+}
+
+/// Deserialize a timestamp emitted by borg, see [parse_timestamp]
+pub(crate) fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    parse_timestamp(&value)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid timestamp: {value}")))
+}
+
+/// Deserialize an optional timestamp emitted by borg, see [parse_timestamp]
+///
+/// Fields using this need `#[serde(default)]` to allow the field to be absent.
+pub(crate) fn deserialize_optional_timestamp<'de, D>(
+    deserializer: D,
+) -> Result<Option<NaiveDateTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(value) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    parse_timestamp(&value)
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid timestamp: {value}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Utc};
+
+    use super::{parse_timestamp, timestamp_to_local, timestamp_to_utc, CacheStats, Repository};
+
+    #[test]
+    fn test_cache_dedup_ratio() {
+        let mut stats = CacheStats {
+            total_chunks: 330,
+            total_csize: 1024155,
+            total_size: 4000,
+            total_unique_chunks: 320,
+            unique_csize: 1011890,
+            unique_size: 1000,
+        };
+        assert_eq!(stats.dedup_ratio(), 0.25);
+
+        stats.total_size = 0;
+        stats.unique_size = 0;
+        assert_eq!(stats.dedup_ratio(), 0.0);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+
+        assert_eq!(
+            parse_timestamp("2023-01-02T10:00:00"),
+            date.and_hms_opt(10, 0, 0)
+        );
+        assert_eq!(
+            parse_timestamp("2023-01-02T10:00:00.302352"),
+            date.and_hms_micro_opt(10, 0, 0, 302352)
+        );
+        assert_eq!(
+            parse_timestamp("2023-01-02T10:00:00.302352+00:00"),
+            date.and_hms_micro_opt(10, 0, 0, 302352)
+        );
+        assert_eq!(
+            parse_timestamp("2023-01-02T12:00:00+02:00"),
+            date.and_hms_opt(10, 0, 0)
+        );
+        assert_eq!(parse_timestamp("Mon, 2023-01-02 10:00:00"), None);
+    }
+
+    #[test]
+    fn test_deserialize_repository_timestamps() {
+        for last_modified in [
+            "2023-01-02T10:00:00",
+            "2023-01-02T10:00:00.000000",
+            "2023-01-02T10:00:00.000000+00:00",
+        ] {
+            let repository: Repository = serde_json::from_str(&format!(
+                r#"{{"id": "e2ff", "location": "/tmp/repo", "last_modified": "{last_modified}"}}"#
+            ))
+            .unwrap();
+            assert_eq!(
+                repository.last_modified,
+                NaiveDate::from_ymd_opt(2023, 1, 2)
+                    .unwrap()
+                    .and_hms_opt(10, 0, 0)
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_timestamp_conversion() {
+        let timestamp = parse_timestamp("2023-01-02T10:00:00.302352").unwrap();
+
+        let utc = timestamp_to_utc(timestamp, true);
+        assert_eq!(utc.naive_utc(), timestamp);
+        assert_eq!(timestamp_to_local(timestamp, true).with_timezone(&Utc), utc);
+
+        // Without the assumption, the timestamp is in the local time of the host running the tests
+        let local = timestamp_to_local(timestamp, false);
+        assert_eq!(local.naive_local(), timestamp);
+        assert_eq!(
+            timestamp_to_utc(timestamp, false),
+            local.with_timezone(&Utc)
+        );
+
+        // Timezone-aware timestamps are parsed as UTC
+        let timestamp = parse_timestamp("2023-01-02T12:00:00+02:00").unwrap();
+        assert_eq!(
+            timestamp_to_utc(timestamp, true).to_rfc3339(),
+            "2023-01-02T10:00:00+00:00"
+        );
+    }
+}
@@ -0,0 +1,57 @@
+//! The definition of the compact command output
+
+use serde::{Deserialize, Serialize};
+
+use crate::output::common::parse_file_size;
+
+/// The result of a borg compact call.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompactReport {
+    /// The repository space that was freed, in bytes.
+    ///
+    /// If [CompactOptions::dry_run](crate::common::CompactOptions::dry_run) is set,
+    /// this is the space that would have been freed.
+    /// borg only reports it rounded to two decimals of its unit, e.g. `1.23 GB`,
+    /// so it is an approximation. It is absent if borg didn't report it,
+    /// which it only does with [LogLevel::Info](crate::common::LogLevel::Info) or lower.
+    pub freed_size: Option<u64>,
+}
+
+impl CompactReport {
+    /// Add a message of the `borg.repository` logger.
+    ///
+    /// Only the summary of the compaction is used, which has the format
+    /// `compaction freed about <size> repository space.`, e.g.
+    /// `compaction freed about 1.23 MB repository space.`
+    pub(crate) fn add_log_message(&mut self, message: &str) {
+        let Some((_, rest)) = message.split_once("freed about ") else {
+            return;
+        };
+
+        let freed_size = match rest.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [size, unit, ..] => parse_file_size(size, unit),
+            _ => None,
+        };
+        if freed_size.is_some() {
+            self.freed_size = freed_size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompactReport;
+
+    #[test]
+    fn test_add_log_message() {
+        let mut report = CompactReport::default();
+        report.add_log_message("compacting segments");
+        assert_eq!(report.freed_size, None);
+
+        report.add_log_message("compaction freed about 1.23 MB repository space.");
+        assert_eq!(report.freed_size, Some(1_230_000));
+
+        report.add_log_message("compaction freed about 0 B repository space.");
+        assert_eq!(report.freed_size, Some(0));
+    }
+}
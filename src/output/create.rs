@@ -1,11 +1,34 @@
 //! The definitions of the borg create command
 
+use std::fmt::{Display, Formatter};
+
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::output::common::{Cache, Encryption, Limits, Repository};
-use crate::output::info::ArchiveStats;
+use crate::output::common::{deserialize_timestamp, Cache, Encryption, Limits, Repository};
+use crate::output::info::{ArchiveStats, Info};
+
+/// The result of [crate::sync::create_idempotent]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum CreateOutcome {
+    /// The archive was created, see [crate::sync::create]
+    Created(Option<Create>),
+    /// The archive already existed, its information was retrieved with borg info
+    Existing(Info),
+}
+
+/// An item of the stream returned by [crate::asynchronous::create_stream]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum CreateEvent {
+    /// A progress update of borg
+    Progress(CreateProgress),
+    /// borg finished, this is the last item of the stream.
+    ///
+    /// Contains [None] if [CreateOptions::dry_run](crate::common::CreateOptions::dry_run) is set,
+    /// as no archive is created.
+    Finished(Option<Box<Create>>),
+}
 
 /// The output of a borg create command
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -20,6 +43,23 @@ pub struct Create {
     pub archive: CreateArchive,
 }
 
+impl Create {
+    /// The compressed and encrypted size of all unique chunks in the repository,
+    /// i.e. the size the repository occupies
+    ///
+    /// Returns [None] if borg didn't report the stats of the cache.
+    pub fn repository_size(&self) -> Option<u64> {
+        self.cache.as_ref().map(|cache| cache.stats.unique_csize)
+    }
+
+    /// The archive created by borg
+    ///
+    /// borg create always creates a single archive, so this is currently never [None].
+    pub fn archive(&self) -> Option<&CreateArchive> {
+        Some(&self.archive)
+    }
+}
+
 /// The archive output of a borg create command
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CreateArchive {
@@ -38,11 +78,145 @@ pub struct CreateArchive {
     /// The chunker parameters the archive has been created with.
     pub chunker_params: Option<Vec<Value>>,
     /// Start timestamp
+    #[serde(deserialize_with = "deserialize_timestamp")]
     pub start: NaiveDateTime,
     /// End timestamp.
+    #[serde(deserialize_with = "deserialize_timestamp")]
     pub end: NaiveDateTime,
     /// The stats of the archive.
     ///
     /// Archive statistics (freshly calculated, this is what makes “info” more expensive)
     pub stats: ArchiveStats,
 }
+
+/// The progress of a borg create command.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum CreateProgress {
+    /// The current progress of the archive
+    Progress {
+        /// The size of the original files
+        original_size: u64,
+        /// The size of the compressed files
+        compressed_size: u64,
+        /// The size of the deduplicated files
+        deduplicated_size: u64,
+        /// The current number of files
+        nfiles: u64,
+        /// The path to the current file
+        path: String,
+        /// Unix timestamp of the progress update
+        time: f64,
+    },
+    /// Finished the creation of the archive
+    Finished,
+}
+
+impl Display for CreateProgress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CreateProgress::Progress {
+                original_size,
+                compressed_size,
+                deduplicated_size,
+                nfiles,
+                path,
+                ..
+            } => {
+                write!(
+                    f,
+                    "O {original_size} C {compressed_size} D {deduplicated_size} N {nfiles} {path}",
+                )
+            }
+            CreateProgress::Finished => write!(f, "Finished"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Create;
+
+    /// Output of `borg create --json` of borg 1.2
+    const CREATE_SAMPLE: &str = r#"{
+    "archive": {
+        "command_line": [
+            "/usr/bin/borg",
+            "create",
+            "--json",
+            "/tmp/repo::host-2023-01-02",
+            "/etc"
+        ],
+        "duration": 0.412881,
+        "end": "2023-01-02T10:00:00.715233",
+        "id": "0b1e1ac2a3fbe6f3d3a5f6e1c9b8c7a3f1e2d3c4b5a6978877665544332211aa",
+        "limits": {
+            "max_archive_size": 0.00005
+        },
+        "name": "host-2023-01-02",
+        "start": "2023-01-02T10:00:00.302352",
+        "stats": {
+            "compressed_size": 1024155,
+            "deduplicated_size": 1024155,
+            "nfiles": 312,
+            "original_size": 2862304
+        }
+    },
+    "cache": {
+        "path": "/root/.cache/borg/e2ffc4d6e1d5e4a5e0a1e2b8c7d9f0a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6e7",
+        "stats": {
+            "total_chunks": 330,
+            "total_csize": 1024155,
+            "total_size": 2862304,
+            "total_unique_chunks": 320,
+            "unique_csize": 1011890,
+            "unique_size": 2831215
+        }
+    },
+    "encryption": {
+        "mode": "repokey"
+    },
+    "repository": {
+        "id": "e2ffc4d6e1d5e4a5e0a1e2b8c7d9f0a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6e7",
+        "last_modified": "2023-01-02T10:00:00.000000",
+        "location": "/tmp/repo"
+    }
+}"#;
+
+    #[test]
+    fn test_deserialize_create() {
+        let create: Create = serde_json::from_str(CREATE_SAMPLE).unwrap();
+
+        assert_eq!(create.repository.location, "/tmp/repo");
+        assert_eq!(create.archive.name, "host-2023-01-02");
+        assert_eq!(create.archive.stats.nfiles, 312);
+        assert_eq!(create.archive.stats.original_size, 2862304);
+        assert_eq!(create.archive.stats.compressed_size, 1024155);
+        assert_eq!(create.archive.stats.deduplicated_size, 1024155);
+        assert_eq!(create.repository_size(), Some(1011890));
+        assert_eq!(
+            create.archive().map(|archive| archive.name.as_str()),
+            Some("host-2023-01-02")
+        );
+        assert_eq!(create.cache.unwrap().stats.total_unique_chunks, 320);
+        assert!(create.archive.chunker_params.is_none());
+    }
+
+    #[test]
+    fn test_create_round_trip() {
+        let create: Create = serde_json::from_str(CREATE_SAMPLE).unwrap();
+        let serialized = serde_json::to_string(&create).unwrap();
+        let round_trip: Create = serde_json::from_str(&serialized).unwrap();
+
+        assert_eq!(round_trip.archive.id, create.archive.id);
+        assert_eq!(round_trip.archive.start, create.archive.start);
+        assert_eq!(round_trip.archive.end, create.archive.end);
+        assert_eq!(
+            round_trip.archive.stats.original_size,
+            create.archive.stats.original_size
+        );
+        assert_eq!(
+            round_trip.repository.last_modified,
+            create.repository.last_modified
+        );
+    }
+}
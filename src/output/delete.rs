@@ -0,0 +1,142 @@
+//! The definition of the delete command output
+
+use serde::{Deserialize, Serialize};
+
+use crate::output::common::parse_file_size;
+
+/// The archives that were deleted by a borg delete call.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DeleteReport {
+    /// The names of the archives that were deleted
+    ///
+    /// If [DeleteOptions::dry_run](crate::common::DeleteOptions::dry_run) is set,
+    /// these are the archives that would have been deleted.
+    pub deleted: Vec<String>,
+    /// The statistics of the deleted data
+    ///
+    /// This is only populated if [DeleteOptions::stats](crate::common::DeleteOptions::stats)
+    /// is set.
+    pub stats: Option<DeleteStats>,
+}
+
+/// The statistics of a borg delete call
+///
+/// The space is only freed in the repository once
+/// [compact](crate::sync::compact) is run, until then the deletion is only logical.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeleteStats {
+    /// The number of deleted archives
+    pub deleted_archives: u64,
+    /// The deduplicated size of the deleted data, in bytes.
+    ///
+    /// This is the space that is freed by compact. borg only reports it rounded to
+    /// two decimals of its unit, e.g. `1.23 GB`, so it is an approximation.
+    pub freed_size: u64,
+}
+
+impl DeleteReport {
+    /// Add the archive of a message of the `borg.output.list` logger.
+    ///
+    /// The message has the format `<action>: <name> <timestamp> [<id>] (<n>/<total>)`, e.g.
+    /// `Deleting archive: host-2023-01-02   Mon, 2023-01-02 10:00:00 [ab12...] (1/2)`
+    pub(crate) fn add_list_message(&mut self, message: &str) {
+        let Some(rest) = message
+            .strip_prefix("Deleting archive:")
+            .or_else(|| message.strip_prefix("Would delete archive:"))
+        else {
+            return;
+        };
+
+        // Strip the counter, the id and the timestamp, which consists of 3 words
+        let Some((rest, _)) = rest.trim().rsplit_once(" [") else {
+            return;
+        };
+        let Some(name) = rest.rsplitn(4, ' ').nth(3) else {
+            return;
+        };
+
+        self.deleted.push(name.trim().to_string());
+    }
+
+    /// Add a message of the `borg.output.stats` logger.
+    ///
+    /// Only the line with the sizes of the deleted data is used, which has the format
+    /// `Deleted data: <original size> <compressed size> <deduplicated size>`, e.g.
+    /// `Deleted data:   -1.23 MB   -800.00 kB   -500.00 kB`
+    pub(crate) fn add_stats_message(&mut self, message: &str) {
+        let Some(sizes) = message.strip_prefix("Deleted data:") else {
+            return;
+        };
+
+        let sizes: Vec<_> = sizes.split_whitespace().collect();
+        let freed_size = match sizes.as_slice() {
+            [.., size, unit] => parse_file_size(size, unit),
+            _ => None,
+        };
+        let Some(freed_size) = freed_size else {
+            return;
+        };
+
+        self.stats = Some(DeleteStats {
+            deleted_archives: self.deleted.len() as u64,
+            freed_size,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeleteReport, DeleteStats};
+
+    #[test]
+    fn test_add_list_message() {
+        let mut report = DeleteReport::default();
+        report.add_list_message(
+            "Deleting archive: host-2023-01-01                      Sun, 2023-01-01 10:00:00 [3a2cde5e7b0b7d61] (1/3)",
+        );
+        report.add_list_message(
+            "Would delete archive: host 2022-12-31                  Sat, 2022-12-31 10:00:00 [9f1b7c2e5d3a8b40] (2/3)",
+        );
+        report.add_list_message("Deleting archive: a-very-long-archive-name-that-exceeds-the-padding Sat, 2022-12-31 09:00:00 [1f1b7c2e5d3a8b40] (3/3)");
+        report.add_list_message("Unrelated message");
+
+        assert_eq!(
+            report.deleted,
+            [
+                "host-2023-01-01",
+                "host 2022-12-31",
+                "a-very-long-archive-name-that-exceeds-the-padding"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_stats_message() {
+        let mut report = DeleteReport::default();
+        report.add_list_message(
+            "Deleting archive: host-2023-01-01                      Sun, 2023-01-01 10:00:00 [3a2cde5e7b0b7d61] (1/2)",
+        );
+        report.add_list_message(
+            "Deleting archive: host-2023-01-02                      Mon, 2023-01-02 10:00:00 [0b1e1ac2a3fbe6f3] (2/2)",
+        );
+        report.add_stats_message(
+            "                       Original size      Compressed size    Deduplicated size",
+        );
+        assert!(report.stats.is_none());
+
+        report.add_stats_message(
+            "Deleted data:                 -2.41 GB             -1.20 GB            -512.50 MB",
+        );
+        assert_eq!(
+            report.stats,
+            Some(DeleteStats {
+                deleted_archives: 2,
+                freed_size: 512_500_000
+            })
+        );
+
+        let mut report = DeleteReport::default();
+        report.add_stats_message("Deleted data:   -10.00 B   -10.00 B   0.00 B");
+        assert_eq!(report.stats.map(|x| x.freed_size), Some(0));
+    }
+}
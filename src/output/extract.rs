@@ -0,0 +1,15 @@
+//! Output from the borg extract command
+
+use serde::{Deserialize, Serialize};
+
+/// A file whose extracted size differs from the size recorded in the archive,
+/// see [ExtractOptions::verify_sizes](crate::common::ExtractOptions::verify_sizes)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SizeMismatch {
+    /// Path of the file relative to the destination
+    pub path: String,
+    /// The size recorded in the archive
+    pub expected: u64,
+    /// The size of the extracted file, [None] if it is missing
+    pub actual: Option<u64>,
+}
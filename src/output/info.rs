@@ -4,7 +4,7 @@ use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::output::common::{Cache, Encryption, Limits, Repository};
+use crate::output::common::{deserialize_timestamp, Cache, Encryption, Limits, Repository};
 
 /// The output of a borg info call.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -33,6 +33,46 @@ pub enum Info {
     },
 }
 
+/// The output of borg info for a repository, see [Info::Repository]
+#[derive(Deserialize)]
+pub(crate) struct InfoRepositoryOutput {
+    repository: Repository,
+    cache: Option<Cache>,
+    encryption: Option<Encryption>,
+    security_dir: Option<String>,
+}
+
+impl From<InfoRepositoryOutput> for Info {
+    fn from(value: InfoRepositoryOutput) -> Self {
+        Info::Repository {
+            repository: value.repository,
+            cache: value.cache,
+            encryption: value.encryption,
+            security_dir: value.security_dir,
+        }
+    }
+}
+
+/// The output of borg info for archives, see [Info::Archives]
+#[derive(Deserialize)]
+pub(crate) struct InfoArchivesOutput {
+    repository: Repository,
+    cache: Option<Cache>,
+    encryption: Option<Encryption>,
+    archives: Vec<InfoArchive>,
+}
+
+impl From<InfoArchivesOutput> for Info {
+    fn from(value: InfoArchivesOutput) -> Self {
+        Info::Archives {
+            repository: value.repository,
+            cache: value.cache,
+            encryption: value.encryption,
+            archives: value.archives,
+        }
+    }
+}
+
 /// Information about an archive
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct InfoArchive {
@@ -51,8 +91,10 @@ pub struct InfoArchive {
     /// The chunker parameters the archive has been created with.
     pub chunker_params: Vec<Value>,
     /// Start timestamp
+    #[serde(deserialize_with = "deserialize_timestamp")]
     pub start: NaiveDateTime,
     /// End timestamp.
+    #[serde(deserialize_with = "deserialize_timestamp")]
     pub end: NaiveDateTime,
     /// The stats of the archive.
     ///
@@ -78,3 +120,53 @@ pub struct ArchiveStats {
     /// Size of files and metadata before compression
     pub original_size: u64,
 }
+
+impl ArchiveStats {
+    /// The size after compression relative to the original size
+    ///
+    /// Returns `0.0` if the original size is zero.
+    pub fn compression_ratio(&self) -> f64 {
+        ratio(self.compressed_size, self.original_size)
+    }
+
+    /// The deduplicated size relative to the original size
+    ///
+    /// Returns `0.0` if the original size is zero.
+    pub fn dedup_ratio(&self) -> f64 {
+        ratio(self.deduplicated_size, self.original_size)
+    }
+}
+
+/// Divide `part` by `total`, yielding `0.0` for an empty `total`
+pub(crate) fn ratio(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    part as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ArchiveStats;
+
+    #[test]
+    fn test_archive_ratios() {
+        let stats = ArchiveStats {
+            compressed_size: 500,
+            deduplicated_size: 250,
+            nfiles: 3,
+            original_size: 1000,
+        };
+        assert_eq!(stats.compression_ratio(), 0.5);
+        assert_eq!(stats.dedup_ratio(), 0.25);
+
+        let empty = ArchiveStats {
+            compressed_size: 0,
+            deduplicated_size: 0,
+            nfiles: 0,
+            original_size: 0,
+        };
+        assert_eq!(empty.compression_ratio(), 0.0);
+        assert_eq!(empty.dedup_ratio(), 0.0);
+    }
+}
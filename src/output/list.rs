@@ -3,7 +3,9 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
-use crate::output::common::{Encryption, Repository};
+use crate::output::common::{
+    deserialize_optional_timestamp, deserialize_timestamp, Encryption, Repository,
+};
 
 /// Output of the borg list command
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -17,6 +19,9 @@ pub struct ListRepository {
 }
 
 /// The short output version of the archive
+///
+/// The optional fields are only present if the corresponding placeholder
+/// was requested with [ListOptions::format](crate::common::ListOptions::format).
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ListArchive {
     /// Hexadecimal archive ID
@@ -24,5 +29,127 @@ pub struct ListArchive {
     /// Name of the archive
     pub name: String,
     /// Start timestamp
+    #[serde(deserialize_with = "deserialize_timestamp")]
     pub start: NaiveDateTime,
+    /// End timestamp
+    #[serde(default, deserialize_with = "deserialize_optional_timestamp")]
+    pub end: Option<NaiveDateTime>,
+    /// Archive comment
+    pub comment: Option<String>,
+    /// Hostname of the creating host
+    pub hostname: Option<String>,
+    /// Name of the creating user
+    pub username: Option<String>,
+}
+
+impl ListArchive {
+    /// Whether the archive is a checkpoint, i.e. its name ends with `.checkpoint`
+    /// or `.checkpoint.N`
+    ///
+    /// Checkpoints are written while borg create is running and represent incomplete backups.
+    pub fn is_checkpoint(&self) -> bool {
+        is_checkpoint(&self.name)
+    }
+}
+
+/// Keep the archives that were started at or after `since`, preserving their order
+pub(crate) fn archives_since(archives: Vec<ListArchive>, since: NaiveDateTime) -> Vec<ListArchive> {
+    archives
+        .into_iter()
+        .filter(|archive| archive.start >= since)
+        .collect()
+}
+
+/// Whether the name of an archive is the name of a checkpoint, see [ListArchive::is_checkpoint]
+pub(crate) fn is_checkpoint(name: &str) -> bool {
+    if name.ends_with(".checkpoint") {
+        return true;
+    }
+    match name.rsplit_once(".checkpoint.") {
+        Some((_, n)) => !n.is_empty() && n.bytes().all(|x| x.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// An item of an archive, as emitted by [crate::asynchronous::list_stream]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ListItem {
+    /// The type of the item, e.g. `-` for a regular file or `d` for a directory
+    #[serde(rename = "type")]
+    pub item_type: String,
+    /// The file mode, e.g. `-rw-r--r--`
+    pub mode: String,
+    /// Name of the owning user, if known
+    pub user: Option<String>,
+    /// Name of the owning group, if known
+    pub group: Option<String>,
+    /// Id of the owning user
+    pub uid: u32,
+    /// Id of the owning group
+    pub gid: u32,
+    /// Path of the item within the archive
+    pub path: String,
+    /// Whether the file is healthy, i.e. no chunks are missing
+    pub healthy: Option<bool>,
+    /// The link target of symlinks and hardlinks
+    pub source: Option<String>,
+    /// Modification timestamp
+    #[serde(deserialize_with = "deserialize_timestamp")]
+    pub mtime: NaiveDateTime,
+    /// Size of the file
+    pub size: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::{archives_since, is_checkpoint, ListArchive};
+
+    fn archive(name: &str, start: &str) -> ListArchive {
+        ListArchive {
+            id: "0b1e".to_string(),
+            name: name.to_string(),
+            start: NaiveDateTime::parse_from_str(start, "%Y-%m-%dT%H:%M:%S").unwrap(),
+            end: None,
+            comment: None,
+            hostname: None,
+            username: None,
+        }
+    }
+
+    #[test]
+    fn test_is_checkpoint() {
+        assert!(is_checkpoint("host-2023-01-02.checkpoint"));
+        assert!(is_checkpoint("host-2023-01-02.checkpoint.3"));
+        assert!(is_checkpoint("host-2023-01-02.checkpoint.12"));
+        assert!(!is_checkpoint("host-2023-01-02"));
+        assert!(!is_checkpoint("host-2023-01-02.checkpoint."));
+        assert!(!is_checkpoint("host-2023-01-02.checkpoint.old"));
+        assert!(!is_checkpoint("checkpoint"));
+        assert!(!is_checkpoint("host.checkpoints"));
+    }
+
+    #[test]
+    fn test_archives_since() {
+        let archives = vec![
+            archive("host-1", "2023-01-01T10:00:00"),
+            archive("host-2", "2023-01-02T09:59:59"),
+            archive("host-3", "2023-01-02T10:00:00"),
+            archive("host-4", "2023-01-03T02:00:00"),
+        ];
+        let since =
+            NaiveDateTime::parse_from_str("2023-01-02T10:00:00", "%Y-%m-%dT%H:%M:%S").unwrap();
+
+        let names = archives_since(archives.clone(), since)
+            .into_iter()
+            .map(|x| x.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["host-3", "host-4"]);
+
+        let since =
+            NaiveDateTime::parse_from_str("2023-01-04T00:00:00", "%Y-%m-%dT%H:%M:%S").unwrap();
+        assert!(archives_since(archives, since).is_empty());
+        assert!(archives_since(Vec::new(), since).is_empty());
+    }
 }
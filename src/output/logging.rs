@@ -100,23 +100,108 @@ pub enum LoggingMessage {
         /// only the last object for an operation can have this property set to true.
         finished: bool,
     },
+    /// A question borg asks, e.g. whether to access a relocated repository.
+    ///
+    /// As stdin of borg is never connected, the question is answered with its default,
+    /// which usually aborts the command.
+    #[serde(rename = "question_prompt")]
+    QuestionPrompt {
+        /// The environment variable that answers the question in advance,
+        /// e.g. `BORG_RELOCATED_REPO_ACCESS_IS_OK`
+        #[serde(rename = "msgid")]
+        msg_id: Option<String>,
+        /// The question
+        message: String,
+    },
     /// Due to a bug in borg, umount failures are directly reported from
     /// fusermount instead of being logged as json.
     UMountError(String),
+    /// A message of a type that is unknown to this crate, e.g. introduced by a newer borg version.
+    ///
+    /// Contains the whole message, including its `type`.
+    /// It is only created by parsing the output of borg and skipped by serde.
+    #[serde(skip)]
+    Unknown(Value),
 }
 
+/// The values of the `type` tag of [LoggingMessage] that are known to this crate
+const KNOWN_TYPES: &[&str] = &[
+    "log_message",
+    "file_status",
+    "progress_percent",
+    "progress_message",
+    "archive_progress",
+    "question_prompt",
+];
+
 impl LoggingMessage {
     /// Given a borg json log, attempt to parse it into a LogMessage
     pub(crate) fn from_str(log_message: &str) -> Result<Self, serde_json::Error> {
         // XXX: There's a bug in borg umount where fusermount errors are not
         //      logged as json so we need to catch them here.
-        if log_message.starts_with("fusermount: entry for") {
-            Ok(LoggingMessage::UMountError(log_message.to_string()))
-        } else {
-            serde_json::from_str(log_message)
+        if log_message.starts_with("fusermount: ") || log_message.starts_with("fusermount3: ") {
+            return Ok(LoggingMessage::UMountError(log_message.to_string()));
+        }
+
+        // archive_progress is emitted many times per second during borg create.
+        // Deserializing the internally tagged enum buffers every field first,
+        // so these lines are detected by their tag and deserialized directly.
+        if log_message.contains(r#""archive_progress""#) {
+            if let Ok(ArchiveProgress {
+                kind: "archive_progress",
+                original_size,
+                compressed_size,
+                deduplicated_size,
+                nfiles,
+                path,
+                time,
+                finished,
+            }) = serde_json::from_str(log_message)
+            {
+                return Ok(LoggingMessage::ArchiveProgress {
+                    original_size,
+                    compressed_size,
+                    deduplicated_size,
+                    nfiles,
+                    path,
+                    time,
+                    finished,
+                });
+            }
+        }
+
+        let err = match serde_json::from_str(log_message) {
+            Ok(message) => return Ok(message),
+            Err(err) => err,
+        };
+
+        // Messages of a known type that fail to parse are still an error
+        let Ok(value) = serde_json::from_str::<Value>(log_message) else {
+            return Err(err);
+        };
+        match value.get("type") {
+            Some(Value::String(kind)) if !KNOWN_TYPES.contains(&kind.as_str()) => {
+                Ok(LoggingMessage::Unknown(value))
+            }
+            _ => Err(err),
         }
     }
 }
+
+/// The fields of [LoggingMessage::ArchiveProgress]
+#[derive(Deserialize)]
+struct ArchiveProgress<'a> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+    original_size: Option<u64>,
+    compressed_size: Option<u64>,
+    deduplicated_size: Option<u64>,
+    nfiles: Option<u64>,
+    path: Option<String>,
+    time: f64,
+    finished: bool,
+}
+
 /// The valid loglevel of borg
 #[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
 #[serde(rename_all = "UPPERCASE")]
@@ -137,8 +222,22 @@ pub enum LevelName {
 /// without actually using the full text, since texts change more frequently.
 ///
 /// Message IDs are unambiguous and reduce the need to parse log messages.
+///
+/// As borg adds message IDs over time, IDs that are unknown to this crate
+/// are deserialized as [MessageId::Unknown].
 #[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
 pub enum MessageId {
+    /// Error: {}
+    Error,
+    /// Error: {}, a traceback is printed
+    ErrorWithTraceback,
+    /// Command Error: {}
+    CommandError,
+    /// Invalid placeholder “{}” in string: {}
+    InvalidPlaceholder,
+    /// Unsupported repository feature(s) {}. A newer version of borg is required to access this repository.
+    MandatoryFeatureUnsupported,
     /// Archive {} already exists
     #[serde(rename = "Archive.AlreadyExists")]
     ArchiveAlreadyExists,
@@ -175,6 +274,10 @@ pub enum MessageId {
     ExtensionModuleError,
     /// Data integrity error: {}
     IntegrityError,
+    /// File failed integrity check: {}
+    FileIntegrityError,
+    /// Decompression error: {}
+    DecompressionError,
     /// Repository has no manifest.
     NoManifestError,
     /// Formatting Error: “{}”.format({}): {}({})
@@ -207,8 +310,18 @@ pub enum MessageId {
     LockError,
     /// Failed to acquire the lock {}.
     LockErrorT,
+    /// Failed to create/acquire the lock {} ({}).
+    LockFailed,
+    /// Failed to create/acquire the lock {} (timeout).
+    LockTimeout,
+    /// Failed to release the lock {} (was not locked).
+    NotLocked,
+    /// Failed to release the lock {} (was/is locked, but not by me).
+    NotMyLock,
     /// Connection closed by remote host
     ConnectionClosed,
+    /// Connection closed by remote host. {}
+    ConnectionClosedWithHint,
     /// RPC method {} is not valid
     InvalidRPCMethod,
     /// Repository path not allowed
@@ -237,6 +350,21 @@ pub enum MessageId {
     /// {} is not a valid repository. Check repo config.
     #[serde(rename = "Repository.InvalidRepository")]
     RepositoryInvalidRepository,
+    /// {} does not have a valid configuration. Check repo config [{}].
+    #[serde(rename = "Repository.InvalidRepositoryConfig")]
+    RepositoryInvalidRepositoryConfig,
+    /// The parent path of the repo directory [{}] does not exist.
+    #[serde(rename = "Repository.ParentPathDoesNotExist")]
+    RepositoryParentPathDoesNotExist,
+    /// There is already something at {}.
+    #[serde(rename = "Repository.PathAlreadyExists")]
+    RepositoryPathAlreadyExists,
+    /// The storage quota ({}) has been exceeded ({}). Try deleting some archives.
+    #[serde(rename = "Repository.StorageQuotaExceeded")]
+    RepositoryStorageQuotaExceeded,
+    /// Permission denied to {}.
+    #[serde(rename = "Repository.PathPermissionDenied")]
+    RepositoryPathPermissionDenied,
     /// Attic repository detected. Please run “borg upgrade {}”.
     #[serde(rename = "Repository.AtticRepository")]
     RepositoryAtticRepository,
@@ -288,11 +416,94 @@ pub enum MessageId {
     /// TODO
     #[serde(rename = "upgrade.convert_segments")]
     UpgradeConvertSegments,
+    /// A message ID that is unknown to this crate, e.g. introduced by a newer borg version
+    #[serde(other)]
+    Unknown,
+}
+
+impl MessageId {
+    /// Whether the message ID is known to this crate, i.e. it isn't [MessageId::Unknown]
+    pub fn is_known(&self) -> bool {
+        *self != MessageId::Unknown
+    }
+
+    /// Map a modern exit code of borg to the corresponding [MessageId].
+    ///
+    /// The modern exit codes are used by borg >= 1.4 if `BORG_EXIT_CODES=modern` is set,
+    /// see [CommonOptions::modern_exit_codes](crate::common::CommonOptions::modern_exit_codes).
+    /// Exit codes without a corresponding [MessageId] (including the generic error code 2)
+    /// are mapped to [None].
+    ///
+    /// See <https://borgbackup.readthedocs.io/en/1.4-maint/internals/frontends.html#message-ids>
+    /// for the list of exit codes.
+    pub fn from_exit_code(exit_code: i32) -> Option<Self> {
+        Some(match exit_code {
+            4 => MessageId::CommandError,
+            5 => MessageId::PlaceholderError,
+            6 => MessageId::InvalidPlaceholder,
+            10 => MessageId::RepositoryAlreadyExists,
+            12 => MessageId::RepositoryCheckNeeded,
+            13 => MessageId::RepositoryDoesNotExist,
+            14 => MessageId::RepositoryInsufficientFreeSpaceError,
+            15 => MessageId::RepositoryInvalidRepository,
+            16 => MessageId::RepositoryInvalidRepositoryConfig,
+            17 => MessageId::RepositoryObjectNotFound,
+            18 => MessageId::RepositoryParentPathDoesNotExist,
+            19 => MessageId::RepositoryPathAlreadyExists,
+            20 => MessageId::RepositoryStorageQuotaExceeded,
+            21 => MessageId::RepositoryPathPermissionDenied,
+            25 => MessageId::MandatoryFeatureUnsupported,
+            26 => MessageId::NoManifestError,
+            27 => MessageId::UnsupportedManifestError,
+            30 => MessageId::ArchiveAlreadyExists,
+            31 => MessageId::ArchiveDoesNotExist,
+            32 => MessageId::ArchiveIncompatibleFilesystemEncodingError,
+            40 => MessageId::KeyfileInvalidError,
+            41 => MessageId::KeyfileMismatchError,
+            42 => MessageId::KeyfileNotFoundError,
+            43 => MessageId::NotABorgKeyFile,
+            44 => MessageId::RepoKeyNotFoundError,
+            45 => MessageId::RepoIdMismatch,
+            46 => MessageId::UnencryptedRepo,
+            47 => MessageId::UnknownKeyType,
+            48 => MessageId::UnsupportedPayloadError,
+            50 => MessageId::NoPassphraseFailure,
+            52 => MessageId::PassphraseWrong,
+            53 => MessageId::PasswordRetriesExceeded,
+            60 => MessageId::CacheCacheInitAbortedError,
+            61 => MessageId::CacheEncryptionMethodMismatch,
+            62 => MessageId::CacheRepositoryAccessAborted,
+            63 => MessageId::CacheRepositoryIDNotUnique,
+            64 => MessageId::CacheRepositoryReplay,
+            70 => MessageId::LockError,
+            71 => MessageId::LockErrorT,
+            72 => MessageId::LockFailed,
+            73 => MessageId::LockTimeout,
+            74 => MessageId::NotLocked,
+            75 => MessageId::NotMyLock,
+            80 => MessageId::ConnectionClosed,
+            81 => MessageId::ConnectionClosedWithHint,
+            82 => MessageId::InvalidRPCMethod,
+            83 => MessageId::PathNotAllowed,
+            84 => MessageId::RemoteRepositoryRPCServerOutdated,
+            85 => MessageId::UnexpectedRPCDataFormatFromClient,
+            86 => MessageId::UnexpectedRPCDataFormatFromServer,
+            90 => MessageId::IntegrityError,
+            91 => MessageId::FileIntegrityError,
+            92 => MessageId::DecompressionError,
+            _ => return None,
+        })
+    }
 }
 
 impl Display for MessageId {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
+            MessageId::Error => write!(f, "Error"),
+            MessageId::ErrorWithTraceback => write!(f, "ErrorWithTraceback"),
+            MessageId::CommandError => write!(f, "CommandError"),
+            MessageId::InvalidPlaceholder => write!(f, "InvalidPlaceholder"),
+            MessageId::MandatoryFeatureUnsupported => write!(f, "MandatoryFeatureUnsupported"),
             MessageId::ArchiveAlreadyExists => write!(f, "Archive.AlreadyExists"),
             MessageId::ArchiveDoesNotExist => write!(f, "Archive.DoesNotExist"),
             MessageId::ArchiveIncompatibleFilesystemEncodingError => {
@@ -307,6 +518,8 @@ impl Display for MessageId {
             MessageId::BufferMemoryLimitExceeded => write!(f, "Buffer.MemoryLimitExceeded"),
             MessageId::ExtensionModuleError => write!(f, "ExtensionModuleError"),
             MessageId::IntegrityError => write!(f, "IntegrityError"),
+            MessageId::FileIntegrityError => write!(f, "FileIntegrityError"),
+            MessageId::DecompressionError => write!(f, "DecompressionError"),
             MessageId::NoManifestError => write!(f, "NoManifestError"),
             MessageId::PlaceholderError => write!(f, "PlaceholderError"),
             MessageId::KeyfileInvalidError => write!(f, "KeyfileInvalidError"),
@@ -323,7 +536,12 @@ impl Display for MessageId {
             MessageId::UnknownKeyType => write!(f, "UnknownKeyType"),
             MessageId::LockError => write!(f, "LockError"),
             MessageId::LockErrorT => write!(f, "LockErrorT"),
+            MessageId::LockFailed => write!(f, "LockFailed"),
+            MessageId::LockTimeout => write!(f, "LockTimeout"),
+            MessageId::NotLocked => write!(f, "NotLocked"),
+            MessageId::NotMyLock => write!(f, "NotMyLock"),
             MessageId::ConnectionClosed => write!(f, "ConnectionClosed"),
+            MessageId::ConnectionClosedWithHint => write!(f, "ConnectionClosedWithHint"),
             MessageId::InvalidRPCMethod => write!(f, "InvalidRPCMethod"),
             MessageId::PathNotAllowed => write!(f, "PathNotAllowed"),
             MessageId::NoPassphraseFailure => write!(f, "NoPassphraseFailure"),
@@ -343,6 +561,19 @@ impl Display for MessageId {
                 write!(f, "Repository.InsufficientFreeSpaceError")
             }
             MessageId::RepositoryInvalidRepository => write!(f, "Repository.InvalidRepository"),
+            MessageId::RepositoryInvalidRepositoryConfig => {
+                write!(f, "Repository.InvalidRepositoryConfig")
+            }
+            MessageId::RepositoryParentPathDoesNotExist => {
+                write!(f, "Repository.ParentPathDoesNotExist")
+            }
+            MessageId::RepositoryPathAlreadyExists => write!(f, "Repository.PathAlreadyExists"),
+            MessageId::RepositoryStorageQuotaExceeded => {
+                write!(f, "Repository.StorageQuotaExceeded")
+            }
+            MessageId::RepositoryPathPermissionDenied => {
+                write!(f, "Repository.PathPermissionDenied")
+            }
             MessageId::RepositoryAtticRepository => write!(f, "Repository.AtticRepository"),
             MessageId::RepositoryObjectNotFound => write!(f, "Repository.ObjectNotFound"),
             MessageId::CacheBeginTransaction => write!(f, "cache.begin_transaction"),
@@ -360,13 +591,14 @@ impl Display for MessageId {
             MessageId::ArchiveCalcStats => write!(f, "archive.calc_stats"),
             MessageId::Prune => write!(f, "prune"),
             MessageId::UpgradeConvertSegments => write!(f, "upgrade.convert_segments"),
+            MessageId::Unknown => write!(f, "Unknown"),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::LoggingMessage;
+    use super::{LoggingMessage, MessageId};
 
     #[test]
     fn test_log_message_parse_mount() {
@@ -377,4 +609,114 @@ mod tests {
             log.expect("Expected LoggingMessage::UMountError to be parsed correctly")
         )
     }
+
+    #[test]
+    fn test_message_id_from_exit_code() {
+        assert_eq!(MessageId::from_exit_code(0), None);
+        assert_eq!(MessageId::from_exit_code(1), None);
+        assert_eq!(MessageId::from_exit_code(2), None);
+        assert_eq!(
+            MessageId::from_exit_code(13),
+            Some(MessageId::RepositoryDoesNotExist)
+        );
+        assert_eq!(
+            MessageId::from_exit_code(30),
+            Some(MessageId::ArchiveAlreadyExists)
+        );
+        assert_eq!(
+            MessageId::from_exit_code(52),
+            Some(MessageId::PassphraseWrong)
+        );
+        assert_eq!(MessageId::from_exit_code(71), Some(MessageId::LockErrorT));
+        assert_eq!(
+            MessageId::from_exit_code(20),
+            Some(MessageId::RepositoryStorageQuotaExceeded)
+        );
+        assert_eq!(MessageId::from_exit_code(100), None);
+    }
+
+    #[test]
+    fn test_message_id_unknown() {
+        let message = r#"{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "something new", "msgid": "Repository.SomethingNew"}"#;
+        let log = LoggingMessage::from_str(message).unwrap();
+        assert!(matches!(
+            log,
+            LoggingMessage::LogMessage {
+                msg_id: Some(MessageId::Unknown),
+                ..
+            }
+        ));
+        assert!(!MessageId::Unknown.is_known());
+
+        let message = r#"{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "full", "msgid": "Repository.StorageQuotaExceeded"}"#;
+        let log = LoggingMessage::from_str(message).unwrap();
+        assert!(matches!(
+            log,
+            LoggingMessage::LogMessage {
+                msg_id: Some(MessageId::RepositoryStorageQuotaExceeded),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_log_message_parse_unknown_type() {
+        let message = r#"{"type": "question_accepted_false", "msgid": "BORG_RELOCATED_REPO_ACCESS_IS_OK", "message": "Aborting."}"#;
+        let log = LoggingMessage::from_str(message).unwrap();
+        assert_eq!(
+            log,
+            LoggingMessage::Unknown(serde_json::from_str(message).unwrap())
+        );
+
+        // Known types with invalid fields and other json are still rejected
+        assert!(LoggingMessage::from_str(r#"{"type": "log_message", "time": 1.0}"#).is_err());
+        assert!(LoggingMessage::from_str(r#"{"message": "no type"}"#).is_err());
+        assert!(LoggingMessage::from_str(r#"{"type": 1}"#).is_err());
+        assert!(LoggingMessage::from_str("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn test_log_message_parse_fusermount3() {
+        let message = "fusermount3: failed to unmount /mnt/borg: Device or resource busy";
+        let log = LoggingMessage::from_str(message);
+        assert_eq!(
+            LoggingMessage::UMountError(message.to_string()),
+            log.expect("Expected LoggingMessage::UMountError to be parsed correctly")
+        )
+    }
+
+    #[test]
+    fn test_log_message_parse_archive_progress() {
+        let message = r#"{"type": "archive_progress", "original_size": 1024, "compressed_size": 512, "deduplicated_size": 256, "nfiles": 3, "path": "/home/user/file", "time": 1700000000.5, "finished": false}"#;
+        let log = LoggingMessage::from_str(message).unwrap();
+        assert_eq!(
+            log,
+            LoggingMessage::ArchiveProgress {
+                original_size: Some(1024),
+                compressed_size: Some(512),
+                deduplicated_size: Some(256),
+                nfiles: Some(3),
+                path: Some("/home/user/file".to_string()),
+                time: 1700000000.5,
+                finished: false,
+            }
+        );
+        assert_eq!(log, serde_json::from_str(message).unwrap());
+
+        let message = r#"{"type": "archive_progress", "time": 1700000001.0, "finished": true}"#;
+        let log = LoggingMessage::from_str(message).unwrap();
+        assert_eq!(log, serde_json::from_str(message).unwrap());
+
+        let message = r#"{"type": "archive_progress", "time": 1700000001.0}"#;
+        assert!(LoggingMessage::from_str(message).is_err());
+
+        let message = r#"{"type": "file_status", "status": "A", "path": "archive_progress"}"#;
+        assert_eq!(
+            LoggingMessage::from_str(message).unwrap(),
+            LoggingMessage::FileStatus {
+                status: "A".to_string(),
+                path: "archive_progress".to_string(),
+            }
+        );
+    }
 }
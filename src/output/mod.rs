@@ -1,7 +1,14 @@
 //! The output that borg can produce, is defined in this module
 
+pub mod benchmark;
 pub mod common;
+pub mod compact;
 pub mod create;
+pub mod delete;
+pub mod extract;
 pub mod info;
 pub mod list;
 pub mod logging;
+pub mod progress;
+pub mod prune;
+pub mod version;
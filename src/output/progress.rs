@@ -0,0 +1,201 @@
+//! Parsing of the progress that borg reports with `--progress`
+
+use std::time::Duration;
+
+use crate::output::create::CreateProgress;
+use crate::output::logging::LoggingMessage;
+
+/// Parses the `archive_progress` messages of borg into [CreateProgress]
+///
+/// Borg may leave out fields of a progress message.
+/// Missing sizes are filled in with the last reported ones, a missing path is left empty.
+#[derive(Debug, Clone, Default)]
+pub struct ProgressParser {
+    original_size: u64,
+    compressed_size: u64,
+    deduplicated_size: u64,
+    nfiles: u64,
+}
+
+impl ProgressParser {
+    /// Create a new [ProgressParser]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a line of borg's log output.
+    ///
+    /// Returns [None] if the line is not an `archive_progress` message.
+    pub fn parse(&mut self, line: &str) -> Option<CreateProgress> {
+        let message = LoggingMessage::from_str(line).ok()?;
+        self.parse_message(&message)
+    }
+
+    /// Convert an already parsed message, see [ProgressParser::parse]
+    pub(crate) fn parse_message(&mut self, message: &LoggingMessage) -> Option<CreateProgress> {
+        let LoggingMessage::ArchiveProgress {
+            original_size,
+            compressed_size,
+            deduplicated_size,
+            nfiles,
+            path,
+            time,
+            finished,
+        } = message
+        else {
+            return None;
+        };
+
+        if *finished {
+            return Some(CreateProgress::Finished);
+        }
+
+        self.original_size = original_size.unwrap_or(self.original_size);
+        self.compressed_size = compressed_size.unwrap_or(self.compressed_size);
+        self.deduplicated_size = deduplicated_size.unwrap_or(self.deduplicated_size);
+        self.nfiles = nfiles.unwrap_or(self.nfiles);
+
+        Some(CreateProgress::Progress {
+            original_size: self.original_size,
+            compressed_size: self.compressed_size,
+            deduplicated_size: self.deduplicated_size,
+            nfiles: self.nfiles,
+            path: path.clone().unwrap_or_default(),
+            time: *time,
+        })
+    }
+}
+
+/// The throughput of borg create between two [CreateProgress::Progress] updates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressRate {
+    /// Original size of the files processed per second, in bytes
+    pub bytes_per_sec: f64,
+    /// Number of files processed per second
+    pub files_per_sec: f64,
+}
+
+impl ProgressRate {
+    /// Calculate the rate between two consecutive progress updates.
+    ///
+    /// Returns [None] if one of them is not [CreateProgress::Progress]
+    /// or no time passed between them.
+    pub fn between(previous: &CreateProgress, current: &CreateProgress) -> Option<Self> {
+        let (
+            CreateProgress::Progress {
+                original_size: previous_size,
+                nfiles: previous_nfiles,
+                time: previous_time,
+                ..
+            },
+            CreateProgress::Progress {
+                original_size,
+                nfiles,
+                time,
+                ..
+            },
+        ) = (previous, current)
+        else {
+            return None;
+        };
+
+        let elapsed = time - previous_time;
+        if elapsed <= 0.0 {
+            return None;
+        }
+
+        Some(Self {
+            bytes_per_sec: original_size.saturating_sub(*previous_size) as f64 / elapsed,
+            files_per_sec: nfiles.saturating_sub(*previous_nfiles) as f64 / elapsed,
+        })
+    }
+
+    /// Estimate the time until `total` bytes are processed, based on the original size of
+    /// `current`.
+    ///
+    /// borg doesn't report the total size of the backup, so it has to be known by the caller,
+    /// e.g. from the original size of the previous archive.
+    /// The estimate is best-effort: the rate varies a lot, as unchanged files are skipped
+    /// quickly thanks to the files cache.
+    ///
+    /// Returns [None] if `current` is not [CreateProgress::Progress] or the rate is zero.
+    pub fn eta(&self, current: &CreateProgress, total: u64) -> Option<Duration> {
+        let CreateProgress::Progress { original_size, .. } = current else {
+            return None;
+        };
+        if self.bytes_per_sec <= 0.0 {
+            return None;
+        }
+
+        let remaining = total.saturating_sub(*original_size) as f64;
+        Some(Duration::from_secs_f64(remaining / self.bytes_per_sec))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ProgressParser, ProgressRate};
+    use crate::output::create::CreateProgress;
+
+    #[test]
+    fn test_parse() {
+        let mut parser = ProgressParser::new();
+
+        assert!(parser
+            .parse(r#"{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg", "message": "working"}"#)
+            .is_none());
+        assert!(parser.parse("not json").is_none());
+
+        let progress = parser
+            .parse(r#"{"type": "archive_progress", "original_size": 100, "compressed_size": 50, "deduplicated_size": 25, "nfiles": 1, "path": "/etc/hosts", "time": 1.0, "finished": false}"#)
+            .unwrap();
+        assert!(matches!(
+            progress,
+            CreateProgress::Progress { original_size: 100, compressed_size: 50, deduplicated_size: 25, nfiles: 1, path, time } if path == "/etc/hosts" && time == 1.0
+        ));
+
+        // Missing fields must not panic, the last values are kept
+        let progress = parser
+            .parse(r#"{"type": "archive_progress", "original_size": 200, "time": 2.0, "finished": false}"#)
+            .unwrap();
+        assert!(matches!(
+            progress,
+            CreateProgress::Progress { original_size: 200, compressed_size: 50, deduplicated_size: 25, nfiles: 1, path, time } if path.is_empty() && time == 2.0
+        ));
+
+        let progress = parser
+            .parse(r#"{"type": "archive_progress", "time": 3.0, "finished": true}"#)
+            .unwrap();
+        assert!(matches!(progress, CreateProgress::Finished));
+    }
+
+    #[test]
+    fn test_progress_rate() {
+        let mut parser = ProgressParser::new();
+        let first = parser
+            .parse(r#"{"type": "archive_progress", "original_size": 100, "nfiles": 1, "time": 1.0, "finished": false}"#)
+            .unwrap();
+        let second = parser
+            .parse(r#"{"type": "archive_progress", "original_size": 300, "nfiles": 5, "time": 3.0, "finished": false}"#)
+            .unwrap();
+        let finished = parser
+            .parse(r#"{"type": "archive_progress", "time": 4.0, "finished": true}"#)
+            .unwrap();
+
+        let rate = ProgressRate::between(&first, &second).unwrap();
+        assert_eq!(
+            rate,
+            ProgressRate {
+                bytes_per_sec: 100.0,
+                files_per_sec: 2.0
+            }
+        );
+        assert_eq!(rate.eta(&second, 1300), Some(Duration::from_secs(10)));
+        assert_eq!(rate.eta(&second, 200), Some(Duration::ZERO));
+
+        assert!(ProgressRate::between(&second, &second).is_none());
+        assert!(ProgressRate::between(&second, &finished).is_none());
+    }
+}
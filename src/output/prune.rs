@@ -0,0 +1,88 @@
+//! The definition of the prune command output
+
+use serde::{Deserialize, Serialize};
+
+/// The retention decisions of a borg prune call.
+///
+/// This is only populated if [PruneOptions::list](crate::common::PruneOptions::list) is set.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PruneReport {
+    /// The names of the archives that were kept
+    pub kept: Vec<String>,
+    /// The names of the archives that were pruned
+    ///
+    /// If [PruneOptions::dry_run](crate::common::PruneOptions::dry_run) is set,
+    /// these are the archives that would have been pruned.
+    pub pruned: Vec<String>,
+}
+
+impl PruneReport {
+    /// Add the decision of a message of the `borg.output.list` logger.
+    ///
+    /// The message has the format `<decision>: <name> <timestamp> [<id>]`, e.g.
+    /// `Keeping archive (rule: daily #1):   host-2023-01-02   Mon, 2023-01-02 10:00:00 [ab12...]`
+    pub(crate) fn add_list_message(&mut self, message: &str) {
+        // The decision may contain the rule, which contains a colon itself
+        let rest = match message.split_once(':') {
+            Some((decision, _)) if decision.contains('(') => message.split_once("):"),
+            x => x,
+        };
+        let Some((_, rest)) = rest else {
+            return;
+        };
+
+        // Strip the id and the timestamp, which consists of 3 words
+        let Some((rest, _)) = rest.trim().rsplit_once(" [") else {
+            return;
+        };
+        let Some(name) = rest.rsplitn(4, ' ').nth(3) else {
+            return;
+        };
+        let name = name.trim().to_string();
+
+        if message.starts_with("Keeping") {
+            self.kept.push(name);
+        } else if message.starts_with("Pruning") || message.starts_with("Would prune") {
+            self.pruned.push(name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PruneReport;
+
+    #[test]
+    fn test_add_list_message() {
+        let mut report = PruneReport::default();
+        report.add_list_message(
+            "Keeping archive (rule: daily #1):        host-2023-01-02                      Mon, 2023-01-02 10:00:00 [0b1e1ac2a3fbe6f3]",
+        );
+        report.add_list_message(
+            "Keeping checkpoint archive:              host 2023-01-01.checkpoint           Sun, 2023-01-01 11:00:00 [6c91a6c0b2b4a2e1]",
+        );
+        report.add_list_message(
+            "Pruning archive (1/2):                   host-2023-01-01                      Sun, 2023-01-01 10:00:00 [3a2cde5e7b0b7d61]",
+        );
+        report.add_list_message(
+            "Would prune:                             host-2022-12-31                      Sat, 2022-12-31 10:00:00 [9f1b7c2e5d3a8b40]",
+        );
+        report.add_list_message("Pruning archive: a-very-long-archive-name-that-exceeds-the-padding Sat, 2022-12-31 09:00:00 [1f1b7c2e5d3a8b40]");
+        report.add_list_message("Unrelated message");
+
+        assert_eq!(
+            report,
+            PruneReport {
+                kept: vec![
+                    "host-2023-01-02".to_string(),
+                    "host 2023-01-01.checkpoint".to_string()
+                ],
+                pruned: vec![
+                    "host-2023-01-01".to_string(),
+                    "host-2022-12-31".to_string(),
+                    "a-very-long-archive-name-that-exceeds-the-padding".to_string()
+                ],
+            }
+        );
+    }
+}
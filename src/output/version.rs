@@ -0,0 +1,87 @@
+//! The definition of the version of borg
+
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+/// The version of borg, as reported by `borg --version`.
+///
+/// Pre-release suffixes like `b1` or `rc2` are ignored.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BorgVersion {
+    /// Major version
+    pub major: u32,
+    /// Minor version
+    pub minor: u32,
+    /// Patch version
+    pub patch: u32,
+}
+
+impl BorgVersion {
+    /// Create a new version
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Whether borg supports the `compact` command (borg >= 1.2)
+    pub fn supports_compact(&self) -> bool {
+        *self >= Self::new(1, 2, 0)
+    }
+
+    /// Whether borg names the flag to not store flags `--noflags` (borg >= 1.2).
+    ///
+    /// Older versions use `--nobsdflags`.
+    pub fn supports_noflags(&self) -> bool {
+        *self >= Self::new(1, 2, 0)
+    }
+
+    /// Whether borg excludes checkpoint archives by default and supports
+    /// `--consider-checkpoints` to include them (borg >= 1.2).
+    pub fn supports_consider_checkpoints(&self) -> bool {
+        *self >= Self::new(1, 2, 0)
+    }
+
+    /// Whether borg supports the modern exit codes (borg >= 1.4)
+    ///
+    /// See [CommonOptions::modern_exit_codes](crate::common::CommonOptions::modern_exit_codes)
+    pub fn supports_modern_exit_codes(&self) -> bool {
+        *self >= Self::new(1, 4, 0)
+    }
+
+    /// Whether borg create supports `--checkpoint-volume` (borg >= 1.4)
+    pub fn supports_checkpoint_volume(&self) -> bool {
+        *self >= Self::new(1, 4, 0)
+    }
+
+    /// Whether borg has the transfer command (borg >= 2.0)
+    pub fn supports_transfer(&self) -> bool {
+        *self >= Self::new(2, 0, 0)
+    }
+
+    /// Whether borg compact supports `--dry-run` (borg >= 2.0)
+    pub fn supports_compact_dry_run(&self) -> bool {
+        *self >= Self::new(2, 0, 0)
+    }
+
+    /// Whether borg compact supports `--cleanup-commits` (borg >= 1.2 and < 2.0)
+    pub fn supports_cleanup_commits(&self) -> bool {
+        *self >= Self::new(1, 2, 0) && *self < Self::new(2, 0, 0)
+    }
+
+    /// Whether borg selects archives with `--match-archives` (borg >= 2.0).
+    ///
+    /// Older versions use `--glob-archives`, which only supports shell patterns.
+    pub fn supports_match_archives(&self) -> bool {
+        *self >= Self::new(2, 0, 0)
+    }
+}
+
+impl Display for BorgVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
@@ -1,9 +1,14 @@
-use std::process::Command;
+use std::process::Output;
 
 use log::{debug, info};
 
-use crate::common::{compact_fmt_args, compact_parse_output, CommonOptions, CompactOptions};
+use crate::common::{
+    compact_check_version, compact_fmt_args, compact_parse_output, CommonOptions, CompactOptions,
+};
 use crate::errors::CompactError;
+use crate::output::compact::CompactReport;
+use crate::sync::execute_borg;
+use crate::utils::find_borg;
 
 ///This command frees repository space by compacting segments.
 ///
@@ -17,20 +22,42 @@ use crate::errors::CompactError;
 /// **Parameter**:
 /// - `options`: Reference to [CompactOptions]
 /// - `common_options`: Reference to [CommonOptions]
+///
+/// The freed space is returned as [CompactReport].
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %options.repository))
+)]
 pub fn compact(
     options: &CompactOptions,
     common_options: &CommonOptions,
-) -> Result<(), CompactError> {
-    let local_path = common_options.local_path.as_ref().map_or("borg", |x| x);
+) -> Result<CompactReport, CompactError> {
+    let res = compact_raw(options, common_options)?;
 
-    let args = compact_fmt_args(options, common_options);
-    debug!("Calling borg: {local_path} {args}");
-    let args = shlex::split(&args).ok_or(CompactError::ShlexError)?;
-    let res = Command::new(local_path).args(args).output()?;
-
-    compact_parse_output(res)?;
+    let report = compact_parse_output(res, common_options)?;
 
     info!("Finished compacting");
 
-    Ok(())
+    Ok(report)
+}
+
+/// Run the borg compact command without parsing its output, see [compact].
+///
+/// **Parameter**:
+/// - `options`: Reference to [CompactOptions]
+/// - `common_options`: Reference to [CommonOptions]
+pub fn compact_raw(
+    options: &CompactOptions,
+    common_options: &CommonOptions,
+) -> Result<Output, CompactError> {
+    compact_check_version(options, common_options)?;
+
+    let local_path = &find_borg(common_options)?;
+
+    let args = compact_fmt_args(options, common_options, false);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(CompactError::ShlexError)?;
+    let res = execute_borg(local_path, args, &None, common_options)?;
+
+    Ok(res)
 }
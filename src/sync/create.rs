@@ -1,9 +1,20 @@
-use log::{debug, info};
+use std::io::Read;
+use std::process::{Output, Stdio};
 
-use crate::common::{create_fmt_args, create_parse_output, CommonOptions, CreateOptions};
+use log::{debug, info, trace};
+
+use crate::common::{
+    create_fmt_args, create_parse_output, CommonOptions, CreateOptions, CreateSource,
+    CreateTempFiles, InfoOptions,
+};
 use crate::errors::CreateError;
-use crate::output::create::Create;
-use crate::sync::execute_borg;
+use crate::output::create::{Create, CreateOutcome, CreateProgress};
+use crate::output::logging::LoggingMessage;
+use crate::output::progress::ProgressParser;
+use crate::sync::{
+    execute_borg, execute_borg_with_stdin, for_each_line_with_timeout, info, read_to_end_in_thread,
+};
+use crate::utils::{borg_command, find_borg};
 
 /// This command creates a backup archive containing all files found
 /// while recursively traversing all paths specified.
@@ -13,20 +24,491 @@ use crate::sync::execute_borg;
 /// **Parameter**:
 /// - `options`: Reference to [CreateOptions]
 /// - `common_options`: Reference to [CommonOptions]
+///
+/// Returns [None] if [CreateOptions::dry_run] is set, as no archive is created.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.archive.repository(),
+            archive = options.archive.archive(),
+        )
+    )
+)]
 pub fn create(
     options: &CreateOptions,
     common_options: &CommonOptions,
-) -> Result<Create, CreateError> {
-    let local_path = common_options.local_path.as_ref().map_or("borg", |x| x);
+) -> Result<Option<Create>, CreateError> {
+    let res = create_raw(options, common_options)?;
+
+    let stats = create_parse_output(res, options, common_options)?;
+
+    info!("Finished creating archive");
+
+    Ok(stats)
+}
+
+/// Run the borg create command without parsing its output, see [create].
+///
+/// **Parameter**:
+/// - `options`: Reference to [CreateOptions]
+/// - `common_options`: Reference to [CommonOptions]
+pub fn create_raw(
+    options: &CreateOptions,
+    common_options: &CommonOptions,
+) -> Result<Output, CreateError> {
+    if let CreateSource::Stdin { .. } = options.source {
+        return Err(CreateError::MissingStdin);
+    }
+
+    let local_path = &find_borg(common_options)?;
+
+    let temp_files = CreateTempFiles::new(options)?;
+    let args = create_fmt_args(options, common_options, false, &temp_files)?;
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(CreateError::ShlexError)?;
+    let res = match temp_files.open_paths()? {
+        Some(paths) => {
+            execute_borg_with_stdin(local_path, args, &options.passphrase, common_options, paths)?
+        }
+        None => execute_borg(local_path, args, &options.passphrase, common_options)?,
+    };
+
+    Ok(res)
+}
+
+/// Like [create], but an already existing archive is not an error.
+///
+/// If the archive already exists, e.g. because a job is retried after borg
+/// created the archive, the information of the existing archive is returned instead.
+/// Fetching it requires a second invocation of borg info, so its stats are those
+/// reported by borg info, not the stats of the original run of borg create.
+///
+/// **Parameter**:
+/// - `options`: Reference to [CreateOptions]
+/// - `common_options`: Reference to [CommonOptions]
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.archive.repository(),
+            archive = options.archive.archive(),
+        )
+    )
+)]
+pub fn create_idempotent(
+    options: &CreateOptions,
+    common_options: &CommonOptions,
+) -> Result<CreateOutcome, CreateError> {
+    match create(options, common_options) {
+        Ok(stats) => Ok(CreateOutcome::Created(stats)),
+        Err(CreateError::ArchiveAlreadyExists) => {
+            info!("Archive already exists, retrieving its info");
+
+            let mut info_options = InfoOptions::new(options.archive.repository().clone());
+            info_options.passphrase = options.passphrase.clone();
+            info_options.archive = Some(options.archive.archive().to_string());
+            let info =
+                info(&info_options, common_options).map_err(CreateError::ExistingArchiveInfo)?;
+
+            Ok(CreateOutcome::Existing(info))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// This command creates a backup archive containing the data read from `stdin`.
+///
+/// This is intended to be used with [CreateSource::Stdin], which specifies the metadata
+/// of the file in the archive.
+///
+/// **Parameter**:
+/// - `options`: Reference to [CreateOptions]
+/// - `common_options`: Reference to [CommonOptions]
+///
+/// Returns [None] if [CreateOptions::dry_run] is set, as no archive is created.
+/// - `stdin`: The data that should be archived
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.archive.repository(),
+            archive = options.archive.archive(),
+        )
+    )
+)]
+pub fn create_stdin(
+    options: &CreateOptions,
+    common_options: &CommonOptions,
+    stdin: impl Read + Send,
+) -> Result<Option<Create>, CreateError> {
+    let local_path = &find_borg(common_options)?;
+
+    let temp_files = CreateTempFiles::new(options)?;
+    let args = create_fmt_args(options, common_options, false, &temp_files)?;
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(CreateError::ShlexError)?;
+    let res =
+        execute_borg_with_stdin(local_path, args, &options.passphrase, common_options, stdin)?;
+
+    let stats = create_parse_output(res, options, common_options)?;
+
+    info!("Finished creating archive");
+
+    Ok(stats)
+}
+
+/// This command creates a backup archive containing all files found
+/// while recursively traversing all paths specified.
+/// Paths are added to the archive as they are given,
+/// that means if relative paths are desired, the command has to be run from the correct directory.
+///
+/// The progress is reported to the provided callback, which is called on the calling thread.
+///
+/// **Parameter**:
+/// - `options`: Reference to [CreateOptions]
+/// - `common_options`: Reference to [CommonOptions]
+/// - `progress_callback`: Called with the [CreateProgress] on every progress update
+///
+/// Returns [None] if [CreateOptions::dry_run] is set, as no archive is created.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.archive.repository(),
+            archive = options.archive.archive(),
+        )
+    )
+)]
+pub fn create_progress(
+    options: &CreateOptions,
+    common_options: &CommonOptions,
+    mut progress_callback: impl FnMut(CreateProgress),
+) -> Result<Option<Create>, CreateError> {
+    if let CreateSource::Stdin { .. } = options.source {
+        return Err(CreateError::MissingStdin);
+    }
+
+    let local_path = &find_borg(common_options)?;
 
-    let args = create_fmt_args(options, common_options, false);
+    let temp_files = CreateTempFiles::new(options)?;
+    let args = create_fmt_args(options, common_options, true, &temp_files)?;
     debug!("Calling borg: {local_path} {args}");
     let args = shlex::split(&args).ok_or(CreateError::ShlexError)?;
-    let res = execute_borg(local_path, args, &options.passphrase)?;
+    let mut child = borg_command(local_path, &options.passphrase, common_options)
+        .args(args)
+        .stdin(temp_files.open_paths()?.map_or(Stdio::null(), Stdio::from))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdout = child.stdout.take().ok_or(CreateError::PipeFailed)?;
+    let stderr_pipe = child.stderr.take().ok_or(CreateError::PipeFailed)?;
+
+    // stdout is drained in the background, as borg blocks if the pipe is full
+    let stdout = read_to_end_in_thread(stdout);
+
+    let mut parser = ProgressParser::new();
+    // Everything except the progress is kept to be parsed after borg exited
+    let mut stderr = Vec::new();
+
+    for_each_line_with_timeout(
+        &mut child,
+        stderr_pipe,
+        common_options.timeout,
+        |line| -> Result<(), CreateError> {
+            let line = line.map_err(CreateError::InvalidBorgOutput)?;
+
+            let progress = LoggingMessage::from_str(&line).ok().and_then(|res| {
+                let progress = parser.parse_message(&res)?;
+                common_options.notify_log_callback(&res);
+                Some(progress)
+            });
+            if let Some(progress) = progress {
+                trace!("Progress: {progress}");
+                progress_callback(progress);
+                return Ok(());
+            }
 
-    let stats = create_parse_output(res)?;
+            stderr.extend_from_slice(line.as_bytes());
+            stderr.push(b'\n');
+            Ok(())
+        },
+    )?;
+
+    let status = child.wait()?;
+    debug!("Child process exited with {status}");
+    let stdout = stdout
+        .join()
+        .map_err(|_| CreateError::PipeFailed)?
+        .map_err(CreateError::InvalidBorgOutput)?;
+
+    let stats = create_parse_output(
+        Output {
+            status,
+            stdout,
+            stderr,
+        },
+        options,
+        common_options,
+    )?;
 
     info!("Finished creating archive");
 
     Ok(stats)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::common::{
+        CreateOptions, CreateSource, Pattern, PatternInstruction, PATHS_FROM_FILE_THRESHOLD,
+    };
+    use crate::errors::CreateError;
+    use crate::output::create::{CreateOutcome, CreateProgress};
+    use crate::output::info::Info;
+    use crate::output::logging::LoggingMessage;
+    use crate::sync::{create, create_idempotent, create_progress, create_stdin};
+    use crate::test_utils::fake_borg;
+
+    #[test]
+    fn test_create_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo 'Traceback (most recent call last):' >&2
+echo '{"type": "archive_progress", "original_size": 100, "compressed_size": 50, "deduplicated_size": 25, "nfiles": 1, "path": "/etc/hosts", "time": 1.0, "finished": false}' >&2
+echo '{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg", "message": "working"}' >&2
+echo '{"type": "archive_progress", "time": 2.0, "finished": true}' >&2
+echo '{"archive": {"command_line": ["borg"], "duration": 0.1, "end": "2023-01-02T10:00:01.000000", "id": "0b1e", "limits": {"max_archive_size": 0.0}, "name": "archive", "start": "2023-01-02T10:00:00.000000", "stats": {"compressed_size": 50, "deduplicated_size": 25, "nfiles": 1, "original_size": 100}}, "repository": {"id": "e2ff", "last_modified": "2023-01-02T10:00:01.000000", "location": "/tmp/repo"}}'
+"#,
+        );
+        let options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/etc".to_string()],
+            vec![],
+        );
+
+        let mut progress = Vec::new();
+        let create = create_progress(&options, &common_options, |p| progress.push(p))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(create.archive.stats.original_size, 100);
+        assert_eq!(progress.len(), 2);
+        assert!(matches!(
+            &progress[0],
+            CreateProgress::Progress { nfiles: 1, path, .. } if path == "/etc/hosts"
+        ));
+        assert!(matches!(progress[1], CreateProgress::Finished));
+    }
+
+    #[test]
+    fn test_create_progress_log_callback() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo '{"type": "archive_progress", "original_size": 100, "compressed_size": 50, "deduplicated_size": 25, "nfiles": 1, "path": "/etc/hosts", "time": 1.0, "finished": false}' >&2
+echo '{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg", "message": "working"}' >&2
+echo '{"type": "archive_progress", "time": 2.0, "finished": true}' >&2
+echo '{"archive": {"command_line": ["borg"], "duration": 0.1, "end": "2023-01-02T10:00:01.000000", "id": "0b1e", "limits": {"max_archive_size": 0.0}, "name": "archive", "start": "2023-01-02T10:00:00.000000", "stats": {"compressed_size": 50, "deduplicated_size": 25, "nfiles": 1, "original_size": 100}}, "repository": {"id": "e2ff", "last_modified": "2023-01-02T10:00:01.000000", "location": "/tmp/repo"}}'
+"#,
+        );
+        let count = Arc::new(AtomicUsize::new(0));
+        common_options.log_callback = Some(Arc::new({
+            let count = count.clone();
+            move |_: &LoggingMessage| {
+                count.fetch_add(1, Ordering::SeqCst);
+            }
+        }));
+        let options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/etc".to_string()],
+            vec![],
+        );
+
+        let mut progress = 0;
+        create_progress(&options, &common_options, |_| progress += 1)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(progress, 2);
+        // Both progress messages and the log message
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_create_inline_patterns() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(
+            &dir,
+            &format!(
+                r#"#!/bin/sh
+while [ $# -gt 0 ]; do
+    if [ "$1" = "--patterns-from" ]; then
+        cp "$2" '{dir}/patterns'
+        echo "$2" > '{dir}/path'
+    fi
+    shift
+done
+echo '{{"archive": {{"command_line": ["borg"], "duration": 0.1, "end": "2023-01-02T10:00:01.000000", "id": "0b1e", "limits": {{"max_archive_size": 0.0}}, "name": "archive", "start": "2023-01-02T10:00:00.000000", "stats": {{"compressed_size": 50, "deduplicated_size": 25, "nfiles": 1, "original_size": 100}}}}, "repository": {{"id": "e2ff", "last_modified": "2023-01-02T10:00:01.000000", "location": "/tmp/repo"}}}}'
+"#,
+                dir = dir.path().display()
+            ),
+        );
+        let options = CreateOptions::builder(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/etc".to_string()],
+        )
+        .inline_patterns(vec![
+            PatternInstruction::Root("/home".to_string()),
+            PatternInstruction::Exclude(Pattern::Shell("**/*.tmp".to_string())),
+        ])
+        .build();
+
+        create(&options, &common_options).unwrap().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join("patterns")).unwrap(),
+            "P /home\n- sh:**/*.tmp\n"
+        );
+        // The temporary file is removed after borg exited
+        let path = fs::read_to_string(dir.path().join("path")).unwrap();
+        assert!(!Path::new(path.trim_end()).exists());
+    }
+
+    #[test]
+    fn test_create_paths_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(
+            &dir,
+            &format!(
+                r#"#!/bin/sh
+echo "$@" > '{dir}/args'
+cat > '{dir}/paths'
+echo '{{"archive": {{"command_line": ["borg"], "duration": 0.1, "end": "2023-01-02T10:00:01.000000", "id": "0b1e", "limits": {{"max_archive_size": 0.0}}, "name": "archive", "start": "2023-01-02T10:00:00.000000", "stats": {{"compressed_size": 50, "deduplicated_size": 25, "nfiles": 1, "original_size": 100}}}}, "repository": {{"id": "e2ff", "last_modified": "2023-01-02T10:00:01.000000", "location": "/tmp/repo"}}}}'
+"#,
+                dir = dir.path().display()
+            ),
+        );
+        let options = CreateOptions::builder(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/etc".to_string(), "/home/user name".to_string()],
+        )
+        .paths_from_file(true)
+        .build();
+
+        create(&options, &common_options).unwrap().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join("args")).unwrap(),
+            "--log-json create --json --paths-from-stdin /tmp/repo::archive\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("paths")).unwrap(),
+            "/etc\n/home/user name\n"
+        );
+    }
+
+    #[test]
+    fn test_create_idempotent() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+case "$*" in
+*" create "*)
+    echo '{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "Archive archive already exists", "msgid": "Archive.AlreadyExists"}' >&2
+    exit 2
+    ;;
+*" info "*" /tmp/repo::archive")
+    echo '{"archives": [], "encryption": {"mode": "none"}, "repository": {"id": "e2ff", "last_modified": "2023-01-02T10:00:01.000000", "location": "/tmp/repo"}}'
+    ;;
+*)
+    exit 2
+    ;;
+esac
+"#,
+        );
+        let options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/etc".to_string()],
+            vec![],
+        );
+
+        let outcome = create_idempotent(&options, &common_options).unwrap();
+        assert!(
+            matches!(outcome, CreateOutcome::Existing(Info::Archives { .. })),
+            "{outcome:?}"
+        );
+    }
+
+    #[test]
+    fn test_create_stdin_passphrase_wrong() {
+        let dir = tempfile::tempdir().unwrap();
+        // borg exits without reading its input
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo '{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "passphrase supplied in BORG_PASSPHRASE is incorrect", "msgid": "PassphraseWrong"}' >&2
+exit 2
+"#,
+        );
+        let mut options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec![],
+            vec![],
+        );
+        options.source = CreateSource::Stdin {
+            name: "dump.sql".to_string(),
+            mode: None,
+            user: None,
+            group: None,
+        };
+
+        // The input exceeds the pipe buffer, so writing it fails once borg exited
+        let input = vec![0u8; 4 * 1024 * 1024];
+        let res = create_stdin(&options, &common_options, input.as_slice());
+        assert!(matches!(res, Err(CreateError::PassphraseWrong)), "{res:?}");
+    }
+
+    #[test]
+    fn test_create_large_paths_borg_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        // borg exits without reading the paths from stdin
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo '{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "Archive archive already exists", "msgid": "Archive.AlreadyExists"}' >&2
+exit 2
+"#,
+        );
+        // The paths exceed the threshold and the pipe buffer
+        let paths: Vec<_> = (0..50_000).map(|x| format!("/srv/data/file-{x}")).collect();
+        assert!(paths.iter().map(|x| x.len() + 1).sum::<usize>() > 4 * PATHS_FROM_FILE_THRESHOLD);
+        let options =
+            CreateOptions::new(Path::new("/tmp/repo"), "archive".to_string(), paths, vec![]);
+
+        let res = create(&options, &common_options);
+        assert!(
+            matches!(res, Err(CreateError::ArchiveAlreadyExists)),
+            "{res:?}"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use std::process::Output;
+
+use log::{debug, info};
+
+use crate::common::{
+    delete_check_selection, delete_fmt_args, delete_parse_output, CommonOptions, DeleteOptions,
+};
+use crate::errors::DeleteError;
+use crate::output::delete::DeleteReport;
+use crate::sync::execute_borg;
+use crate::utils::find_borg;
+
+/// The entry point for the borg delete command
+///
+/// Deletes the archive or all archives matching the glob of [DeleteOptions].
+/// If neither is set, [DeleteError::NoArchivesSelected] is returned,
+/// as borg would delete the whole repository.
+///
+/// The space of the deleted archives is only freed by [compact](crate::sync::compact).
+///
+/// **Parameter**:
+/// - `options`: Reference to [DeleteOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+///
+/// Returns the deleted archives, or the archives that would be deleted
+/// if [DeleteOptions::dry_run] is set.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.repository,
+            archive = options.archive.as_ref().map(|x| x.archive()),
+        )
+    )
+)]
+pub fn delete(
+    options: &DeleteOptions,
+    common_options: &CommonOptions,
+) -> Result<DeleteReport, DeleteError> {
+    let res = delete_raw(options, common_options)?;
+
+    let report = delete_parse_output(res, common_options)?;
+
+    info!("Finished deleting");
+
+    Ok(report)
+}
+
+/// Run the borg delete command without parsing its output, see [delete].
+///
+/// **Parameter**:
+/// - `options`: Reference to [DeleteOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+pub fn delete_raw(
+    options: &DeleteOptions,
+    common_options: &CommonOptions,
+) -> Result<Output, DeleteError> {
+    delete_check_selection(options)?;
+
+    let local_path = &find_borg(common_options)?;
+
+    let args = delete_fmt_args(options, common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(DeleteError::ShlexError)?;
+    let res = execute_borg(local_path, args, &options.passphrase, common_options)?;
+
+    Ok(res)
+}
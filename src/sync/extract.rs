@@ -0,0 +1,174 @@
+use log::{debug, info};
+
+use crate::common::{
+    extract_fmt_args, extract_parse_output, list_items_parse_output, list_stream_fmt_args,
+    CommonOptions, ExtractOptions, ListOptions,
+};
+use crate::errors::ExtractError;
+use crate::sync::{execute_borg, execute_borg_in_dir};
+use crate::utils::find_borg;
+
+/// Extract the contents of an archive into [ExtractOptions::destination].
+///
+/// The paths of the archive are extracted relative to the destination.
+/// Use [ExtractOptions::strip_components] to remove leading path elements.
+///
+/// Returns the paths of the extracted files if [ExtractOptions::list_files] is set.
+/// If [ExtractOptions::verify_sizes] is set, differing sizes are returned as
+/// [ExtractError::SizeMismatch].
+///
+/// **Parameter**:
+/// - `options`: Reference to [ExtractOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.archive.repository(),
+            archive = options.archive.archive(),
+        )
+    )
+)]
+pub fn extract(
+    options: &ExtractOptions,
+    common_options: &CommonOptions,
+) -> Result<Vec<String>, ExtractError> {
+    let local_path = &find_borg(common_options)?;
+
+    let args = extract_fmt_args(options, common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(ExtractError::ShlexError)?;
+    let res = execute_borg_in_dir(
+        local_path,
+        args,
+        &options.passphrase,
+        common_options,
+        &options.destination,
+    )?;
+
+    let files = extract_parse_output(res, common_options)?;
+
+    if options.verify_sizes {
+        verify_sizes(local_path, options, common_options)?;
+    }
+
+    info!("Finished extracting archive");
+
+    Ok(files)
+}
+
+/// List the files of the archive and compare their sizes with the extracted files,
+/// see [ExtractOptions::verify_sizes]
+fn verify_sizes(
+    local_path: &str,
+    options: &ExtractOptions,
+    common_options: &CommonOptions,
+) -> Result<(), ExtractError> {
+    let mut list_options = ListOptions::new(options.archive.repository().clone());
+    list_options.passphrase = options.passphrase.clone();
+
+    let args = list_stream_fmt_args(&list_options, options.archive.archive(), common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(ExtractError::ShlexError)?;
+    let res = execute_borg(local_path, args, &options.passphrase, common_options)?;
+
+    let items = list_items_parse_output(res, common_options).map_err(ExtractError::VerifyFailed)?;
+    let mismatches = options.size_mismatches(&items);
+    if !mismatches.is_empty() {
+        return Err(ExtractError::SizeMismatch(mismatches));
+    }
+
+    info!("Verified the sizes of the extracted files");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::thread;
+
+    use crate::common::ExtractOptions;
+    use crate::errors::ExtractError;
+    use crate::output::extract::SizeMismatch;
+    use crate::sync::extract;
+    use crate::test_utils::fake_borg;
+
+    #[test]
+    fn test_extract_concurrently() {
+        let dir = tempfile::tempdir().unwrap();
+        // Writes the last argument (repo::archive) into a file in the working directory
+        let common_options = fake_borg(
+            &dir,
+            "#!/bin/sh\nfor arg; do :; done\nsleep 0.1\necho \"$arg\" > extracted\n",
+        );
+        let current_dir = std::env::current_dir().unwrap();
+
+        let destinations: Vec<_> = (0..2).map(|_| tempfile::tempdir().unwrap()).collect();
+        thread::scope(|s| {
+            for (i, destination) in destinations.iter().enumerate() {
+                let common_options = &common_options;
+                s.spawn(move || {
+                    let options = ExtractOptions::new(
+                        Path::new("/tmp/repo"),
+                        format!("archive-{i}"),
+                        destination.path().to_str().unwrap().to_string(),
+                    );
+                    extract(&options, common_options).unwrap();
+                });
+            }
+        });
+
+        for (i, destination) in destinations.iter().enumerate() {
+            let extracted = fs::read_to_string(destination.path().join("extracted")).unwrap();
+            assert_eq!(extracted, format!("/tmp/repo::archive-{i}\n"));
+        }
+        assert_eq!(std::env::current_dir().unwrap(), current_dir);
+    }
+
+    #[test]
+    fn test_extract_verify_sizes() {
+        let dir = tempfile::tempdir().unwrap();
+        // Extracts a truncated file, the archive records 220 bytes
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+case "$*" in
+*" extract "*)
+    mkdir -p etc
+    printf 'truncated' > etc/hosts
+    ;;
+*" list "*)
+    echo '{"type": "d", "mode": "drwxr-xr-x", "user": "root", "group": "root", "uid": 0, "gid": 0, "path": "etc", "healthy": true, "source": "", "linktarget": "", "flags": null, "mtime": "2023-01-02T10:00:00.000000", "size": 0}'
+    echo '{"type": "-", "mode": "-rw-r--r--", "user": "root", "group": "root", "uid": 0, "gid": 0, "path": "etc/hosts", "healthy": true, "source": "", "linktarget": "", "flags": null, "mtime": "2023-01-02T10:00:00.000000", "size": 220}'
+    ;;
+esac
+"#,
+        );
+        let destination = tempfile::tempdir().unwrap();
+        let mut options = ExtractOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            destination.path().to_str().unwrap().to_string(),
+        );
+
+        // Not verified by default
+        extract(&options, &common_options).unwrap();
+
+        options.verify_sizes = true;
+        let res = extract(&options, &common_options);
+        let Err(ExtractError::SizeMismatch(mismatches)) = res else {
+            panic!("{res:?}");
+        };
+        assert_eq!(
+            mismatches,
+            vec![SizeMismatch {
+                path: "etc/hosts".to_string(),
+                expected: 220,
+                actual: Some(9),
+            }]
+        );
+    }
+}
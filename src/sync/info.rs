@@ -0,0 +1,114 @@
+use log::{debug, info};
+
+use crate::common::{info_fmt_args, info_parse_output, CommonOptions, InfoOptions, Repository};
+use crate::errors::InfoError;
+use crate::output::info::Info;
+use crate::sync::execute_borg;
+use crate::utils::find_borg;
+
+/// The entry point for the borg info command
+///
+/// Returns [Info::Archives] if an archive or any of the archive filters of [InfoOptions]
+/// is set, otherwise [Info::Repository].
+///
+/// **Parameter**:
+/// - `options`: Reference to [InfoOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.repository,
+            archive = options.archive.as_deref(),
+        )
+    )
+)]
+pub fn info(options: &InfoOptions, common_options: &CommonOptions) -> Result<Info, InfoError> {
+    let local_path = &find_borg(common_options)?;
+
+    let args = info_fmt_args(options, common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(InfoError::ShlexError)?;
+    let res = execute_borg(local_path, args, &options.passphrase, common_options)?;
+
+    let info = info_parse_output(res, options, common_options)?;
+
+    info!("Finished retrieving info");
+
+    Ok(info)
+}
+
+/// Retrieve the comment of an archive.
+///
+/// The comment is set with [CreateOptions::comment](crate::common::CreateOptions::comment).
+/// Returns an empty string if the archive has no comment.
+///
+/// **Parameter**:
+/// - `repository`: The repository that contains the archive
+/// - `archive`: The name of the archive
+/// - `passphrase`: The passphrase for the repository
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %repository, archive = archive))
+)]
+pub fn get_comment(
+    repository: &Repository,
+    archive: &str,
+    passphrase: &Option<String>,
+    common_options: &CommonOptions,
+) -> Result<String, InfoError> {
+    let mut options = InfoOptions::new(repository.clone());
+    options.passphrase = passphrase.clone();
+    options.archive = Some(archive.to_string());
+
+    match info(&options, common_options)? {
+        Info::Archives { archives, .. } => archives
+            .into_iter()
+            .next()
+            .map(|archive| archive.comment)
+            .ok_or(InfoError::ArchiveDoesNotExist),
+        Info::Repository { .. } => Err(InfoError::ArchiveDoesNotExist),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::Path;
+
+    use crate::common::Repository;
+    use crate::errors::InfoError;
+    use crate::sync::get_comment;
+    use crate::test_utils::fake_borg;
+
+    #[test]
+    fn test_get_comment() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+case "$*" in
+*" /tmp/repo::archive")
+    echo '{"archives": [{"id": "0b1e", "name": "archive", "command_line": ["borg"], "limits": {"max_archive_size": 0.0}, "duration": 0.1, "chunker_params": ["buzhash", 19, 23, 21, 4095], "start": "2023-01-02T10:00:00.000000", "end": "2023-01-02T10:00:01.000000", "stats": {"compressed_size": 50, "deduplicated_size": 25, "nfiles": 1, "original_size": 100}, "hostname": "host", "username": "user", "comment": "{\"verified\": true}"}], "encryption": {"mode": "none"}, "repository": {"id": "e2ff", "last_modified": "2023-01-02T10:00:01.000000", "location": "/tmp/repo"}}'
+    ;;
+*)
+    echo '{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "Archive missing does not exist", "msgid": "Archive.DoesNotExist"}' >&2
+    exit 2
+    ;;
+esac
+"#,
+        );
+        let repository = Repository::from(Path::new("/tmp/repo"));
+
+        let comment = get_comment(&repository, "archive", &None, &common_options).unwrap();
+        assert_eq!(comment, r#"{"verified": true}"#);
+
+        let res = get_comment(&repository, "missing", &None, &common_options);
+        assert!(
+            matches!(res, Err(InfoError::ArchiveDoesNotExist)),
+            "{res:?}"
+        );
+    }
+}
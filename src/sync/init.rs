@@ -1,16 +1,41 @@
+use std::process::Output;
+
 use log::{debug, info};
 
 use crate::common::{init_fmt_args, init_parse_result, CommonOptions, InitOptions};
 use crate::errors::InitError;
 use crate::sync::execute_borg;
+use crate::utils::find_borg;
 
 /// The entry point for the borg init command
 ///
 /// **Parameter**:
 /// - `options`: Reference to [InitOptions]
 /// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %options.repository))
+)]
 pub fn init(options: &InitOptions, common_options: &CommonOptions) -> Result<(), InitError> {
-    let local_path = common_options.local_path.as_ref().map_or("borg", |x| x);
+    let res = init_raw(options, common_options)?;
+
+    init_parse_result(res, common_options)?;
+
+    info!("Repository {} created", options.repository);
+
+    Ok(())
+}
+
+/// Run the borg init command without parsing its output, see [init].
+///
+/// **Parameter**:
+/// - `options`: Reference to [InitOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+pub fn init_raw(
+    options: &InitOptions,
+    common_options: &CommonOptions,
+) -> Result<Output, InitError> {
+    let local_path = &find_borg(common_options)?;
 
     let args = init_fmt_args(options, common_options);
     let passphrase = options.encryption_mode.get_passphrase();
@@ -18,13 +43,9 @@ pub fn init(options: &InitOptions, common_options: &CommonOptions) -> Result<(),
     debug!("Calling borg: {local_path} {args}");
 
     let args = shlex::split(&args).ok_or(InitError::ShlexError)?;
-    let res = execute_borg(local_path, args, &passphrase)?;
+    let res = execute_borg(local_path, args, &passphrase, common_options)?;
 
-    init_parse_result(res)?;
-
-    info!("Repository {} created", options.repository);
-
-    Ok(())
+    Ok(res)
 }
 
 #[cfg(test)]
@@ -39,34 +60,22 @@ mod tests {
         let pw = String::from("pw");
 
         let init_options = [
+            InitOptions::new(dir.path().join("none"), EncryptionMode::None),
             InitOptions::new(
-                format!("{}", dir.path().join("none").display()),
-                EncryptionMode::None,
-            ),
-            InitOptions::new(
-                format!("{}", dir.path().join("auth").display()),
+                dir.path().join("auth"),
                 EncryptionMode::Authenticated(pw.clone()),
             ),
             InitOptions::new(
-                format!("{}", dir.path().join("authb2").display()),
+                dir.path().join("authb2"),
                 EncryptionMode::AuthenticatedBlake2(pw.clone()),
             ),
+            InitOptions::new(dir.path().join("repo"), EncryptionMode::Repokey(pw.clone())),
+            InitOptions::new(dir.path().join("key"), EncryptionMode::Keyfile(pw.clone())),
             InitOptions::new(
-                format!("{}", dir.path().join("repo").display()),
-                EncryptionMode::Repokey(pw.clone()),
-            ),
-            InitOptions::new(
-                format!("{}", dir.path().join("key").display()),
-                EncryptionMode::Keyfile(pw.clone()),
-            ),
-            InitOptions::new(
-                format!("{}", dir.path().join("repob2").display()),
+                dir.path().join("repob2"),
                 EncryptionMode::RepokeyBlake2(pw.clone()),
             ),
-            InitOptions::new(
-                format!("{}", dir.path().join("keyb2").display()),
-                EncryptionMode::KeyfileBlake2(pw),
-            ),
+            InitOptions::new(dir.path().join("keyb2"), EncryptionMode::KeyfileBlake2(pw)),
         ];
 
         for init_options in &init_options {
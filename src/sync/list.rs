@@ -1,30 +1,373 @@
+use std::process::{Output, Stdio};
+
+use chrono::NaiveDateTime;
 use log::{debug, info};
 
-use crate::common::{list_fmt_args, list_parse_output, CommonOptions, ListOptions};
+use crate::common::{
+    list_fmt_args, list_parse_log, list_parse_output, list_stream_fmt_args, CommonOptions,
+    ListOptions, Repository,
+};
 use crate::errors::ListError;
-use crate::output::list::ListRepository;
-use crate::sync::execute_borg;
+use crate::output::list::{archives_since, ListArchive, ListItem, ListRepository};
+use crate::sync::{execute_borg, for_each_line_with_timeout, read_to_end_in_thread};
+use crate::utils::{borg_command, find_borg};
 
 /// The entry point for the borg list command
 ///
+/// The archives are requested with `--json`, so borg emits the listing as one document,
+/// which is collected completely. To list the contents of an archive, which can be huge,
+/// use [list_stream], which uses `--json-lines` to process one item at a time.
+///
 /// **Parameter**:
 /// - `options`: Reference to [ListOptions]
 /// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %options.repository))
+)]
 pub fn list(
     options: &ListOptions,
     common_options: &CommonOptions,
 ) -> Result<ListRepository, ListError> {
-    let local_path = common_options.local_path.as_ref().map_or("borg", |x| x);
+    let res = list_raw(options, common_options)?;
+
+    let list_output = list_parse_output(res, options, common_options)?;
+
+    info!("Finished listing repository");
+
+    Ok(list_output)
+}
+
+/// Run the borg list command without parsing its output, see [list].
+///
+/// **Parameter**:
+/// - `options`: Reference to [ListOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+pub fn list_raw(
+    options: &ListOptions,
+    common_options: &CommonOptions,
+) -> Result<Output, ListError> {
+    let local_path = &find_borg(common_options)?;
 
     let args = list_fmt_args(options, common_options);
     debug!("Calling borg: {local_path} {args}");
 
     let args = shlex::split(&args).ok_or(ListError::ShlexError)?;
-    let res = execute_borg(local_path, args, &options.passphrase)?;
+    let res = execute_borg(local_path, args, &options.passphrase, common_options)?;
 
-    let list_output = list_parse_output(res)?;
+    Ok(res)
+}
 
-    info!("Finished listing repository");
+/// List the archives of a repository that were started at or after `since`.
+///
+/// Borg has no option to select archives by time, so all archives are listed
+/// and filtered on [ListArchive::start] on the client side.
+/// Borg reports the timestamps in the local time of the host running borg,
+/// so `since` has to be given in that time as well.
+/// Checkpoints are excluded, see [ListOptions::consider_checkpoints].
+///
+/// **Parameter**:
+/// - `repository`: The repository to list
+/// - `since`: The earliest start of the returned archives
+/// - `passphrase`: The passphrase for the repository
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %repository, since = %since))
+)]
+pub fn list_since(
+    repository: &Repository,
+    since: NaiveDateTime,
+    passphrase: &Option<String>,
+    common_options: &CommonOptions,
+) -> Result<Vec<ListArchive>, ListError> {
+    let mut options = ListOptions::new(repository.clone());
+    options.passphrase = passphrase.clone();
 
-    Ok(list_output)
+    let list_repo = list(&options, common_options)?;
+
+    Ok(archives_since(list_repo.archives, since))
+}
+
+/// List the contents of an archive.
+///
+/// The listing is requested with `--json-lines`, which emits one item per line,
+/// instead of `--json`, which emits the whole listing as one document.
+/// Every item is passed to the provided callback as soon as borg emitted it,
+/// so the memory usage stays low even for archives with millions of files.
+///
+/// **Parameter**:
+/// - `options`: Reference to [ListOptions]
+/// - `archive`: The name of the archive to list
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+/// - `item_callback`: Called with every [ListItem] of the archive, on the calling thread
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(repository = %options.repository, archive = archive))
+)]
+pub fn list_stream(
+    options: &ListOptions,
+    archive: &str,
+    common_options: &CommonOptions,
+    mut item_callback: impl FnMut(ListItem),
+) -> Result<(), ListError> {
+    let local_path = &find_borg(common_options)?;
+
+    let args = list_stream_fmt_args(options, archive, common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(ListError::ShlexError)?;
+    let mut child = borg_command(local_path, &options.passphrase, common_options)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdout = child.stdout.take().ok_or(ListError::PipeFailed)?;
+    let stderr = child.stderr.take().ok_or(ListError::PipeFailed)?;
+
+    // stderr is drained in the background, as borg blocks if the pipe is full
+    let stderr = read_to_end_in_thread(stderr);
+
+    for_each_line_with_timeout(
+        &mut child,
+        stdout,
+        common_options.timeout,
+        |line| -> Result<(), ListError> {
+            let line = line.map_err(ListError::InvalidBorgOutput)?;
+
+            let item: ListItem = serde_json::from_str(&line)?;
+            item_callback(item);
+            Ok(())
+        },
+    )?;
+
+    let status = child.wait()?;
+    debug!("Child process exited with {status}");
+    let stderr = stderr
+        .join()
+        .map_err(|_| ListError::PipeFailed)?
+        .map_err(ListError::InvalidBorgOutput)?;
+
+    list_parse_log(
+        &Output {
+            status,
+            stdout: Vec::new(),
+            stderr,
+        },
+        common_options,
+    )?;
+
+    info!("Finished listing archive");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::num::NonZeroU32;
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    use chrono::NaiveDateTime;
+
+    use crate::common::{CommonOptions, ListOptions, Repository, RetryPolicy};
+    use crate::errors::ListError;
+    use crate::sync::{list, list_raw, list_since, list_stream};
+    use crate::test_utils::fake_borg;
+    use crate::utils::borg_command;
+
+    #[test]
+    fn test_list_raw() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(
+            &dir,
+            "#!/bin/sh\necho \"$BORG_PASSPHRASE\"\necho 'not json' >&2\nexit 2\n",
+        );
+        let mut options = ListOptions::new(Path::new("/tmp/repo"));
+        options.passphrase = Some("secret".to_string());
+
+        let output = list_raw(&options, &common_options).unwrap();
+        assert_eq!(output.status.code(), Some(2));
+        assert_eq!(output.stdout, b"secret\n");
+        assert_eq!(output.stderr, b"not json\n");
+
+        let command = borg_command("borg", &options.passphrase, &common_options);
+        let envs: HashMap<&OsStr, Option<&OsStr>> = command.get_envs().collect();
+        assert_eq!(
+            envs[OsStr::new("BORG_PASSPHRASE")],
+            Some(OsStr::new("secret"))
+        );
+    }
+
+    #[test]
+    fn test_list_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = CommonOptions {
+            timeout: Some(Duration::from_millis(200)),
+            ..fake_borg(&dir, "#!/bin/sh\necho 'started' >&2\nexec sleep 30\n")
+        };
+
+        let start = Instant::now();
+        let res = list(&ListOptions::new(Path::new("/tmp/repo")), &common_options);
+        assert!(matches!(res, Err(ListError::Timeout)), "{res:?}");
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        // Commands finishing in time are not affected
+        fake_borg(&dir, "#!/bin/sh\necho 'not json' >&2\nexit 2\n");
+        let output = list_raw(&ListOptions::new(Path::new("/tmp/repo")), &common_options).unwrap();
+        assert_eq!(output.status.code(), Some(2));
+        assert_eq!(output.stderr, b"not json\n");
+    }
+
+    #[test]
+    fn test_list_relocated_repo_prompt() {
+        let dir = tempfile::tempdir().unwrap();
+        // Like borg, the default answer is used if stdin is closed
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+printf '%s\n' '{"type": "question_prompt", "msgid": "BORG_RELOCATED_REPO_ACCESS_IS_OK", "message": "Warning: The repository at location /tmp/repo was previously located at /old/repo\nDo you want to continue? [yN] "}' >&2
+if read answer; then
+    exit 0
+fi
+echo '{"type": "question_accepted_default", "msgid": "BORG_RELOCATED_REPO_ACCESS_IS_OK", "message": "Aborting."}' >&2
+echo '{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "Repository access aborted", "msgid": "Cache.RepositoryAccessAborted"}' >&2
+exit 2
+"#,
+        );
+
+        let res = list(&ListOptions::new(Path::new("/tmp/repo")), &common_options);
+        assert!(
+            matches!(&res, Err(ListError::InteractivePromptRequired { prompt }) if prompt.ends_with("Do you want to continue? [yN] ")),
+            "{res:?}"
+        );
+    }
+
+    #[test]
+    fn test_list_retry() {
+        let dir = tempfile::tempdir().unwrap();
+        let attempts = dir.path().join("attempts");
+        // Fails with a closed connection on the first attempt
+        let mut common_options = fake_borg(
+            &dir,
+            &format!(
+                r#"#!/bin/sh
+echo attempt >> '{attempts}'
+if [ "$(wc -l < '{attempts}')" -eq 1 ]; then
+    echo '{{"type": "log_message", "time": 1.0, "levelname": "ERROR", "name": "borg.archiver", "message": "Connection closed by remote host", "msgid": "ConnectionClosed"}}' >&2
+    exit 2
+fi
+echo '{{"archives": [], "encryption": {{"mode": "none"}}, "repository": {{"id": "e2ff", "last_modified": "2023-01-02T10:00:01.000000", "location": "/tmp/repo"}}}}'
+"#,
+                attempts = attempts.display()
+            ),
+        );
+        let res = list(&ListOptions::new(Path::new("/tmp/repo")), &common_options);
+        assert!(
+            matches!(res, Err(ListError::UnexpectedMessageId(_))),
+            "{res:?}"
+        );
+
+        fs::remove_file(&attempts).unwrap();
+        common_options.retry = Some(RetryPolicy::new(
+            NonZeroU32::new(3).unwrap(),
+            Duration::from_millis(10),
+        ));
+        let res = list(&ListOptions::new(Path::new("/tmp/repo")), &common_options).unwrap();
+        assert!(res.archives.is_empty());
+        assert_eq!(fs::read_to_string(&attempts).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn test_list_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo '{"type": "d", "mode": "drwxr-xr-x", "user": "root", "group": "root", "uid": 0, "gid": 0, "path": "etc", "healthy": true, "source": "", "linktarget": "", "flags": null, "mtime": "2023-01-02T10:00:00.123456", "size": 0}'
+echo '{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg", "message": "listing"}' >&2
+echo '{"type": "-", "mode": "-rw-r--r--", "user": null, "group": null, "uid": 1000, "gid": 1000, "path": "etc/hosts", "healthy": true, "source": "", "linktarget": "", "flags": null, "mtime": "2023-01-02T10:00:00.000000", "size": 220}'
+"#,
+        );
+
+        let mut items = vec![];
+        list_stream(
+            &ListOptions::new(Path::new("/tmp/repo")),
+            "archive",
+            &common_options,
+            |item| items.push(item),
+        )
+        .unwrap();
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(
+            (items[0].item_type.as_str(), items[0].path.as_str()),
+            ("d", "etc")
+        );
+        assert_eq!(items[1].path, "etc/hosts");
+        assert_eq!(items[1].size, 220);
+
+        // borg failing after emitting some items
+        fake_borg(
+            &dir,
+            "#!/bin/sh\necho '{\"type\": \"log_message\", \"time\": 1.0, \"levelname\": \"ERROR\", \"name\": \"borg.archiver\", \"message\": \"Archive archive does not exist\", \"msgid\": \"Archive.DoesNotExist\"}' >&2\nexit 2\n",
+        );
+        let res = list_stream(
+            &ListOptions::new(Path::new("/tmp/repo")),
+            "archive",
+            &common_options,
+            |_| {},
+        );
+        assert!(res.is_err(), "{res:?}");
+
+        // borg is killed if its output can't be parsed
+        let pid_file = dir.path().join("pid");
+        fake_borg(
+            &dir,
+            &format!(
+                "#!/bin/sh\necho $$ > {}\necho 'not json'\nexec sleep 30\n",
+                pid_file.display()
+            ),
+        );
+        let start = Instant::now();
+        let res = list_stream(
+            &ListOptions::new(Path::new("/tmp/repo")),
+            "archive",
+            &common_options,
+            |_| {},
+        );
+        assert!(
+            matches!(res, Err(ListError::DeserializeError(_))),
+            "{res:?}"
+        );
+        assert!(start.elapsed() < Duration::from_secs(10));
+        let pid = fs::read_to_string(&pid_file).unwrap();
+        let alive = std::process::Command::new("kill")
+            .args(["-0", pid.trim()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(!alive.success());
+    }
+
+    #[test]
+    fn test_list_since() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = fake_borg(
+            &dir,
+            r#"#!/bin/sh
+echo '{"archives": [{"archive": "host-1", "name": "host-1", "id": "01", "start": "2023-01-01T10:00:00.000000", "time": "2023-01-01T10:00:00.000000"}, {"archive": "host-2", "name": "host-2", "id": "02", "start": "2023-01-02T10:00:00.000000", "time": "2023-01-02T10:00:00.000000"}, {"archive": "host-3", "name": "host-3", "id": "03", "start": "2023-01-03T10:00:00.000000", "time": "2023-01-03T10:00:00.000000"}], "encryption": {"mode": "none"}, "repository": {"id": "e2ff", "last_modified": "2023-01-03T10:00:01.000000", "location": "/tmp/repo"}}'
+"#,
+        );
+        let repository = Repository::from(Path::new("/tmp/repo"));
+        let since =
+            NaiveDateTime::parse_from_str("2023-01-02T00:00:00", "%Y-%m-%dT%H:%M:%S").unwrap();
+
+        let archives = list_since(&repository, since, &None, &common_options).unwrap();
+        let names = archives.iter().map(|x| x.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["host-2", "host-3"]);
+    }
 }
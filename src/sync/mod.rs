@@ -1,33 +1,244 @@
 //! The synchronous versions of the borg command are defined in this module
+//!
+//! The `*_raw` variants of the commands are an escape hatch for output that the
+//! commands fail to parse. They return the raw [Output] of borg, parsing it is up to the caller.
 
 use std::io;
-use std::process::{Command, Output};
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-pub use compact::compact;
-pub use create::create;
-pub use init::init;
-pub use list::list;
-pub use mount::{mount, umount};
-pub use prune::prune;
+use crate::common::CommonOptions;
+use crate::utils::{borg_command, stdin_output, timeout_error};
 
+pub use crate::output::create::CreateProgress;
+pub use benchmark::benchmark_crud;
+pub use compact::{compact, compact_raw};
+pub use config::config;
+pub use create::{create, create_idempotent, create_progress, create_raw, create_stdin};
+pub use delete::{delete, delete_raw};
+pub use extract::extract;
+pub use info::{get_comment, info};
+pub use init::{init, init_raw};
+pub use list::{list, list_raw, list_since, list_stream};
+pub use mount::{mount, mount_guarded, mount_raw, umount, MountGuard};
+pub use prune::{prune, prune_raw};
+pub use raw::run_raw;
+pub use recreate::set_comment;
+pub use serve::{serve, ServeHandle};
+pub use transfer::transfer;
+pub use version::version;
+pub use workflow::backup;
+
+mod benchmark;
 mod compact;
+mod config;
 mod create;
+mod delete;
+mod extract;
+mod info;
 mod init;
 mod list;
 mod mount;
 mod prune;
+mod raw;
+mod recreate;
+mod serve;
+mod transfer;
+mod version;
+mod workflow;
 
 pub(crate) fn execute_borg(
     local_path: &str,
     args: Vec<String>,
     passphrase: &Option<String>,
+    common_options: &CommonOptions,
+) -> Result<Output, io::Error> {
+    let mut command = borg_command(local_path, passphrase, common_options);
+    command.args(args);
+    output_with_retry(command, common_options)
+}
+
+/// Execute borg with `current_dir` as its working directory.
+///
+/// Only the working directory of the child is changed,
+/// so this is safe to use concurrently.
+pub(crate) fn execute_borg_in_dir(
+    local_path: &str,
+    args: Vec<String>,
+    passphrase: &Option<String>,
+    common_options: &CommonOptions,
+    current_dir: &str,
+) -> Result<Output, io::Error> {
+    let mut command = borg_command(local_path, passphrase, common_options);
+    command.current_dir(current_dir).args(args);
+    output_with_retry(command, common_options)
+}
+
+pub(crate) fn execute_borg_with_stdin(
+    local_path: &str,
+    args: Vec<String>,
+    passphrase: &Option<String>,
+    common_options: &CommonOptions,
+    mut stdin: impl Read + Send,
+) -> Result<Output, io::Error> {
+    let mut child = borg_command(local_path, passphrase, common_options)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let mut child_stdin = child.stdin.take().ok_or(io::ErrorKind::BrokenPipe)?;
+
+    // The input has to be written from a separate thread as borg may block on
+    // writing its output while we're still writing the input
+    thread::scope(|s| {
+        let writer = s.spawn(move || io::copy(&mut stdin, &mut child_stdin));
+        let output = wait_with_timeout(child, common_options.timeout)?;
+        let written = writer.join().map_err(|_| io::ErrorKind::BrokenPipe)?;
+        stdin_output(written, output)
+    })
+}
+
+/// The interval in which the exit of borg is checked if a timeout is set
+const TIMEOUT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Execute the command and collect its output, retrying it according to
+/// [CommonOptions::retry]
+///
+/// [CommonOptions::timeout] applies to each attempt.
+pub(crate) fn output_with_retry(
+    mut command: Command,
+    common_options: &CommonOptions,
+) -> Result<Output, io::Error> {
+    let mut attempt = 1;
+    loop {
+        let output = output_with_timeout(&mut command, common_options.timeout)?;
+        let Some(delay) = common_options
+            .retry
+            .as_ref()
+            .and_then(|retry| retry.retry_delay(attempt, &output))
+        else {
+            return Ok(output);
+        };
+        thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+/// Execute the command and collect its output, see [wait_with_timeout]
+pub(crate) fn output_with_timeout(
+    command: &mut Command,
+    timeout: Option<Duration>,
+) -> Result<Output, io::Error> {
+    if timeout.is_none() {
+        return command.output();
+    }
+
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    wait_with_timeout(child, timeout)
+}
+
+/// Wait for the child to exit and collect its output.
+///
+/// If the child didn't exit within `timeout`, it is killed and
+/// an [io::ErrorKind::TimedOut] error is returned.
+pub(crate) fn wait_with_timeout(
+    mut child: Child,
+    timeout: Option<Duration>,
 ) -> Result<Output, io::Error> {
-    Ok(if let Some(passphrase) = passphrase {
-        Command::new(local_path)
-            .env("BORG_PASSPHRASE", passphrase)
-            .args(args)
-            .output()?
-    } else {
-        Command::new(local_path).args(args).output()?
+    let Some(timeout) = timeout else {
+        return child.wait_with_output();
+    };
+    let deadline = Instant::now() + timeout;
+
+    // The pipes are drained in separate threads, as borg blocks if they are full.
+    // The threads are not joined on timeout, as the pipes may be held open by
+    // processes borg spawned, e.g. ssh.
+    let stdout = child.stdout.take().map(read_to_end_in_thread);
+    let stderr = child.stderr.take().map(read_to_end_in_thread);
+
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Err(timeout_error());
+        }
+        thread::sleep(TIMEOUT_POLL_INTERVAL);
+    };
+
+    let join = |reader: Option<thread::JoinHandle<io::Result<Vec<u8>>>>| match reader {
+        Some(reader) => reader.join().map_err(|_| io::ErrorKind::BrokenPipe)?,
+        None => Ok(Vec::new()),
+    };
+
+    Ok(Output {
+        status,
+        stdout: join(stdout)?,
+        stderr: join(stderr)?,
+    })
+}
+
+/// Pass the lines of `pipe` to `line_callback` until borg closes it.
+///
+/// The lines are read in a separate thread, so the deadline of `timeout` can be checked
+/// while waiting for them. If the timeout elapsed or `line_callback` returned an error,
+/// the child is killed and waited for before the error is returned.
+pub(crate) fn for_each_line_with_timeout<E: From<io::Error>>(
+    child: &mut Child,
+    pipe: impl Read + Send + 'static,
+    timeout: Option<Duration>,
+    mut line_callback: impl FnMut(io::Result<String>) -> Result<(), E>,
+) -> Result<(), E> {
+    let (line_tx, line_rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).lines() {
+            if line_tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+    let err = loop {
+        let line = match deadline {
+            Some(deadline) => {
+                match line_rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(line) => line,
+                    Err(mpsc::RecvTimeoutError::Timeout) => break E::from(timeout_error()),
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+                }
+            }
+            None => match line_rx.recv() {
+                Ok(line) => line,
+                Err(_) => return Ok(()),
+            },
+        };
+        if let Err(err) = line_callback(line) {
+            break err;
+        }
+    };
+
+    child.kill()?;
+    child.wait()?;
+    Err(err)
+}
+
+pub(crate) fn read_to_end_in_thread(
+    mut pipe: impl Read + Send + 'static,
+) -> thread::JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        pipe.read_to_end(&mut buf).map(|_| buf)
     })
 }
@@ -1,43 +1,197 @@
-use log::{debug, info};
+use std::process::Output;
+
+use log::{debug, error, info};
 
 use crate::common::{mount_fmt_args, mount_parse_output, CommonOptions, MountOptions};
 use crate::errors::MountError;
 use crate::sync::execute_borg;
+use crate::utils::find_borg;
 
 /// Mount an archive or repo as a FUSE filesystem.
 ///
 /// **Parameter**:
 /// - `options`: Reference to [MountOptions]
 /// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.mount_source.repository(),
+            archive = options.mount_source.archive(),
+        )
+    )
+)]
 pub fn mount(options: &MountOptions, common_options: &CommonOptions) -> Result<(), MountError> {
-    let local_path = common_options.local_path.as_ref().map_or("borg", |x| x);
-
-    let args = mount_fmt_args(options, common_options);
-    debug!("Calling borg: {local_path} {args}");
-    let args = shlex::split(&args).ok_or(MountError::ShlexError)?;
-    let res = execute_borg(local_path, args, &options.passphrase)?;
+    let res = mount_raw(options, common_options)?;
 
-    mount_parse_output(res)?;
+    mount_parse_output(res, common_options)?;
 
     info!("Finished mounting");
 
     Ok(())
 }
 
+/// Run the borg mount command without parsing its output, see [mount].
+///
+/// **Parameter**:
+/// - `options`: Reference to [MountOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+pub fn mount_raw(
+    options: &MountOptions,
+    common_options: &CommonOptions,
+) -> Result<Output, MountError> {
+    let local_path = &find_borg(common_options)?;
+
+    let args = mount_fmt_args(options, common_options);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(MountError::ShlexError)?;
+    let res = execute_borg(local_path, args, &options.passphrase, common_options)?;
+
+    Ok(res)
+}
+
 /// Unmount a previously mounted archive or repository.
 ///
 /// **Parameter**:
 /// - `mountpoint`: The mountpoint to be unmounted.
 /// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(mountpoint = %mountpoint)))]
 pub fn umount(mountpoint: String, common_options: &CommonOptions) -> Result<(), MountError> {
-    let local_path = common_options.local_path.as_ref().map_or("borg", |x| x);
+    let local_path = &find_borg(common_options)?;
 
     let args = vec!["umount".to_string(), mountpoint];
-    let res = execute_borg(local_path, args, &None)?;
+    let res = execute_borg(local_path, args, &None, common_options)?;
 
-    mount_parse_output(res)?;
+    mount_parse_output(res, common_options)?;
 
     info!("Finished mounting");
 
     Ok(())
 }
+
+/// A mounted archive or repository that is unmounted when the guard is dropped.
+///
+/// Unmounting on drop is best-effort: errors are logged, but can't be handled.
+/// Use [MountGuard::umount] to unmount explicitly and receive the error instead.
+/// If the process exits without running destructors, e.g. on `panic = "abort"` or
+/// [std::process::exit], the mountpoint stays mounted.
+#[derive(Debug)]
+pub struct MountGuard {
+    mountpoint: String,
+    common_options: CommonOptions,
+    mounted: bool,
+}
+
+impl MountGuard {
+    /// The mountpoint of the archive or repository
+    pub fn mountpoint(&self) -> &str {
+        &self.mountpoint
+    }
+
+    /// Unmount the archive or repository.
+    ///
+    /// Unlike dropping the guard, this returns the error of [umount].
+    pub fn umount(mut self) -> Result<(), MountError> {
+        self.mounted = false;
+        umount(self.mountpoint.clone(), &self.common_options)
+    }
+}
+
+impl Drop for MountGuard {
+    fn drop(&mut self) {
+        if !self.mounted {
+            return;
+        }
+        if let Err(err) = umount(self.mountpoint.clone(), &self.common_options) {
+            error!("Could not unmount {}: {err}", self.mountpoint);
+        }
+    }
+}
+
+/// Mount an archive or repo as a FUSE filesystem, which is unmounted when
+/// the returned [MountGuard] is dropped.
+///
+/// [MountOptions::foreground] is ignored, as borg has to daemonize to return.
+///
+/// **Parameter**:
+/// - `options`: Reference to [MountOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.mount_source.repository(),
+            archive = options.mount_source.archive(),
+        )
+    )
+)]
+pub fn mount_guarded(
+    options: &MountOptions,
+    common_options: &CommonOptions,
+) -> Result<MountGuard, MountError> {
+    let options = MountOptions {
+        foreground: false,
+        ..options.clone()
+    };
+    mount(&options, common_options)?;
+
+    Ok(MountGuard {
+        mountpoint: options.mountpoint,
+        common_options: common_options.clone(),
+        mounted: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::common::{MountOptions, MountSource};
+    use crate::sync::mount_guarded;
+    use crate::test_utils::fake_borg;
+
+    #[test]
+    fn test_mount_guarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let calls = dir.path().join("calls");
+        let common_options = fake_borg(
+            &dir,
+            &format!("#!/bin/sh\necho \"$@\" >> '{}'\n", calls.display()),
+        );
+        let mut options = MountOptions::new(
+            MountSource::Repository {
+                name: Path::new("/tmp/repo").into(),
+                first_n_archives: None,
+                last_n_archives: None,
+                glob_archives: None,
+            },
+            "/mnt/borg".to_string(),
+        );
+        options.foreground = true;
+
+        let guard = mount_guarded(&options, &common_options).unwrap();
+        assert_eq!(guard.mountpoint(), "/mnt/borg");
+        assert_eq!(
+            fs::read_to_string(&calls).unwrap(),
+            "--log-json mount /tmp/repo /mnt/borg\n"
+        );
+
+        drop(guard);
+        assert_eq!(
+            fs::read_to_string(&calls).unwrap(),
+            "--log-json mount /tmp/repo /mnt/borg\numount /mnt/borg\n"
+        );
+
+        // An explicit umount isn't repeated on drop
+        fs::remove_file(&calls).unwrap();
+        let guard = mount_guarded(&options, &common_options).unwrap();
+        guard.umount().unwrap();
+        assert_eq!(
+            fs::read_to_string(&calls).unwrap(),
+            "--log-json mount /tmp/repo /mnt/borg\numount /mnt/borg\n"
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use std::process::Output;
+
+use log::debug;
+
+use crate::common::{raw_fmt_args, CommonOptions};
+use crate::errors::RawError;
+use crate::sync::execute_borg;
+use crate::utils::find_borg;
+
+/// Run an arbitrary borg command.
+///
+/// This is an escape hatch for commands that are not supported by this crate.
+/// `--log-json` and the [CommonOptions] are prepended to `args`,
+/// e.g. `vec!["key".to_string(), "migrate-to-repokey".to_string(), repository]`.
+///
+/// The raw [Output] of borg is returned, parsing it is the responsibility of the caller.
+///
+/// **Parameter**:
+/// - `args`: The arguments of the borg command
+/// - `passphrase`: The passphrase of the repository, if required
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn run_raw(
+    args: Vec<String>,
+    passphrase: Option<&str>,
+    common_options: &CommonOptions,
+) -> Result<Output, RawError> {
+    let local_path = &find_borg(common_options)?;
+
+    let common_args = raw_fmt_args(common_options);
+    debug!("Calling borg: {local_path} {common_args} {args:?}");
+    let mut borg_args = shlex::split(&common_args).ok_or(RawError::ShlexError)?;
+    borg_args.extend(args);
+
+    let passphrase = passphrase.map(str::to_string);
+    let res = execute_borg(local_path, borg_args, &passphrase, common_options)?;
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::common::CommonOptions;
+    use crate::sync::run_raw;
+    use crate::test_utils::fake_borg;
+
+    #[test]
+    fn test_run_raw() {
+        let dir = tempfile::tempdir().unwrap();
+        let common_options = CommonOptions {
+            lock_wait: Some(10),
+            ..fake_borg(&dir, "#!/bin/sh\necho \"$BORG_PASSPHRASE\"\necho \"$@\"\n")
+        };
+
+        let output = run_raw(
+            vec![
+                "key".to_string(),
+                "migrate-to-repokey".to_string(),
+                "/tmp/my repo".to_string(),
+            ],
+            Some("secret"),
+            &common_options,
+        )
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "secret\n--log-json --lock-wait 10 key migrate-to-repokey /tmp/my repo\n"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use log::{debug, info};
+
+use crate::common::{
+    transfer_check_version, transfer_fmt_args, transfer_parse_output, CommonOptions,
+    TransferOptions,
+};
+use crate::errors::TransferError;
+use crate::sync::output_with_retry;
+use crate::utils::{borg_transfer_command, find_borg};
+
+/// Transfer archives from one repository to another.
+///
+/// This requires borg >= 2.0.
+///
+/// **Parameter**:
+/// - `options`: Reference to [TransferOptions]
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %options.destination,
+            source = %options.source,
+        )
+    )
+)]
+pub fn transfer(
+    options: &TransferOptions,
+    common_options: &CommonOptions,
+) -> Result<(), TransferError> {
+    transfer_check_version(common_options)?;
+
+    let local_path = &find_borg(common_options)?;
+
+    let args = transfer_fmt_args(options, common_options, false);
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(TransferError::ShlexError)?;
+    let mut command = borg_transfer_command(local_path, options, common_options);
+    command.args(args);
+    let res = output_with_retry(command, common_options)?;
+
+    transfer_parse_output(res, common_options)?;
+
+    info!("Finished transferring archives");
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use log::debug;
+
+use crate::common::{version_parse_output, CommonOptions};
+use crate::errors::VersionError;
+use crate::output::version::BorgVersion;
+use crate::sync::execute_borg;
+use crate::utils::find_borg;
+
+/// Retrieve the version of borg
+///
+/// This can be used to check if borg supports a specific feature,
+/// see the methods of [BorgVersion].
+///
+/// **Parameter**:
+/// - `common_options`: The [CommonOptions] that can be applied to any command
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn version(common_options: &CommonOptions) -> Result<BorgVersion, VersionError> {
+    let local_path = &find_borg(common_options)?;
+
+    debug!("Calling borg: {local_path} --version");
+    let res = execute_borg(
+        local_path,
+        vec!["--version".to_string()],
+        &None,
+        common_options,
+    )?;
+
+    version_parse_output(res)
+}
@@ -0,0 +1,106 @@
+use log::info;
+
+use crate::common::{CommonOptions, CreateOptions, PruneOptions};
+use crate::errors::BackupError;
+use crate::output::create::Create;
+use crate::output::prune::PruneReport;
+use crate::sync::{create, prune};
+
+/// Create an archive and prune the repository afterwards.
+///
+/// Prune is only run if the archive was created successfully,
+/// the first error is returned.
+///
+/// **Parameter**:
+/// - `create_options`: Reference to [CreateOptions]
+/// - `prune_options`: Reference to [PruneOptions], usually for the same repository
+/// - `common_options`: The [CommonOptions] that are used for both commands
+///
+/// Returns the results of [create] and [prune].
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(
+            repository = %create_options.archive.repository(),
+            archive = create_options.archive.archive(),
+        )
+    )
+)]
+pub fn backup(
+    create_options: &CreateOptions,
+    prune_options: &PruneOptions,
+    common_options: &CommonOptions,
+) -> Result<(Option<Create>, PruneReport), BackupError> {
+    let create = create(create_options, common_options)?;
+    let report = prune(prune_options, common_options)?;
+
+    info!("Finished backup");
+
+    Ok((create, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::num::NonZeroU16;
+    use std::path::Path;
+
+    use crate::common::{CreateOptions, PruneOptions};
+    use crate::errors::{BackupError, CreateError};
+    use crate::sync::backup;
+    use crate::test_utils::fake_borg;
+
+    #[test]
+    fn test_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let pruned = dir.path().join("pruned");
+        let common_options = fake_borg(
+            &dir,
+            &format!(
+                r#"#!/bin/sh
+case "$*" in
+*" create "*)
+    echo '{{"archive": {{"command_line": ["borg"], "duration": 0.1, "end": "2023-01-02T10:00:01.000000", "id": "0b1e", "limits": {{"max_archive_size": 0.0}}, "name": "archive", "start": "2023-01-02T10:00:00.000000", "stats": {{"compressed_size": 50, "deduplicated_size": 25, "nfiles": 1, "original_size": 100}}}}, "repository": {{"id": "e2ff", "last_modified": "2023-01-02T10:00:01.000000", "location": "/tmp/repo"}}}}'
+    ;;
+*" prune "*)
+    touch '{pruned}'
+    echo '{{"type": "log_message", "time": 1.0, "levelname": "INFO", "name": "borg.output.list", "message": "Pruning archive (1/1):                   old                                  Sun, 2023-01-01 10:00:00 [3a2cde5e7b0b7d61]"}}' >&2
+    ;;
+esac
+"#,
+                pruned = pruned.display()
+            ),
+        );
+        let create_options = CreateOptions::new(
+            Path::new("/tmp/repo"),
+            "archive".to_string(),
+            vec!["/etc".to_string()],
+            vec![],
+        );
+        let mut prune_options = PruneOptions::new(Path::new("/tmp/repo"));
+        prune_options.keep_daily = NonZeroU16::new(7);
+        prune_options.list = true;
+
+        let (create, report) = backup(&create_options, &prune_options, &common_options).unwrap();
+        assert_eq!(create.unwrap().archive.name, "archive");
+        assert_eq!(report.pruned, vec!["old".to_string()]);
+        assert!(pruned.exists());
+
+        // prune is not run if create fails
+        fs::remove_file(&pruned).unwrap();
+        fake_borg(
+            &dir,
+            &format!(
+                "#!/bin/sh\ncase \"$*\" in\n*\" prune \"*) touch '{}' ;;\nesac\nexit 2\n",
+                pruned.display()
+            ),
+        );
+        let res = backup(&create_options, &prune_options, &common_options);
+        assert!(
+            matches!(res, Err(BackupError::Create(CreateError::Unknown(_)))),
+            "{res:?}"
+        );
+        assert!(!pruned.exists());
+    }
+}
@@ -0,0 +1,22 @@
+//! Helpers shared by the tests
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+use tempfile::TempDir;
+
+use crate::common::CommonOptions;
+
+/// Write `script` as an executable `borg` into `dir`.
+///
+/// The returned [CommonOptions] use it as [CommonOptions::local_path].
+pub(crate) fn fake_borg(dir: &TempDir, script: &str) -> CommonOptions {
+    let borg = dir.path().join("borg");
+    fs::write(&borg, script).unwrap();
+    fs::set_permissions(&borg, fs::Permissions::from_mode(0o755)).unwrap();
+
+    CommonOptions {
+        local_path: Some(borg.to_str().unwrap().to_string()),
+        ..CommonOptions::default()
+    }
+}
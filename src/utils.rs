@@ -1,3 +1,13 @@
+use std::collections::VecDeque;
+use std::env;
+use std::ffi::OsStr;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+use crate::common::{CommonOptions, TransferOptions, DEFAULT_MAX_ERROR_CONTEXT};
+
 /// Helper function for shell escaping
 ///
 /// It will try to quote the entire string and escape characters within that need escaping
@@ -14,3 +24,433 @@ pub(crate) fn shell_escape(input: &str) -> String {
     s.push('\'');
     s
 }
+
+/// The `PATH` that is searched if it isn't set, equal to the default of `execvp`
+const DEFAULT_PATH: &str = "/bin:/usr/bin";
+
+/// Resolve the borg executable to execute
+///
+/// If [CommonOptions::local_path] is set, it is used as is.
+/// Otherwise, borg is searched in `PATH`, so a missing borg is reported
+/// with a clear error instead of a generic error from spawning the process.
+pub(crate) fn find_borg(common_options: &CommonOptions) -> io::Result<String> {
+    if let Some(local_path) = &common_options.local_path {
+        return Ok(local_path.clone());
+    }
+
+    let path = env::var_os("PATH").unwrap_or_else(|| DEFAULT_PATH.into());
+    find_borg_in(&path)
+}
+
+/// Search borg in the directories of `path`, which is formatted like `PATH`
+fn find_borg_in(path: &OsStr) -> io::Result<String> {
+    env::split_paths(path)
+        .map(|dir| dir.join("borg"))
+        .find(|borg| is_executable(borg))
+        // Paths that aren't valid UTF-8 are left to be resolved when spawning borg
+        .map(|borg| borg.to_str().unwrap_or("borg").to_string())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, BorgNotFound))
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|x| x.is_file() && x.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// The payload of the error returned by [find_borg]
+#[derive(Debug)]
+struct BorgNotFound;
+
+impl Display for BorgNotFound {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "borg was not found in PATH")
+    }
+}
+
+impl std::error::Error for BorgNotFound {}
+
+/// Check whether the error was returned by [find_borg] because borg wasn't found
+///
+/// It is mapped to the `BorgNotFound` variant of the error of the command.
+pub(crate) fn is_borg_not_found_error(error: &io::Error) -> bool {
+    error.get_ref().is_some_and(|x| x.is::<BorgNotFound>())
+}
+
+/// Helper function to create the [Command] that executes borg
+///
+/// All environment variables are only set for the child process,
+/// the environment of the current process is left untouched.
+/// If [CommonOptions::command_wrapper] is set, borg is executed by the wrapper.
+pub(crate) fn borg_command(
+    local_path: &str,
+    passphrase: &Option<String>,
+    common_options: &CommonOptions,
+) -> Command {
+    let mut command = match common_options.command_wrapper.as_deref() {
+        Some([wrapper, args @ ..]) => {
+            let mut command = Command::new(wrapper);
+            command.args(args).arg(local_path);
+            command
+        }
+        _ => Command::new(local_path),
+    };
+    // borg reads the answers to its questions from stdin, which must not block.
+    // Commands that pass data to borg replace it.
+    command.stdin(Stdio::null());
+
+    if let Some(passphrase) = passphrase {
+        command.env("BORG_PASSPHRASE", passphrase);
+    }
+    if let Some(base_dir) = &common_options.base_dir {
+        command.env("BORG_BASE_DIR", base_dir);
+    }
+    if let Some(cache_dir) = &common_options.cache_dir {
+        command.env("BORG_CACHE_DIR", cache_dir);
+    }
+    if let Some(config_dir) = &common_options.config_dir {
+        command.env("BORG_CONFIG_DIR", config_dir);
+    }
+    if let Some(key_file) = &common_options.key_file {
+        command.env("BORG_KEY_FILE", key_file);
+    }
+    if let Some(keys_dir) = &common_options.keys_dir {
+        command.env("BORG_KEYS_DIR", keys_dir);
+    }
+    if let Some(security_dir) = &common_options.security_dir {
+        command.env("BORG_SECURITY_DIR", security_dir);
+    }
+    if common_options.relocated_repo_access_ok {
+        command.env("BORG_RELOCATED_REPO_ACCESS_IS_OK", "yes");
+    }
+    if common_options.unknown_unencrypted_repo_access_ok {
+        command.env("BORG_UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK", "yes");
+    }
+    if common_options.modern_exit_codes {
+        command.env("BORG_EXIT_CODES", "modern");
+    }
+
+    command
+}
+
+/// Helper function to create the [Command] that executes borg transfer
+///
+/// In addition to [borg_command], the passphrase of the source repository
+/// is set as `BORG_OTHER_PASSPHRASE` for the child process.
+pub(crate) fn borg_transfer_command(
+    local_path: &str,
+    options: &TransferOptions,
+    common_options: &CommonOptions,
+) -> Command {
+    let mut command = borg_command(local_path, &options.passphrase, common_options);
+
+    if let Some(other_passphrase) = &options.other_passphrase {
+        command.env("BORG_OTHER_PASSPHRASE", other_passphrase);
+    }
+
+    command
+}
+
+/// Combine the output of borg with the result of writing the input to its stdin
+///
+/// borg may exit without reading its whole input, e.g. if the passphrase is wrong,
+/// which fails writing the input with [io::ErrorKind::BrokenPipe]. The output is
+/// returned in that case, so the error of borg can be parsed from it. Other errors
+/// while writing the input are only returned if borg succeeded, as its input was incomplete.
+pub(crate) fn stdin_output(written: io::Result<u64>, output: Output) -> io::Result<Output> {
+    match written {
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe && output.status.success() => Err(err),
+        _ => Ok(output),
+    }
+}
+
+/// The error that is returned if borg didn't finish within [CommonOptions::timeout]
+///
+/// It is mapped to the `Timeout` variant of the error of the command.
+pub(crate) fn timeout_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        "borg did not finish within the timeout",
+    )
+}
+
+/// Collects the most recent lines of borg's output to be used as context in errors
+///
+/// Lines are dropped from the front once the total size exceeds the limit.
+/// If a single line exceeds the limit, only its end is kept.
+pub(crate) struct ErrorContext {
+    lines: VecDeque<String>,
+    size: usize,
+    max_size: usize,
+}
+
+impl ErrorContext {
+    /// Create an empty context that keeps at most `max_size` bytes.
+    ///
+    /// If `max_size` is [None], [DEFAULT_MAX_ERROR_CONTEXT] is used.
+    pub(crate) fn new(max_size: Option<usize>) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            size: 0,
+            max_size: max_size.unwrap_or(DEFAULT_MAX_ERROR_CONTEXT),
+        }
+    }
+
+    /// Append a line, dropping the oldest lines if the limit is exceeded
+    pub(crate) fn push_line(&mut self, line: &str) {
+        let mut line = format!("{line}\n");
+
+        if line.len() > self.max_size {
+            let mut start = line.len() - self.max_size;
+            while !line.is_char_boundary(start) {
+                start += 1;
+            }
+            line.drain(..start);
+        }
+
+        self.size += line.len();
+        self.lines.push_back(line);
+
+        while self.size > self.max_size {
+            match self.lines.pop_front() {
+                Some(front) => self.size -= front.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Join the collected lines
+    pub(crate) fn into_string(self) -> String {
+        let mut s = String::with_capacity(self.size);
+        s.extend(self.lines);
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::ffi::OsStr;
+    use std::path::{Path, PathBuf};
+
+    use crate::common::{CommonOptions, TransferOptions};
+    use crate::errors::ListError;
+    use crate::utils::{borg_command, borg_transfer_command, find_borg_in, ErrorContext};
+
+    #[test]
+    fn test_borg_command_env() {
+        let common_options = CommonOptions {
+            base_dir: Some("/srv/borg".to_string()),
+            cache_dir: Some("/srv/borg/cache".to_string()),
+            config_dir: Some("/srv/borg/config".to_string()),
+            ..CommonOptions::default()
+        };
+        let command = borg_command("borg", &Some("pw".to_string()), &common_options);
+        let envs: HashMap<&OsStr, Option<&OsStr>> = command.get_envs().collect();
+
+        assert_eq!(envs.len(), 4);
+        assert_eq!(envs[OsStr::new("BORG_PASSPHRASE")], Some(OsStr::new("pw")));
+        assert_eq!(
+            envs[OsStr::new("BORG_BASE_DIR")],
+            Some(OsStr::new("/srv/borg"))
+        );
+        assert_eq!(
+            envs[OsStr::new("BORG_CACHE_DIR")],
+            Some(OsStr::new("/srv/borg/cache"))
+        );
+        assert_eq!(
+            envs[OsStr::new("BORG_CONFIG_DIR")],
+            Some(OsStr::new("/srv/borg/config"))
+        );
+        assert!(!envs.contains_key(OsStr::new("BORG_KEYS_DIR")));
+
+        let command = borg_command("borg", &None, &CommonOptions::default());
+        assert_eq!(command.get_envs().count(), 0);
+    }
+
+    #[test]
+    fn test_borg_command_access_is_ok() {
+        let common_options = CommonOptions {
+            relocated_repo_access_ok: true,
+            unknown_unencrypted_repo_access_ok: true,
+            ..CommonOptions::default()
+        };
+        let command = borg_command("borg", &None, &common_options);
+        let envs: HashMap<&OsStr, Option<&OsStr>> = command.get_envs().collect();
+
+        assert_eq!(envs.len(), 2);
+        assert_eq!(
+            envs[OsStr::new("BORG_RELOCATED_REPO_ACCESS_IS_OK")],
+            Some(OsStr::new("yes"))
+        );
+        assert_eq!(
+            envs[OsStr::new("BORG_UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK")],
+            Some(OsStr::new("yes"))
+        );
+    }
+
+    #[test]
+    fn test_borg_command_modern_exit_codes() {
+        let common_options = CommonOptions {
+            modern_exit_codes: true,
+            ..CommonOptions::default()
+        };
+        let command = borg_command("borg", &None, &common_options);
+        let envs: HashMap<&OsStr, Option<&OsStr>> = command.get_envs().collect();
+
+        assert_eq!(
+            envs[OsStr::new("BORG_EXIT_CODES")],
+            Some(OsStr::new("modern"))
+        );
+    }
+
+    #[test]
+    fn test_error_context() {
+        let mut context = ErrorContext::new(None);
+        context.push_line("first");
+        context.push_line("second");
+        assert_eq!(context.into_string(), "first\nsecond\n");
+
+        let mut context = ErrorContext::new(Some(16));
+        for i in 0..100 {
+            context.push_line(&format!("line {i}"));
+        }
+        assert_eq!(context.into_string(), "line 98\nline 99\n");
+
+        let mut context = ErrorContext::new(Some(6));
+        context.push_line("short");
+        context.push_line("a very long line äöü");
+        assert_eq!(context.into_string(), "öü\n");
+
+        let mut context = ErrorContext::new(Some(0));
+        context.push_line("dropped");
+        assert_eq!(context.into_string(), "");
+    }
+
+    #[test]
+    fn test_borg_transfer_command_env() {
+        let mut options = TransferOptions::new(Path::new("/tmp/src"), Path::new("/tmp/dst"));
+        options.passphrase = Some("dst-pw".to_string());
+        options.other_passphrase = Some("src-pw".to_string());
+
+        let command = borg_transfer_command("borg", &options, &CommonOptions::default());
+        let envs: HashMap<&OsStr, Option<&OsStr>> = command.get_envs().collect();
+
+        assert_eq!(envs.len(), 2);
+        assert_eq!(
+            envs[OsStr::new("BORG_PASSPHRASE")],
+            Some(OsStr::new("dst-pw"))
+        );
+        assert_eq!(
+            envs[OsStr::new("BORG_OTHER_PASSPHRASE")],
+            Some(OsStr::new("src-pw"))
+        );
+    }
+
+    #[test]
+    fn test_find_borg_in() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let empty = tempfile::tempdir().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let borg = dir.path().join("borg");
+        fs::write(&borg, "#!/bin/sh\n").unwrap();
+
+        let path = std::env::join_paths([empty.path(), dir.path()]).unwrap();
+        // Files that aren't executable are skipped
+        let err = find_borg_in(&path).unwrap_err();
+        assert!(matches!(ListError::from(err), ListError::BorgNotFound));
+
+        fs::set_permissions(&borg, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(find_borg_in(&path).unwrap(), borg.to_str().unwrap());
+
+        let err = find_borg_in(OsStr::new("")).unwrap_err();
+        assert!(matches!(ListError::from(err), ListError::BorgNotFound));
+    }
+
+    #[test]
+    fn test_borg_command_key_file() {
+        let common_options = CommonOptions {
+            key_file: Some(PathBuf::from("/run/secrets/borg key")),
+            ..CommonOptions::default()
+        };
+        let mut command = borg_command("sh", &None, &common_options);
+        let envs: HashMap<&OsStr, Option<&OsStr>> = command.get_envs().collect();
+        assert_eq!(
+            envs[OsStr::new("BORG_KEY_FILE")],
+            Some(OsStr::new("/run/secrets/borg key"))
+        );
+        assert_eq!(envs.len(), 1);
+
+        let output = command
+            .args(["-c", r#"printf '%s' "$BORG_KEY_FILE""#])
+            .output()
+            .unwrap();
+        assert_eq!(output.stdout, b"/run/secrets/borg key");
+    }
+
+    #[test]
+    fn test_borg_command_keys_and_security_dir() {
+        let common_options = CommonOptions {
+            keys_dir: Some(PathBuf::from("/srv/tenant-a/keys")),
+            security_dir: Some(PathBuf::from("/srv/tenant-a/security")),
+            ..CommonOptions::default()
+        };
+        let command = borg_command("borg", &None, &common_options);
+        let envs: HashMap<&OsStr, Option<&OsStr>> = command.get_envs().collect();
+
+        assert_eq!(envs.len(), 2);
+        assert_eq!(
+            envs[OsStr::new("BORG_KEYS_DIR")],
+            Some(OsStr::new("/srv/tenant-a/keys"))
+        );
+        assert_eq!(
+            envs[OsStr::new("BORG_SECURITY_DIR")],
+            Some(OsStr::new("/srv/tenant-a/security"))
+        );
+        assert!(!envs.contains_key(OsStr::new("BORG_KEY_FILE")));
+    }
+
+    #[test]
+    fn test_borg_command_wrapper() {
+        let common_options = CommonOptions {
+            command_wrapper: Some(
+                ["nice", "-n", "19", "ionice", "-c3"]
+                    .map(String::from)
+                    .to_vec(),
+            ),
+            ..CommonOptions::default()
+        };
+        let mut command = borg_command("/usr/bin/borg", &None, &common_options);
+        command.args(["list", "/tmp/repo"]);
+        assert_eq!(command.get_program(), "nice");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            [
+                "-n",
+                "19",
+                "ionice",
+                "-c3",
+                "/usr/bin/borg",
+                "list",
+                "/tmp/repo"
+            ]
+        );
+
+        let common_options = CommonOptions {
+            command_wrapper: Some(Vec::new()),
+            ..CommonOptions::default()
+        };
+        let command = borg_command("/usr/bin/borg", &None, &common_options);
+        assert_eq!(command.get_program(), "/usr/bin/borg");
+        assert_eq!(command.get_args().count(), 0);
+    }
+}